@@ -1,19 +1,44 @@
 use crate::{
     config::{
-        structs::Config,
-        utils::{convert_to_string_if_some, parse_request},
+        har::read_har,
+        openapi::read_openapi,
+        structs::{Config, Targets},
+        utils::{convert_to_string_if_some, parse_curl, parse_request},
     },
-    network::utils::{DataType, Headers},
+    network::{
+        utils::{load_ca_certs, load_identity, BodyFraming, DataType, Headers, RedirectPolicy, RetryPolicy},
+        values::ValueGenerators,
+    },
+    runner::{
+        categories::read_categories,
+        utils::{ChunkStrategy, ReflectionParam},
+    },
+    STREAM_WORDLIST_SIZE, UNSAFE_METHODS, WEBSOCKET_KEY,
 };
-use clap::{crate_version, App, AppSettings, Arg};
-use std::{collections::HashMap, error::Error, fs, io::{self, Write}};
+use clap::{crate_version, App, AppSettings, Arg, ArgMatches, SubCommand};
+use std::{collections::HashMap, error::Error, ffi::OsString, fs, io::{self, Write}, path::Path};
 use tokio::time::Duration;
 use url::Url;
 
-use super::utils::{read_urls_if_possible, mimic_browser_headers, add_default_headers};
+use super::utils::{parse_size, destructive_word, strip_destructive_values, read_urls_if_possible, read_urls_file, websocket_to_http, mimic_browser_headers, add_default_headers, parse_identity, parse_shard, read_body};
 
+/// parses the command line arguments of the process
 pub fn get_config() -> Result<Config, Box<dyn Error>> {
-    let app = App::new("x8")
+    parse_args(&app().get_matches())
+}
+
+/// parses arguments the same way as the command line ones, so the library gets the same defaults
+/// the first argument is the name of the binary: ["x8", "-u", "https://example.com/"]
+pub fn get_config_from<I, T>(args: I) -> Result<Config, Box<dyn Error>>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<OsString> + Clone,
+{
+    parse_args(&app().get_matches_from_safe(args)?)
+}
+
+fn app() -> App<'static, 'static> {
+    App::new("x8")
         .setting(AppSettings::ArgRequiredElseHelp)
         .version(crate_version!())
         .author("sh1yo <sh1yo@tuta.io>")
@@ -29,10 +54,39 @@ pub fn get_config() -> Result<Config, Box<dyn Error>> {
         .arg(Arg::with_name("request")
             .short("r")
             .long("request")
-            .help("The file with the raw http request")
+            .alias("request-file")
+            .help("The file with the raw http request (exported from Burp or ZAP)")
             .takes_value(true)
             .conflicts_with("url")
         )
+        .arg(Arg::with_name("curl")
+            .long("curl")
+            .value_name("command")
+            .help("The curl command to take the method, url, headers, cookies and body from (\"Copy as cURL\" in browser devtools)")
+            .takes_value(true)
+            .conflicts_with_all(&["url", "request", "urls-file", "method", "headers", "body", "mimic-browser", "http", "websocket"])
+        )
+        .arg(Arg::with_name("openapi")
+            .long("openapi")
+            .value_name("spec.json")
+            .help("Check every operation from the OpenAPI/Swagger json spec for undocumented parameters.\nDocumented parameters aren't checked, required ones are sent with example values.\nUse -u to override the base url from the spec")
+            .takes_value(true)
+            .conflicts_with_all(&["request", "curl", "urls-file", "method"])
+        )
+        .arg(Arg::with_name("har")
+            .long("har")
+            .value_name("file.har")
+            .help("Check requests from the HAR file exported from browser devtools, including cookies and bodies.\nThe list of requests is shown to select the ones to check unless --har-entries is specified")
+            .takes_value(true)
+            .conflicts_with_all(&["url", "request", "curl", "openapi", "urls-file", "method", "headers", "body", "mimic-browser", "websocket"])
+        )
+        .arg(Arg::with_name("har-entries")
+            .long("har-entries")
+            .value_name("indexes")
+            .help("Requests from the HAR file to check: --har-entries 1,3-5. Every request is checked in case the value is empty")
+            .takes_value(true)
+            .requires("har")
+        )
         .arg(Arg::with_name("proto")
             .long("proto")
             .help("Protocol to use with request file (default is \"https\")")
@@ -59,10 +113,26 @@ pub fn get_config() -> Result<Config, Box<dyn Error>> {
             Arg::with_name("wordlist")
                 .short("w")
                 .long("wordlist")
-                .help("The file with parameters (leave empty to read from stdin)")
+                .help("The file with parameters (leave empty to read from stdin)\nComma separated parameters are accepted as well: -w debug,admin,test")
                 .default_value("")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("param")
+                .long("param")
+                .value_name("name")
+                .help("A parameter to check. Can be repeated: --param debug --param admin")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+        )
+        .arg(
+            Arg::with_name("shard")
+                .long("shard")
+                .value_name("index/total")
+                .help("Check only every total-th chunk of the wordlist starting with the index-th one: --shard 2/5.\nAllows splitting a huge wordlist between a few machines")
+                .takes_value(true)
+        )
         .arg(
             Arg::with_name("parameter-template")
                 .short("P")
@@ -81,15 +151,58 @@ pub fn get_config() -> Result<Config, Box<dyn Error>> {
             Arg::with_name("body")
                 .short("b")
                 .long("body")
-                .help("Example: --body '{\"x\":{%s}}'\nAvailable variables: {{random}}")
+                .help("Example: --body '{\"x\":{%s}}'\nAvailable variables: {{random}}, {{file:path}}\nUse @filename to read the body from a file: --body @body.json, and @@ to send the body that starts with @")
                 .value_name("body")
                 .conflicts_with("request")
         )
+        .arg(
+            Arg::with_name("body-framing")
+                .long("body-framing")
+                .help("How to frame the body: auto, content-length, chunked, both\nboth - sends Content-Length together with Transfer-Encoding: chunked")
+                .default_value("auto")
+                .takes_value(true)
+        )
+        .arg(
+            Arg::with_name("retries")
+                .long("retries")
+                .help("How many times to retry a failed request. Every next retry waits twice as long")
+                .default_value("1")
+                .takes_value(true)
+        )
+        .arg(
+            Arg::with_name("retry-on")
+                .long("retry-on")
+                .help("Failures to retry: error (any failed request), connect, timeout or status codes, e.g. --retry-on error 429 503\n(default is \"error\")")
+                .takes_value(true)
+                .min_values(1)
+        )
+        .arg(
+            Arg::with_name("value-type")
+                .long("value-type")
+                .help("Values for parameters: random, numeric, bool, list:a,b,c, format:<sample>, realistic\nformat - random values in the format of the sample, e.g. format:2024-01-31\nrealistic - emails, uuids, dates and numbers depending on names, random values otherwise")
+                .default_value("random")
+                .takes_value(true)
+        )
+        .arg(
+            Arg::with_name("value-transform")
+                .long("value-transform")
+                .help("Pipe parameter values through the command before sending them, e.g. a custom encryptor the app uses client-side.\nThe command gets values one per line within stdin and needs to print transformed values one per line")
+                .value_name("command")
+                .takes_value(true)
+        )
+        .arg(
+            Arg::with_name("param-value-type")
+                .long("param-value-type")
+                .help("Value types for parameters that match the regex. Example: --param-value-type '(?i)id$=numeric' '^is_=bool'")
+                .value_name("regex=type")
+                .takes_value(true)
+                .min_values(1)
+        )
         .arg(
             Arg::with_name("data-type")
                 .short("t")
                 .long("data-type")
-                .help("Available: urlencode, json\nCan be detected automatically if --body is specified (default is \"urlencode\")")
+                .help("Available: urlencode, json, multipart\nCan be detected automatically if --body is specified (default is \"urlencode\")")
                 .value_name("data-type")
         )
         .arg(
@@ -99,6 +212,42 @@ pub fn get_config() -> Result<Config, Box<dyn Error>> {
                 .value_name("proxy")
                 .takes_value(true)
         )
+        .arg(
+            Arg::with_name("cert")
+                .long("cert")
+                .help("The client certificate for mutual TLS (PEM or PKCS#12). May contain the private key as well")
+                .value_name("file")
+                .takes_value(true)
+        )
+        .arg(
+            Arg::with_name("key")
+                .long("key")
+                .help("The private key of the client certificate (PEM)")
+                .value_name("file")
+                .takes_value(true)
+                .requires("cert")
+        )
+        .arg(
+            Arg::with_name("cert-password")
+                .long("cert-password")
+                .help("The password of the PKCS#12 client certificate")
+                .value_name("password")
+                .takes_value(true)
+                .requires("cert")
+        )
+        .arg(
+            Arg::with_name("ca-cert")
+                .long("ca-cert")
+                .help("Verify server certificates with the custom root certificates (PEM or DER)")
+                .value_name("file")
+                .takes_value(true)
+        )
+        .arg(
+            Arg::with_name("insecure")
+                .long("insecure")
+                .help("Don't verify server certificates with --ca-cert. Certificates aren't verified without it anyway")
+                .requires("ca-cert")
+        )
         .arg(
             Arg::with_name("burp-proxy")
                 .short("B")
@@ -113,6 +262,25 @@ pub fn get_config() -> Result<Config, Box<dyn Error>> {
                 .default_value("0")
                 .takes_value(true)
         )
+        .arg(
+            Arg::with_name("rate")
+                .long("rate")
+                .help("The max number of requests per second to a single host. Combined with --rate-limit, the lower one wins. 0 - no limit")
+                .default_value("0")
+                .takes_value(true)
+        )
+        .arg(
+            Arg::with_name("rate-limit")
+                .long("rate-limit")
+                .help("The max number of requests per second in total, regardless of --rate. 0 - no limit")
+                .default_value("0")
+                .takes_value(true)
+        )
+        .arg(
+            Arg::with_name("adaptive-rate")
+                .long("adaptive-rate")
+                .help("Halve --rate-limit on 429 and 503 responses and restore it gradually afterwards")
+        )
         .arg(
             Arg::with_name("output")
                 .short("o")
@@ -124,7 +292,7 @@ pub fn get_config() -> Result<Config, Box<dyn Error>> {
             Arg::with_name("output-format")
                 .short("O")
                 .long("output-format")
-                .help("standart, json, url, request")
+                .help("standart, json, jsonl, url, request, template")
                 .default_value("standart")
                 .takes_value(true)
         )
@@ -133,6 +301,50 @@ pub fn get_config() -> Result<Config, Box<dyn Error>> {
                 .long("append")
                 .help("Append to the output file instead of overwriting it.")
         )
+        .arg(
+            Arg::with_name("compare-with")
+                .long("compare-with")
+                .value_name("file")
+                .help("Load the json output of a previous run and report new and removed parameters")
+                .takes_value(true)
+        )
+        .arg(
+            Arg::with_name("categories")
+                .long("categories")
+                .value_name("file")
+                .help("Name and color findings by the rules from the json file.\nExample: [{\"name\": \"privilege\", \"color\": \"bright red\", \"param\": \"^(admin|role)$\"}]")
+                .takes_value(true)
+        )
+        .arg(
+            Arg::with_name("save-baseline")
+                .long("save-baseline")
+                .value_name("file")
+                .help("Append initial responses to the file to use them later with --baseline")
+                .takes_value(true)
+        )
+        .arg(
+            Arg::with_name("baseline")
+                .long("baseline")
+                .value_name("file")
+                .help("Load initial responses saved with --save-baseline instead of fetching them.\nUseful when the clean state is reachable only at certain times")
+                .takes_value(true)
+                .conflicts_with("save-baseline")
+        )
+        .arg(
+            Arg::with_name("compare-url")
+                .long("compare-url")
+                .value_name("url")
+                .help("Check another url (authenticated vs unauthenticated, v1 vs v2) with the same parameters and report parameters that behave differently")
+                .takes_value(true)
+        )
+        .arg(
+            Arg::with_name("max-printed")
+                .long("max-printed")
+                .value_name("amount")
+                .help("Stop printing found parameters one by one after the amount is exceeded for a single url. They are still saved to the output file.\n0 to print every parameter")
+                .default_value("100")
+                .takes_value(true)
+        )
         .arg(
             Arg::with_name("remove-empty")
                 .long("remove-empty")
@@ -149,6 +361,11 @@ pub fn get_config() -> Result<Config, Box<dyn Error>> {
                 .min_values(1)
                 .conflicts_with("request")
         )
+        .arg(
+            Arg::with_name("discover-methods")
+                .long("discover-methods")
+                .help("Send OPTIONS first and check the url with methods from Allow and Access-Control-Allow-Methods as well")
+        )
         .arg(
             Arg::with_name("headers")
                 .short("H")
@@ -157,6 +374,32 @@ pub fn get_config() -> Result<Config, Box<dyn Error>> {
                 .min_values(1)
                 .conflicts_with("request")
         )
+        .arg(
+            Arg::with_name("as")
+                .long("as")
+                .value_name("name:headers")
+                .help("Run the discovery as every identity and report which parameters behave differently per role.\nExample: --as admin:@admin_headers.txt --as 'guest:Cookie: session=guest'")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .conflicts_with("compare-url")
+        )
+        .arg(
+            Arg::with_name("sticky-cookie")
+                .long("sticky-cookie")
+                .help("Capture the load balancer affinity cookie from the first response and send it with every request.\nExample: --sticky-cookie AWSALB JSESSIONID")
+                .value_name("names")
+                .takes_value(true)
+                .min_values(1)
+        )
+        .arg(
+            Arg::with_name("sticky-header")
+                .long("sticky-header")
+                .help("Capture the header from the first response and send it with every request to stay on the same backend.\nExample: --sticky-header X-Backend-Server")
+                .value_name("names")
+                .takes_value(true)
+                .min_values(1)
+        )
         .arg(
             Arg::with_name("invert")
                 .long("invert")
@@ -171,6 +414,102 @@ It's possible to overwrite this behavior by specifying the option")
                 .conflicts_with("invert")
                 .conflicts_with("param-template")
         )
+        .arg(
+            Arg::with_name("resume")
+                .long("resume")
+                .help("Save the progress to the file and continue the interrupted scan from it on the next run with the same file")
+                .value_name("file")
+                .takes_value(true)
+        )
+        .arg(
+            Arg::with_name("save-skipped")
+                .long("save-skipped")
+                .help("Save words that weren't checked (connection errors, Ctrl+C, ..) to the file, so they can be checked again with -w")
+                .value_name("file")
+                .takes_value(true)
+        )
+        .arg(
+            Arg::with_name("duplicate-keys")
+                .long("duplicate-keys")
+                .help("Send every found parameter twice with distinct values (k=v1&k=v2) and report whether the first or the last value is used")
+        )
+        .arg(
+            Arg::with_name("combinations")
+                .long("combinations")
+                .help("Send every pair of the top <amount> found parameters together and report pairs that change the page only together")
+                .value_name("amount")
+                .takes_value(true)
+        )
+        .arg(
+            Arg::with_name("header-folding")
+                .long("header-folding")
+                .help("Resend found headers with unusual whitespace (space before colon, tab continuation) and report how the target handles them")
+                .requires("headers-discovery")
+                .conflicts_with("cookies")
+        )
+        .arg(
+            Arg::with_name("path-segments")
+                .long("path-segments")
+                .help("Search for path segments instead of parameters: /api/users/123/<word>.\nNumeric ids within the path are checked as well")
+                .conflicts_with_all(&["headers-discovery", "cookies", "invert", "max", "recursion-depth"])
+        )
+        .arg(
+            Arg::with_name("partial-json")
+                .long("partial-json")
+                .help("Send every parameter as the only member of the json body instead of appending parameters to the body.\nUseful for PATCH endpoints that validate unknown fields")
+                .conflicts_with_all(&["body", "chunking", "max", "recursion-depth", "headers-discovery", "cookies", "path-segments"])
+        )
+        .arg(
+            Arg::with_name("keep-query")
+                .long("keep-query")
+                .help("Send the query from the url byte for byte and in the same order.\nWords that are already within the query aren't checked, so existing values are never overwritten")
+        )
+        .arg(
+            Arg::with_name("max-page-params")
+                .long("max-page-params")
+                .value_name("amount")
+                .help("The max amount of words scraped from the page to check along with the wordlist. 0 - no limit")
+                .default_value("0")
+                .takes_value(true)
+        )
+        .arg(
+            Arg::with_name("source-maps")
+                .long("source-maps")
+                .help("Fetch javascript source maps referenced by the page and its scripts and check identifiers from the original sources first")
+        )
+        .arg(
+            Arg::with_name("framework-words")
+                .long("framework-words")
+                .help("Check parameters specific to the backend framework detected by headers, cookies and error pages (Rails, Django, Spring, PHP, ..) first")
+        )
+        .arg(
+            Arg::with_name("match-casing")
+                .long("match-casing")
+                .help("Rewrite multiword parameters from the wordlist (user_id, userId, user-id) in the naming style used by the target.\nThe style is detected by names within the page and the url query")
+        )
+        .arg(
+            Arg::with_name("nested")
+                .long("nested")
+                .value_name("parameter")
+                .help("Check parameters within the value of the url parameter: base64 encoded json, an urlencoded string or a php serialized array.\nThe value is decoded, extended with parameters and encoded back per request")
+                .takes_value(true)
+                .conflicts_with_all(&["headers-discovery", "path-segments", "partial-json"])
+        )
+        .arg(
+            Arg::with_name("required")
+                .long("required")
+                .value_name("key=value")
+                .help("A parameter to send with every request, e.g. a mandatory id or token. Can be repeated: --required id=1 --required token=abc\nThese parameters aren't checked")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .conflicts_with_all(&["headers-discovery", "path-segments"])
+        )
+        .arg(
+            Arg::with_name("raw-path")
+                .long("raw-path")
+                .help("Send the path and query byte for byte.\nBy default spaces, unicode and other unsafe chars are percent encoded (already encoded sequences stay as is)")
+        )
         .arg(
             Arg::with_name("force")
                 .long("force")
@@ -235,13 +574,31 @@ It's possible to overwrite this behavior by specifying the option")
             Arg::with_name("follow-redirects")
                 .long("follow-redirects")
                 .short("L")
-                .help("Follow redirections")
+                .help("Follow redirections within the same host and compare the final responses")
+        )
+        .arg(
+            Arg::with_name("max-redirects")
+                .long("max-redirects")
+                .help("The max amount of redirects to follow")
+                .default_value("10")
+                .takes_value(true)
+        )
+        .arg(
+            Arg::with_name("cross-host-redirects")
+                .long("cross-host-redirects")
+                .help("Follow redirects to other hosts as well")
+                .requires("follow-redirects")
         )
         .arg(
             Arg::with_name("encode")
                 .long("encode")
                 .help("Encodes query or body before making a request, i.e & -> %26, = -> %3D\nList of chars to encode: \", `, , <, >, &, #, ;, /, =, %")
         )
+        .arg(
+            Arg::with_name("safe")
+                .long("safe")
+                .help("Refuse DELETE, PUT and PATCH methods and don't send custom parameters like delete=true\nUseful for testing production targets")
+        )
         .arg(
             Arg::with_name("strict")
                 .long("strict")
@@ -250,7 +607,7 @@ It's possible to overwrite this behavior by specifying the option")
         .arg(
             Arg::with_name("test")
                 .long("test")
-                .help("Prints request and response")
+                .help("Prints request and response. Prints them as json with -O json")
         )
         .arg(
             Arg::with_name("verbose")
@@ -266,6 +623,25 @@ It's possible to overwrite this behavior by specifying the option")
                 .help("Save request and response to a directory when a parameter is found")
                 .takes_value(true)
         )
+        .arg(
+            Arg::with_name("diffs-file")
+                .long("diffs-file")
+                .value_name("file")
+                .help("Write every evaluated diff (parameters, diff lines and the decision made) to a file as ndjson")
+                .takes_value(true)
+        )
+        .arg(
+            Arg::with_name("low-memory")
+                .long("low-memory")
+                .help("Compare hashes of lines instead of copying pages while diffing. Useful for pages of 10MB+ checked with high concurrency")
+        )
+        .arg(
+            Arg::with_name("audit-log")
+                .long("audit-log")
+                .value_name("file")
+                .help("Write the exact send time of every request and the time passed since the previous request to the same host to a file as ndjson.\nAllows proving compliance with agreed rate limits")
+                .takes_value(true)
+        )
         .arg(
             Arg::with_name("learn-requests-count")
                 .long("learn-requests")
@@ -281,11 +657,74 @@ Conflicts with --verify for now.")
                 .takes_value(true)
                 .conflicts_with("verify")
         )
+        .arg(
+            Arg::with_name("watch-wordlist")
+                .long("watch-wordlist")
+                .value_name("file")
+                .help("Check parameters appended to the file while the scan is running, e.g. echo debug_mode >> extra.txt")
+                .takes_value(true)
+        )
+        .arg(
+            Arg::with_name("max-bytes")
+                .long("max-bytes")
+                .value_name("size")
+                .help("Stop the run once the amount of bytes is sent and received in total, e.g. 500M. For metered APIs")
+                .takes_value(true)
+        )
+        .arg(
+            Arg::with_name("json-depth")
+                .long("json-depth")
+                .value_name("levels")
+                .help("Check keys within objects of found json parameters up to the amount of levels: {\"user\":{%s}}")
+                .takes_value(true)
+        )
+        .arg(
+            Arg::with_name("stop-after")
+                .long("stop-after")
+                .value_name("amount")
+                .help("Stop checking the url and method once the amount of parameters is found. Useful when the only question is whether the endpoint has hidden parameters at all")
+                .takes_value(true)
+        )
         .arg(
             Arg::with_name("max")
                 .short("m")
                 .long("max")
-                .help("Change the maximum number of parameters per request.\n(default is <= 256 for query, 64 for headers and 512 for body)")
+                .help("Change the maximum number of parameters per request.\n(default is <= 256 for query, 64 for headers and <= 512 for body)")
+                .takes_value(true)
+        )
+        .arg(
+            Arg::with_name("chunking")
+                .long("chunking")
+                .help("How to group parameters into requests: fixed, url-length, single\nfixed - up to --max parameters per request\nurl-length - up to --max parameters while the url is shorter than --max-url-length\nsingle - one parameter per request")
+                .default_value("fixed")
+                .takes_value(true)
+        )
+        .arg(
+            Arg::with_name("max-url-length")
+                .long("max-url-length")
+                .help("The maximum url length in bytes for the url-length chunking")
+                .default_value("2048")
+                .takes_value(true)
+        )
+        .arg(
+            Arg::with_name("warmup")
+                .long("warmup")
+                .value_name("connections")
+                .help("Open the given amount of connections before learning and report whether they are reused. 0 disables the warmup")
+                .default_value("0")
+                .takes_value(true)
+        )
+        .arg(
+            Arg::with_name("network-estimates")
+                .long("network-estimates")
+                .help("Estimate dns, connect, tls and ttfb timings with a few separate probes after the check to tell network slowness from application slowness. Checked requests aren't measured and the detection isn't affected")
+                .conflicts_with_all(&["proxy", "burp-proxy"])
+        )
+        .arg(
+            Arg::with_name("max-header-bytes")
+                .long("max-header-bytes")
+                .help("The maximum size of headers per request in bytes while searching for headers. 0 disables the limit")
+                .default_value("7168")
                 .takes_value(true)
         )
         .arg(
@@ -295,6 +734,28 @@ Conflicts with --verify for now.")
                 .default_value("15")
                 .takes_value(true)
         )
+        .arg(
+            Arg::with_name("connect-timeout")
+                .long("connect-timeout")
+                .help("The time to establish a connection in seconds. Split between resolved addresses")
+                .default_value("5")
+                .takes_value(true)
+        )
+        .arg(
+            Arg::with_name("doh")
+                .long("doh")
+                .help("Resolve hosts via DNS-over-HTTPS (json api): --doh https://1.1.1.1/dns-query")
+                .value_name("url")
+                .takes_value(true)
+                .conflicts_with_all(&["proxy", "burp-proxy"])
+        )
+        .arg(
+            Arg::with_name("cache-ttl")
+                .long("cache-ttl")
+                .help("How long responses to identical control requests (setup, baseline code and reachability checks, verification) are reused, in seconds. 0 disables the cache")
+                .default_value("0")
+                .takes_value(true)
+        )
         .arg(
             Arg::with_name("concurrency")
                 .short("c")
@@ -315,16 +776,63 @@ Conflicts with --verify for now.")
                 .long("verify")
                 .help("Verify found parameters.")
         )
+        .arg(
+            Arg::with_name("verify-proxy")
+                .long("verify-proxy")
+                .value_name("proxy")
+                .help("Verify found parameters through another proxy, so findings caused by per-ip behavior (geo blocks, rate limiting) are filtered out.\nImplies --verify")
+                .takes_value(true)
+                .conflicts_with("recursion-depth")
+        )
+        .arg(
+            Arg::with_name("keep-aliases")
+                .long("keep-aliases")
+                .help("Don't collapse found parameters that produce identical responses with the same value (id, user_id, userId)")
+        )
         .arg(
             Arg::with_name("reflected-only")
                 .long("reflected-only")
                 .help("Disable page comparison and search for reflected parameters only.")
         )
+        .arg(
+            Arg::with_name("reflection-param")
+                .long("reflection-param")
+                .help("How to send the random parameter that counts the default amount of reflections: inline, separate, off\ninline - along with the initial request\nseparate - within another request, so the initial response stays without unknown parameters\noff - don't send it and don't search for reflections")
+                .default_value("inline")
+                .takes_value(true)
+        )
+        .arg(
+            Arg::with_name("reflection-param-length")
+                .long("reflection-param-length")
+                .help("The length of the random parameter's name and value")
+                .default_value("10")
+                .takes_value(true)
+        )
+        .arg(
+            Arg::with_name("urls-file")
+                .long("urls-file")
+                .help("The file with urls to check, one per line, or a json document with urls and per host settings.\nUse - to read urls from stdin")
+                .takes_value(true)
+                .conflicts_with_all(&["url", "request"])
+        )
+        .arg(
+            Arg::with_name("host-workers")
+                .long("host-workers")
+                .help("The max number of concurrent url checks per host. 0 - no limit")
+                .default_value("0")
+                .takes_value(true)
+        )
         .arg(
             Arg::with_name("one-worker-per-host")
                 .long("one-worker-per-host")
                 .help("Multiple urls with the same host will be checked one after another,\nwhile urls with different hosts - are in parallel.\nDoesn't increase the number of workers")
         )
+        .arg(
+            Arg::with_name("websocket")
+                .long("websocket")
+                .help("Experimental. Send requests as WebSocket handshakes and compare handshake responses along with the first frame after them.\nws:// and wss:// urls are supported")
+                .conflicts_with_all(&["request", "http"])
+        )
         .arg(
             Arg::with_name("mimic-browser")
                 .long("mimic-browser")
@@ -344,28 +852,105 @@ Conflicts with --verify for now.")
             Arg::with_name("cookies")
                 .long("cookies")
                 .help("Shortcut for adding injection point to cookies")
+        ).arg(
+            Arg::with_name("cors")
+                .long("cors")
+                .help("Report parameters that change Access-Control-Allow-* headers")
+        ).arg(
+            Arg::with_name("security-headers")
+                .long("security-headers")
+                .help("Report parameters that make security headers (CSP, X-Frame-Options, HSTS, ..) appear, disappear or change")
+        ).arg(
+            Arg::with_name("cors-origin")
+                .long("cors-origin")
+                .help("Send the Origin header with every request. Example: --cors-origin https://evil.com")
+                .takes_value(true)
+                .requires("cors")
         ).arg(
             Arg::with_name("remove-banner")
                 .long("remove-banner")
                 .help("Do not print initial banner")
-        );
+        ).arg(
+            Arg::with_name("disable-preflight")
+                .long("disable-preflight")
+                .help("Skip dns, connection, tls and baseline response checks before checking the url")
+        ).arg(
+            Arg::with_name("json-only")
+                .long("json-only")
+                .help("Check a single url and print exactly one json document with findings, stats and errors. Other output is suppressed.\nExit codes: 0 - parameters were found, 1 - nothing was found, 2 - errors")
+                .conflicts_with_all(&["test", "one-worker-per-host"])
+        ).arg(
+            Arg::with_name("fail-on-found")
+                .long("fail-on-found")
+                .help("Exit with code 1 in case any parameter was found (or an error happened). Useful for CI checks together with --compare-with")
+                .conflicts_with("fail-on-error-only")
+        ).arg(
+            Arg::with_name("fail-on-error-only")
+                .long("fail-on-error-only")
+                .help("Exit with non-zero code only in case an error happened while checking any of the urls")
+        ).subcommand(
+            SubCommand::with_name("serve")
+                .about("Run as a service with a REST api to submit targets and fetch results")
+                .arg(
+                    Arg::with_name("listen")
+                        .long("listen")
+                        .help("Address to listen on")
+                        .default_value("127.0.0.1:8089")
+                        .takes_value(true)
+                )
+        )
+}
+
+fn parse_args(args: &ArgMatches) -> Result<Config, Box<dyn Error>> {
 
-    let args = app.clone().get_matches();
+    // targets are submitted via the api in the serve mode
+    let listen = match args.subcommand_matches("serve") {
+        Some(serve) => serve.value_of("listen").unwrap().to_string(),
+        None => String::new(),
+    };
 
-    if args.value_of("url").is_none() && args.value_of("request").is_none() {
+    if args.value_of("url").is_none() && args.value_of("request").is_none() && args.value_of("curl").is_none() && args.value_of("openapi").is_none() && args.value_of("har").is_none() && args.value_of("urls-file").is_none() && listen.is_empty() {
         Err("A target was not provided")?;
     }
 
     // parse numbers
     let delay = Duration::from_millis(args.value_of("delay").unwrap().parse()?);
+    let rate = args.value_of("rate").unwrap().parse()?;
+    let rate_limit = args.value_of("rate-limit").unwrap().parse()?;
+
+    if args.is_present("adaptive-rate") && rate_limit == 0 {
+        Err("--adaptive-rate requires --rate-limit")?;
+    }
 
     let learn_requests_count = args.value_of("learn-requests-count").unwrap().parse()?;
     let concurrency = args.value_of("concurrency").unwrap().parse()?;
     let workers = args.value_of("workers").unwrap().parse()?;
+    let host_workers = args.value_of("host-workers").unwrap().parse()?;
     let verbose = args.value_of("verbose").unwrap().parse()?;
     let timeout = args.value_of("timeout").unwrap().parse()?;
+    let connect_timeout = args.value_of("connect-timeout").unwrap().parse()?;
+
+    if timeout == 0 || connect_timeout == 0 {
+        Err("--timeout and --connect-timeout should be greater than 0")?;
+    }
     let recursion_depth = args.value_of("recursion-depth").unwrap_or("0").parse()?;
     let progress_bar_len = args.value_of("progress-bar-len").unwrap().parse()?;
+    let max_printed = args.value_of("max-printed").unwrap().parse()?;
+    let max_page_params = args.value_of("max-page-params").unwrap().parse()?;
+
+    let redirects = RedirectPolicy {
+        max_hops: if args.is_present("follow-redirects") {
+            args.value_of("max-redirects").unwrap().parse()?
+        } else {
+            0
+        },
+        same_host: !args.is_present("cross-host-redirects"),
+    };
+
+    let shard = match args.value_of("shard") {
+        Some(val) => Some(parse_shard(val)?),
+        None => None,
+    };
 
     let max: Option<usize> = if args.is_present("max") {
         Some(args.value_of("max").unwrap().parse()?)
@@ -373,6 +958,99 @@ Conflicts with --verify for now.")
         None
     };
 
+    // partial json bodies contain only one parameter
+    let chunk_strategy = match args.value_of("chunking").unwrap() {
+        _ if args.is_present("partial-json") => ChunkStrategy::Single,
+        "fixed" => ChunkStrategy::Fixed,
+        "url-length" => ChunkStrategy::UrlLength(args.value_of("max-url-length").unwrap().parse()?),
+        "single" => ChunkStrategy::Single,
+        _ => Err("Incorrect --chunking strategy specified")?,
+    };
+
+    let reflection_param_length: usize = args.value_of("reflection-param-length").unwrap().parse()?;
+
+    if reflection_param_length == 0 {
+        Err("--reflection-param-length should be greater than 0. Use --reflection-param off to disable the parameter")?;
+    }
+
+    let reflection_param = match args.value_of("reflection-param").unwrap() {
+        "inline" => ReflectionParam::Inline(reflection_param_length),
+        "separate" => ReflectionParam::Separate(reflection_param_length),
+        "off" if args.is_present("reflected-only") => Err("--reflected-only can't be used with --reflection-param off")?,
+        "off" => ReflectionParam::Off,
+        _ => Err("Incorrect --reflection-param specified")?,
+    };
+
+    let body_framing = match args.value_of("body-framing").unwrap() {
+        "auto" => BodyFraming::Auto,
+        "content-length" => BodyFraming::ContentLength,
+        "chunked" => BodyFraming::Chunked,
+        "both" => BodyFraming::Both,
+        _ => Err("Incorrect --body-framing specified")?,
+    };
+
+    let retries = args.value_of("retries").unwrap().parse()?;
+    let retry = match args.values_of("retry-on") {
+        Some(val) => RetryPolicy::new(retries, val)?,
+        None => RetryPolicy {
+            retries,
+            ..Default::default()
+        },
+    };
+
+    // -w debug,admin is an inline wordlist unless there's such a file
+    let (wordlist, mut inline_params) = match args.value_of("wordlist").unwrap_or("") {
+        val if val.contains(',') && !Path::new(val).exists() => (
+            String::new(),
+            val.split(',').map(|x| x.trim()).filter(|x| !x.is_empty()).map(|x| x.to_string()).collect(),
+        ),
+        val => (val.to_string(), Vec::new()),
+    };
+
+    inline_params.extend(args.values_of("param").map(|x| x.map(|x| x.to_string()).collect::<Vec<String>>()).unwrap_or_default());
+
+    let value_generators = ValueGenerators::new(
+        args.value_of("value-type").unwrap(),
+        &args.values_of("param-value-type").map(|x| x.collect::<Vec<&str>>()).unwrap_or_default(),
+    )?;
+
+    let value_transform = args.value_of("value-transform").unwrap_or("").to_string();
+
+    let required = args
+        .values_of("required")
+        .map(|x| {
+            x.map(|x| match x.split_once('=') {
+                Some((k, v)) if !k.is_empty() => Ok((k.to_string(), v.to_string())),
+                _ => Err(format!("Unable to parse --required {}. Use the key=value format", x)),
+            })
+            .collect::<Result<Vec<_>, _>>()
+        })
+        .transpose()?
+        .unwrap_or_default();
+
+    let identities = args
+        .values_of("as")
+        .map(|x| x.map(parse_identity).collect::<Result<Vec<_>, _>>())
+        .transpose()?
+        .unwrap_or_default();
+
+    let targets = match args.value_of("urls-file") {
+        Some(filename) => read_urls_file(filename)?,
+        None => Targets::default(),
+    };
+
+    // the wordlist can't be piped along with urls
+    if args.value_of("urls-file") == Some("-") && wordlist.is_empty() && inline_params.is_empty() {
+        Err("Urls are read from stdin, so specify parameters via -w or --param")?;
+    }
+
+    // huge wordlists are read by parts while they're checked
+    let stream_wordlist = !wordlist.is_empty() && fs::metadata(&wordlist).map(|x| x.len() > STREAM_WORDLIST_SIZE).unwrap_or(false);
+
+    if stream_wordlist && args.is_present("resume") {
+        Err("--resume needs the whole wordlist in memory, so it doesn't work with wordlists larger than 64MB. Split the wordlist, e.g. with --shard")?;
+    }
+
     if workers == 1 && args.is_present("one-worker-per-host") && !args.is_present("force") {
         Err("The --one-worker-per-host option doesn't increase the amount of workers. \
 So there's no point in --one-worker-per-host with 1 worker. \
@@ -391,6 +1069,8 @@ Increase the amount of workers to remove the error or use --force.")?;
                 Some(DataType::Json)
             } else if val == "urlencoded" {
                 Some(DataType::Urlencoded)
+            } else if val == "multipart" {
+                Some(DataType::Multipart)
             } else {
                 Err("Incorrect --data-type specified")?
             }
@@ -402,12 +1082,8 @@ Increase the amount of workers to remove the error or use --force.")?;
     // either via the request file or via provided parameters
     let (methods, urls, mut headers, body, data_type, http_version) = if !request.is_empty() {
         // if the request file is specified - get protocol (https/http) from args, specify scheme and port, and parse request file
-        let proto = args
-            .value_of("proto")
-            .unwrap_or("https")
-            .to_string();
-
-        let scheme = proto.replace("://", "");
+        // the scheme from the absolute url in the request line is used unless --proto is specified
+        let scheme = args.value_of("proto").map(|x| x.replace("://", ""));
 
         let port: Option<u16> = if args.value_of("port").is_some() {
             Some(args.value_of("port").unwrap().parse()?)
@@ -415,7 +1091,9 @@ Increase the amount of workers to remove the error or use --force.")?;
             None
         };
 
-        parse_request(&request, &scheme, port, data_type, args.value_of("split-by"))?
+        parse_request(&request, scheme.as_deref(), port, data_type, args.value_of("split-by"))?
+    } else if let Some(command) = args.value_of("curl") {
+        parse_curl(command, data_type)?
     } else {
         // parse everything from user-supplied command line arguments
         let methods = if args.is_present("method") {
@@ -484,18 +1162,18 @@ Increase the amount of workers to remove the error or use --force.")?;
             None
         };
 
-        let urls = args
-            .values_of("url")
-            .unwrap();
-
-        let urls = if urls.len() == 1 && !urls.clone().any(|x| x.contains("://")) {
-            // it can be a file
-            match read_urls_if_possible(urls.clone().next().unwrap())? {
-                Some(urls) => urls,
-                None => Err("The provided --url value is neither url nor a filename.")?
+        let urls: Vec<String> = match args.values_of("url") {
+            _ if !targets.urls.is_empty() => targets.urls.clone(),
+            Some(urls) if urls.len() == 1 && !urls.clone().any(|x| x.contains("://")) => {
+                // it can be a file
+                match read_urls_if_possible(urls.clone().next().unwrap())? {
+                    Some(urls) => urls,
+                    None => Err("The provided --url value is neither url nor a filename.")?
+                }
             }
-        } else {
-            urls.map(|x| x.to_string()).collect()
+            Some(urls) => urls.map(|x| x.to_string()).collect(),
+            // the serve mode
+            None => Vec::new(),
         };
 
         let urls = urls.iter().map(|x| Url::parse(x))
@@ -514,13 +1192,67 @@ Increase the amount of workers to remove the error or use --force.")?;
                     .map(|x| x.as_ref().unwrap().to_string())
                     .collect::<Vec<String>>(),
                 headers,
-                args.value_of("body").unwrap_or("").to_string(),
+                read_body(args.value_of("body").unwrap_or(""))?,
                 data_type,
                 http_version
             )
         }
     };
 
+    // urls and methods are taken from the spec (-u is the base url then) or the HAR file
+    let mut operations = if let Some(filename) = args.value_of("openapi") {
+        read_openapi(filename, urls.first().map(|x| x.as_str()))?
+    } else if let Some(filename) = args.value_of("har") {
+        read_har(filename, args.value_of("har-entries"))?
+    } else {
+        Vec::new()
+    };
+
+    // unsafe operations are skipped instead of refusing the whole spec
+    if args.is_present("safe") {
+        operations.retain(|x| !UNSAFE_METHODS.contains(&x.method.as_str()));
+
+        if operations.is_empty() && (args.is_present("openapi") || args.is_present("har")) {
+            Err("Every operation was skipped because of --safe")?;
+        }
+    }
+
+    let (methods, urls) = if operations.is_empty() {
+        (methods, urls)
+    } else {
+        let mut methods: Vec<String> = Vec::new();
+        let mut urls: Vec<String> = Vec::new();
+
+        for operation in operations.iter() {
+            if !methods.contains(&operation.method) {
+                methods.push(operation.method.clone());
+            }
+            if !urls.contains(&operation.url) {
+                urls.push(operation.url.clone());
+            }
+        }
+
+        (methods, urls)
+    };
+
+    let categories = match args.value_of("categories") {
+        Some(filename) => read_categories(filename)?,
+        None => Vec::new(),
+    };
+
+    // every parameter is sent as the only member of the json body
+    let (body, data_type) = if args.is_present("partial-json") {
+        let has_body = |method: &String| ["POST", "PUT", "PATCH", "DELETE"].contains(&method.as_str());
+
+        if methods.iter().any(|x| has_body(x) == args.is_present("invert")) {
+            Err("--partial-json sends parameters within the body. Use methods like PATCH or --invert")?;
+        }
+
+        ("{%s}".to_string(), Some(DataType::Json))
+    } else {
+        (body, data_type)
+    };
+
     // generate custom param values like admin=true
     let custom_keys: Vec<String> = match args.values_of("custom-parameters") {
         Some(val) => val.map(|x| x.to_string()).collect(),
@@ -560,6 +1292,22 @@ Increase the amount of workers to remove the error or use --force.")?;
         custom_parameters.insert(key.to_string(), values);
     }
 
+    if args.is_present("safe") {
+        if let Some(method) = methods.iter().find(|x| UNSAFE_METHODS.contains(&x.to_uppercase().as_str())) {
+            Err(format!("--safe refuses the {} method", method))?;
+        }
+
+        strip_destructive_values(&mut custom_parameters);
+
+        if let Some(word) = destructive_word(&body) {
+            writeln!(
+                io::stdout(),
+                "[#] The body looks like a state-changing operation ({}). Every request is going to repeat it",
+                word
+            ).ok();
+        }
+    }
+
     // disable colors
     if args.is_present("disable-colors") {
         colored::control::set_override(false);
@@ -578,6 +1326,23 @@ Increase the amount of workers to remove the error or use --force.")?;
         verbose
     };
 
+    // the only output is the final json document
+    let json_only = args.is_present("json-only");
+    let verbose = if json_only { 0 } else { verbose };
+
+    let cert = args.value_of("cert").unwrap_or("").to_string();
+    let key = args.value_of("key").unwrap_or("").to_string();
+    let cert_password = args.value_of("cert-password").unwrap_or("").to_string();
+    let ca_cert = args.value_of("ca-cert").unwrap_or("").to_string();
+
+    // otherwise every url would fail with the same error
+    if !cert.is_empty() {
+        load_identity(&cert, &key, &cert_password)?;
+    }
+    if !ca_cert.is_empty() {
+        load_ca_certs(&ca_cert)?;
+    }
+
     let proxy = if args.is_present("burp-proxy") {
         "http://localhost:8080".to_string()
     } else {
@@ -586,39 +1351,94 @@ Increase the amount of workers to remove the error or use --force.")?;
 
     if args.is_present("cookies") {
         if let Some(index) = headers.get_index_case_insensitive("cookie") {
-            headers[index] = (headers[index].0.clone(), headers[index].1.clone()+";%s")
+            headers[index] = (headers[index].0.clone(), headers[index].1.clone()+"; %s")
         } else {
             headers.push(("Cookie".to_string(), "%s".to_string()));
         }
     }
 
+    // handshakes are possible only over HTTP/1.1
+    let (urls, http_version) = if args.is_present("websocket") {
+        for (k, v) in [
+            ("Upgrade", "websocket"),
+            ("Connection", "Upgrade"),
+            ("Sec-WebSocket-Version", "13"),
+            ("Sec-WebSocket-Key", WEBSOCKET_KEY),
+        ] {
+            match headers.get_index_case_insensitive(k) {
+                Some(index) => headers[index].1 = v.to_string(),
+                None => headers.push((k.to_string(), v.to_string())),
+            }
+        }
+
+        (urls.iter().map(|x| websocket_to_http(x)).collect(), Some(http::Version::HTTP_11))
+    } else {
+        (urls, http_version)
+    };
+
+    if let Some(origin) = args.value_of("cors-origin") {
+        if let Some(index) = headers.get_index_case_insensitive("origin") {
+            headers[index] = (headers[index].0.clone(), origin.to_string())
+        } else {
+            headers.push(("Origin".to_string(), origin.to_string()));
+        }
+    }
+
     // TODO maybe replace empty with None
     Ok(Config {
+        listen,
         urls,
         methods,
-        wordlist: args.value_of("wordlist").unwrap_or("").to_string(),
+        wordlist,
+        stream_wordlist,
+        inline_params,
         custom_parameters,
         proxy,
+        cert,
+        key,
+        cert_password,
+        ca_cert,
+        insecure: args.is_present("insecure"),
         replay_proxy: args.value_of("replay-proxy").unwrap_or("").to_string(),
         replay_once: args.is_present("replay-once"),
         output_file: args.value_of("output").unwrap_or("").to_string(),
         save_responses: args.value_of("save-responses").unwrap_or("").to_string(),
+        diffs_file: args.value_of("diffs-file").unwrap_or("").to_string(),
+        audit_log: args.value_of("audit-log").unwrap_or("").to_string(),
+        low_memory: args.is_present("low-memory"),
+        websocket: args.is_present("websocket"),
+        compare_with: args.value_of("compare-with").unwrap_or("").to_string(),
+        categories,
+        compare_url: args.value_of("compare-url").unwrap_or("").to_string(),
+        save_baseline: args.value_of("save-baseline").unwrap_or("").to_string(),
+        baseline: args.value_of("baseline").unwrap_or("").to_string(),
+        identities,
         output_format: args.value_of("output-format").unwrap_or("").to_string(),
         append: args.is_present("append"),
         remove_empty: args.is_present("remove-empty"),
         force: args.is_present("force"),
         strict: args.is_present("strict"),
-        disable_progress_bar: args.is_present("disable-progress-bar"),
+        safe: args.is_present("safe"),
+        disable_progress_bar: args.is_present("disable-progress-bar") || json_only,
         progress_bar_len,
-        follow_redirects: args.is_present("follow-redirects"),
+        max_printed,
+        shard,
+        redirects,
         test: args.is_present("test"),
         verbose,
         learn_requests_count,
         concurrency,
         workers,
         timeout,
+        connect_timeout,
         recursion_depth,
-        verify: args.is_present("verify"),
+        stop_after: args.value_of("stop-after").unwrap_or("0").parse()?,
+        json_depth: args.value_of("json-depth").unwrap_or("0").parse()?,
+        watch_wordlist: args.value_of("watch-wordlist").unwrap_or("").to_string(),
+        max_bytes: parse_size(args.value_of("max-bytes").unwrap_or("0"))?,
+        verify: args.is_present("verify") || args.is_present("verify-proxy"),
+        verify_proxy: args.value_of("verify-proxy").unwrap_or("").to_string(),
+        keep_aliases: args.is_present("keep-aliases"),
         reflected_only: args.is_present("reflected-only"),
         http_version,
         template: convert_to_string_if_some(args.value_of("parameter-template")),
@@ -626,19 +1446,60 @@ Increase the amount of workers to remove the error or use --force.")?;
         encode: args.is_present("encode"),
         disable_custom_parameters: args.is_present("disable-custom-parameters"),
         one_worker_per_host: args.is_present("one-worker-per-host"),
+        host_workers,
+        host_overrides: targets.hosts,
         invert: args.is_present("invert"),
         headers_discovery: args.is_present("headers-discovery") || args.is_present("cookies"),
+        header_folding: args.is_present("header-folding"),
+        duplicate_keys: args.is_present("duplicate-keys"),
+        save_skipped: args.value_of("save-skipped").unwrap_or("").to_string(),
+        combinations: args.value_of("combinations").unwrap_or("0").parse()?,
+        resume: args.value_of("resume").unwrap_or("").to_string(),
+        path_segments: args.is_present("path-segments"),
+        keep_query: args.is_present("keep-query"),
+        match_casing: args.is_present("match-casing"),
+        source_maps: args.is_present("source-maps"),
+        framework_words: args.is_present("framework-words"),
+        max_page_params,
+        nested: args.value_of("nested").unwrap_or("").to_string(),
+        required,
+        operations,
+        discover_methods: args.is_present("discover-methods"),
+        raw_path: args.is_present("raw-path"),
         body,
         delay,
+        rate,
+        rate_limit,
+        adaptive_rate: args.is_present("adaptive-rate"),
         custom_headers: headers
             .iter()
             .map(|(k, v)| (k.to_string(), v.to_string()))
             .collect(),
         data_type,
         max,
+        chunk_strategy,
+        reflection_param,
+        body_framing,
+        retry,
+        value_generators,
+        value_transform,
+        warmup: args.value_of("warmup").unwrap().parse()?,
+        network_estimates: args.is_present("network-estimates"),
+        max_header_bytes: args.value_of("max-header-bytes").unwrap().parse()?,
         disable_colors: args.is_present("disable-colors"),
-        remove_banner: args.is_present("remove-banner"),
+        remove_banner: args.is_present("remove-banner") || json_only,
+        json_only,
+        sticky_cookies: args.values_of("sticky-cookie").map(|x| x.map(|x| x.to_string()).collect()).unwrap_or_default(),
+        sticky_headers: args.values_of("sticky-header").map(|x| x.map(|x| x.to_string()).collect()).unwrap_or_default(),
+        doh: args.value_of("doh").unwrap_or("").to_string(),
+        cache_ttl: args.value_of("cache-ttl").unwrap().parse()?,
+        disable_preflight: args.is_present("disable-preflight"),
+        fail_on_found: args.is_present("fail-on-found"),
+        fail_on_error_only: args.is_present("fail-on-error-only"),
         disable_trustdns: args.is_present("disable-trustdns"),
         check_binary: args.is_present("check-binary"),
+        cors: args.is_present("cors"),
+        security_headers: args.is_present("security-headers"),
+        state: Default::default(),
     })
 }
@@ -0,0 +1,179 @@
+use std::{
+    error::Error,
+    io::{self, Write},
+};
+
+use atty::Stream;
+use serde_json::Value;
+use url::Url;
+
+use crate::network::utils::DataType;
+
+use super::openapi::Operation;
+
+/// requests for these files don't have parameters worth checking
+const STATIC_EXTENSIONS: [&str; 14] = [
+    ".js", ".css", ".map", ".png", ".jpg", ".jpeg", ".gif", ".svg", ".ico", ".webp", ".woff", ".woff2", ".ttf", ".eot",
+];
+
+/// reads requests from the HAR file (--har)
+/// entries are selected by indexes like 1,3-5. In case they aren't specified, the list is shown and the selection is asked for
+pub fn read_har(filename: &str, selection: Option<&str>) -> Result<Vec<Operation>, Box<dyn Error>> {
+    let text = match std::fs::read_to_string(filename) {
+        Ok(val) => val,
+        Err(err) => Err(format!("Unable to read the HAR file {}: {}", filename, err))?,
+    };
+
+    let entries = parse_har(&serde_json::from_str(&text)?)?;
+
+    if entries.is_empty() {
+        Err(format!("No requests were found within {}", filename))?;
+    }
+
+    let selection = match selection {
+        Some(val) => val.to_string(),
+        // the answer can't be read from the piped stdin
+        None if atty::is(Stream::Stdin) && atty::is(Stream::Stdout) => ask_selection(&entries)?,
+        None => String::new(),
+    };
+
+    let indexes = parse_selection(&selection, entries.len())?;
+
+    let mut operations: Vec<Operation> = Vec::new();
+
+    for index in indexes {
+        let entry = &entries[index - 1];
+
+        // only one request per method and url can be checked
+        if !operations.iter().any(|x| x.method == entry.method && x.url == entry.url) {
+            operations.push(entry.clone());
+        }
+    }
+
+    Ok(operations)
+}
+
+/// requests from the HAR document except for static files and methods without parameters
+pub fn parse_har(har: &Value) -> Result<Vec<Operation>, Box<dyn Error>> {
+    let entries = match har["log"]["entries"].as_array() {
+        Some(val) => val,
+        None => Err("Unable to parse the HAR file: log.entries is missing")?,
+    };
+
+    let mut operations = Vec::new();
+
+    for request in entries.iter().map(|x| &x["request"]) {
+        let method = request["method"].as_str().unwrap_or_default().to_uppercase();
+
+        let url = match request["url"].as_str().map(Url::parse) {
+            Some(Ok(val)) => val,
+            _ => continue,
+        };
+
+        if ["OPTIONS", "HEAD", "CONNECT", "TRACE"].contains(&method.as_str())
+            || !["http", "https"].contains(&url.scheme())
+            || STATIC_EXTENSIONS.iter().any(|x| url.path().to_lowercase().ends_with(x))
+        {
+            continue;
+        }
+
+        let mut headers: Vec<(String, String)> = Vec::new();
+
+        for header in request["headers"].as_array().into_iter().flatten() {
+            let (k, v) = match (header["name"].as_str(), header["value"].as_str()) {
+                (Some(k), Some(v)) => (k, v),
+                _ => continue,
+            };
+
+            // http/2 pseudo headers, the host is taken from the url and the length is calculated per request
+            if k.starts_with(':') || k.eq_ignore_ascii_case("host") || k.eq_ignore_ascii_case("content-length") {
+                continue;
+            }
+
+            // br and zstd responses can't be decoded
+            if k.eq_ignore_ascii_case("accept-encoding") {
+                headers.push((k.to_string(), "gzip, deflate".to_string()));
+            } else {
+                headers.push((k.to_string(), v.to_string()));
+            }
+        }
+
+        // some browsers export cookies separately
+        if !headers.iter().any(|(k, _)| k.eq_ignore_ascii_case("cookie")) {
+            let cookies: Vec<String> = request["cookies"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|x| Some(format!("{}={}", x["name"].as_str()?, x["value"].as_str()?)))
+                .collect();
+
+            if !cookies.is_empty() {
+                headers.push(("Cookie".to_string(), cookies.join("; ")));
+            }
+        }
+
+        let mime_type = request["postData"]["mimeType"].as_str().unwrap_or_default();
+
+        let data_type = if mime_type.contains("json") {
+            Some(DataType::Json)
+        } else if mime_type.contains("multipart/form-data") {
+            Some(DataType::Multipart)
+        } else {
+            None
+        };
+
+        operations.push(Operation {
+            method,
+            url: url.to_string(),
+            data_type,
+            headers,
+            body: request["postData"]["text"].as_str().unwrap_or_default().to_string(),
+            ..Default::default()
+        });
+    }
+
+    Ok(operations)
+}
+
+/// 1-based indexes from the selection like 1,3-5. An empty selection selects every entry
+pub fn parse_selection(selection: &str, len: usize) -> Result<Vec<usize>, Box<dyn Error>> {
+    if selection.trim().is_empty() {
+        return Ok((1..=len).collect());
+    }
+
+    let mut indexes = Vec::new();
+
+    for part in selection.split(',').map(|x| x.trim()).filter(|x| !x.is_empty()) {
+        let (start, end) = match part.split_once('-') {
+            Some((start, end)) => (start.trim().parse::<usize>()?, end.trim().parse::<usize>()?),
+            None => (part.parse()?, part.parse()?),
+        };
+
+        if start == 0 || start > end || end > len {
+            Err(format!("Incorrect HAR entries {}. There are {} entries", part, len))?;
+        }
+
+        for index in start..=end {
+            if !indexes.contains(&index) {
+                indexes.push(index);
+            }
+        }
+    }
+
+    Ok(indexes)
+}
+
+/// prints the entries and reads the selection from stdin
+fn ask_selection(entries: &[Operation]) -> Result<String, Box<dyn Error>> {
+    for (i, entry) in entries.iter().enumerate() {
+        writeln!(io::stdout(), "{:>4}  {} {}", i + 1, entry.method, entry.url).ok();
+    }
+
+    write!(io::stdout(), "Entries to check (e.g. 1,3-5, empty for all): ").ok();
+    io::stdout().flush().ok();
+
+    let mut selection = String::new();
+    io::stdin().read_line(&mut selection)?;
+
+    Ok(selection)
+}
@@ -1,3 +1,7 @@
 pub mod args;
+pub mod har;
+pub mod openapi;
 pub mod structs;
 pub mod utils;
+
+mod tests;
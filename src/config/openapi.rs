@@ -0,0 +1,254 @@
+use std::error::Error;
+
+use serde_json::{Map, Value};
+use url::Url;
+
+use crate::network::utils::DataType;
+
+/// methods that are checked from the spec
+const METHODS: [&str; 5] = ["get", "post", "put", "patch", "delete"];
+
+/// a documented operation from the spec (--openapi) or a request from the HAR file (--har)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Operation {
+    pub method: String,
+
+    /// the url with example values in place of path parameters
+    pub url: String,
+
+    /// the format of the request body. None in case the operation has no body
+    pub data_type: Option<DataType>,
+
+    /// names of documented query and body parameters. They aren't checked
+    pub documented: Vec<String>,
+
+    /// required documented body parameters with example values. They're sent with every request
+    /// required query parameters are a part of the url instead, so they stay within the query regardless of the injection place
+    pub required: Vec<(String, String)>,
+
+    /// headers to send instead of the default ones. Empty in case the default ones are sent
+    pub headers: Vec<(String, String)>,
+
+    /// the original body parameters are added to
+    pub body: String,
+}
+
+/// reads operations from the OpenAPI 3 or Swagger 2 json spec
+/// the base url is taken from the spec unless it's provided via -u
+pub fn read_openapi(filename: &str, base_url: Option<&str>) -> Result<Vec<Operation>, Box<dyn Error>> {
+    let text = match std::fs::read_to_string(filename) {
+        Ok(val) => val,
+        Err(err) => Err(format!("Unable to read the spec from {}: {}", filename, err))?,
+    };
+
+    if !text.trim_start().starts_with('{') {
+        Err("Only json specs are supported. Convert the yaml one first, e.g. with `yq -o json`")?;
+    }
+
+    let operations = parse_openapi(&serde_json::from_str(&text)?, base_url)?;
+
+    if operations.is_empty() {
+        Err(format!("No operations were found within {}", filename))?;
+    }
+
+    Ok(operations)
+}
+
+pub fn parse_openapi(spec: &Value, base_url: Option<&str>) -> Result<Vec<Operation>, Box<dyn Error>> {
+    let base_url = match base_url {
+        Some(val) => val.to_string(),
+        None => spec_base_url(spec)?,
+    };
+
+    let empty = Map::new();
+    let mut operations = Vec::new();
+
+    for (path, item) in spec["paths"].as_object().unwrap_or(&empty) {
+        let item = resolve(spec, item);
+
+        for method in METHODS {
+            let operation = match item.get(method) {
+                Some(val) => val,
+                None => continue,
+            };
+
+            // operation parameters override path parameters with the same name and location
+            let mut parameters: Vec<&Value> = Vec::new();
+
+            for parameter in item["parameters"].as_array().into_iter().chain(operation["parameters"].as_array()).flatten() {
+                let parameter = resolve(spec, parameter);
+                parameters.retain(|x| x["name"] != parameter["name"] || x["in"] != parameter["in"]);
+                parameters.push(parameter);
+            }
+
+            let mut path = path.to_string();
+            let mut data_type = None;
+            let mut documented = Vec::new();
+            let mut required = Vec::new();
+            let mut required_query = Vec::new();
+
+            for parameter in parameters {
+                let name = match parameter["name"].as_str() {
+                    Some(val) => val,
+                    None => continue,
+                };
+
+                let schema = resolve(spec, &parameter["schema"]);
+
+                match parameter["in"].as_str() {
+                    Some("path") => path = path.replace(&format!("{{{}}}", name), &example_value(parameter, schema)),
+                    // swagger 2 body
+                    Some("body") => {
+                        data_type = Some(DataType::Json);
+                        add_properties(spec, schema, &mut documented, &mut required);
+                    }
+                    Some(place @ ("query" | "formData")) => {
+                        if place == "formData" {
+                            data_type = Some(DataType::Urlencoded);
+                        }
+
+                        documented.push(name.to_string());
+
+                        if parameter["required"] == true {
+                            let value = example_value(parameter, schema);
+
+                            if place == "query" {
+                                required_query.push((name.to_string(), value));
+                            } else {
+                                required.push((name.to_string(), value));
+                            }
+                        }
+                    }
+                    // headers and cookies
+                    _ => (),
+                }
+            }
+
+            // openapi 3 body
+            let content = resolve(spec, &operation["requestBody"])["content"].as_object().unwrap_or(&empty);
+
+            for (content_type, media) in content {
+                data_type = if content_type.contains("json") {
+                    Some(DataType::Json)
+                } else if content_type.contains("x-www-form-urlencoded") {
+                    Some(DataType::Urlencoded)
+                } else if content_type.contains("multipart/form-data") {
+                    Some(DataType::Multipart)
+                } else {
+                    continue;
+                };
+
+                add_properties(spec, resolve(spec, &media["schema"]), &mut documented, &mut required);
+                break;
+            }
+
+            // path parameters that aren't described within the operation
+            while let (Some(start), Some(end)) = (path.find('{'), path.find('}')) {
+                if end < start {
+                    break;
+                }
+                path.replace_range(start..=end, "1");
+            }
+
+            let mut url = Url::parse(&format!("{}{}", base_url.trim_end_matches('/'), path))?;
+
+            if !required_query.is_empty() {
+                url.query_pairs_mut().extend_pairs(&required_query);
+            }
+
+            operations.push(Operation {
+                method: method.to_uppercase(),
+                url: url.to_string(),
+                data_type,
+                documented,
+                required,
+                ..Default::default()
+            });
+        }
+    }
+
+    Ok(operations)
+}
+
+/// the first server from openapi 3 or the host with the base path from swagger 2
+fn spec_base_url(spec: &Value) -> Result<String, Box<dyn Error>> {
+    let base_url = if let Some(server) = spec["servers"].get(0) {
+        let mut url = server["url"].as_str().unwrap_or_default().to_string();
+
+        for (name, variable) in server["variables"].as_object().into_iter().flatten() {
+            url = url.replace(&format!("{{{}}}", name), variable["default"].as_str().unwrap_or_default());
+        }
+
+        url
+    } else if let Some(host) = spec["host"].as_str() {
+        format!(
+            "{}://{}{}",
+            spec["schemes"][0].as_str().unwrap_or("https"),
+            host,
+            spec["basePath"].as_str().unwrap_or_default()
+        )
+    } else {
+        String::new()
+    };
+
+    if !base_url.starts_with("http://") && !base_url.starts_with("https://") {
+        Err("The spec doesn't contain an absolute server url. Provide the base url via -u")?;
+    }
+
+    Ok(base_url)
+}
+
+/// adds properties of the object schema, including the ones from allOf
+fn add_properties(spec: &Value, schema: &Value, documented: &mut Vec<String>, required: &mut Vec<(String, String)>) {
+    for schema in schema["allOf"].as_array().into_iter().flatten() {
+        add_properties(spec, resolve(spec, schema), documented, required);
+    }
+
+    let properties = match schema["properties"].as_object() {
+        Some(val) => val,
+        None => return,
+    };
+
+    for (name, property) in properties {
+        documented.push(name.to_string());
+
+        if schema["required"].as_array().is_some_and(|x| x.iter().any(|x| x == name.as_str())) {
+            let property = resolve(spec, property);
+            required.push((name.to_string(), example_value(property, property)));
+        }
+    }
+}
+
+/// the example, the default or the first allowed value. A value of the right type otherwise
+fn example_value(parameter: &Value, schema: &Value) -> String {
+    let value = [
+        &parameter["example"],
+        &parameter["default"],
+        &parameter["enum"][0],
+        &schema["example"],
+        &schema["default"],
+        &schema["enum"][0],
+    ]
+    .iter()
+    .copied()
+    .find(|x| !x.is_null());
+
+    match value {
+        Some(Value::String(val)) => val.to_string(),
+        Some(val) => val.to_string(),
+        // swagger 2 parameters have types without schemas
+        None => match schema["type"].as_str().or_else(|| parameter["type"].as_str()) {
+            Some("integer") | Some("number") => "1".to_string(),
+            Some("boolean") => "true".to_string(),
+            _ => "test".to_string(),
+        },
+    }
+}
+
+/// follows the local $ref like #/components/schemas/User
+fn resolve<'a>(spec: &'a Value, value: &'a Value) -> &'a Value {
+    match value["$ref"].as_str().and_then(|x| x.strip_prefix('#')) {
+        Some(pointer) => spec.pointer(pointer).unwrap_or(&Value::Null),
+        None => value,
+    }
+}
@@ -1,12 +1,63 @@
-use std::{collections::HashMap, time::Duration};
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use serde::Deserialize;
+use url::Url;
+
+use crate::{
+    config::openapi::Operation,
+    network::{
+        state::RunState,
+        utils::{BodyFraming, DataType, RedirectPolicy, RetryPolicy},
+        values::ValueGenerators,
+    },
+    runner::{
+        categories::Category,
+        utils::{ChunkStrategy, ReflectionParam},
+    },
+};
+
+/// a name and headers to send as the identity (--as)
+pub type Identity = (String, Vec<(String, String)>);
+
+/// settings of a single host from the targets file (--urls-file)
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HostOverrides {
+    pub concurrency: Option<usize>,
+
+    /// in milliseconds
+    pub delay: Option<u64>,
+
+    /// requests per second
+    pub rate: Option<u32>,
+
+    /// concurrent url checks
+    pub workers: Option<usize>,
+
+    /// overwrite headers with the same names
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+}
 
-use crate::network::utils::DataType;
+/// the json variant of the targets file
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Targets {
+    pub urls: Vec<String>,
+
+    /// host -> overrides
+    #[serde(default)]
+    pub hosts: HashMap<String, HostOverrides>,
+}
 
 #[derive(Debug, Clone)]
 pub struct Config {
     /// default urls without any changes (except from when used from request file, maybe change this logic TODO)
     pub urls: Vec<String>,
 
+    /// address to listen on in the serve mode. Empty otherwise
+    pub listen: String,
+
     /// a list of methods to check urls with
     pub methods: Vec<String>,
 
@@ -16,13 +67,55 @@ pub struct Config {
     /// how much to sleep between requests in millisecs
     pub delay: Duration,
 
+    /// max amount of requests per second to a single host. 0 - no limit
+    pub rate: u32,
+
+    /// max amount of requests per second in total. 0 - no limit
+    pub rate_limit: u32,
+
+    /// lower the rate limit while the server responds with 429 or 503
+    pub adaptive_rate: bool,
+
     /// user supplied wordlist file
     pub wordlist: String,
 
+    /// the wordlist is too large to be loaded, so it's read by parts while being checked
+    pub stream_wordlist: bool,
+
+    /// parameters from the command line (-w a,b,c and --param)
+    pub inline_params: Vec<String>,
+
+    /// (index starting from 1, total) to check only a part of the wordlist (--shard 2/5)
+    pub shard: Option<(usize, usize)>,
+
     /// max amount of parameters to send per request.
     /// Can be specified by user otherwise detects automatically based on the request method
     pub max: Option<usize>,
 
+    /// how to group parameters into requests
+    pub chunk_strategy: ChunkStrategy,
+
+    /// the amount of connections to open before learning. 0 disables the warmup
+    pub warmup: usize,
+
+    /// estimate dns, connect, tls and ttfb timings with separate probes
+    pub network_estimates: bool,
+
+    /// the maximum size of headers per request while searching for headers. 0 in case there's no limit
+    pub max_header_bytes: usize,
+
+    /// how request bodies are framed (Content-Length or Transfer-Encoding)
+    pub body_framing: BodyFraming,
+
+    /// which failed requests are retried and how many times
+    pub retry: RetryPolicy,
+
+    /// the command to pass parameter values through before sending them (client-side encryption)
+    pub value_transform: String,
+
+    /// values for parameters without a user supplied value
+    pub value_generators: ValueGenerators,
+
     /// parameter template, for example %k=%v
     pub template: Option<String>,
 
@@ -45,6 +138,21 @@ pub struct Config {
     /// proxy server with schema or http:// by default.
     pub proxy: String,
 
+    /// the pem or PKCS#12 file with the client certificate (mutual tls)
+    pub cert: String,
+
+    /// the pem file with the private key of the client certificate. Empty in case the key is within the certificate file
+    pub key: String,
+
+    /// the password of the PKCS#12 client certificate
+    pub cert_password: String,
+
+    /// the pem file with custom root certificates
+    pub ca_cert: String,
+
+    /// skip verification of server certificates even with --ca-cert
+    pub insecure: bool,
+
     /// file to output
     pub output_file: String,
 
@@ -57,9 +165,39 @@ pub struct Config {
     /// output format for file & stdout outputs
     pub output_format: String,
 
+    /// json output of a previous run to report only new and removed parameters
+    pub compare_with: String,
+
+    /// named categories of findings (--categories)
+    pub categories: Vec<Category>,
+
+    /// a file to append initial responses to
+    pub save_baseline: String,
+
+    /// a file to load initial responses from instead of fetching them (--save-baseline output)
+    pub baseline: String,
+
+    /// the second url to check with the same parameters and compare results with (authenticated vs unauthenticated, v1 vs v2)
+    pub compare_url: String,
+
+    /// (name, headers) to run the discovery as (--as)
+    pub identities: Vec<Identity>,
+
     /// a directory for saving request & responses with found parameters
     pub save_responses: String,
 
+    /// file to write every evaluated diff to (ndjson)
+    pub diffs_file: String,
+
+    /// file to write send times of every request to (ndjson)
+    pub audit_log: String,
+
+    /// compare hashes of lines instead of copying pages
+    pub low_memory: bool,
+
+    /// send requests as WebSocket handshakes and compare the first frame after them
+    pub websocket: bool,
+
     /// ignore some custom errors like when page's size > MAX_PAGE_SIZE
     pub force: bool,
 
@@ -68,6 +206,9 @@ pub struct Config {
     /// greatly reduces false positives and a bit increases false negatives
     pub strict: bool,
 
+    /// refuse DELETE, PUT and PATCH methods and custom parameters like delete=true
+    pub safe: bool,
+
     /// custom parameters to check like <admin, [true, 1, false, ..]>
     pub custom_parameters: HashMap<String, Vec<String>>,
 
@@ -76,6 +217,9 @@ pub struct Config {
     /// the size of progress bar in chars
     pub progress_bar_len: usize,
 
+    /// stop printing found parameters one by one after the amount is exceeded for a single url. 0 - no limit
+    pub max_printed: usize,
+
     /// proxy to resend requests with found parameter
     pub replay_proxy: String,
 
@@ -98,6 +242,18 @@ pub struct Config {
     /// conflicts with --verify for now. Will be updated in the future.
     pub recursion_depth: usize,
 
+    /// stops checking the target once this amount of parameters is found. 0 - no limit
+    pub stop_after: usize,
+
+    /// checks keys within objects of found json parameters up to this amount of levels. 0 - disabled
+    pub json_depth: usize,
+
+    /// a file to take extra parameters from while the scan is running
+    pub watch_wordlist: String,
+
+    /// stops the run once that many bytes were sent and received. 0 - no limit
+    pub max_bytes: usize,
+
     /// amount of concurrent requests per url
     pub concurrency: usize,
 
@@ -107,17 +263,35 @@ pub struct Config {
     /// http request timeout in seconds
     pub timeout: usize,
 
+    /// the time to establish a connection in seconds. Capped by the request timeout
+    pub connect_timeout: usize,
+
     /// whether the verify found parameters one time more.
     /// in future wil check for _false_potives like when every parameter that starts with _ is found
     pub verify: bool,
 
+    /// proxy to verify found parameters through
+    pub verify_proxy: String,
+
+    /// don't collapse found parameters that produce the same responses
+    pub keep_aliases: bool,
+
     /// check only for reflected parameters in order to decrease the amount of requests
     /// usually makes 2+learn_request_count+words/max requests
     /// but in rare cases its number may be higher
     pub reflected_only: bool,
 
+    /// how the random parameter that counts the default amount of reflections is sent
+    pub reflection_param: ReflectionParam,
+
     pub one_worker_per_host: bool,
 
+    /// the max amount of concurrent url checks per host. 0 - no limit
+    pub host_workers: usize,
+
+    /// per host settings from the targets file
+    pub host_overrides: HashMap<String, HostOverrides>,
+
     pub http_version: Option<http::Version>,
 
     /// by default parameters are sent within the body only in case PUT or POST methods are used.
@@ -127,14 +301,162 @@ pub struct Config {
     /// true in case the injection points is within the header or the headers are injection point itself
     pub headers_discovery: bool,
 
-    pub follow_redirects: bool,
+    /// resend found headers with malformed whitespace to detect differential handling
+    pub header_folding: bool,
+
+    /// send found parameters twice with distinct values to detect which one is used
+    pub duplicate_keys: bool,
+
+    /// the amount of top found parameters to check in pairs. 0 - disabled
+    pub combinations: usize,
+
+    /// the file to save the scan state to and to continue the interrupted scan from
+    pub resume: String,
+
+    /// the file to append words that weren't checked to
+    pub save_skipped: String,
+
+    /// search for path segments (sub-resources and ids) instead of parameters
+    pub path_segments: bool,
+
+    /// send the query from the url as is and skip words that are already within it
+    pub keep_query: bool,
+
+    /// the max amount of words scraped from the page to check. 0 - no limit
+    pub max_page_params: usize,
+
+    /// check identifiers from source maps referenced by the page first
+    pub source_maps: bool,
+
+    /// check words specific to the detected backend framework first
+    pub framework_words: bool,
+
+    /// rewrite multiword parameters in the naming style used by the target
+    pub match_casing: bool,
+
+    /// the url parameter with a nested structure within its value to check parameters within
+    pub nested: String,
+
+    /// key=value pairs sent with every request and never checked (--required)
+    pub required: Vec<(String, String)>,
+
+    /// documented operations from the spec (--openapi) or requests from the HAR file (--har). Urls and methods are taken from them
+    pub operations: Vec<Operation>,
+
+    /// add methods from the OPTIONS response to the list of methods
+    pub discover_methods: bool,
+
+    /// send the path and query without percent encoding unsafe chars
+    pub raw_path: bool,
+
+    pub redirects: RedirectPolicy,
 
     pub disable_colors: bool,
 
     pub remove_banner: bool,
 
+    /// skip dns, connection, tls and baseline checks before the learn phase
+    pub disable_preflight: bool,
+
+    /// DNS-over-HTTPS server to resolve hosts with. Empty in case the system resolver is used
+    pub doh: String,
+
+    /// load balancer affinity cookies to capture from the first response and send with every request
+    pub sticky_cookies: Vec<String>,
+
+    /// the same as sticky_cookies, but for headers
+    pub sticky_headers: Vec<String>,
+
+    /// how long responses to control requests are reused (in seconds)
+    pub cache_ttl: u64,
+
+    /// print nothing except the final json document
+    pub json_only: bool,
+
+    /// exit with non-zero code in case any parameter was found
+    pub fail_on_found: bool,
+
+    /// exit with non-zero code only in case of errors
+    pub fail_on_error_only: bool,
+
     pub disable_trustdns: bool,
 
     /// check body of responses with binary content type
     pub check_binary: bool,
+
+    /// report parameters that change Access-Control-Allow-* headers
+    pub cors: bool,
+
+    /// report parameters that change security headers like Content-Security-Policy
+    pub security_headers: bool,
+
+    /// pacing, rate limits, learned settings and the traffic of the run. Every config gets its own
+    pub state: Arc<RunState>,
+}
+
+impl Config {
+    /// overrides for the host of the url from the targets file
+    pub fn host_overrides(&self, url: &str) -> Option<&HostOverrides> {
+        let url = Url::parse(url).ok()?;
+        self.host_overrides.get(url.host_str()?)
+    }
+
+    /// the documented operation from the spec (--openapi)
+    pub fn operation(&self, method: &str, url: &str) -> Option<&Operation> {
+        self.operations.iter().find(|x| x.method == method && x.url == url)
+    }
+
+    /// the config with the body, headers and required parameters of the operation applied. None in case there's no such operation
+    pub fn for_operation(&self, method: &str, url: &str) -> Option<Config> {
+        let operation = self.operation(method, url)?;
+        let mut config = self.clone();
+
+        if operation.data_type.is_some() {
+            config.data_type = operation.data_type.clone();
+        }
+
+        if !operation.headers.is_empty() {
+            config.custom_headers = operation.headers.clone();
+        }
+
+        if !operation.body.is_empty() {
+            config.body = operation.body.clone();
+        }
+
+        // parameters can't be sent along with headers or path segments being checked
+        for (k, v) in operation.required.iter().filter(|_| !self.headers_discovery && !self.path_segments) {
+            if !config.required.iter().any(|(x, _)| x == k) {
+                config.required.push((k.to_owned(), v.to_owned()));
+            }
+        }
+
+        Some(config)
+    }
+
+    /// the config with settings of the url's host applied. None in case the host has no overrides
+    pub fn for_url(&self, url: &str) -> Option<Config> {
+        let overrides = self.host_overrides(url)?;
+        let mut config = self.clone();
+
+        if let Some(concurrency) = overrides.concurrency {
+            config.concurrency = concurrency;
+        }
+
+        if let Some(delay) = overrides.delay {
+            config.delay = Duration::from_millis(delay);
+        }
+
+        if let Some(rate) = overrides.rate {
+            config.rate = rate;
+        }
+
+        for (k, v) in overrides.headers.iter() {
+            match config.custom_headers.iter().position(|(x, _)| x.eq_ignore_ascii_case(k)) {
+                Some(index) => config.custom_headers[index].1 = v.to_owned(),
+                None => config.custom_headers.push((k.to_owned(), v.to_owned())),
+            }
+        }
+
+        Some(config)
+    }
 }
@@ -0,0 +1,240 @@
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::config::{
+        args::get_config_from,
+        har::{parse_har, parse_selection},
+        openapi::parse_openapi,
+        utils::{destructive_word, parse_curl, read_body, strip_destructive_values, websocket_to_http},
+    };
+    use crate::network::utils::{looks_like_json, verifies_certs, DataType};
+
+    #[test]
+    fn safe_mode() {
+        for method in ["DELETE", "PUT", "patch"] {
+            assert!(get_config_from(["x8", "-u", "http://localhost/", "-X", method, "--safe"]).is_err());
+        }
+        assert!(get_config_from(["x8", "-u", "http://localhost/", "-X", "POST", "--safe"]).is_ok());
+
+        let mut custom_parameters: HashMap<String, Vec<String>> = HashMap::new();
+        custom_parameters.insert("delete".to_string(), vec!["true".to_string(), "false".to_string()]);
+        custom_parameters.insert("confirmAction".to_string(), vec!["1".to_string()]);
+        custom_parameters.insert("admin".to_string(), vec!["true".to_string()]);
+
+        strip_destructive_values(&mut custom_parameters);
+
+        assert_eq!(custom_parameters["delete"], ["false"]);
+        assert!(!custom_parameters.contains_key("confirmAction"));
+        assert_eq!(custom_parameters["admin"], ["true"]);
+
+        assert_eq!(destructive_word(r#"{"action":"delete","id":1}"#), Some("delete"));
+        assert_eq!(destructive_word("deleted=1"), None);
+    }
+
+    #[test]
+    fn certificate_verification() {
+        let mut config = get_config_from(["x8", "-u", "https://localhost/"]).unwrap();
+        assert!(!verifies_certs(&config));
+
+        // the client certificate alone doesn't turn the verification on
+        config.cert = "client.pem".to_string();
+        assert!(!verifies_certs(&config));
+
+        config.ca_cert = "ca.pem".to_string();
+        assert!(verifies_certs(&config));
+
+        config.insecure = true;
+        assert!(!verifies_certs(&config));
+
+        // certificates aren't verified without --ca-cert anyway
+        assert!(get_config_from(["x8", "-u", "https://localhost/", "--insecure"]).is_err());
+    }
+
+    #[test]
+    fn body_from_file() {
+        let dir = std::env::temp_dir();
+        let payload = dir.join("x8_body_payload.bin");
+        let template = dir.join("x8_body_template.json");
+
+        std::fs::write(&payload, "a".repeat(100_000) + "%").unwrap();
+        std::fs::write(&template, "a".repeat(64 * 1024) + "%s}").unwrap();
+
+        // files without injection points are streamed instead of being read
+        assert_eq!(
+            read_body(&format!("@{}", payload.display())).unwrap(),
+            format!("{{{{file:{}}}}}", payload.display())
+        );
+        // the marker on the border of two chunks
+        assert_eq!(read_body(&format!("@{}", template.display())).unwrap(), "a".repeat(64 * 1024) + "%s}");
+
+        assert!(!looks_like_json(&read_body(&format!("@{}", payload.display())).unwrap()));
+
+        assert_eq!(read_body("@@mention").unwrap(), "@mention");
+        assert_eq!(read_body("{\"a\":1}").unwrap(), "{\"a\":1}");
+        assert!(read_body("@/nonexistent/x8/body").is_err());
+    }
+
+    #[test]
+    fn websocket_urls() {
+        assert_eq!(websocket_to_http("ws://example.com/chat"), "http://example.com/chat");
+        assert_eq!(websocket_to_http("wss://example.com:8443/chat?a=b"), "https://example.com:8443/chat?a=b");
+        assert_eq!(websocket_to_http("https://example.com/"), "https://example.com/");
+
+        let config = get_config_from(["x8", "-u", "wss://example.com/chat", "--websocket"]).unwrap();
+
+        assert_eq!(config.urls, vec!["https://example.com/chat"]);
+        assert!(config.websocket);
+        assert!(config.custom_headers.iter().any(|(k, v)| k == "Upgrade" && v == "websocket"));
+    }
+
+    #[test]
+    fn curl_command_parsing() {
+        let (methods, urls, headers, body, data_type, _) = parse_curl(
+            "curl 'https://example.com/api?x=1' \\\n  -H 'content-type: application/json' \\\n  -H \"Accept: */*\" \\\n  -b 'session=abc' \\\n  --data-raw $'{\"a\":\"b\\u0021\"}' \\\n  --compressed",
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(methods, vec!["POST"]);
+        assert_eq!(urls, vec!["https://example.com/api?x=1"]);
+        assert_eq!(
+            headers,
+            vec![
+                ("content-type".to_string(), "application/json".to_string()),
+                ("Accept".to_string(), "*/*".to_string()),
+                ("Cookie".to_string(), "session=abc".to_string()),
+            ]
+        );
+        assert_eq!(body, "{\"a\":\"b!\"}");
+        assert_eq!(data_type, Some(DataType::ProbablyJson));
+
+        let (methods, urls, headers, body, _, _) = parse_curl("curl -XPUT -G -d a=1 example.com", None).unwrap();
+
+        assert_eq!(methods, vec!["PUT"]);
+        assert_eq!(urls, vec!["http://example.com/?a=1"]);
+        assert!(headers.is_empty());
+        assert!(body.is_empty());
+
+        assert!(parse_curl("curl 'https://example.com", None).is_err());
+
+        // the value of an unknown option isn't taken as the url
+        assert!(parse_curl("curl --limit-rate 10k https://example.com", None).is_err());
+        assert_eq!(parse_curl("curl -sSL https://example.com", None).unwrap().1, vec!["https://example.com/"]);
+    }
+
+    #[test]
+    fn openapi_operations() {
+        let spec = serde_json::json!({
+            "servers": [{"url": "https://{env}.example.com/v1", "variables": {"env": {"default": "api"}}}],
+            "paths": {
+                "/users/{id}": {
+                    "parameters": [{"name": "id", "in": "path", "required": true, "schema": {"type": "integer"}}],
+                    "get": {
+                        "parameters": [
+                            {"$ref": "#/components/parameters/Token"},
+                            {"name": "fields", "in": "query", "schema": {"type": "string"}}
+                        ]
+                    },
+                    "patch": {
+                        "requestBody": {
+                            "content": {"application/json": {"schema": {"$ref": "#/components/schemas/User"}}}
+                        }
+                    },
+                    "head": {}
+                }
+            },
+            "components": {
+                "parameters": {"Token": {"name": "token", "in": "query", "required": true, "example": "abc"}},
+                "schemas": {
+                    "User": {
+                        "required": ["name"],
+                        "properties": {"name": {"type": "string", "enum": ["admin"]}, "age": {"type": "integer"}}
+                    }
+                }
+            }
+        });
+
+        let operations = parse_openapi(&spec, None).unwrap();
+
+        assert_eq!(operations.len(), 2);
+
+        // PATCH operations are skipped with --safe
+        let filename = std::env::temp_dir().join("x8_openapi_safe_test.json");
+        std::fs::write(&filename, spec.to_string()).unwrap();
+
+        let config = get_config_from(["x8", "--openapi", filename.to_str().unwrap(), "--safe"]).unwrap();
+
+        std::fs::remove_file(&filename).ok();
+
+        assert_eq!(config.operations.len(), 1);
+        assert_eq!(config.methods, vec!["GET"]);
+
+        assert_eq!(operations[0].method, "GET");
+        assert_eq!(operations[0].url, "https://api.example.com/v1/users/1?token=abc");
+        assert_eq!(operations[0].data_type, None);
+        assert_eq!(operations[0].documented, vec!["token", "fields"]);
+        // required query parameters stay within the query
+        assert!(operations[0].required.is_empty());
+
+        assert_eq!(operations[1].method, "PATCH");
+        assert_eq!(operations[1].data_type, Some(DataType::Json));
+        assert_eq!(operations[1].documented, vec!["age", "name"]);
+        assert_eq!(operations[1].required, vec![("name".to_string(), "admin".to_string())]);
+
+        // swagger 2 with the base url from -u
+        let spec = serde_json::json!({
+            "host": "example.com",
+            "paths": {"/login": {"post": {"parameters": [{"name": "user", "in": "formData", "type": "string", "required": true}]}}}
+        });
+
+        let operations = parse_openapi(&spec, Some("http://localhost:8080/")).unwrap();
+
+        assert_eq!(operations[0].url, "http://localhost:8080/login");
+        assert_eq!(operations[0].data_type, Some(DataType::Urlencoded));
+        assert_eq!(operations[0].required, vec![("user".to_string(), "test".to_string())]);
+
+        assert!(parse_openapi(&serde_json::json!({"servers": [{"url": "/v1"}], "paths": {}}), None).is_err());
+    }
+
+    #[test]
+    fn har_entries() {
+        let har = serde_json::json!({"log": {"entries": [
+            {"request": {
+                "method": "POST",
+                "url": "https://example.com/api/login",
+                "headers": [
+                    {"name": ":authority", "value": "example.com"},
+                    {"name": "Content-Type", "value": "application/json"},
+                    {"name": "Content-Length", "value": "13"},
+                    {"name": "Accept-Encoding", "value": "gzip, deflate, br, zstd"}
+                ],
+                "cookies": [{"name": "session", "value": "abc"}],
+                "postData": {"mimeType": "application/json", "text": "{\"user\":\"a\"}"}
+            }},
+            {"request": {"method": "GET", "url": "https://example.com/static/app.js", "headers": []}},
+            {"request": {"method": "GET", "url": "https://example.com/search?q=1", "headers": []}}
+        ]}});
+
+        let entries = parse_har(&har).unwrap();
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].method, "POST");
+        assert_eq!(entries[0].data_type, Some(DataType::Json));
+        assert_eq!(entries[0].body, "{\"user\":\"a\"}");
+        assert_eq!(
+            entries[0].headers,
+            vec![
+                ("Content-Type".to_string(), "application/json".to_string()),
+                ("Accept-Encoding".to_string(), "gzip, deflate".to_string()),
+                ("Cookie".to_string(), "session=abc".to_string()),
+            ]
+        );
+        assert_eq!(entries[1].url, "https://example.com/search?q=1");
+
+        assert_eq!(parse_selection("", 3).unwrap(), vec![1, 2, 3]);
+        assert_eq!(parse_selection("3, 1-2,2", 3).unwrap(), vec![3, 1, 2]);
+        assert!(parse_selection("2-4", 3).is_err());
+        assert!(parse_selection("0", 3).is_err());
+    }
+}
@@ -2,14 +2,43 @@ use std::{
     fs::File,
     collections::HashMap,
     error::Error,
-    io::{self, BufRead, Write},
+    io::{self, BufRead, Read, Write},
 };
 
 use colored::Colorize;
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use url::Url;
 
-use crate::network::utils::DataType;
+use crate::{
+    network::utils::{split_body_includes, BodyPart, DataType},
+    utils::read_stdin_lines,
+    BODY_FILE_CHUNK_SIZE,
+};
+
+use super::structs::{Config, Identity, Targets};
+
+/// parts of parameter names that may trigger state changes when set to a truthy value (--safe)
+const DESTRUCTIVE_KEYS: [&str; 12] = [
+    "delete", "remove", "destroy", "drop", "purge", "erase", "wipe", "reset", "confirm", "force", "overwrite", "truncate",
+];
+
+const TRUTHY_VALUES: [&str; 5] = ["1", "true", "yes", "on", "y"];
+
+/// words in the body of a state-changing operation (--safe)
+const DESTRUCTIVE_WORDS: [&str; 14] = [
+    "delete", "remove", "destroy", "drop", "purge", "erase", "wipe", "reset", "cancel", "revoke", "transfer", "withdraw",
+    "approve", "execute",
+];
 
-use super::structs::Config;
+/// methods, urls, headers, body, data type and http version of the parsed request
+pub type ParsedRequest = (
+    Vec<String>,
+    Vec<String>,
+    Vec<(String, String)>,
+    String,
+    Option<DataType>,
+    Option<reqwest::Version>,
+);
 
 /// shorcut to convert Option<&str> to Option<String> to be able to return it from the function
 pub(super) fn convert_to_string_if_some(el: Option<&str>) -> Option<String> {
@@ -23,21 +52,11 @@ pub(super) fn convert_to_string_if_some(el: Option<&str>) -> Option<String> {
 /// parse request from the request file
 pub(super) fn parse_request<'a>(
     request: &'a str,
-    scheme: &str,
+    scheme: Option<&str>,
     port: Option<u16>,
     mut data_type: Option<DataType>,
     split_by: Option<&str>,
-) -> Result<
-    (
-        Vec<String>,              // method
-        Vec<String>,              // url
-        Vec<(String, String)>,    // headers
-        String,                   // body
-        Option<DataType>,
-        Option<reqwest::Version>, // http version
-    ),
-    Box<dyn Error>,
-> {
+) -> Result<ParsedRequest, Box<dyn Error>> {
     // request by lines
     let lines = if let Some(val) = split_by {
         request
@@ -57,12 +76,27 @@ pub(super) fn parse_request<'a>(
         .next()
         .ok_or("Unable to parse method")?
         .to_string();
-    let path = firstline.next().ok_or("Unable to parse path")?.to_string(); //include ' ' in path too?
+    let mut path = firstline.next().ok_or("Unable to parse path")?.to_string(); //include ' ' in path too?
     let http2 = firstline
         .next()
         .ok_or("Unable to parse http version")?
         .contains("HTTP/2");
 
+    // requests sent through proxies contain absolute urls: GET https://example.com/ HTTP/1.1
+    let mut url_scheme = None;
+    let mut url_host = String::new();
+
+    if path.starts_with("http://") || path.starts_with("https://") {
+        let url = Url::parse(&path)?;
+
+        url_scheme = Some(url.scheme().to_string());
+        url_host = match url.port() {
+            Some(port) => format!("{}:{}", url.host_str().unwrap_or_default(), port),
+            None => url.host_str().unwrap_or_default().to_string(),
+        };
+        path = url[url::Position::BeforePath..].to_string();
+    }
+
     // parse headers
     for line in lines.by_ref() {
         if line.is_empty() {
@@ -84,6 +118,8 @@ pub(super) fn parse_request<'a>(
             "content-type" => {
                 if value.contains("json") && data_type.is_none() {
                     data_type = Some(DataType::ProbablyJson)
+                } else if value.contains("multipart/form-data") && data_type.is_none() {
+                    data_type = Some(DataType::Multipart)
                 }
             }
             "host" => {
@@ -110,6 +146,16 @@ pub(super) fn parse_request<'a>(
         }
     }
 
+    if host.is_empty() {
+        host = url_host;
+    }
+
+    if host.is_empty() {
+        Err("Unable to find the host. Add the Host header to the request file")?;
+    }
+
+    let scheme = scheme.or(url_scheme.as_deref()).unwrap_or("https");
+
     // port from the --port argument has a priority against port within the host header
     let (host, port) = if port.is_some() {
        (host.split(':').next().unwrap().to_string(), port.unwrap())
@@ -143,7 +189,11 @@ pub fn write_banner_config(config: &Config, params: &Vec<String>) {
         "methods".blue(),
         config.methods.join(" "),
         "wordlist len".cyan(),
-        params.len(),
+        if config.stream_wordlist {
+            format!("{} + streamed from {}", params.len(), config.wordlist)
+        } else {
+            params.len().to_string()
+        },
     );
 
     if !config.proxy.is_empty() {
@@ -154,6 +204,10 @@ pub fn write_banner_config(config: &Config, params: &Vec<String>) {
         output += &format!("\n{}: {}", "replay proxy".magenta(), &config.replay_proxy)
     }
 
+    if let Some((index, total)) = config.shard {
+        output += &format!("\n{}:        {}/{}", "shard".cyan(), index, total)
+    }
+
     if config.recursion_depth != 0 {
         output += &format!(
             "\n{}: {}",
@@ -180,6 +234,430 @@ pub fn read_urls_if_possible(filename: &str) -> Result<Option<Vec<String>>, io::
     Ok(Some(urls))
 }
 
+/// reads urls from the file or from stdin in case the filename is -
+/// either one url per line (empty lines and lines starting with # are skipped)
+/// or a json document with urls and per host settings
+pub(super) fn read_urls_file(filename: &str) -> Result<Targets, Box<dyn Error>> {
+    let text = if filename == "-" {
+        read_stdin_lines().join("\n")
+    } else {
+        match std::fs::read_to_string(filename) {
+            Ok(val) => val,
+            Err(err) => Err(format!("Unable to read urls from {}: {}", filename, err))?,
+        }
+    };
+
+    let targets = if text.trim_start().starts_with('{') {
+        match serde_json::from_str(&text) {
+            Ok(val) => val,
+            Err(err) => Err(format!("Unable to parse the targets file {}: {}", filename, err))?,
+        }
+    } else {
+        Targets {
+            urls: text
+                .lines()
+                .map(|x| x.trim().to_string())
+                .filter(|x| !x.is_empty() && !x.starts_with('#'))
+                .collect(),
+            ..Default::default()
+        }
+    };
+
+    if targets.urls.is_empty() {
+        Err(format!("No urls were found within {}", filename))?;
+    }
+
+    Ok(targets)
+}
+
+/// parses the curl command (--curl), e.g. from "Copy as cURL" of browser devtools
+/// only options that change the request are taken into account, known options that don't are skipped
+/// unknown options are rejected, since their values would be mistaken for the url
+pub fn parse_curl(command: &str, mut data_type: Option<DataType>) -> Result<ParsedRequest, Box<dyn Error>> {
+    let words = split_shell_words(command)?;
+    let mut words = words.iter().map(|x| x.as_str());
+
+    if words.next() != Some("curl") {
+        Err("The --curl value should start with curl")?;
+    }
+
+    let mut method = None;
+    let mut url = None;
+    let mut headers: Vec<(String, String)> = Vec::new();
+    let mut cookies: Vec<String> = Vec::new();
+    let mut data: Vec<String> = Vec::new();
+    let mut get = false;
+    let mut head = false;
+    let mut http_version = None;
+
+    while let Some(word) = words.next() {
+        // short options may be followed by the value without a space: -XPOST
+        let (option, attached) = if word.starts_with('-') && !word.starts_with("--") && word.len() > 2 {
+            (&word[..2], Some(&word[2..]))
+        } else {
+            (word, None)
+        };
+
+        let mut value = || -> Result<&str, Box<dyn Error>> {
+            match attached.or_else(|| words.next()) {
+                Some(val) => Ok(val),
+                None => Err(format!("The {} option of the curl command requires a value", option).into()),
+            }
+        };
+
+        match option {
+            "-X" | "--request" => method = Some(value()?.to_string()),
+            "-H" | "--header" => {
+                let header = value()?;
+                match header.split_once(':') {
+                    Some((k, v)) => headers.push((k.trim().to_string(), v.trim().to_string())),
+                    None => Err(format!("Unable to parse the header of the curl command: {}", header))?,
+                }
+            }
+            "-b" | "--cookie" => {
+                let cookie = value()?;
+                if !cookie.contains('=') {
+                    Err("Cookie files (-b filename) aren't supported. Pass cookies as -b 'name=value'")?;
+                }
+                cookies.push(cookie.to_string());
+            }
+            "-A" | "--user-agent" => headers.push(("User-Agent".to_string(), value()?.to_string())),
+            "-e" | "--referer" => headers.push(("Referer".to_string(), value()?.trim_end_matches(";auto").to_string())),
+            "-d" | "--data" | "--data-ascii" | "--data-binary" => data.push(read_body(value()?)?),
+            "--data-raw" => data.push(value()?.to_string()),
+            "--data-urlencode" => {
+                let val = value()?;
+                data.push(match val.split_once('=') {
+                    Some((name, content)) if !name.is_empty() => {
+                        format!("{}={}", name, utf8_percent_encode(content, NON_ALPHANUMERIC))
+                    }
+                    Some((_, content)) => utf8_percent_encode(content, NON_ALPHANUMERIC).to_string(),
+                    None => utf8_percent_encode(val, NON_ALPHANUMERIC).to_string(),
+                });
+            }
+            "-F" | "--form" => Err("Multipart forms (-F) from curl commands aren't supported. Use --request with the raw request instead")?,
+            "-u" | "--user" => Err("Credentials (-u) from curl commands aren't supported. Add the Authorization header instead")?,
+            "-G" | "--get" => get = true,
+            "-I" | "--head" => head = true,
+            "--http2" | "--http2-prior-knowledge" => http_version = Some(http::Version::HTTP_2),
+            "--http1.1" => http_version = Some(http::Version::HTTP_11),
+            "--url" => url = Some(value()?.to_string()),
+            // options with values that don't change the request
+            "-o" | "--output" | "-m" | "--max-time" | "--connect-timeout" | "-x" | "--proxy" | "--retry" | "-w"
+            | "--write-out" | "--cacert" | "-E" | "--cert" | "--key" | "--resolve" | "-c" | "--cookie-jar" => {
+                value()?;
+            }
+            // options without values that don't change the request. Short ones may be combined: -sSL
+            "-s" | "--silent" | "-S" | "--show-error" | "-L" | "--location" | "-k" | "--insecure" | "-i" | "--include"
+            | "-v" | "--verbose" | "-f" | "--fail" | "-g" | "--globoff" | "-N" | "--no-buffer" | "--compressed"
+            | "--path-as-is" => (),
+            _ if word.starts_with('-') => Err(format!(
+                "Unsupported option of the curl command: {}. Remove it or pass the raw request via --request",
+                word
+            ))?,
+            _ if url.is_none() => url = Some(word.to_string()),
+            _ => Err(format!("Unable to parse the curl command: unexpected {}", word))?,
+        }
+    }
+
+    // curl defaults to http in case the scheme is omitted
+    let url = match url {
+        Some(url) if url.contains("://") => url,
+        Some(url) => format!("http://{}", url),
+        None => Err("Unable to find the url within the curl command")?,
+    };
+    let mut url = Url::parse(&url)?.to_string();
+
+    if !cookies.is_empty() {
+        match headers.iter_mut().find(|(k, _)| k.eq_ignore_ascii_case("cookie")) {
+            Some((_, v)) => {
+                v.push_str("; ");
+                v.push_str(&cookies.join("; "));
+            }
+            None => headers.push(("Cookie".to_string(), cookies.join("; "))),
+        }
+    }
+
+    // -G moves the data to the query
+    let mut body = data.join("&");
+
+    if get && !body.is_empty() {
+        url.push(if url.contains('?') { '&' } else { '?' });
+        url.push_str(&body);
+        body.clear();
+    }
+
+    let method = match method {
+        Some(method) => method,
+        None if head => "HEAD".to_string(),
+        None if !body.is_empty() => "POST".to_string(),
+        None => "GET".to_string(),
+    };
+
+    // curl sends data as a form unless the content type is set
+    match headers.iter().find(|(k, _)| k.eq_ignore_ascii_case("content-type")) {
+        Some((_, v)) if data_type.is_none() && v.contains("json") => data_type = Some(DataType::ProbablyJson),
+        Some((_, v)) if data_type.is_none() && v.contains("multipart/form-data") => data_type = Some(DataType::Multipart),
+        Some(_) => (),
+        None if !body.is_empty() => {
+            headers.push(("Content-Type".to_string(), "application/x-www-form-urlencoded".to_string()))
+        }
+        None => (),
+    }
+
+    // the same as within the request file
+    let http2 = http_version == Some(http::Version::HTTP_2);
+    headers.retain(|(k, _)| !k.eq_ignore_ascii_case("content-length") && (!http2 || !k.eq_ignore_ascii_case("host")));
+
+    Ok((vec![method], vec![url], headers, body, data_type, http_version))
+}
+
+/// splits the command into words the way bash does: 'single', "double" and $'ansi-c' quotes, escapes and line continuations
+fn split_shell_words(command: &str) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut chars = command.chars().peekable();
+
+    let unterminated = || -> Box<dyn Error> { "Unable to parse the curl command: unterminated quote".into() };
+
+    while let Some(c) = chars.next() {
+        match c {
+            ' ' | '\t' | '\r' | '\n' => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            '\\' => match chars.next() {
+                // line continuation
+                Some('\n') => (),
+                Some('\r') if chars.peek() == Some(&'\n') => {
+                    chars.next();
+                }
+                Some(c) => {
+                    word.push(c);
+                    in_word = true;
+                }
+                None => (),
+            },
+            '\'' => {
+                in_word = true;
+                loop {
+                    match chars.next().ok_or_else(unterminated)? {
+                        '\'' => break,
+                        c => word.push(c),
+                    }
+                }
+            }
+            '"' => {
+                in_word = true;
+                loop {
+                    match chars.next().ok_or_else(unterminated)? {
+                        '"' => break,
+                        '\\' => match chars.next().ok_or_else(unterminated)? {
+                            c @ ('$' | '`' | '"' | '\\') => word.push(c),
+                            '\n' => (),
+                            c => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                        },
+                        c => word.push(c),
+                    }
+                }
+            }
+            '$' if chars.peek() == Some(&'\'') => {
+                chars.next();
+                in_word = true;
+                loop {
+                    match chars.next().ok_or_else(unterminated)? {
+                        '\'' => break,
+                        '\\' => match chars.next().ok_or_else(unterminated)? {
+                            'n' => word.push('\n'),
+                            'r' => word.push('\r'),
+                            't' => word.push('\t'),
+                            radix @ ('x' | 'u') => {
+                                let len = if radix == 'x' { 2 } else { 4 };
+                                let mut hex = String::new();
+                                while hex.len() < len && chars.peek().is_some_and(|x| x.is_ascii_hexdigit()) {
+                                    hex.push(chars.next().unwrap());
+                                }
+                                match u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32) {
+                                    Some(c) => word.push(c),
+                                    None => Err("Unable to parse the curl command: invalid escape sequence")?,
+                                }
+                            }
+                            c => word.push(c),
+                        },
+                        c => word.push(c),
+                    }
+                }
+            }
+            c => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+
+    if in_word {
+        words.push(word);
+    }
+
+    Ok(words)
+}
+
+/// ws://example.com/chat -> http://example.com/chat
+/// the handshake is a usual http request
+pub(super) fn websocket_to_http(url: &str) -> String {
+    if let Some(rest) = url.strip_prefix("ws://") {
+        format!("http://{}", rest)
+    } else if let Some(rest) = url.strip_prefix("wss://") {
+        format!("https://{}", rest)
+    } else {
+        url.to_string()
+    }
+}
+
+/// reads the body from a file in case the value starts with @. @@ escapes the literal @
+/// files without injection points and variables are streamed from disk like {{file:path}} includes
+/// checks that every {{file:path}} include can be read
+pub(super) fn read_body(value: &str) -> Result<String, Box<dyn Error>> {
+    let body = if let Some(literal) = value.strip_prefix("@@") {
+        format!("@{}", literal)
+    } else if let Some(filename) = value.strip_prefix('@') {
+        let body = match has_markers(filename) {
+            Ok(true) => std::fs::read_to_string(filename),
+            Ok(false) => Ok(format!("{{{{file:{}}}}}", filename)),
+            Err(err) => Err(err),
+        };
+
+        match body {
+            Ok(body) => body,
+            Err(err) => Err(format!("Unable to read the body from {}: {}", filename, err))?,
+        }
+    } else {
+        value.to_string()
+    };
+
+    for part in split_body_includes(&body) {
+        if let BodyPart::File(path) = part {
+            if let Err(err) = std::fs::metadata(&path) {
+                Err(format!("Unable to include {} into the body: {}", path, err))?;
+            }
+        }
+    }
+
+    Ok(body)
+}
+
+/// whether the file contains injection points or variables (%s, {{random}} and so on)
+/// the file is read in chunks, so big payloads aren't kept in memory
+fn has_markers(filename: &str) -> io::Result<bool> {
+    let mut file = File::open(filename)?;
+    let mut buf = vec![0; BODY_FILE_CHUNK_SIZE + 1];
+    let mut carried = 0;
+
+    loop {
+        let read = file.read(&mut buf[carried..])?;
+        if read == 0 {
+            return Ok(false);
+        }
+
+        let chunk = &buf[..carried + read];
+        if chunk.windows(2).any(|x| x == b"%s" || x == b"{{") {
+            return Ok(true);
+        }
+
+        // the marker may start at the end of the chunk
+        buf[0] = chunk[chunk.len() - 1];
+        carried = 1;
+    }
+}
+
+/// parses --as <name>:@<file> or <name>:<header>: <value>
+/// the file contains one `Header: value` per line
+pub(super) fn parse_identity(value: &str) -> Result<Identity, Box<dyn Error>> {
+    let (name, headers) = match value.split_once(':') {
+        Some((name, headers)) if !name.is_empty() && !headers.is_empty() => (name, headers),
+        _ => Err(format!("Incorrect --as specified: {}. Use <name>:@<file> or <name>:<header>: <value>", value))?,
+    };
+
+    let headers = match headers.strip_prefix('@') {
+        Some(filename) => match std::fs::read_to_string(filename) {
+            Ok(val) => val,
+            Err(err) => Err(format!("Unable to read headers for {} from {}: {}", name, filename, err))?,
+        },
+        None => headers.to_string(),
+    };
+
+    let mut parsed = Vec::new();
+
+    for line in headers.lines().map(|x| x.trim()).filter(|x| !x.is_empty() && !x.starts_with('#')) {
+        match line.split_once(':') {
+            Some((k, v)) => parsed.push((k.trim().to_string(), v.trim().to_string())),
+            None => Err(format!("Unable to parse the header for {}: {}", name, line))?,
+        }
+    }
+
+    Ok((name.to_string(), parsed))
+}
+
+/// removes truthy values from custom parameters like delete or confirm (--safe)
+/// parameters without values left are removed as well
+pub(super) fn strip_destructive_values(custom_parameters: &mut HashMap<String, Vec<String>>) {
+    for (key, values) in custom_parameters.iter_mut() {
+        let key = key.to_lowercase();
+
+        if DESTRUCTIVE_KEYS.iter().any(|x| key.contains(x)) {
+            values.retain(|x| !TRUTHY_VALUES.contains(&x.to_lowercase().as_str()));
+        }
+    }
+
+    custom_parameters.retain(|_, values| !values.is_empty());
+}
+
+/// returns the first word within the body that hints at a state-changing operation like "delete" or "transfer"
+pub(super) fn destructive_word(body: &str) -> Option<&'static str> {
+    let words: Vec<String> = body
+        .split(|c: char| !c.is_ascii_alphabetic())
+        .map(|x| x.to_lowercase())
+        .collect();
+
+    DESTRUCTIVE_WORDS.iter().find(|x| words.iter().any(|word| word == *x)).copied()
+}
+
+/// parses --shard <index>/<total>
+pub(super) fn parse_shard(value: &str) -> Result<(usize, usize), Box<dyn Error>> {
+    let shard = value
+        .split_once('/')
+        .and_then(|(index, total)| Some((index.trim().parse().ok()?, total.trim().parse().ok()?)));
+
+    match shard {
+        Some((index, total)) if index > 0 && index <= total => Ok((index, total)),
+        _ => Err(format!("Incorrect --shard specified: {}. Use <index>/<total>, e.g. 2/5", value))?,
+    }
+}
+
+/// parses sizes like 1024, 64K, 500M or 2G
+pub fn parse_size(value: &str) -> Result<usize, Box<dyn Error>> {
+    let value = value.trim().to_uppercase();
+    let value = value.trim_end_matches('B');
+
+    let (number, multiplier) = match value.chars().last() {
+        Some('K') => (&value[..value.len() - 1], 1 << 10),
+        Some('M') => (&value[..value.len() - 1], 1 << 20),
+        Some('G') => (&value[..value.len() - 1], 1 << 30),
+        _ => (value, 1),
+    };
+
+    match number.trim().parse::<usize>() {
+        Ok(val) => Ok(val * multiplier),
+        Err(_) => Err(format!("Incorrect size specified: {}. Use bytes or K, M, G suffixes, e.g. 500M", value))?,
+    }
+}
+
 pub(super) fn add_default_headers(curr_headers: HashMap<&str, String>) -> Vec<(String, String)> {
     let default_headers = [
         ("User-Agent", "Mozilla/5.0 (Macintosh; Intel Mac OS X 12) AppleWebKit/601.3.9 (KHTML, like Gecko) Version/9.0.2 Firefox/99.0"),
@@ -1,16 +1,185 @@
 //! Mostly taken from https://github.com/changeutils/diff-rs/blob/master/src/lib.rs
 
-use std::{collections::VecDeque, io};
+use std::{
+    collections::{hash_map::DefaultHasher, VecDeque},
+    convert::TryInto,
+    env,
+    fs::{self, File, OpenOptions},
+    hash::{Hash, Hasher},
+    io::{self, BufWriter, Read, Seek, SeekFrom, Write},
+    path::PathBuf,
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// the amount of hashes read from the temporary file at once
+const HASH_BLOCK: usize = 1024;
 
 pub fn diff(text1: &str, text2: &str) -> io::Result<Vec<String>> {
+    diff_lines(text1.lines().collect(), text2.lines().collect())
+}
+
+/// compares hashes of lines instead of lines themselves, so texts don't need to be copied or kept around
+/// the result is the same as the one of diff() because only positions of changed lines are returned
+pub fn diff_hashed<'a>(
+    lines1: impl Iterator<Item = &'a str>,
+    lines2: impl Iterator<Item = &'a str>,
+) -> io::Result<Vec<String>> {
+    diff_lines(lines1.map(hash_line).collect(), lines2.map(hash_line).collect())
+}
+
+/// the external memory version of diff_hashed() for huge pages
+/// hashes of lines are streamed to temporary files, the common beginning and end are found by reading the files block by block,
+/// and only hashes of the changed part in between are loaded into memory
+/// the result is the same as the one of diff()
+pub fn diff_external<'a>(
+    lines1: impl Iterator<Item = &'a str>,
+    lines2: impl Iterator<Item = &'a str>,
+) -> io::Result<Vec<String>> {
+    let mut file1 = HashFile::new(lines1)?;
+    let mut file2 = HashFile::new(lines2)?;
+
+    let max = file1.len.min(file2.len);
+
+    let mut prefix = 0;
+    while prefix < max {
+        let len = HASH_BLOCK.min(max - prefix);
+        let (block1, block2) = (file1.read(prefix, len)?, file2.read(prefix, len)?);
+
+        let equal = block1.iter().zip(block2.iter()).take_while(|(x, y)| x == y).count();
+        prefix += equal;
+
+        if equal < len {
+            break;
+        }
+    }
+
+    let mut suffix = 0;
+    while suffix < max - prefix {
+        let len = HASH_BLOCK.min(max - prefix - suffix);
+        let (block1, block2) = (
+            file1.read(file1.len - suffix - len, len)?,
+            file2.read(file2.len - suffix - len, len)?,
+        );
+
+        let equal = block1.iter().rev().zip(block2.iter().rev()).take_while(|(x, y)| x == y).count();
+        suffix += equal;
+
+        if equal < len {
+            break;
+        }
+    }
+
+    let middle1 = file1.read(prefix, file1.len - prefix - suffix)?;
+    let middle2 = file2.read(prefix, file2.len - prefix - suffix)?;
+
+    // the same calls as the ones myers makes for the whole pages: the common beginning, the changed part and the common end
+    let mut processor = Processor::new();
+    {
+        let mut replace = diffs::Replace::new(&mut processor);
+
+        if prefix > 0 {
+            diffs::Diff::equal(&mut replace, 0, 0, prefix)?;
+        }
+
+        diffs::myers::diff(&mut Offset { d: &mut replace, offset: prefix }, &middle1, &middle2)?;
+
+        if suffix > 0 {
+            diffs::Diff::equal(&mut replace, file1.len - suffix, file2.len - suffix, suffix)?;
+        }
+
+        diffs::Diff::finish(&mut replace)?;
+    }
+    Ok(processor.result())
+}
+
+fn hash_line(line: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    line.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// hashes of lines within a temporary file, 8 bytes per line. The file is removed once it's dropped
+struct HashFile {
+    path: PathBuf,
+    file: File,
+    len: usize,
+}
+
+impl HashFile {
+    fn new<'a>(lines: impl Iterator<Item = &'a str>) -> io::Result<Self> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+        let path = env::temp_dir().join(format!("x8-{}-{}.hashes", process::id(), COUNTER.fetch_add(1, Ordering::Relaxed)));
+        let file = OpenOptions::new().read(true).write(true).create_new(true).open(&path)?;
+
+        let mut hash_file = HashFile { path, file, len: 0 };
+        let mut writer = BufWriter::new(&hash_file.file);
+
+        for line in lines {
+            writer.write_all(&hash_line(line).to_le_bytes())?;
+            hash_file.len += 1;
+        }
+
+        writer.flush()?;
+        drop(writer);
+
+        Ok(hash_file)
+    }
+
+    /// len hashes starting from the index
+    fn read(&mut self, index: usize, len: usize) -> io::Result<Vec<u64>> {
+        let mut bytes = vec![0; len * 8];
+
+        self.file.seek(SeekFrom::Start(index as u64 * 8))?;
+        self.file.read_exact(&mut bytes)?;
+
+        Ok(bytes.chunks_exact(8).map(|x| u64::from_le_bytes(x.try_into().unwrap())).collect())
+    }
+}
+
+impl Drop for HashFile {
+    fn drop(&mut self) {
+        fs::remove_file(&self.path).ok();
+    }
+}
+
+/// shifts positions of the changed part of pages by the length of their common beginning
+struct Offset<D: diffs::Diff> {
+    d: D,
+    offset: usize,
+}
+
+impl<D: diffs::Diff> diffs::Diff for Offset<D> {
+    type Error = D::Error;
+
+    fn equal(&mut self, old: usize, new: usize, len: usize) -> Result<(), Self::Error> {
+        self.d.equal(old + self.offset, new + self.offset, len)
+    }
+
+    fn delete(&mut self, old: usize, len: usize) -> Result<(), Self::Error> {
+        self.d.delete(old + self.offset, len)
+    }
+
+    fn insert(&mut self, old: usize, new: usize, new_len: usize) -> Result<(), Self::Error> {
+        self.d.insert(old + self.offset, new + self.offset, new_len)
+    }
+
+    fn replace(&mut self, old: usize, old_len: usize, new: usize, new_len: usize) -> Result<(), Self::Error> {
+        self.d.replace(old + self.offset, old_len, new + self.offset, new_len)
+    }
+
+    // the common end goes after the changed part
+    fn finish(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+fn diff_lines<T: PartialEq>(lines1: Vec<T>, lines2: Vec<T>) -> io::Result<Vec<String>> {
     let mut processor = Processor::new();
     {
         let mut replace = diffs::Replace::new(&mut processor);
-        diffs::myers::diff(
-            &mut replace,
-            &text1.lines().collect::<Vec<&str>>(),
-            &text2.lines().collect::<Vec<&str>>(),
-        )?;
+        diffs::myers::diff(&mut replace, &lines1, &lines2)?;
     }
     Ok(processor.result())
 }
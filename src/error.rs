@@ -0,0 +1,73 @@
+use std::io;
+
+/// errors of the library functions, so the kind of failure can be matched instead of the message
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// the url can't be parsed or doesn't have a host or a known port
+    #[error("Incorrect url: {0}")]
+    Url(String),
+
+    /// the request failed: connection errors, timeouts, tls errors and so on
+    #[error(transparent)]
+    Network(#[from] reqwest::Error),
+
+    /// both the request and the control request without parameters failed, so the server or the network is down
+    #[error("Unable to reach server ({0})")]
+    Unreachable(String),
+
+    /// responses to identical requests differ, so parameters can't be detected
+    #[error("The page became unstable ({0})")]
+    UnstablePage(String),
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    /// incorrect options, unsupported formats and the rest
+    #[error("{0}")]
+    Other(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl From<url::ParseError> for Error {
+    fn from(err: url::ParseError) -> Self {
+        Error::Url(err.to_string())
+    }
+}
+
+impl From<&str> for Error {
+    fn from(msg: &str) -> Self {
+        Error::Other(msg.to_string())
+    }
+}
+
+impl From<String> for Error {
+    fn from(msg: String) -> Self {
+        Error::Other(msg)
+    }
+}
+
+/// most of the internal functions return boxed errors. The kind is restored in case it was boxed from a known error
+impl From<Box<dyn std::error::Error>> for Error {
+    fn from(err: Box<dyn std::error::Error>) -> Self {
+        let err = match err.downcast::<Error>() {
+            Ok(err) => return *err,
+            Err(err) => err,
+        };
+
+        let err = match err.downcast::<reqwest::Error>() {
+            Ok(err) => return Error::Network(*err),
+            Err(err) => err,
+        };
+
+        let err = match err.downcast::<io::Error>() {
+            Ok(err) => return Error::Io(*err),
+            Err(err) => err,
+        };
+
+        match err.downcast::<url::ParseError>() {
+            Ok(err) => Error::from(*err),
+            Err(err) => Error::Other(err.to_string()),
+        }
+    }
+}
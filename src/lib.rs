@@ -1,16 +1,151 @@
+use std::sync::Arc;
+
+use indicatif::ProgressBar;
+
 pub mod config;
 pub mod diff;
+pub mod error;
 pub mod network;
 pub mod runner;
+pub mod serve;
 pub mod utils;
 
+pub use config::{args::get_config_from, structs::Config};
+pub use error::Error;
+pub use network::request::RequestDefaults;
+pub use runner::utils::FoundParameter;
+
+use runner::{output::PreviousFindings, scan::run};
+
 const RANDOM_CHARSET: &[u8] = b"abcdefghijklmnopqrstuvwxyz0123456789";
 
 /// To ignore pages with size > 25MB. Usually it's some binary things. Can be ignored with --force
 const MAX_PAGE_SIZE: usize = 25 * 1024 * 1024;
 
+/// With --low-memory, pages bigger than that are diffed via temporary files instead of keeping hashes of every line in memory
+const LOW_MEMORY_EXTERNAL_SIZE: usize = 1024 * 1024;
+
 const DEFAULT_PROGRESS_URL_MAX_LEN: usize = 36;
 
+/// The longest url to check while detecting the url length limit.
+/// Longer urls are rarely needed: 256 parameters usually fit into ~6KB
+const MAX_URL_LENGTH: usize = 8000;
+
+/// The default amount of parameters per request within the body. Lowered in case the body doesn't accept that many keys
+const BODY_MAX: usize = 512;
+
+/// Codes of requests that are rejected because of their size. 0 is used in case the connection was dropped
+const SIZE_REJECTION_CODES: [u16; 5] = [0, 400, 413, 414, 431];
+
+/// Url length limit detection stops once the difference between accepted and rejected lengths is less than that
+const URL_LENGTH_PRECISION: usize = 128;
+
+/// Included body files are read and sent by chunks of that size
+const BODY_FILE_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Concurrency is halved after that many chunks in a row fail because of connection errors
+const CONCURRENCY_DECREASE_AFTER: usize = 3;
+
+/// Concurrency is increased by one after that many successful chunks in a row
+const CONCURRENCY_INCREASE_AFTER: usize = 10;
+
+/// The scan state is saved after every that many checked chunks (--resume)
+const CHECKPOINT_CHUNKS: usize = 10;
+
+/// The median request time for the eta is recomputed after every that many checked chunks
+const ETA_MEDIAN_CHUNKS: u64 = 100;
+
+/// How many times chunks with connection errors are retried
+const CHUNK_RETRIES: usize = 2;
+
+/// The delay before the first retry of a failed request (in milliseconds). Doubles with every next retry
+const RETRY_DELAY: u64 = 1000;
+
+/// The longest delay between retries including the one from Retry-After (in milliseconds)
+const MAX_RETRY_DELAY: u64 = 30_000;
+
+/// Methods that are refused in the safe mode (--safe)
+const UNSAFE_METHODS: [&str; 3] = ["DELETE", "PUT", "PATCH"];
+
+/// The adaptive rate limit grows back by a quarter after that many responses in a row without 429 or 503 (--adaptive-rate)
+const ADAPTIVE_RATE_RECOVERY: usize = 20;
+
+/// The adaptive rate limit isn't lowered below that many requests per second
+const MIN_ADAPTIVE_RATE: f64 = 0.5;
+
+/// The adaptive rate limit is halved at most once within that many milliseconds,
+/// because responses to requests sent before the decrease keep coming with 429 for a while
+const ADAPTIVE_RATE_INTERVAL: u64 = 1_000;
+
+/// The response body within --test -O json is truncated to that many bytes
+const TEST_BODY_LIMIT: usize = 4096;
+
+/// Extra learning requests to check whether the page alternates between a few variants (A/B, blue/green)
+const VARIANT_PROBES: usize = 6;
+
+/// Probes to estimate dns, connect, tls and ttfb timings (--network-estimates)
+const NETWORK_PROBES: usize = 3;
+
+/// More distinct responses than that are considered as an unstable page rather than variants
+const MAX_PAGE_VARIANTS: usize = 3;
+
+/// The wordlist is split into chunks of that size between shards (--shard)
+const SHARD_CHUNK_SIZE: usize = 64;
+
+/// Wordlists larger than that (in bytes) are read by parts while they're checked instead of being loaded into memory
+const STREAM_WORDLIST_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Chunks per parallel request to read from the streamed wordlist at once
+const STREAM_CHUNKS: usize = 4;
+
+/// The max amount of scripts and source maps to fetch (--source-maps)
+const MAX_SOURCE_MAPS: usize = 10;
+
+/// Shorter words scraped from the page aren't checked
+const MIN_PAGE_PARAM_LENGTH: usize = 2;
+
+/// The key of every WebSocket handshake (--websocket). A fixed key keeps Sec-WebSocket-Accept the same between responses
+const WEBSOCKET_KEY: &str = "dGhlIHNhbXBsZSBub25jZQ==";
+
+/// How long to wait for the first frame after the WebSocket handshake in ms (--websocket)
+const WEBSOCKET_READ_TIMEOUT: u64 = 1_000;
+
+/// The value of every found parameter within -O template
+const TEMPLATE_PLACEHOLDER: &str = "FUZZ";
+
 /// Default random value sizes
 const VALUE_LENGTH: usize = 6;
-const RANDOM_LENGTH: usize = 5;
\ No newline at end of file
+const RANDOM_LENGTH: usize = 5;
+
+/// checks the words against the target described by request defaults and returns found parameters
+/// options are usually created with get_config_from(["x8", "-u", url, "-v", "0"]) and the defaults with RequestDefaults::from_config
+/// with -v 1 and higher found parameters are written to stdout as well
+/// every call gets its own pacing, rate limits, learned settings and traffic, even with the same options
+pub async fn find_parameters(
+    mut defaults: RequestDefaults,
+    wordlist: Vec<String>,
+    options: &Config,
+) -> error::Result<Vec<FoundParameter>> {
+    let mut params = wordlist;
+
+    let options = Config {
+        state: Default::default(),
+        ..options.clone()
+    };
+    defaults.state = Arc::clone(&options.state);
+
+    let output = run(
+        &options,
+        &mut defaults,
+        &mut params,
+        &[],
+        &ProgressBar::hidden(),
+        1,
+        &PreviousFindings::new(),
+    )
+    .await;
+
+    options.state.flush_audit();
+
+    Ok(output?.found_params)
+}
@@ -1,64 +1,110 @@
 extern crate x8;
 use std::{
+    env,
     error::Error,
-    sync::Arc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
     io::{self, Write},
-    iter::FromIterator,
+    time::Instant,
 };
 
 use parking_lot::Mutex;
 use tokio::{fs::{self, OpenOptions}, io::AsyncWriteExt};
 use atty::Stream;
 use futures::StreamExt;
-use indicatif::ProgressBar;
 use colored::Colorize;
+use indicatif::ProgressBar;
+use serde_json::json;
 
 use x8::{
     config::args::get_config,
     config::{structs::Config, utils::write_banner_config},
     network::{
         request::{Request, RequestDefaults},
-        utils::Headers,
+        state::RunState,
+        utils::transform_values,
     },
     runner::{
-        output::{ParseOutputs, RunnerOutput},
-        runner::Runner,
-        utils::{Parameters, ReasonKind},
+        batch::{Scheduler, UrlSummary},
+        output::{load_previous_findings, ParseOutputs, PreviousFindings, RunnerOutput},
+        scan::{scan_url, url_methods},
     },
-    utils::{self, init_progress, read_lines, read_stdin_lines},
+    serve::serve,
+    utils::{self, init_progress, interrupt, read_lines, read_stdin_lines, shard},
 };
 
 #[cfg(windows)]
 #[tokio::main]
 async fn main() {
     colored::control::set_virtual_terminal(true).unwrap();
-    std::process::exit(match init().await {
-        Ok(_) => 0,
-        Err(err) => {
-            utils::error(err, None, None, None);
-            1
-        }
-    });
+    let code = match init().await {
+        Ok(code) => code,
+        Err(err) => exit_with_error(err),
+    };
+
+    std::process::exit(code);
 }
 
 #[cfg(not(windows))]
 #[tokio::main]
 async fn main() {
-    std::process::exit(match init().await {
-        Ok(_) => 0,
-        Err(err) => {
-            utils::error(err, None, None, None);
-            1
-        }
-    });
+    let code = match init().await {
+        Ok(code) => code,
+        Err(err) => exit_with_error(err),
+    };
+
+    std::process::exit(code);
 }
 
-/// initializes runners and passes them to run()
-/// also manages outputs. Probably better to rename?
-async fn init() -> Result<(), Box<dyn Error>> {
+/// the conventional exit code of processes stopped with Ctrl+C
+const INTERRUPTED_EXIT_CODE: i32 = 130;
+
+/// prints the error and returns the exit code
+fn exit_with_error(err: Box<dyn Error>) -> i32 {
+    // the config may be not parsed yet
+    if env::args().any(|x| x == "--json-only") {
+        writeln!(io::stdout(), "{}", json_document(&[], &[err.to_string()], &Instant::now())).ok();
+        2
+    } else {
+        utils::error(err, None, None, None);
+        1
+    }
+}
+
+/// parses the config and starts the scan
+/// returns the exit code
+async fn init() -> Result<i32, Box<dyn Error>> {
     env_logger::init();
 
     let config: Config = get_config()?;
+    let code = start(&config).await;
+
+    // --audit-log entries are buffered within the state of the run
+    config.state.flush_audit();
+
+    code
+}
+
+/// initializes runners and passes them to run()
+/// also manages outputs. Probably better to rename?
+/// returns the exit code
+async fn start(config: &Config) -> Result<i32, Box<dyn Error>> {
+    // a broken command would fail the scan on the first request with parameters
+    if !config.value_transform.is_empty() {
+        if let Err(err) = transform_values(&config.value_transform, &["x8"]).await {
+            Err(format!("Unable to run --value-transform: {}", err))?;
+        }
+    }
+
+    if config.listen.is_empty() {
+        handle_interrupt(Arc::clone(&config.state));
+    }
+
+    if config.json_only {
+        return Ok(json_only(config).await);
+    }
 
     //if --test option is used - print request/response and quit
     if config.test {
@@ -68,52 +114,36 @@ async fn init() -> Result<(), Box<dyn Error>> {
             Err("--test option works only with 1 method")?;
         }
 
-        //TODO move to func?
-        writeln!(
-            io::stdout(),
-            "{}",
-            Request::new_random(
-                &RequestDefaults::from_config(
-                    &config,
-                    config.methods[0].as_str(),
-                    config.urls[0].as_str()
-                )?,
-                config.max.unwrap_or(16)
-            )
-            .send()
-            .await?
-            .print_all()
-        )
-        .ok();
-        return Ok(());
-    }
+        // the request from the spec or the HAR file has its own body and headers
+        let operation_config = config.for_operation(&config.methods[0], &config.urls[0]);
+        let request_defaults = RequestDefaults::from_config(
+            operation_config.as_ref().unwrap_or(config),
+            config.methods[0].as_str(),
+            config.urls[0].as_str(),
+        )?;
+        let response = Request::new_random(&request_defaults, config.max.unwrap_or(16)).send().await?;
+
+        // wrapper scripts can validate the parsed configuration
+        let output = if config.output_format == "json" {
+            serde_json::to_string_pretty(&response.exchange())?
+        } else {
+            response.print_all()
+        };
 
-    if !config.save_responses.is_empty() {
-        fs::create_dir_all(&config.save_responses).await?;
+        writeln!(io::stdout(), "{}", output).ok();
+        return Ok(0);
     }
 
-    let mut params: Vec<String> = Vec::new();
+    let (previous_findings, params) = prepare_run(config).await?;
 
-    if !config.wordlist.is_empty() {
-        // read parameters from a file
-        for line in read_lines(&config.wordlist)?.flatten() {
-            params.push(line);
-        }
-    // just accept piped stdin
-    } else if !atty::is(Stream::Stdin) {
-        // read parameters from stdin
-        params = read_stdin_lines();
+    // targets are submitted via the REST api
+    if !config.listen.is_empty() {
+        serve(config, params).await?;
+        return Ok(0);
     }
 
     if !config.remove_banner {
-        write_banner_config(&config, &params);
-    }
-
-    // such headers usually cause server to timeout
-    // especially when http/2 is used
-    // probably better to add a flag for keeping such parameters?
-    if config.headers_discovery {
-        params.retain(|x| "content-length" != x.to_lowercase() && "host" != x.to_lowercase());
+        write_banner_config(config, &params);
     }
 
     // -W 0 is a special option to run everything in parallel
@@ -145,8 +175,15 @@ async fn init() -> Result<(), Box<dyn Error>> {
 
     let shared_output_file = Arc::new(Mutex::new(&mut output_file));
 
+    // shares the client between urls and limits concurrent checks per host
+    let scheduler = Scheduler::new(config)?;
+
+    // used to determine the exit code
+    let found = AtomicBool::new(false);
+    let errored = AtomicBool::new(false);
+
     let runner_outputs =
-        futures::stream::iter(init_progress(&config).iter().enumerate().skip(1).map(
+        futures::stream::iter(init_progress(config).iter().enumerate().skip(1).map(
             |(id, (progress_bar, url_set))| {
 
                 let shared_output_file = Arc::clone(&shared_output_file);
@@ -155,7 +192,10 @@ async fn init() -> Result<(), Box<dyn Error>> {
                 let params = params.clone();
 
                 // each url set should have it's own immutable pointer to config
-                let config = &config;
+                                let previous_findings = &previous_findings;
+                let found = &found;
+                let errored = &errored;
+                let scheduler = &scheduler;
 
                 //let output_file = output_file.as_ref().unwrap().try_clone();
 
@@ -165,52 +205,59 @@ async fn init() -> Result<(), Box<dyn Error>> {
                     // for now url set are used only in case --one-worker-per-host option is provided
                     // otherwise it's just url sets of 1 url
                     for url in url_set {
-                        for method in &config.methods.clone() {
-                            // each method should have each own list of parameters (we're changing this list through the run)
-                            let mut params = params.clone();
+                        if config.state.is_stopped() {
+                            break;
+                        }
 
-                            let mut request_defaults = match RequestDefaults::from_config(
-                                config,
-                                method.as_str(),
-                                url.as_str(),
-                            ) {
-                                Ok(val) => val,
-                                Err(err) => {
-                                    utils::error(err, Some(url), Some(progress_bar), Some(config));
-                                    continue;
-                                }
-                            };
+                        let _permit = scheduler.acquire(url).await;
 
-                            // get cookies
-                            if let Err(err) =
-                                Request::new(&request_defaults, Vec::new()).send().await
-                            {
-                                utils::error(err, Some(url), Some(progress_bar), Some(config));
-                                continue;
-                            };
+                        for method in &url_methods(config, url, progress_bar, id).await {
+                            if config.state.is_stopped() {
+                                break;
+                            }
+
+                            // each method should have each own list of parameters (we're changing this list through the run)
+                            let mut params = params.clone();
 
-                            match run(
+                            match scan_url(
                                 config,
-                                &mut request_defaults,
+                                url,
+                                method,
                                 &mut params,
-                                &progress_bar,
+                                progress_bar,
                                 id,
+                                previous_findings,
+                                scheduler.client(),
                             )
                             .await
                             {
                                 Ok(val) => {
+                                    scheduler.record(url, Some(&val));
+
+                                    if val.has_new_params() {
+                                        found.store(true, Ordering::Relaxed);
+                                    }
+
                                     // if output format is not json we can print output and write to file in real time
                                     if config.output_format != "json" {
                                         let mut output_file = shared_output_file.lock();
                                         let output = val.parse(config);
 
-                                        if output_file.is_some() && !(config.remove_empty && val.found_params.is_empty()) {
+                                        // jsonl outputs nothing for urls without parameters
+                                        if output.is_empty() {
+                                            continue;
+                                        }
+
+                                        if output_file.is_some() && !(config.remove_empty && val.is_empty()) {
 
                                             match output_file.as_mut().unwrap().write_all(
                                                 &strip_ansi_escapes::strip(&(output.normal().clear().to_string()+"\n").as_bytes()).unwrap()
                                             ).await {
                                                 Ok(()) => output_file.as_mut().unwrap().flush().await.unwrap(),
-                                                Err(err) => utils::error(err, Some(url), Some(progress_bar), Some(config)),
+                                                Err(err) => {
+                                                    errored.store(true, Ordering::Relaxed);
+                                                    utils::error(err, Some(url), Some(progress_bar), Some(config))
+                                                },
                                             };
                                         }
 
@@ -231,6 +278,8 @@ async fn init() -> Result<(), Box<dyn Error>> {
                                     }
                                 },
                                 Err(err) => {
+                                    scheduler.record(url, None);
+                                    errored.store(true, Ordering::Relaxed);
                                     utils::error(err, Some(url), Some(progress_bar), Some(config))
                                 }
                             }
@@ -251,9 +300,9 @@ async fn init() -> Result<(), Box<dyn Error>> {
         let output = runner_outputs
             .into_iter()
             .flatten()
-            .filter(|x| !(config.remove_empty && x.found_params.is_empty()))
+            .filter(|x| !(config.remove_empty && x.is_empty()))
             .collect::<Vec<RunnerOutput>>()
-            .parse_output(&config);
+            .parse_output(config);
 
         if output_file.is_some() {
             output_file.as_mut().unwrap().write_all(output.as_bytes()).await?;
@@ -263,91 +312,237 @@ async fn init() -> Result<(), Box<dyn Error>> {
         write!(io::stdout(), "\n{}", output).ok();
     }
 
-    Ok(())
+    if config.urls.len() > 1 && config.verbose > 0 {
+        write_batch_summary(&scheduler.summaries());
+    }
+
+    if config.state.is_stopped() {
+        return Ok(INTERRUPTED_EXIT_CODE);
+    }
+
+    Ok(exit_code(config, found.into_inner(), errored.into_inner()))
 }
 
-async fn run(
-    config: &Config,
-    request_defaults: &mut RequestDefaults,
-    params: &mut Vec<String>,
-    progress_bar: &ProgressBar,
-    id: usize,
-) -> Result<RunnerOutput, Box<dyn Error>> {
-    let mut runner_output = Runner::new(config, request_defaults, progress_bar, id)
-        .await?
-        .run(params)
-        .await?;
-
-    // the whole block related to the recursive searching
-    if !runner_output.found_params.is_empty() {
-        for depth in 1..config.recursion_depth + 1 {
-            // remove already found parameters from the list to prevent duplicates
-            params.retain(|x| !runner_output.found_params.contains_name(x));
-
-            // custom parameters work badly with recursion enabled
-            request_defaults.disable_custom_parameters = true;
-
-            // so we are keeping parameters that don't change pages' code
-            // or change it to 200
-            // we cant simply overwrite request_defaults.parameters because there's user-supplied parameters as well.
-            request_defaults.parameters.append(&mut Vec::from_iter(
-                runner_output
-                    .found_params
-                    .iter()
-                    .filter(|x| {
-                        !request_defaults.parameters.contains_key(&x.name)
-                            && (x.reason_kind != ReasonKind::Code || x.status == 200)
-                    })
-                    .map(|x| (x.get())),
-            ));
-
-            utils::info(
-                config,
-                id,
-                progress_bar,
-                "recursion",
-                format!(
-                    "({}) repeating with {}",
-                    depth,
-                    request_defaults
-                        .parameters
-                        .iter()
-                        .map(|x| x.0.as_str())
-                        .collect::<Vec<&str>>()
-                        .join(", ")
-                ),
-            );
-
-            let mut new_found_params = Runner::new(config, request_defaults, progress_bar, id)
-                .await?
-                .run(params)
-                .await?
-                .found_params;
-
-            // no new params where found - just quit the loop
-            if !new_found_params
-                .iter()
-                .any(|x| !runner_output.found_params.contains_name(&x.name))
-            {
-                break;
-            }
-
-            runner_output.found_params.append(&mut new_found_params);
+/// the first Ctrl+C stops sending new requests and lets the scan print what was found so far
+/// the second one exits immediately
+fn handle_interrupt(state: Arc<RunState>) {
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_err() {
+            return;
+        }
+
+        interrupt();
+        writeln!(
+            io::stderr(),
+            "\n{} waiting for sent requests to finish. Press Ctrl+C again to exit immediately",
+            "Interrupted:".yellow()
+        )
+        .ok();
+
+        if tokio::signal::ctrl_c().await.is_ok() {
+            state.flush_audit();
+            std::process::exit(INTERRUPTED_EXIT_CODE);
         }
+    });
+}
+
+/// prints how many parameters were found within every url of the batch scan
+fn write_batch_summary(summaries: &[UrlSummary]) {
+    let found = summaries.iter().filter(|x| x.found > 0).count();
+    let failed = summaries.iter().filter(|x| x.errors > 0).count();
+    let stopped = summaries.iter().filter(|x| x.stopped).count();
+
+    let mut message = format!(
+        "\n{} urls checked: {} with parameters, {} failed",
+        summaries.len(),
+        found.to_string().green(),
+        failed.to_string().red()
+    );
+
+    if stopped > 0 {
+        message += &format!(", {} stopped early", stopped.to_string().yellow());
     }
 
-    // we probably changed request_defaults.parameters within the loop above
-    // so we are removing all of the added parameters in there
-    // leaving only user-supplied ones
-    // (to not cause double parameters in some output types)
-    request_defaults.parameters = request_defaults
-        .parameters
-        .iter()
-        .filter(|x| !runner_output.found_params.contains_name(&x.0))
-        .map(|x| x.to_owned())
-        .collect();
+    for summary in summaries.iter().filter(|x| x.found > 0 || x.errors > 0) {
+        message += &format!("\n    {}: {} parameters", summary.url, summary.found);
 
-    runner_output.prepare(config, request_defaults);
+        if !summary.backend.is_empty() {
+            message += &format!(" ({})", summary.backend.join(", "));
+        }
+
+        if summary.errors > 0 {
+            message += &format!(", {} failed methods", summary.errors);
+        }
+
+        if summary.stopped {
+            message += ", stopped early";
+        }
+    }
+
+    writeln!(io::stdout(), "{}", message).ok();
+}
+
+/// returns the exit code depending on the outcome and --fail-on-found/--fail-on-error-only
+fn exit_code(config: &Config, found: bool, errored: bool) -> i32 {
+    let error_code = if config.json_only { 2 } else { 1 };
+
+    if errored && (config.json_only || config.fail_on_found || config.fail_on_error_only) {
+        error_code
+    } else if config.fail_on_found {
+        found as i32
+    } else if config.fail_on_error_only {
+        0
+    } else if config.json_only {
+        !found as i32
+    } else {
+        0
+    }
+}
+
+/// creates the output directory, empties files that are appended during the run and reads previous findings and parameters
+async fn prepare_run(config: &Config) -> Result<(PreviousFindings, Vec<String>), Box<dyn Error>> {
+    if !config.save_responses.is_empty() {
+        fs::create_dir_all(&config.save_responses).await?;
+    }
+
+    // diffs are appended during the run so the file needs to be emptied first
+    if !config.diffs_file.is_empty() {
+        fs::write(&config.diffs_file, "").await?;
+    }
+
+    if !config.audit_log.is_empty() {
+        fs::write(&config.audit_log, "").await?;
+    }
+
+    if !config.save_skipped.is_empty() {
+        fs::write(&config.save_skipped, "").await?;
+    }
+
+    let previous_findings = if !config.compare_with.is_empty() {
+        load_previous_findings(&config.compare_with)?
+    } else {
+        PreviousFindings::new()
+    };
+
+    Ok((previous_findings, read_params(config)?))
+}
+
+/// reads parameters from the wordlist or stdin
+fn read_params(config: &Config) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut params: Vec<String> = Vec::new();
+
+    // huge wordlists are read by the runner itself
+    if !config.wordlist.is_empty() && !config.stream_wordlist {
+        // read parameters from a file
+        for line in read_lines(&config.wordlist)?.flatten() {
+            params.push(line);
+        }
+    // just accept piped stdin
+    } else if config.wordlist.is_empty() && config.inline_params.is_empty() && !atty::is(Stream::Stdin) {
+        // read parameters from stdin
+        params = read_stdin_lines();
+    }
+
+    for param in config.inline_params.iter() {
+        if !params.contains(param) {
+            params.push(param.to_owned());
+        }
+    }
+
+    // such headers usually cause server to timeout
+    // especially when http/2 is used
+    // probably better to add a flag for keeping such parameters?
+    if config.headers_discovery {
+        params.retain(|x| "content-length" != x.to_lowercase() && "host" != x.to_lowercase());
+    }
+
+    if let Some((index, total)) = config.shard {
+        params = shard(params, index, total);
+    }
+
+    Ok(params)
+}
+
+/// checks a single url and prints exactly one json document
+/// returns 0 in case parameters were found, 1 in case nothing was found and 2 in case of errors (by default)
+async fn json_only(config: &Config) -> i32 {
+    let start = Instant::now();
+
+    let mut runner_outputs = Vec::new();
+    let mut errors = Vec::new();
+
+    if let Err(err) = json_only_scan(config, &mut runner_outputs, &mut errors).await {
+        errors.push(err.to_string());
+    }
+
+    let mut output = json_document(&runner_outputs, &errors, &start);
+
+    // the file is written first, so the error of writing it is a part of the printed document
+    if !config.output_file.is_empty() {
+        if let Err(err) = fs::write(&config.output_file, &output).await {
+            errors.push(format!("Unable to write to {}: {}", config.output_file, err));
+            output = json_document(&runner_outputs, &errors, &start);
+        }
+    }
+
+    writeln!(io::stdout(), "{}", output).ok();
+
+    exit_code(
+        config,
+        runner_outputs.iter().any(|x| x.has_new_params()),
+        !errors.is_empty(),
+    )
+}
+
+/// checks the url with every method. Errors within separate methods don't stop the scan
+async fn json_only_scan(
+    config: &Config,
+    runner_outputs: &mut Vec<RunnerOutput>,
+    errors: &mut Vec<String>,
+) -> Result<(), Box<dyn Error>> {
+    if config.urls.len() != 1 {
+        Err("--json-only works only with 1 url")?;
+    }
+
+    let (previous_findings, params) = prepare_run(config).await?;
+    let progress_bar = ProgressBar::hidden();
+
+    for method in url_methods(config, &config.urls[0], &progress_bar, 1).await.iter() {
+        if config.state.is_stopped() {
+            break;
+        }
+
+        let mut params = params.clone();
+
+        match scan_url(
+            config,
+            &config.urls[0],
+            method,
+            &mut params,
+            &progress_bar,
+            1,
+            &previous_findings,
+            None,
+        )
+        .await
+        {
+            Ok(val) => runner_outputs.push(val),
+            Err(err) => errors.push(format!("{} {}: {}", method, config.urls[0], err)),
+        }
+    }
+
+    Ok(())
+}
 
-    Ok(runner_output)
+/// the --json-only document
+fn json_document(runner_outputs: &[RunnerOutput], errors: &[String], start: &Instant) -> String {
+    json!({
+        "results": runner_outputs,
+        "errors": errors,
+        "stats": {
+            "found": runner_outputs.iter().map(|x| x.found_params.len()).sum::<usize>(),
+            "elapsed_ms": start.elapsed().as_millis(),
+        },
+    })
+    .to_string()
 }
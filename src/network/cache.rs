@@ -0,0 +1,64 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    time::{Duration, Instant},
+};
+
+use parking_lot::Mutex;
+
+use super::{
+    request::Request,
+    response::{Baseline, Response},
+};
+
+/// caches responses to identical control requests of the url and method (--cache-ttl), so they aren't refetched within the ttl:
+/// the HTTP/2 check, the request for cookies and the initial one, checks of the baseline code, reachability checks and verification
+/// learning requests and chunks are never cached, since a reused response hides the parts of the page that change by themselves
+#[derive(Debug, Default)]
+pub struct ResponseCache {
+    /// responses older than that are refetched. Zero disables the cache
+    ttl: Duration,
+
+    responses: Mutex<HashMap<String, (Instant, Baseline)>>,
+}
+
+impl ResponseCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            responses: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// returns a fresh response for the key. The response doesn't contain the request
+    pub fn get(&self, key: &str) -> Option<Baseline> {
+        let responses = self.responses.lock();
+
+        match responses.get(key) {
+            Some((time, response)) if time.elapsed() < self.ttl => Some(response.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn insert(&self, key: String, response: &Response) {
+        if self.ttl.is_zero() {
+            return;
+        }
+
+        self.responses.lock().insert(key, (Instant::now(), response.baseline()));
+    }
+
+    /// sends the request only in case there's no fresh response to the same control request. Failed requests aren't cached
+    pub async fn send(&self, request: Request) -> Result<Response, Box<dyn Error>> {
+        let key = request.cache_key();
+
+        if let Some(response) = self.get(&key) {
+            return Ok(Response::from_baseline(response, request));
+        }
+
+        let response = request.send().await?;
+        self.insert(key, &response);
+
+        Ok(response)
+    }
+}
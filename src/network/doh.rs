@@ -0,0 +1,73 @@
+use std::{error::Error, net::IpAddr, time::Duration};
+
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+struct DohResponse {
+    #[serde(rename = "Status")]
+    status: u32,
+
+    #[serde(rename = "Answer", default)]
+    answer: Vec<DohAnswer>,
+}
+
+#[derive(Deserialize)]
+struct DohAnswer {
+    #[serde(rename = "type")]
+    record_type: u16,
+
+    data: String,
+}
+
+/// resolves the host via DNS-over-HTTPS
+/// uses the json api (application/dns-json) that is supported by cloudflare, google and others
+/// ipv4 addresses go first
+pub async fn resolve(server: &str, host: &str, timeout: usize) -> Result<Vec<IpAddr>, Box<dyn Error>> {
+    if let Ok(ip) = host.trim_start_matches('[').trim_end_matches(']').parse() {
+        return Ok(vec![ip]);
+    }
+
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(timeout as u64))
+        .use_rustls_tls()
+        .build()?;
+
+    let mut addrs = Vec::new();
+
+    // A and AAAA records
+    for record_type in ["A", "AAAA"] {
+        let text = client
+            .get(server)
+            .query(&[("name", host), ("type", record_type)])
+            .header("Accept", "application/dns-json")
+            .send()
+            .await?
+            .text()
+            .await?;
+
+        addrs.append(&mut parse_answer(&text)?);
+    }
+
+    if addrs.is_empty() {
+        Err(format!("{} doesn't have A or AAAA records according to {}", host, server))?;
+    }
+
+    Ok(addrs)
+}
+
+/// returns addresses from the A and AAAA records of the json response
+pub fn parse_answer(text: &str) -> Result<Vec<IpAddr>, Box<dyn Error>> {
+    let response: DohResponse = serde_json::from_str(text)?;
+
+    // 3 -- NXDOMAIN
+    if response.status != 0 && response.status != 3 {
+        Err(format!("The DoH server returned an error (rcode {})", response.status))?;
+    }
+
+    Ok(response
+        .answer
+        .iter()
+        .filter(|x| x.record_type == 1 || x.record_type == 28)
+        .filter_map(|x| x.data.parse().ok())
+        .collect())
+}
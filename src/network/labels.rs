@@ -0,0 +1,89 @@
+use std::collections::{HashMap, HashSet};
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+lazy_static! {
+    static ref RE_ERROR: Regex = Regex::new(
+        r#"(?i)\b(error|exception|invalid|denied|forbidden|not allowed|unauthorized|required|missing|failed|warning|traceback)\b"#
+    )
+    .unwrap();
+    static ref RE_FORM_FIELD: Regex = Regex::new(r#"(?i)<(input|select|textarea|button)\b"#).unwrap();
+    static ref RE_TITLE: Regex = Regex::new(r#"(?is)<title[^>]*>(.*?)</title>"#).unwrap();
+    // bodies are beautified, so the opening quote of a key may remain on the previous line
+    static ref RE_JSON_KEY: Regex = Regex::new(r#"^\s*[{,]?\s*"?([\w.-]+)"\s*:\s*(.*?)\s*,?\s*$"#).unwrap();
+}
+
+/// describes how the page changed in human words like "json key `role` value changed"
+/// the diff itself contains positions only, so the normalized bodies are compared instead
+/// returns None in case nothing recognizable changed
+pub fn label(baseline_body: &str, body: &str) -> Option<String> {
+    let old_lines: HashSet<&str> = baseline_body.lines().map(|x| x.trim()).collect();
+    let new_lines: HashSet<&str> = body.lines().map(|x| x.trim()).collect();
+
+    let is_error = |x: &&&str| RE_ERROR.is_match(x);
+    let added_errors = new_lines.difference(&old_lines).filter(is_error).count();
+    let removed_errors = old_lines.difference(&new_lines).filter(is_error).count();
+
+    let mut labels = Vec::new();
+
+    match (removed_errors, added_errors) {
+        (0, 0) => {}
+        (0, _) => labels.push("error message appeared".to_string()),
+        (_, 0) => labels.push("error message disappeared".to_string()),
+        _ => labels.push("error message changed".to_string()),
+    }
+
+    labels.extend(json_changes(baseline_body, body));
+
+    let old_fields = RE_FORM_FIELD.find_iter(baseline_body).count();
+    let new_fields = RE_FORM_FIELD.find_iter(body).count();
+
+    if new_fields > old_fields {
+        labels.push("new form field appeared".to_string());
+    } else if new_fields < old_fields {
+        labels.push("form field disappeared".to_string());
+    }
+
+    let title = |x| RE_TITLE.captures(x).map(|x| x[1].trim().to_string());
+    if title(baseline_body) != title(body) {
+        labels.push("page title changed".to_string());
+    }
+
+    if labels.is_empty() {
+        None
+    } else {
+        Some(labels.join(", "))
+    }
+}
+
+/// "json key `key` value changed" for the first changed key, appeared or disappeared keys
+fn json_changes(baseline_body: &str, body: &str) -> Vec<String> {
+    let keys = |body: &str| -> Vec<(String, String)> {
+        body.lines()
+            .filter_map(|x| RE_JSON_KEY.captures(x))
+            .map(|x| (x[1].to_string(), x[2].to_string()))
+            .collect()
+    };
+
+    let old_keys = keys(baseline_body);
+    let new_keys = keys(body);
+    let old_map: HashMap<&str, &str> = old_keys.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect();
+    let new_map: HashMap<&str, &str> = new_keys.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect();
+
+    let mut changes = Vec::new();
+
+    if let Some((key, _)) = new_keys.iter().find(|(k, v)| matches!(old_map.get(k.as_str()), Some(old) if old != v)) {
+        changes.push(format!("json key `{}` value changed", key));
+    }
+
+    if let Some((key, _)) = new_keys.iter().find(|(k, _)| !old_map.contains_key(k.as_str())) {
+        changes.push(format!("json key `{}` appeared", key));
+    }
+
+    if let Some((key, _)) = old_keys.iter().find(|(k, _)| !new_map.contains_key(k.as_str())) {
+        changes.push(format!("json key `{}` disappeared", key));
+    }
+
+    changes
+}
@@ -0,0 +1,325 @@
+use std::{
+    error::Error,
+    fmt,
+    net::SocketAddr,
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+
+use crate::{config::structs::Config, runner::utils::percentile};
+
+use super::{
+    doh,
+    request::{Request, RequestDefaults},
+    utils::create_client,
+};
+
+/// phases of a request in milliseconds estimated with separate probes (--network-estimates)
+/// the client doesn't expose them, so they aren't known for the checked requests
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct NetworkEstimates {
+    pub dns: u128,
+    pub connect: u128,
+
+    /// 0 for http
+    pub tls: u128,
+
+    /// from the sent request to response headers over an established connection
+    pub ttfb: u128,
+}
+
+/// resolves the host, connects to it and makes 2 requests with a fresh client:
+/// the first one over a new connection and the second one over the same connection
+/// tls = first request - second request - connect
+pub async fn probe(config: &Config, request_defaults: &RequestDefaults) -> Result<NetworkEstimates, Box<dyn Error>> {
+    if !config.proxy.is_empty() {
+        Err("Network estimates aren't available with a proxy")?;
+    }
+
+    let start = Instant::now();
+
+    let addrs: Vec<SocketAddr> = if config.doh.is_empty() {
+        tokio::net::lookup_host(format!("{}:{}", request_defaults.host, request_defaults.port))
+            .await?
+            .collect()
+    } else {
+        doh::resolve(&config.doh, &request_defaults.host, config.timeout)
+            .await?
+            .into_iter()
+            .map(|ip| SocketAddr::new(ip, request_defaults.port))
+            .collect()
+    };
+
+    let dns = start.elapsed().as_millis();
+
+    let addr = *addrs.first().ok_or("The host doesn't have any addresses")?;
+
+    let start = Instant::now();
+    tokio::time::timeout(Duration::from_secs(config.timeout as u64), tokio::net::TcpStream::connect(addr)).await??;
+    let connect = start.elapsed().as_millis();
+
+    // the same address as above, so dns isn't a part of the request time
+    let client = create_client(config, false, Some((&request_defaults.host, addr)))?;
+
+    let cold = Request::new(request_defaults, Vec::new()).send_by(&client).await?.time;
+    let ttfb = Request::new(request_defaults, Vec::new()).send_by(&client).await?.time;
+
+    let tls = if request_defaults.scheme == "https" {
+        cold.saturating_sub(ttfb + connect)
+    } else {
+        0
+    };
+
+    Ok(NetworkEstimates { dns, connect, tls, ttfb })
+}
+
+/// the median of every phase
+pub fn median(timings: &[NetworkEstimates]) -> NetworkEstimates {
+    let phase = |f: fn(&NetworkEstimates) -> u128| percentile(&timings.iter().map(f).collect::<Vec<u128>>(), 50);
+
+    NetworkEstimates {
+        dns: phase(|x| x.dns),
+        connect: phase(|x| x.connect),
+        tls: phase(|x| x.tls),
+        ttfb: phase(|x| x.ttfb),
+    }
+}
+
+/// usage of the client shared by every stage of a url|method pair (learning, checking, verification, custom parameters)
+/// the client clones share the same connection pool, so the counters are shared as well
+#[derive(Debug, Default)]
+pub struct PoolStats {
+    requests: AtomicUsize,
+    http2: AtomicUsize,
+    in_flight: AtomicUsize,
+    peak_in_flight: AtomicUsize,
+}
+
+/// a snapshot of PoolStats
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct PoolMetrics {
+    pub requests: usize,
+
+    /// requests served over HTTP/2
+    pub http2: usize,
+
+    /// the max amount of requests that were waiting for responses at the same time
+    pub peak_in_flight: usize,
+}
+
+impl PoolStats {
+    pub fn start(&self) {
+        let in_flight = self.in_flight.fetch_add(1, Ordering::Relaxed) + 1;
+        self.peak_in_flight.fetch_max(in_flight, Ordering::Relaxed);
+    }
+
+    /// version is None in case the request failed
+    pub fn finish(&self, version: Option<http::Version>) {
+        self.in_flight.fetch_sub(1, Ordering::Relaxed);
+
+        if let Some(version) = version {
+            self.requests.fetch_add(1, Ordering::Relaxed);
+
+            if version == http::Version::HTTP_2 {
+                self.http2.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    pub fn metrics(&self) -> PoolMetrics {
+        PoolMetrics {
+            requests: self.requests.load(Ordering::Relaxed),
+            http2: self.http2.load(Ordering::Relaxed),
+            peak_in_flight: self.peak_in_flight.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl PoolMetrics {
+    /// HTTP/2 requests were sent concurrently, so they were multiplexed over the pooled connection
+    pub fn is_multiplexed(&self) -> bool {
+        self.http2 > 0 && self.peak_in_flight > 1
+    }
+}
+
+/// the part of the scan a request is sent within
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    /// the initial request, stability checks and the max amount of parameters per request
+    Learn = 0,
+
+    /// checking the wordlist
+    Fuzz = 1,
+
+    /// verification, aliases, duplicate keys and header folding
+    Verify = 2,
+
+    /// parameters with non random values like debug=true
+    Custom = 3,
+}
+
+/// requests sent within every stage of a url|method pair. Stages go one after another, so the current one is shared
+#[derive(Debug, Default)]
+pub struct StageStats {
+    stage: AtomicUsize,
+    requests: [AtomicUsize; 4],
+    retries: AtomicUsize,
+
+    /// the longest wait the server asked for via Retry-After in milliseconds
+    longest_wait: AtomicU64,
+}
+
+/// a snapshot of StageStats
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct StageMetrics {
+    pub learn: usize,
+    pub fuzz: usize,
+    pub verify: usize,
+    pub custom: usize,
+
+    /// requests that were sent once more after errors or retryable codes. Not a part of the stages
+    pub retries: usize,
+}
+
+impl StageStats {
+    pub fn set_stage(&self, stage: Stage) {
+        self.stage.store(stage as usize, Ordering::Relaxed);
+    }
+
+    pub fn sent(&self) {
+        self.requests[self.stage.load(Ordering::Relaxed)].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn retried(&self) {
+        self.retries.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn waited(&self, delay: Duration) {
+        self.longest_wait.fetch_max(delay.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    pub fn longest_wait(&self) -> Duration {
+        Duration::from_millis(self.longest_wait.load(Ordering::Relaxed))
+    }
+
+    pub fn metrics(&self) -> StageMetrics {
+        let requests = |stage: Stage| self.requests[stage as usize].load(Ordering::Relaxed);
+
+        StageMetrics {
+            learn: requests(Stage::Learn),
+            fuzz: requests(Stage::Fuzz),
+            verify: requests(Stage::Verify),
+            custom: requests(Stage::Custom),
+            retries: self.retries.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl StageMetrics {
+    pub fn total(&self) -> usize {
+        self.learn + self.fuzz + self.verify + self.custom + self.retries
+    }
+}
+
+impl fmt::Display for StageMetrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "learn {} | fuzz {} | verify {} | custom {} | retries {}",
+            self.learn, self.fuzz, self.verify, self.custom, self.retries
+        )
+    }
+}
+
+/// how often the hash pre-check allowed to skip diffing responses with the baseline
+#[derive(Debug, Default)]
+pub struct HashStats {
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+    collisions: AtomicUsize,
+}
+
+/// a snapshot of HashStats
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct HashMetrics {
+    /// responses identical to the baseline, the diff was skipped
+    pub hits: usize,
+
+    /// responses that were diffed
+    pub misses: usize,
+
+    /// responses with the same hash but different text. They were diffed as well
+    pub collisions: usize,
+}
+
+impl HashStats {
+    pub fn hit(&self) {
+        self.hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn miss(&self) {
+        self.misses.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn collision(&self) {
+        self.collisions.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn metrics(&self) -> HashMetrics {
+        HashMetrics {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            collisions: self.collisions.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// bytes sent and received within a url|method pair. Shared between clones
+/// bodies are counted after decompression, so the amount is an upper bound for compressed responses
+#[derive(Debug, Default)]
+pub struct TrafficStats {
+    sent: AtomicUsize,
+    received: AtomicUsize,
+}
+
+/// a snapshot of TrafficStats
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct TrafficMetrics {
+    pub sent: usize,
+    pub received: usize,
+}
+
+impl TrafficStats {
+    pub fn record(&self, sent: usize, received: usize) {
+        self.sent.fetch_add(sent, Ordering::Relaxed);
+        self.received.fetch_add(received, Ordering::Relaxed);
+    }
+
+    pub fn metrics(&self) -> TrafficMetrics {
+        TrafficMetrics {
+            sent: self.sent.load(Ordering::Relaxed),
+            received: self.received.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// 1536 -> "1.5 KB"
+pub fn format_bytes(bytes: usize) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+
+    let mut value = bytes as f64 / 1024.;
+    let mut unit = 0;
+
+    while value >= 1024. && unit < UNITS.len() - 1 {
+        value /= 1024.;
+        unit += 1;
+    }
+
+    format!("{:.1} {}", value, UNITS[unit])
+}
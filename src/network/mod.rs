@@ -1,5 +1,13 @@
+pub mod cache;
+pub mod doh;
+pub mod labels;
+pub mod metrics;
+pub mod nested;
 pub mod request;
 pub mod response;
+pub mod state;
 pub mod utils;
+pub mod values;
+pub mod websocket;
 
 mod tests;
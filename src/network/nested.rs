@@ -0,0 +1,221 @@
+use std::{error::Error, fmt};
+
+use percent_encoding::{percent_decode_str, utf8_percent_encode, NON_ALPHANUMERIC};
+
+use super::utils::json_escape;
+
+const BASE64_CHARSET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const BASE64_URL_CHARSET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+/// how a structure within a parameter's value is encoded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NestedEncoding {
+    /// {"a":1} -> eyJhIjoxfQ==
+    Base64Json {
+        url_safe: bool,
+        padded: bool,
+    },
+
+    /// a=1&b=2 -> a%3D1%26b%3D2
+    Urlencoded,
+
+    /// a:1:{s:1:"a";i:1;}
+    PhpSerialized,
+}
+
+/// a parameter within the url query whose value is a nested structure (--nested)
+/// parameters are injected into the decoded structure and the value is encoded back per request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NestedValue {
+    pub name: String,
+    pub encoding: NestedEncoding,
+
+    /// the decoded value
+    pub decoded: String,
+}
+
+impl fmt::Display for NestedEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NestedEncoding::Base64Json { .. } => write!(f, "base64 encoded json"),
+            NestedEncoding::Urlencoded => write!(f, "an urlencoded string"),
+            NestedEncoding::PhpSerialized => write!(f, "a php serialized array"),
+        }
+    }
+}
+
+impl NestedValue {
+    /// returns None in case the value isn't a known nested structure
+    pub fn new(name: &str, raw_value: &str) -> Option<Self> {
+        let value = percent_decode_str(raw_value).decode_utf8().ok()?.to_string();
+
+        let (encoding, decoded) = if value.starts_with("a:") && value.ends_with('}') && php_array_len(&value).is_some() {
+            (NestedEncoding::PhpSerialized, value)
+        } else if let Some((decoded, url_safe, padded)) = base64_decode(&value) {
+            let decoded = String::from_utf8(decoded).ok()?;
+
+            if !serde_json::from_str::<serde_json::Value>(&decoded).ok()?.is_object() {
+                return None;
+            }
+
+            (NestedEncoding::Base64Json { url_safe, padded }, decoded)
+        } else if raw_value.to_lowercase().contains("%3d") && value.split('&').all(|x| x.contains('=')) {
+            (NestedEncoding::Urlencoded, value)
+        } else {
+            return None;
+        };
+
+        Some(Self {
+            name: name.to_string(),
+            encoding,
+            decoded,
+        })
+    }
+
+    /// adds parameters to the decoded structure and encodes it back
+    pub fn encode<'a>(&self, parameters: impl Iterator<Item = &'a (String, String)>) -> String {
+        let parameters: Vec<&(String, String)> = parameters.collect();
+
+        let value = match self.encoding {
+            NestedEncoding::Base64Json { url_safe, padded } => {
+                let members = parameters
+                    .iter()
+                    .map(|(k, v)| format!("\"{}\":\"{}\"", json_escape(k), json_escape(v)))
+                    .collect::<Vec<String>>()
+                    .join(",");
+
+                let json = self.decoded.trim_end();
+                let json = &json[..json.len() - 1];
+
+                let json = if members.is_empty() || json.trim_end().ends_with('{') {
+                    format!("{}{}}}", json, members)
+                } else {
+                    format!("{},{}}}", json, members)
+                };
+
+                base64_encode(json.as_bytes(), url_safe, padded)
+            }
+            NestedEncoding::Urlencoded => parameters
+                .iter()
+                .map(|(k, v)| format!("{}={}", k, v))
+                .fold(self.decoded.clone(), |acc, x| if acc.is_empty() { x } else { acc + "&" + &x }),
+            NestedEncoding::PhpSerialized => {
+                let len = php_array_len(&self.decoded).unwrap_or_default();
+                let elements: String = parameters
+                    .iter()
+                    .map(|(k, v)| format!("s:{}:\"{}\";s:{}:\"{}\";", k.len(), k, v.len(), v))
+                    .collect();
+
+                // a:<len>:{<elements>}
+                let (_, rest) = self.decoded.split_once(":{").unwrap();
+
+                format!(
+                    "a:{}:{{{}{}}}",
+                    len + parameters.len(),
+                    &rest[..rest.len() - 1],
+                    elements
+                )
+            }
+        };
+
+        utf8_percent_encode(&value, NON_ALPHANUMERIC).to_string()
+    }
+}
+
+/// returns parameters within the url query with nested values
+pub fn find_nested(url: &str) -> Vec<NestedValue> {
+    let query = match url.split('#').next().unwrap_or_default().split_once('?') {
+        Some((_, query)) => query,
+        None => return Vec::new(),
+    };
+
+    query
+        .split('&')
+        .filter_map(|x| x.split_once('='))
+        .filter_map(|(k, v)| NestedValue::new(k, v))
+        .collect()
+}
+
+/// replaces the value of the parameter within the url query with the injection point
+pub fn inject(url: &str, name: &str) -> Result<(String, NestedValue), Box<dyn Error>> {
+    let (url, fragment) = match url.split_once('#') {
+        Some((url, fragment)) => (url, format!("#{}", fragment)),
+        None => (url, String::new()),
+    };
+
+    let (base, query) = url.split_once('?').ok_or("--nested requires a parameter within the url query")?;
+
+    let mut nested = None;
+    let mut parts = Vec::new();
+
+    for part in query.split('&') {
+        match part.split_once('=') {
+            Some((k, v)) if k == name && nested.is_none() => {
+                nested = Some(NestedValue::new(k, v).ok_or_else(|| {
+                    format!("The value of {} isn't base64 encoded json, an urlencoded string or a php serialized array", name)
+                })?);
+                parts.push(format!("{}=%s", k));
+            }
+            _ => parts.push(part.to_string()),
+        }
+    }
+
+    match nested {
+        Some(nested) => Ok((format!("{}?{}{}", base, parts.join("&"), fragment), nested)),
+        None => Err(format!("{} wasn't found within the url query", name))?,
+    }
+}
+
+/// a:<len>:{..} -> len
+fn php_array_len(value: &str) -> Option<usize> {
+    value.strip_prefix("a:")?.split_once(":{")?.0.parse().ok()
+}
+
+/// returns decoded bytes, whether the url safe alphabet was used and whether the value was padded
+fn base64_decode(value: &str) -> Option<(Vec<u8>, bool, bool)> {
+    let padded = value.ends_with('=');
+    let value = value.trim_end_matches('=');
+
+    // short values are usually ordinary words
+    if value.len() < 4 || value.len() % 4 == 1 {
+        return None;
+    }
+
+    let url_safe = value.contains('-') || value.contains('_');
+    let charset = if url_safe { BASE64_URL_CHARSET } else { BASE64_CHARSET };
+
+    let mut bits: u32 = 0;
+    let mut bits_len = 0;
+    let mut decoded = Vec::with_capacity(value.len() * 3 / 4);
+
+    for c in value.bytes() {
+        bits = (bits << 6) | charset.iter().position(|x| *x == c)? as u32;
+        bits_len += 6;
+
+        if bits_len >= 8 {
+            bits_len -= 8;
+            decoded.push((bits >> bits_len) as u8);
+        }
+    }
+
+    Some((decoded, url_safe, padded))
+}
+
+fn base64_encode(value: &[u8], url_safe: bool, padded: bool) -> String {
+    let charset = if url_safe { BASE64_URL_CHARSET } else { BASE64_CHARSET };
+    let mut encoded = String::with_capacity(value.len() * 4 / 3 + 4);
+
+    for chunk in value.chunks(3) {
+        let bits = chunk.iter().enumerate().fold(0u32, |acc, (i, x)| acc | (*x as u32) << (16 - i * 8));
+
+        for i in 0..=chunk.len() {
+            encoded.push(charset[(bits >> (18 - i * 6) & 0x3f) as usize] as char);
+        }
+
+        if padded {
+            encoded += &"=".repeat(3 - chunk.len());
+        }
+    }
+
+    encoded
+}
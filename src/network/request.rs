@@ -1,16 +1,21 @@
-use crate::{config::structs::Config, utils::random_line, VALUE_LENGTH, RANDOM_LENGTH};
+use crate::{config::structs::Config, error, utils::random_line, VALUE_LENGTH, RANDOM_LENGTH, BODY_FILE_CHUNK_SIZE};
+use futures::{future::Either, stream, Stream, StreamExt};
 use itertools::Itertools;
 use lazy_static::lazy_static;
 use percent_encoding::utf8_percent_encode;
 use regex::Regex;
 use reqwest::Client;
 use std::{
+    cell::RefCell,
     collections::HashMap,
     convert::TryFrom,
     error::Error,
+    io,
     iter::FromIterator,
-    time::{Duration, Instant},
+    sync::Arc,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
+use tokio::io::AsyncReadExt;
 use url::Url;
 
 /// in order to be able to use make_query() for headers as well
@@ -18,9 +23,28 @@ const HEADERS_TEMPLATE: &str = "%k\x00@%=%@\x00%v";
 const HEADERS_MIDDLE: &str = "\x00@%=%@\x00";
 const HEADERS_JOINER: &str = "\x01@%&%@\x01";
 
+/// a part per parameter, %b is replaced with the boundary
+const MULTIPART_TEMPLATE: &str = "--%b\r\nContent-Disposition: form-data; name=\"%k\"\r\n\r\n%v\r\n";
+
+/// injection points within the body with their own (template, joiner, is_json)
+/// for hybrid bodies like {"a":1, %s:json%, "query":"b=2&%s:urlencoded%"}
+const BODY_MARKERS: [(&str, &str, &str, bool); 2] = [
+    ("%s:json%", "\"%k\":%v", ",", true),
+    ("%s:urlencoded%", "%k=%v", "&", false),
+];
+
 use super::{
+    cache::ResponseCache,
+    metrics::{HashStats, PoolStats, StageStats, TrafficStats},
+    nested::{self, NestedValue},
     response::Response,
-    utils::{DataType, Headers, InjectionPlace, FRAGMENT, create_client, is_binary_content},
+    state::RunState,
+    values::ValueGenerators,
+    websocket,
+    utils::{
+        AuditEntry, BodyFraming, BodyPart, DataType, DelayLanes, Headers, InjectionPlace, FRAGMENT, REDIRECT_CHAIN, RetryPolicy, create_client, is_binary_content, json_escape, normalize_path,
+        looks_like_json, split_body_includes, transform_values,
+    },
 };
 
 #[derive(Debug, Clone, Default)]
@@ -38,6 +62,24 @@ pub struct RequestDefaults {
     /// how much to sleep between requests in millisecs
     pub delay: Duration, //MOVE to config
 
+    /// max amount of requests per second to the host. 0 - no limit
+    pub rate: u32,
+
+    /// max amount of requests per second in total. 0 - no limit
+    pub rate_limit: u32,
+
+    /// max amount of bytes to send and receive within the whole run. 0 - no limit
+    pub max_bytes: usize,
+
+    /// the command to pass parameter values through before sending them
+    pub value_transform: String,
+
+    /// lower the rate limit on 429 and 503 responses
+    pub adaptive_rate: bool,
+
+    /// spreads the delay between concurrent requests. Shared between clones
+    pub delay_lanes: Arc<DelayLanes>,
+
     /// default reqwest client
     pub client: Client,
 
@@ -71,11 +113,57 @@ pub struct RequestDefaults {
 
     /// check body of responses with binary content type
     pub check_binary: bool,
+
+    /// how the body is framed
+    pub body_framing: BodyFraming,
+
+    /// values for parameters without a user supplied value
+    pub value_generators: ValueGenerators,
+
+    /// usage of the client. Shared between clones along with the client's connection pool
+    pub pool: Arc<PoolStats>,
+
+    /// how many responses were compared with the baseline by hashes only
+    pub hashing: Arc<HashStats>,
+
+    /// requests per stage of the scan. Shared between clones as well
+    pub stages: Arc<StageStats>,
+
+    /// bytes sent and received. Shared between clones as well
+    pub traffic: Arc<TrafficStats>,
+
+    /// the parameter within the query whose value contains the injection point (--nested)
+    pub nested: Option<NestedValue>,
+
+    /// file to write send times of every request to (--audit-log)
+    pub audit_log: String,
+
+    /// compare hashes of lines instead of copying pages (--low-memory)
+    pub low_memory: bool,
+
+    /// the first frame after the handshake is read as the body (--websocket)
+    pub websocket: bool,
+
+    /// the boundary of the multipart/form-data body. Empty in case the body isn't multipart
+    pub boundary: String,
+
+    /// which failed requests are retried
+    pub retry: RetryPolicy,
+
+    /// pacing, rate limits and the traffic of the whole run. Shared between every url of the run
+    pub state: Arc<RunState>,
+
+    /// responses to identical control requests of the url and method (--cache-ttl). Shared between clones
+    pub cache: Arc<ResponseCache>,
 }
 
+/// owns its copy of the defaults, so requests and responses can outlive them and be moved between tasks
 #[derive(Debug, Clone)]
-pub struct Request<'a> {
-    pub defaults: &'a RequestDefaults,
+pub struct Request {
+    pub defaults: Arc<RequestDefaults>,
+
+    /// we can't use defaults.method because there can be an injection point
+    pub method: String,
 
     /// vector of supplied parameters
     pub parameters: Vec<String>,
@@ -83,6 +171,10 @@ pub struct Request<'a> {
     /// parsed parameters (key, value)
     pub prepared_parameters: Vec<(String, String)>,
 
+    /// prepared parameters with values passed through --value-transform. Empty without the transform
+    /// reflections are still searched by the original values
+    pub transformed_parameters: Vec<(String, String)>,
+
     /// parameters with not random values
     /// we need this vector to ignore searching for reflections for these parameters
     /// for example admin=1 - its obvious that 1 can be reflected unpredictable amount of times
@@ -98,23 +190,34 @@ pub struct Request<'a> {
     /// whether the request was prepared
     /// {{random}} things replaced, prepared_parameters filled
     pub prepared: bool,
+
+    /// whether prepared_parameters were filled. Happens before the rest of the preparation in case values are transformed
+    parameters_prepared: bool,
 }
 
-impl<'a> Request<'a> {
-    pub fn new(l: &'a RequestDefaults, parameters: Vec<String>) -> Self {
+impl Request {
+    pub fn new(l: &RequestDefaults, parameters: Vec<String>) -> Self {
+        Self::shared(Arc::new(l.clone()), parameters)
+    }
+
+    /// a request with defaults shared with other requests
+    pub fn shared(l: Arc<RequestDefaults>, parameters: Vec<String>) -> Self {
         Self {
             path: l.path.to_owned(),
-            defaults: l,
+            method: l.method.to_owned(),
             headers: Vec::new(),
             body: l.body.clone(),
             parameters,
             prepared_parameters: Vec::new(), //l.parameters.clone(),
+            transformed_parameters: Vec::new(),
             non_random_parameters: Vec::new(),
             prepared: false,
+            parameters_prepared: false,
+            defaults: l,
         }
     }
 
-    pub fn new_random(l: &'a RequestDefaults, max: usize) -> Self {
+    pub fn new_random(l: &RequestDefaults, max: usize) -> Self {
         let parameters = Vec::from_iter((0..max).map(|_| random_line(VALUE_LENGTH)));
         Request::new(l, parameters)
     }
@@ -137,36 +240,39 @@ impl<'a> Request<'a> {
     }
 
     pub fn make_query(&self) -> String {
+        self.make_query_with(&self.defaults.template, &self.defaults.joiner, self.defaults.is_json)
+    }
+
+    /// joins parameters using the given template instead of the default one
+    pub fn make_query_with(&self, template: &str, joiner: &str, is_json: bool) -> String {
         lazy_static! {
             static ref RE_JSON_WORDS_WITHOUT_QUOTES: Regex =
                 Regex::new(r#"^([1-9]\d*|null|false|true)$"#).unwrap();
         }
 
-        let query = if self.defaults.is_json {
-            self.prepared_parameters
-                .iter()
-                .chain(self.defaults.parameters.iter())
+        let query = if is_json {
+            self.sent_parameters()
                 // not very optimal because we know that there's a lot of random parameters
                 // that doesn't need to be checked
+                // keys and values are escaped so words with quotes or backslashes don't break the body
                 .map(|(k, v)| {
+                    let k = json_escape(k);
+
                     if RE_JSON_WORDS_WITHOUT_QUOTES.is_match(v) {
-                        self.defaults.template.replace("%k", k).replace("%v", v)
+                        template.replace("%k", &k).replace("%v", v)
                     } else {
-                        self.defaults
-                            .template
-                            .replace("%k", k)
-                            .replace("%v", &format!("\"{}\"", v))
+                        template
+                            .replace("%k", &k)
+                            .replace("%v", &format!("\"{}\"", json_escape(v)))
                     }
                 })
                 .collect::<Vec<String>>()
-                .join(&self.defaults.joiner)
+                .join(joiner)
         } else {
-            self.prepared_parameters
-                .iter()
-                .chain(self.defaults.parameters.iter())
-                .map(|(k, v)| self.defaults.template.replace("%k", k).replace("%v", v))
+            self.sent_parameters()
+                .map(|(k, v)| template.replace("%k", k).replace("%v", v))
                 .collect::<Vec<String>>()
-                .join(&self.defaults.joiner)
+                .join(joiner)
         };
 
         if self.defaults.encode {
@@ -176,6 +282,34 @@ impl<'a> Request<'a> {
         }
     }
 
+    /// parameters with values as they're sent along with parameters added to every request
+    fn sent_parameters(&self) -> impl Iterator<Item = &(String, String)> {
+        let parameters = if self.transformed_parameters.is_empty() {
+            &self.prepared_parameters
+        } else {
+            &self.transformed_parameters
+        };
+
+        parameters.iter().chain(self.defaults.parameters.iter())
+    }
+
+    /// name=value pairs joined with "; "
+    /// in case --encode is provided, only names and values are encoded so the cookie stays valid
+    pub fn make_cookies(&self) -> String {
+        let encode = |x: &str| {
+            if self.defaults.encode {
+                utf8_percent_encode(x, &FRAGMENT).to_string()
+            } else {
+                x.to_string()
+            }
+        };
+
+        self.sent_parameters()
+            .map(|(k, v)| format!("{}={}", encode(k), encode(v)))
+            .collect::<Vec<String>>()
+            .join("; ")
+    }
+
     /// replace injection points with parameters
     /// replace templates ({{random}}) with random values
     /// additional param is for reflection counting TODO REMOVE
@@ -189,41 +323,13 @@ impl<'a> Request<'a> {
         }
         self.prepared = true;
 
-        self.non_random_parameters = Vec::from_iter(
-            self.parameters
-                .iter()
-                .filter(|x| x.contains('='))
-                .map(|x| x.split('='))
-                .map(|mut x| {
-                    (
-                        x.next().unwrap().to_owned(),
-                        x.next().unwrap_or("").to_owned(),
-                    )
-                }),
-        );
+        self.prepare_parameters();
 
-        self.prepared_parameters = Vec::from_iter(
-            // append self.prepared_parameters (can be set from RequestDefaults using recursive search)
-            self.prepared_parameters
-                .iter()
-                .map(|(k, v)| (k.to_owned(), v.to_owned()))
-                // append parameters with not random values
-                .chain(
-                    self.non_random_parameters
-                        .iter()
-                        .map(|(k, v)| (k.to_owned(), v.to_owned())),
-                )
-                // append random parameters
-                .chain(
-                    self.parameters
-                        .iter()
-                        .filter(|x| !x.is_empty() && !x.contains("="))
-                        .map(|x| (x.to_owned(), random_line(VALUE_LENGTH))),
-                ),
-        );
+        let defaults = Arc::clone(&self.defaults);
 
-        if self.defaults.injection_place != InjectionPlace::HeaderValue {
-            for (k, v) in self.defaults.custom_headers.iter() {
+        if !matches!(self.defaults.injection_place, InjectionPlace::HeaderValue | InjectionPlace::Cookie) {
+            // headers with injection points within names are added below
+            for (k, v) in defaults.custom_headers.iter().filter(|(k, _)| !k.contains("%s")) {
                 self.set_header(k, &v.replace("{{random}}", &random_line(RANDOM_LENGTH)));
             }
         }
@@ -231,40 +337,92 @@ impl<'a> Request<'a> {
         self.body = self.body.replace("{{random}}", &random_line(RANDOM_LENGTH));
 
         match self.defaults.injection_place {
-            InjectionPlace::Path => self.path = self.path.replace("%s", &self.make_query()),
+            InjectionPlace::Path | InjectionPlace::PathSegment => {
+                let query = match &self.defaults.nested {
+                    Some(nested) => nested.encode(self.sent_parameters()),
+                    None => self.make_query(),
+                };
+
+                self.path = self.path.replace("%s", &query)
+            }
             InjectionPlace::Body => {
+                // named markers go first because they contain %s as well
+                for (marker, template, joiner, is_json) in BODY_MARKERS.iter() {
+                    if self.body.contains(marker) {
+                        self.body = self.body.replace(marker, &self.make_query_with(template, joiner, *is_json));
+                    }
+                }
+
                 self.body = self.body.replace("%s", &self.make_query());
 
                 if !self.defaults.custom_headers.contains_key("Content-Type") {
-                    if self.defaults.is_json {
+                    if !self.defaults.boundary.is_empty() {
+                        self.set_header("Content-Type", &format!("multipart/form-data; boundary={}", self.defaults.boundary));
+                    } else if self.defaults.is_json {
                         self.set_header("Content-Type", "application/json");
                     } else {
                         self.set_header("Content-Type", "application/x-www-form-urlencoded");
                     }
                 }
             }
-            InjectionPlace::HeaderValue => {
+            InjectionPlace::HeaderValue | InjectionPlace::Cookie => {
                 // in case someone searches headers while sending a valid body - it's usually important to set Content-Type header as well.
                 if !self.defaults.custom_headers.contains_key("Content-Type") && self.defaults.method != "GET" && self.defaults.method != "HEAD" && !self.body.is_empty() {
-                    if self.body.starts_with('{') {
+                    if looks_like_json(&self.body) {
                         self.set_header("Content-Type", "application/json");
                     } else {
                         self.set_header("Content-Type", "application/x-www-form-urlencoded");
                     }
                 }
 
-                for (k, v) in self.defaults.custom_headers.iter() {
+                let query = if self.defaults.injection_place == InjectionPlace::Cookie {
+                    self.make_cookies()
+                } else {
+                    self.make_query()
+                };
+
+                for (k, v) in defaults.custom_headers.iter() {
                     self.set_header(
                         k,
                         &v.replace("{{random}}", &random_line(RANDOM_LENGTH))
-                            .replace("%s", &self.make_query()),
+                            .replace("%s", &query),
                     );
                 }
             }
+            InjectionPlace::Method => {
+                let query = self.make_query();
+
+                // requests without parameters (initial, warmup, probes) are sent with the method around the injection point
+                self.method = if query.is_empty() {
+                    match self.method.replace("%s", "") {
+                        method if method.is_empty() => "GET".to_string(),
+                        method => method,
+                    }
+                } else {
+                    self.method.replace("%s", &query)
+                };
+            }
+            InjectionPlace::HeaderName => {
+                let names: Vec<String> = self
+                    .make_query()
+                    .split(&self.defaults.joiner)
+                    .filter(|x| !x.is_empty())
+                    .map(|x| x.to_owned())
+                    .collect();
+
+                for (k, v) in defaults.custom_headers.iter().filter(|(k, _)| k.contains("%s")) {
+                    for name in names.iter() {
+                        self.set_header(
+                            k.replace("%s", name),
+                            v.replace("{{random}}", &random_line(RANDOM_LENGTH)),
+                        );
+                    }
+                }
+            }
             InjectionPlace::Headers => {
                 // in case someone searches headers while sending a valid body - it's usually important to set Content-Type header as well.
                 if !self.defaults.custom_headers.contains_key("Content-Type") && self.defaults.method != "GET" && self.defaults.method != "HEAD" && !self.body.is_empty() {
-                    if self.body.starts_with('{') {
+                    if looks_like_json(&self.body) {
                         self.set_header("Content-Type", "application/json");
                     } else {
                         self.set_header("Content-Type", "application/x-www-form-urlencoded");
@@ -284,54 +442,233 @@ impl<'a> Request<'a> {
         }
     }
 
-    pub async fn send_by(self, clients: &Client) -> Result<Response<'a>, Box<dyn Error>> {
-        match self.clone().request(clients).await {
-            Ok(val) => Ok(val),
-            Err(_) => {
-                tokio::time::sleep(Duration::from_secs(10)).await;
-                Ok(self.clone().request(clients).await?)
-            }
+    /// fills prepared_parameters with the supplied and generated values
+    fn prepare_parameters(&mut self) {
+        if self.parameters_prepared {
+            return;
+        }
+        self.parameters_prepared = true;
+
+        self.non_random_parameters = Vec::from_iter(
+            self.parameters
+                .iter()
+                .filter(|x| x.contains('='))
+                .map(|x| x.split('='))
+                .map(|mut x| {
+                    (
+                        x.next().unwrap().to_owned(),
+                        x.next().unwrap_or("").to_owned(),
+                    )
+                }),
+        );
+
+        let defaults = Arc::clone(&self.defaults);
+        let value_generators = &defaults.value_generators;
+
+        let generated_parameters: Vec<(String, String)> = self
+            .parameters
+            .iter()
+            .filter(|x| !x.is_empty() && !x.contains('='))
+            .map(|x| (x.to_owned(), value_generators.get(x).generate()))
+            .collect();
+
+        self.prepared_parameters = Vec::from_iter(
+            // append self.prepared_parameters (can be set from RequestDefaults using recursive search)
+            self.prepared_parameters
+                .iter()
+                .map(|(k, v)| (k.to_owned(), v.to_owned()))
+                // append parameters with not random values
+                .chain(
+                    self.non_random_parameters
+                        .iter()
+                        .map(|(k, v)| (k.to_owned(), v.to_owned())),
+                )
+                // append parameters with generated values
+                .chain(generated_parameters.iter().cloned()),
+        );
+
+        // values like true/false repeat, so they can't be used to search for reflections
+        self.non_random_parameters.extend(
+            generated_parameters
+                .into_iter()
+                .filter(|(k, _)| !value_generators.get(k).is_unique()),
+        );
+    }
+
+    /// passes values of prepared parameters through --value-transform
+    /// needs to be called before prepare(), otherwise the original values are sent
+    pub async fn transform(&mut self) -> error::Result<()> {
+        if self.defaults.value_transform.is_empty() || self.prepared {
+            return Ok(());
+        }
+
+        self.prepare_parameters();
+
+        if self.prepared_parameters.is_empty() {
+            return Ok(());
+        }
+
+        let values: Vec<&str> = self.prepared_parameters.iter().map(|(_, v)| v.as_str()).collect();
+        let transformed = transform_values(&self.defaults.value_transform, &values).await?;
+
+        self.transformed_parameters = self
+            .prepared_parameters
+            .iter()
+            .zip(transformed)
+            .map(|((k, _), v)| (k.to_owned(), v))
+            .collect();
+
+        Ok(())
+    }
+
+    pub async fn send_by(self, client: &Client) -> error::Result<Response> {
+        let retry = &self.defaults.retry;
+        let mut attempt = 0;
+
+        self.defaults.stages.sent();
+
+        loop {
+            let delay = match self.clone().request(client).await {
+                Ok(response) if attempt < retry.retries && retry.codes.contains(&response.code) => {
+                    match RetryPolicy::retry_after(&response.headers) {
+                        Some(delay) => {
+                            self.defaults.stages.waited(delay);
+                            delay
+                        }
+                        None => RetryPolicy::backoff(attempt),
+                    }
+                }
+                Err(error::Error::Network(err)) if attempt < retry.retries && retry.is_retryable(&err) => RetryPolicy::backoff(attempt),
+                response => return response,
+            };
+
+            log::debug!("Retrying {} in {}ms", self.url(), delay.as_millis());
+
+            tokio::time::sleep(delay).await;
+            self.defaults.stages.retried();
+            attempt += 1;
         }
     }
 
     // we need to somehow impl Send and Sync for error (for using send() within async recursive func)
     // therefore we are wrapping the original call to send()
     // not a good way tho, maybe someone can suggest a better one
-    pub async fn wrapped_send(self) -> Result<Response<'a>, Box<dyn Error + Send + Sync>> {
+    pub async fn wrapped_send(self) -> Result<Response, Box<dyn Error + Send + Sync>> {
         match self.send().await {
             Err(err) => Err(err.to_string().into()),
             Ok(val) => Ok(val),
         }
     }
 
-    pub async fn send(self) -> Result<Response<'a>, Box<dyn Error>> {
-        let dc = &self.defaults.client;
-        self.send_by(dc).await
+    pub async fn send(self) -> error::Result<Response> {
+        let defaults = Arc::clone(&self.defaults);
+
+        defaults.pool.start();
+        let response = self.send_by(&defaults.client).await;
+        defaults.pool.finish(response.as_ref().ok().and_then(|x| x.http_version));
+
+        response
     }
 
-    async fn request(mut self, client: &Client) -> Result<Response<'a>, reqwest::Error> {
+    async fn request(mut self, client: &Client) -> error::Result<Response> {
+        self.transform().await?;
         self.prepare();
 
         let mut request = http::Request::builder()
-            .method(self.defaults.method.as_str())
+            .method(self.method.as_str())
             .uri(self.url());
 
         for (k, v) in &self.headers {
             request = request.header(k, v)
         }
 
-        let request = request.body(self.body.to_owned()).unwrap();
+        let body_parts = split_body_includes(&self.body);
+        let has_files = body_parts.iter().any(|x| matches!(x, BodyPart::File(_)));
+
+        // the request line, headers and the body
+        let sent_bytes = self.method.len()
+            + self.url().len()
+            + headers_length(&self.headers)
+            + body_length(&body_parts).unwrap_or(self.body.len() as u64) as usize;
+        let framing = if self.body.is_empty() { BodyFraming::Auto } else { self.defaults.body_framing };
+
+        if matches!(framing, BodyFraming::ContentLength | BodyFraming::Both) || (framing == BodyFraming::Auto && has_files) {
+            // otherwise streamed files are sent chunked
+            if let Some(length) = body_length(&body_parts) {
+                if self.headers.get_value_case_insensitive("content-length").is_none() {
+                    request = request.header("Content-Length", length.to_string());
+                }
+            }
+        }
+
+        if framing == BodyFraming::Both && self.headers.get_value_case_insensitive("transfer-encoding").is_none() {
+            request = request.header("Transfer-Encoding", "chunked");
+        }
 
-        tokio::time::sleep(self.defaults.delay).await;
+        // large included files are streamed from disk
+        // streamed bodies without the known length are sent chunked
+        let body = if has_files || matches!(framing, BodyFraming::Chunked | BodyFraming::Both) {
+            reqwest::Body::wrap_stream(body_stream(body_parts))
+        } else {
+            reqwest::Body::from(self.body.to_owned())
+        };
+
+        // e.g. a word that isn't a valid method or a header value with control chars
+        let request = request
+            .body(body)
+            .map_err(|err| error::Error::Other(format!("Unable to build the request: {}", err)))?;
 
-        let reqwest_req = reqwest::Request::try_from(request).unwrap();
+        self.defaults.delay_lanes.wait(self.defaults.delay).await;
+
+        if self.defaults.rate > 0 {
+            self.defaults.state.throttle(&self.defaults.host, self.defaults.rate).await;
+        }
+
+        if self.defaults.rate_limit > 0 {
+            self.defaults.state.limit_rate(self.defaults.rate_limit).await;
+        }
+
+        let reqwest_req = reqwest::Request::try_from(request)?;
 
         let start = Instant::now();
 
-        let res = client.execute(reqwest_req).await?;
+        // taken right before sending, so the configured delay doesn't affect it
+        let sent_at = SystemTime::now().duration_since(UNIX_EPOCH).map(|x| x.as_micros()).unwrap_or_default();
+        let since_previous = if self.defaults.audit_log.is_empty() {
+            None
+        } else {
+            self.defaults.state.record_sent(&self.defaults.host, sent_at)
+        };
+
+        // counted before sending, so failed and timed out requests are a part of the traffic as well
+        self.defaults.traffic.record(sent_bytes, 0);
+        self.defaults.state.record_traffic(sent_bytes, self.defaults.max_bytes);
+
+        let (res, redirects) = REDIRECT_CHAIN
+            .scope(RefCell::new(Vec::new()), async {
+                let res = client.execute(reqwest_req).await;
+                (res, REDIRECT_CHAIN.with(|x| x.take()))
+            })
+            .await;
 
         let duration = start.elapsed();
 
+        if !self.defaults.audit_log.is_empty() {
+            self.defaults.state.write_audit(
+                &self.defaults.audit_log,
+                &AuditEntry {
+                    sent_at,
+                    since_previous,
+                    method: &self.method,
+                    url: &self.url(),
+                    code: res.as_ref().ok().map(|x| x.status().as_u16()),
+                    time: duration.as_millis(),
+                },
+            );
+        }
+
+        let res = res?;
+
         let mut headers: Vec<(String, String)> = Vec::new();
 
         for (k, v) in res.headers() {
@@ -350,14 +687,32 @@ impl<'a> Request<'a> {
         }
 
         let code = res.status().as_u16();
+
+        if self.defaults.adaptive_rate {
+            if let Some(rate) = self.defaults.state.adapt_rate(code) {
+                log::info!("The rate limit was changed to {:.1} requests per second", rate);
+            }
+        }
         let http_version = Some(res.version());
+        let remote_addr = res.remote_addr();
 
-        let body_bytes = res.bytes().await?.to_vec();
+        // handshakes don't have a body, while the first frame shows how the connection behaves
+        let body_bytes = if self.defaults.websocket && code == 101 {
+            websocket::first_frame(res).await
+        } else {
+            res.bytes().await?.to_vec()
+        };
+
+        // the status line, headers and the decoded body
+        let received_bytes = 16 + headers_length(&headers) + body_bytes.len();
+        self.defaults.traffic.record(0, received_bytes);
+        self.defaults.state.record_traffic(received_bytes, self.defaults.max_bytes);
 
+        // the body isn't copied in case it's valid utf8
         let text = if is_binary_content(headers.get_value_case_insensitive("content-type")) && !self.defaults.check_binary {
             String::new()
         } else {
-            String::from_utf8_lossy(&body_bytes).to_string()
+            String::from_utf8(body_bytes).unwrap_or_else(|err| String::from_utf8_lossy(err.as_bytes()).to_string())
         };
 
         let mut response = Response {
@@ -365,9 +720,11 @@ impl<'a> Request<'a> {
             headers,
             time: duration.as_millis(),
             text,
-            request: Some(self),
+            request: self,
             reflected_parameters: HashMap::new(),
             http_version,
+            remote_addr,
+            redirects,
         };
 
         response.beautify_body();
@@ -377,7 +734,7 @@ impl<'a> Request<'a> {
     }
 
     /// the function is used when there was a error during the request
-    pub fn empty_response(mut self) -> Response<'a> {
+    pub fn empty_response(mut self) -> Response {
         self.prepare();
         Response {
             time: 0,
@@ -385,11 +742,74 @@ impl<'a> Request<'a> {
             headers: Vec::new(),
             text: String::new(),
             reflected_parameters: HashMap::new(),
-            request: Some(self),
+            request: self,
             http_version: None,
+            remote_addr: None,
+            redirects: Vec::new(),
         }
     }
 
+    /// the prepared request minus random values
+    /// requests that differ only by random parameters and {{random}} variables share the key
+    pub fn cache_key(&self) -> String {
+        let mut non_random_parameters: Vec<&String> = self.parameters.iter().filter(|x| x.contains('=')).collect();
+        non_random_parameters.sort();
+
+        format!(
+            "{} {}\n{:?}\n{:?}\n{:?}\n{}\n{}",
+            self.method,
+            self.defaults.url(),
+            self.defaults.custom_headers,
+            self.defaults.parameters,
+            non_random_parameters,
+            self.parameters.iter().filter(|x| !x.is_empty() && !x.contains('=')).count(),
+            self.defaults.body
+        )
+    }
+
+    /// a one-line command to reproduce the request with only the given parameter
+    /// the full url for GET requests with parameters in the url, curl command otherwise
+    /// default headers like User-Agent don't matter for the reproduction, so they don't force the curl command
+    pub fn reproduction(&self, parameter: &str) -> String {
+        let name = parameter.split('=').next().unwrap_or(parameter);
+
+        // keep the value that was actually sent
+        let parameter = match self.prepared_parameters.iter().find(|(k, _)| k == name) {
+            Some((k, v)) => format!("{}={}", k, v),
+            None => parameter.to_string(),
+        };
+
+        let mut request = Request::shared(Arc::clone(&self.defaults), vec![parameter]);
+        request.prepare();
+
+        let port = if self.defaults.port == 443 || self.defaults.port == 80 {
+            String::new()
+        } else {
+            format!(":{}", self.defaults.port)
+        };
+
+        let url = format!("{}://{}{}{}", self.defaults.scheme, self.defaults.host, port, request.path);
+
+        if request.method == "GET"
+            && request.body.is_empty()
+            && matches!(self.defaults.injection_place, InjectionPlace::Path | InjectionPlace::PathSegment)
+        {
+            return url;
+        }
+
+        let mut command = format!("curl -X {} {}", shell_quote(&request.method), shell_quote(&url));
+
+        for (k, v) in request.headers.iter() {
+            command += &format!(" -H {}", shell_quote(&format!("{}: {}", k, v)));
+        }
+
+        if !request.body.is_empty() {
+            command += &format!(" --data-raw {}", shell_quote(&request.body));
+        }
+
+        command
+    }
+
     pub fn print(&mut self) -> String {
         self.prepare();
         self.print_sent()
@@ -404,7 +824,7 @@ impl<'a> Request<'a> {
 
         let mut str_req = format!(
             "{} {} HTTP/1.1\nHost: {}\n",
-            &self.defaults.method, self.path, host
+            &self.method, self.path, host
         );
 
         for (k, v) in self.headers.iter().sorted() {
@@ -419,28 +839,137 @@ impl<'a> Request<'a> {
     }
 }
 
+/// the size of headers as they're sent: "name: value\r\n"
+fn headers_length(headers: &[(String, String)]) -> usize {
+    headers.iter().map(|(k, v)| k.len() + v.len() + 4).sum()
+}
+
+/// the length of the body with included files. None in case some file can't be read
+fn body_length(parts: &[BodyPart]) -> Option<u64> {
+    let mut length = 0;
+
+    for part in parts {
+        length += match part {
+            BodyPart::Text(text) => text.len() as u64,
+            BodyPart::File(path) => std::fs::metadata(path).ok()?.len(),
+        };
+    }
+
+    Some(length)
+}
+
+/// text parts as is and files chunk by chunk
+fn body_stream(parts: Vec<BodyPart>) -> impl Stream<Item = io::Result<Vec<u8>>> {
+    stream::iter(parts).flat_map(|part| match part {
+        BodyPart::Text(text) => Either::Left(stream::once(async move { Ok(text.into_bytes()) })),
+        BodyPart::File(path) => Either::Right(file_stream(path)),
+    })
+}
+
+fn file_stream(path: String) -> impl Stream<Item = io::Result<Vec<u8>>> {
+    stream::unfold(Some((path, None)), |state| async move {
+        let (path, file) = state?;
+
+        let mut file = match file {
+            Some(file) => file,
+            None => match tokio::fs::File::open(&path).await {
+                Ok(file) => file,
+                Err(err) => return Some((Err(err), None)),
+            },
+        };
+
+        let mut buf = vec![0; BODY_FILE_CHUNK_SIZE];
+
+        match file.read(&mut buf).await {
+            Ok(0) => None,
+            Ok(n) => {
+                buf.truncate(n);
+                Some((Ok(buf), Some((path, Some(file)))))
+            }
+            Err(err) => Some((Err(err), None)),
+        }
+    })
+}
+
+/// wraps the string in single quotes for posix shells when needed
+fn shell_quote(s: &str) -> String {
+    if !s.is_empty() && s.chars().all(|c| c.is_ascii_alphanumeric()) {
+        s.to_string()
+    } else {
+        format!("'{}'", s.replace('\'', "'\\''"))
+    }
+}
+
 impl<'a> RequestDefaults {
     pub fn from_config<S: Into<String>>(
         config: &Config,
         method: S,
         url: S,
-    ) -> Result<Self, Box<dyn Error>> {
-        Self::new(
+    ) -> error::Result<Self> {
+        let url = url.into();
+
+        // the value of the parameter becomes the injection point
+        let (url, nested) = if config.nested.is_empty() {
+            (url, None)
+        } else {
+            let (url, nested) = nested::inject(&url, &config.nested)?;
+            (url, Some(nested))
+        };
+
+        let mut request_defaults = Self::new(
             method.into().as_str(), //method needs to be set explicitly via .set_method()
-            url.into().as_str(),    //as well as url
+            url.as_str(),           //as well as url
             config.custom_headers.clone(),
             config.delay,
-            create_client(config, false)?,
+            create_client(config, false, None)?,
             config.template.clone(),
             config.joiner.clone(),
             config.encode,
             config.data_type.clone(),
             config.invert,
             config.headers_discovery,
+            config.path_segments,
             &config.body,
             config.disable_custom_parameters,
             config.check_binary
-        )
+        )?;
+
+        if config.raw_path {
+            request_defaults.keep_raw_path(&url)?;
+        } else {
+            if config.keep_query {
+                request_defaults.keep_raw_query(&url)?;
+            }
+
+            request_defaults.path = normalize_path(&request_defaults.path);
+        }
+
+        if nested.is_some() && request_defaults.injection_place != InjectionPlace::Path {
+            Err("--nested works only with parameters within the url query. Use --invert for methods with a body")?;
+        }
+
+        if !config.required.is_empty() && !matches!(request_defaults.injection_place, InjectionPlace::Path | InjectionPlace::Body) {
+            Err("--required works only with parameters within the query or the body")?;
+        }
+
+        request_defaults.parameters = config.required.clone();
+        request_defaults.body_framing = config.body_framing;
+        request_defaults.value_generators = config.value_generators.clone();
+        request_defaults.nested = nested;
+        request_defaults.audit_log = config.audit_log.clone();
+        request_defaults.low_memory = config.low_memory;
+        request_defaults.websocket = config.websocket;
+        request_defaults.rate = config.rate;
+        request_defaults.rate_limit = config.rate_limit;
+        request_defaults.max_bytes = config.max_bytes;
+        request_defaults.value_transform = config.value_transform.clone();
+        request_defaults.adaptive_rate = config.adaptive_rate;
+        request_defaults.delay_lanes = Arc::new(DelayLanes::new(config.concurrency));
+        request_defaults.retry = config.retry.clone();
+        request_defaults.state = Arc::clone(&config.state);
+        request_defaults.cache = Arc::new(ResponseCache::new(Duration::from_secs(config.cache_ttl)));
+
+        Ok(request_defaults)
     }
 
     pub fn new<S: Into<String> + From<String> + std::fmt::Debug>(
@@ -455,13 +984,20 @@ impl<'a> RequestDefaults {
         mut data_type: Option<DataType>,
         invert: bool,
         headers_discovery: bool,
+        path_segments: bool,
         body: &str,
         disable_custom_parameters: bool,
         check_binary: bool,
-    ) -> Result<Self, Box<dyn Error>> {
+    ) -> error::Result<Self> {
 
-        let mut injection_place = if headers_discovery {
+        let mut injection_place = if method.contains("%s") {
+            InjectionPlace::Method
+        } else if custom_headers.iter().any(|x| x.0.contains("%s")) {
+            InjectionPlace::HeaderName
+        } else if headers_discovery {
             InjectionPlace::Headers
+        } else if path_segments {
+            InjectionPlace::PathSegment
         } else if (method == "POST" || method == "PUT" || method == "PATCH" || method == "DELETE") && !invert
         || (method != "POST" && method != "PUT" && method != "PATCH" && method != "DELETE" && invert) {
             InjectionPlace::Body
@@ -469,14 +1005,25 @@ impl<'a> RequestDefaults {
             InjectionPlace::Path
         };
 
-        if headers_discovery {
+        if injection_place == InjectionPlace::Method || injection_place == InjectionPlace::HeaderName {
+            data_type = None;
+        } else if headers_discovery {
             data_type = Some(DataType::Headers);
             
-            if custom_headers.iter().any(|x| x.1.contains("%s")) {
+            if custom_headers.iter().any(|x| x.0.eq_ignore_ascii_case("cookie") && x.1.contains("%s")) {
+                injection_place = InjectionPlace::Cookie;
+            } else if custom_headers.iter().any(|x| x.1.contains("%s")) {
                 injection_place = InjectionPlace::HeaderValue;
             }
         }
 
+        // parts make no sense outside of the body
+        let data_type = if data_type == Some(DataType::Multipart) && injection_place != InjectionPlace::Body {
+            Some(DataType::Urlencoded)
+        } else {
+            data_type
+        };
+
         let data_type = if data_type != Some(DataType::ProbablyJson) {
             data_type
 
@@ -485,7 +1032,7 @@ impl<'a> RequestDefaults {
         // we don't need probablyurlencoded because urlencoded is fine for get requests
         } else if injection_place == InjectionPlace::Body && data_type == Some(DataType::ProbablyJson) {
             Some(DataType::Json)
-        } else if injection_place == InjectionPlace::Path {
+        } else if injection_place == InjectionPlace::Path || injection_place == InjectionPlace::PathSegment {
             Some(DataType::Urlencoded)
         } else {
             unreachable!()
@@ -494,9 +1041,24 @@ impl<'a> RequestDefaults {
         let (guessed_template, guessed_joiner, is_json, data_type) =
             RequestDefaults::guess_data_format(body, &injection_place, data_type);
 
+        // the boundary from the supplied Content-Type header is kept so the existing parts stay valid
+        let boundary = if data_type == Some(DataType::Multipart) {
+            custom_headers
+                .get_value_case_insensitive("content-type")
+                .and_then(|x| {
+                    x.split("boundary=")
+                        .nth(1)
+                        .map(|x| x.split(';').next().unwrap_or_default().trim().trim_matches('"').to_string())
+                })
+                .filter(|x| !x.is_empty())
+                .unwrap_or_else(|| format!("x8{}", random_line(16)))
+        } else {
+            String::new()
+        };
+
         let (template, joiner) = (
             template
-                .unwrap_or_else(|| guessed_template.to_string().into())
+                .unwrap_or_else(|| guessed_template.replace("%b", &boundary).into())
                 .into(),
             joiner.unwrap_or_else(|| guessed_joiner.to_string().into()).into().replace("\\r", "\r").replace("\\n", "\n"),
         );
@@ -511,6 +1073,7 @@ impl<'a> RequestDefaults {
                 &joiner,
                 &injection_place,
                 data_type,
+                &boundary,
             )
         } else {
             // injection within headers
@@ -524,24 +1087,60 @@ impl<'a> RequestDefaults {
             method: method.to_string(),
             scheme: url.scheme().to_string(),
             path,
-            host: url.host().ok_or("Host missing")?.to_string(),
+            host: url.host().ok_or_else(|| error::Error::Url("host missing".to_string()))?.to_string(),
             custom_headers,
-            port: url.port_or_known_default().ok_or("Wrong scheme")?,
+            port: url.port_or_known_default().ok_or_else(|| error::Error::Url("unknown scheme".to_string()))?,
             delay,
+            rate: 0,
+            rate_limit: 0,
+            max_bytes: 0,
+            value_transform: String::new(),
+            adaptive_rate: false,
+            delay_lanes: Arc::new(DelayLanes::new(1)),
             client,
             template,
             joiner,
             encode,
             is_json,
             body,
-            disable_custom_parameters,
+            // custom parameters like admin=true make no sense as path segments, methods or header names
+            disable_custom_parameters: disable_custom_parameters
+                || matches!(injection_place, InjectionPlace::PathSegment | InjectionPlace::Method | InjectionPlace::HeaderName),
             injection_place,
 
             amount_of_reflections: 0,
 
             parameters: Vec::new(),
 
-            check_binary
+            check_binary,
+
+            body_framing: BodyFraming::default(),
+
+            value_generators: ValueGenerators::default(),
+
+            pool: Arc::new(PoolStats::default()),
+
+            hashing: Arc::new(HashStats::default()),
+
+            stages: Arc::new(StageStats::default()),
+
+            traffic: Arc::new(TrafficStats::default()),
+
+            nested: None,
+
+            audit_log: String::new(),
+
+            low_memory: false,
+
+            websocket: false,
+
+            boundary,
+
+            retry: RetryPolicy::default(),
+
+            state: Arc::new(RunState::default()),
+
+            cache: Arc::new(ResponseCache::default()),
         })
     }
 
@@ -551,11 +1150,15 @@ impl<'a> RequestDefaults {
         injection_place: &InjectionPlace,
         data_type: Option<DataType>,
     ) -> (&'a str, &'a str, bool, Option<DataType>) {
-        if data_type.is_some() && data_type != Some(DataType::Headers) {
+        if *injection_place == InjectionPlace::PathSegment {
+            // only the key is sent, the value stays unused
+            ("%k", "/", false, Some(DataType::Urlencoded))
+        } else if data_type.is_some() && data_type != Some(DataType::Headers) {
             match data_type {
                 // %v isn't within quotes because not every json value needs to be in quotes
                 Some(DataType::Json) => ("\"%k\":%v", ",", true, Some(DataType::Json)),
                 Some(DataType::Urlencoded) => ("%k=%v", "&", false, Some(DataType::Urlencoded)),
+                Some(DataType::Multipart) => (MULTIPART_TEMPLATE, "", false, Some(DataType::Multipart)),
                 _ => unreachable!(),
             }
         } else {
@@ -568,7 +1171,11 @@ impl<'a> RequestDefaults {
                     }
                 }
                 InjectionPlace::HeaderValue => ("%k=%v", ";", false, None),
+                InjectionPlace::Cookie => ("%k=%v", "; ", false, None),
+                InjectionPlace::HeaderName => ("%k", HEADERS_JOINER, false, None),
+                InjectionPlace::Method => ("%k", "", false, None),
                 InjectionPlace::Path => ("%k=%v", "&", false, Some(DataType::Urlencoded)),
+                InjectionPlace::PathSegment => unreachable!(),
                 InjectionPlace::Headers => (HEADERS_TEMPLATE, HEADERS_JOINER, false, None),
             }
         }
@@ -581,6 +1188,7 @@ impl<'a> RequestDefaults {
         joiner: &str,
         injection_place: &InjectionPlace,
         data_type: DataType,
+        boundary: &str,
     ) -> (String, String) {
         match injection_place {
             InjectionPlace::Body => {
@@ -590,6 +1198,7 @@ impl<'a> RequestDefaults {
                     match data_type {
                         DataType::Urlencoded => (path.to_string(), "%s".to_string()),
                         DataType::Json => (path.to_string(), "{%s}".to_string()),
+                        DataType::Multipart => (path.to_string(), format!("%s--{}--\r\n", boundary)),
                         _ => unreachable!(),
                     }
                 } else {
@@ -605,13 +1214,24 @@ impl<'a> RequestDefaults {
                                 (path.to_string(), format!("{}%s}}", body))
                             }
                         }
+                        // new parts go right before the closing delimiter
+                        DataType::Multipart => match body.rfind(&format!("--{}--", boundary)) {
+                            Some(pos) => (path.to_string(), format!("{}%s{}", &body[..pos], &body[pos..])),
+                            None => (path.to_string(), format!("{}%s--{}--\r\n", body, boundary)),
+                        },
                         _ => unreachable!(),
                     }
                 }
             }
             InjectionPlace::Path => {
+                // the fragment isn't sent anyway and the injection point must not end up within it
+                let path = path.split('#').next().unwrap_or_default();
+
                 if path.contains("%s") {
                     (path.to_string(), body.to_string())
+                } else if path.ends_with('?') || path.ends_with(joiner) {
+                    // /path? or /path?a=b& -- no need for another joiner
+                    (format!("{}%s", path), body.to_string())
                 } else if path.contains('?') {
                     (format!("{}{}%s", path, joiner), body.to_string())
                 } else if joiner == "&" {
@@ -621,10 +1241,106 @@ impl<'a> RequestDefaults {
                     (format!("{}%s", path), body.to_string())
                 }
             }
+            InjectionPlace::PathSegment => {
+                if path.contains("%s") {
+                    (path.to_string(), body.to_string())
+                } else {
+                    // the query needs to stay after the new segment
+                    let (path, query) = match path.split_once('?') {
+                        Some((path, query)) => (path, format!("?{}", query)),
+                        None => (path, String::new()),
+                    };
+
+                    (format!("{}/%s{}", path.trim_end_matches('/'), query), body.to_string())
+                }
+            }
             _ => (path.to_string(), body.to_string()),
         }
     }
 
+    /// replaces the query normalized by the url parser with the query from the supplied url as is
+    /// so existing pairs are sent byte for byte and in the same order
+    pub fn keep_raw_query(&mut self, url: &str) -> error::Result<()> {
+        let normalized = match Url::parse(url)?.query() {
+            Some(val) => format!("?{}", val),
+            None => return Ok(()),
+        };
+
+        // the query starts after the first '?' and ends before the fragment
+        let raw = match url.split('#').next().unwrap_or_default().split_once('?') {
+            Some((_, raw)) => format!("?{}", raw),
+            None => return Ok(()),
+        };
+
+        self.path = self.path.replacen(&normalized, &raw, 1);
+
+        Ok(())
+    }
+
+    /// replaces the path and the query normalized by the url parser with the ones from the supplied url as is (--raw-path)
+    /// dot segments, spaces and unicode are sent without changes
+    pub fn keep_raw_path(&mut self, url: &str) -> error::Result<()> {
+        let parsed = Url::parse(url)?;
+        let normalized = &parsed[url::Position::BeforePath..url::Position::AfterQuery];
+
+        // the path starts after the host and ends before the fragment
+        let raw = match url.split('#').next().unwrap_or_default().split_once("://") {
+            Some((_, rest)) => match rest.find(['/', '?']) {
+                Some(start) => &rest[start..],
+                None => return Ok(()),
+            },
+            None => return Ok(()),
+        };
+
+        self.path = self.path.replacen(normalized, raw, 1);
+
+        Ok(())
+    }
+
+    /// returns names of the parameters that were within the supplied query
+    pub fn query_names(&self) -> Vec<String> {
+        let query = match self.path.split_once('?') {
+            Some((_, query)) => query,
+            None => return Vec::new(),
+        };
+
+        query
+            .split(&self.joiner)
+            .filter(|x| !x.is_empty() && !x.contains("%s"))
+            .map(|x| x.split('=').next().unwrap_or_default().to_string())
+            .collect()
+    }
+
+    /// returns paths with numeric ids swapped for their neighbours and without the injection point
+    /// /api/users/123/%s?a=b -> [/api/users/122?a=b, /api/users/124?a=b]
+    pub fn path_id_candidates(&self) -> Vec<String> {
+        let (path, query) = match self.path.split_once('?') {
+            Some((path, query)) => (path, format!("?{}", query)),
+            None => (self.path.as_str(), String::new()),
+        };
+
+        let segments: Vec<&str> = path.split('/').filter(|x| *x != "%s").collect();
+        let mut candidates = Vec::new();
+
+        for (i, segment) in segments.iter().enumerate() {
+            if let Ok(id) = segment.parse::<u64>() {
+                for neighbour in [id.checked_sub(1), id.checked_add(1)].iter().flatten() {
+                    let neighbour = neighbour.to_string();
+
+                    let mut swapped = segments.clone();
+                    swapped[i] = &neighbour;
+
+                    let candidate = format!("{}{}", swapped.join("/"), query);
+                    if !candidates.contains(&candidate) {
+                        candidates.push(candidate);
+                    }
+                }
+            }
+        }
+
+        candidates
+    }
+
     /// recreates url
     pub fn url(&self) -> String {
         format!("{}://{}:{}{}", self.scheme, self.host, self.port, self.path)
@@ -1,20 +1,24 @@
-use std::{collections::HashMap, error::Error, iter::FromIterator, io::{self, Write}};
+use std::{collections::{hash_map::DefaultHasher, HashMap, HashSet}, error::Error, hash::{Hash, Hasher}, iter::{self, FromIterator}, io::{self, Write}, net::SocketAddr, sync::Arc};
 
+use aho_corasick::AhoCorasick;
 use colored::Colorize;
 use indicatif::ProgressBar;
 use itertools::Itertools;
 use lazy_static::lazy_static;
+use parking_lot::Mutex;
 use regex::Regex;
+use serde::{Deserialize, Serialize};
 
-use crate::{config::structs::Config, diff::diff, runner::utils::ReasonKind, utils::{color_id, is_id_important}};
+use crate::{config::structs::Config, error, diff::{diff, diff_external, diff_hashed}, runner::{categories::categorize, utils::ReasonKind}, utils::{color_id, is_id_important}, LOW_MEMORY_EXTERNAL_SIZE, TEST_BODY_LIMIT};
 
 use super::{
+    labels,
     request::Request,
     utils::{save_request, Headers},
 };
 
-#[derive(Debug, Clone, Default)]
-pub struct Response<'a> {
+#[derive(Debug, Clone)]
+pub struct Response {
     /// time from the sent request to response headers
     pub time: u128,
 
@@ -31,15 +35,36 @@ pub struct Response<'a> {
     pub reflected_parameters: HashMap<String, usize>,
 
     /// the sent request struct itself
-    /// None only in initial_request due to lifetime issues
-    pub request: Option<Request<'a>>,
+    pub request: Request,
 
     /// None only when the request failed
     pub http_version: Option<http::Version>,
+
+    /// the address that served the response. None in case it's unknown (failed request, proxy)
+    pub remote_addr: Option<SocketAddr>,
+
+    /// urls the request was redirected to in the order they were followed
+    pub redirects: Vec<String>,
 }
 
-//Owo
-unsafe impl Send for Response<'_> {}
+/// the response every other response is compared with
+/// doesn't depend on the request, so it can be shared between tasks
+#[derive(Debug, Clone, Default)]
+pub struct Baseline {
+    pub time: u128,
+    pub code: u16,
+    pub headers: Vec<(String, String)>,
+
+    /// headers + body
+    pub text: String,
+
+    pub http_version: Option<http::Version>,
+    pub remote_addr: Option<SocketAddr>,
+
+    /// (hash of the learned diffs, hash of the body without the lines within them)
+    /// responses with the same hash are compared line by line instead of diffing
+    pub hashed_body: Arc<Mutex<Option<(u64, u64)>>>,
+}
 
 /// helps manage response codes
 #[derive(PartialEq, Eq)]
@@ -51,28 +76,106 @@ pub enum Status {
     Other,
 }
 
-impl<'a> Response<'a> {
+impl Response {
     /// count how many times we can see the string in the response
     pub fn count(&self, string: &str) -> usize {
-        let re = Regex::new(&format!("(?i){}", string)).unwrap();
-        re.find_iter(&self.text).count()
+        count(&self.text, string)
+    }
+
+    /// returns headers with lowercased names that match the predicate
+    /// sorted in order to be comparable between responses
+    pub fn filter_headers(&self, predicate: fn(&str) -> bool) -> Vec<(String, String)> {
+        filter_headers(&self.headers, predicate)
+    }
+
+    /// returns changes of headers that match the predicate compared to the baseline:
+    /// "+name: value" for new headers, "-name: value" for removed ones and "name: old -> new" for changed ones
+    pub fn headers_delta(&self, baseline: &Baseline, predicate: fn(&str) -> bool) -> Vec<String> {
+        let old_headers = baseline.filter_headers(predicate);
+        let new_headers = self.filter_headers(predicate);
+
+        let mut delta = Vec::new();
+
+        for (k, v) in new_headers.iter() {
+            match old_headers.get_value(k) {
+                Some(old_value) if &old_value != v => delta.push(format!("{}: {} -> {}", k, old_value, v)),
+                None => delta.push(format!("+{}: {}", k, v)),
+                _ => (),
+            }
+        }
+
+        for (k, v) in old_headers.iter() {
+            if !new_headers.contains_key(k) {
+                delta.push(format!("-{}: {}", k, v));
+            }
+        }
+
+        delta
+    }
+
+    /// returns names of headers that contain the string
+    pub fn reflected_headers(&self, string: &str) -> Vec<String> {
+        let string = string.to_lowercase();
+
+        self.headers
+            .iter()
+            .filter(|(_, v)| v.to_lowercase().contains(&string))
+            .map(|(k, _)| k.to_owned())
+            .collect()
     }
 
     /// calls check_diffs & returns code and found diffs
     pub fn compare(
         &self,
-        initial_response: &'a Response<'a>,
+        baseline: &Baseline,
         old_diffs: &[String],
-    ) -> Result<(bool, Vec<String>), Box<dyn Error>> {
+    ) -> error::Result<(bool, Vec<String>)> {
         let mut is_code_diff: bool = false;
         let mut diffs: Vec<String> = Vec::new();
 
-        if initial_response.code != self.code {
+        if baseline.code != self.code {
             is_code_diff = true
         }
 
+        // most responses are identical to the baseline except for the learned lines like the Date header, so the diff can be skipped for them
+        let stats = &self.request.defaults.hashing;
+        let skipped = learned_lines(old_diffs);
+
+        if body_hash(&self.headers, &self.text, &skipped) == baseline.body_hash(old_diffs, &skipped) {
+            if self.code == baseline.code
+                && self.http_version == baseline.http_version
+                && equal_except(
+                    iter::once(status_line(self.http_version, self.code).as_str()).chain(self.text.lines()),
+                    iter::once(status_line(baseline.http_version, baseline.code).as_str()).chain(baseline.text.lines()),
+                    &skipped,
+                )
+            {
+                stats.hit();
+                return Ok((false, diffs));
+            }
+
+            stats.collision();
+        }
+
+        stats.miss();
+
+        // huge pages aren't copied in the low memory mode
+        let found_diffs = if self.request.defaults.low_memory {
+            let (status, baseline_status) = (status_line(self.http_version, self.code), status_line(baseline.http_version, baseline.code));
+            let lines = iter::once(status.as_str()).chain(self.text.lines());
+            let baseline_lines = iter::once(baseline_status.as_str()).chain(baseline.text.lines());
+
+            if self.text.len().max(baseline.text.len()) > LOW_MEMORY_EXTERNAL_SIZE {
+                diff_external(lines, baseline_lines)?
+            } else {
+                diff_hashed(lines, baseline_lines)?
+            }
+        } else {
+            diff(&self.print(), &baseline.print())?
+        };
+
         // just push every found diff to the vector of diffs
-        for diff in diff(&self.print(), &initial_response.print())? {
+        for diff in found_diffs {
             if !diffs.contains(&diff) && !old_diffs.contains(&diff) {
                 diffs.push(diff);
             // sometimes returns a few same diffs. They should be considered as well
@@ -123,47 +226,28 @@ impl<'a> Response<'a> {
     }
 
     /// finds parameters with the different amount of reflections and adds them to self.reflected_parameters
-    pub fn fill_reflected_parameters(&mut self, initial_response: &Response) {
+    pub fn fill_reflected_parameters(&mut self, baseline: &Baseline) {
         // remove non random parameters from prepared parameters because they would cause false positives in this check
-        let prepated_parameters: Vec<&(String, String)> = if !self
-            .request
-            .as_ref()
-            .unwrap()
-            .non_random_parameters
-            .is_empty()
-        {
+        let prepated_parameters: Vec<&(String, String)> = if !self.request.non_random_parameters.is_empty() {
             Vec::from_iter(
                 self.request
-                    .as_ref()
-                    .unwrap()
                     .prepared_parameters
                     .iter()
-                    .filter(|x| {
-                        !self
-                            .request
-                            .as_ref()
-                            .unwrap()
-                            .non_random_parameters
-                            .contains_key(&x.0)
-                    }),
+                    .filter(|x| !self.request.non_random_parameters.contains_key(&x.0)),
             )
         } else {
-            Vec::from_iter(self.request.as_ref().unwrap().prepared_parameters.iter())
+            Vec::from_iter(self.request.prepared_parameters.iter())
         };
 
-        for (k, v) in prepated_parameters.iter() {
-            // maybe it's better to remove count from the initial response
-            // sure it's increases accuracy a bit, but the performance impact is high
-            let new_count = self.count(v) - initial_response.count(v);
+        // every value is counted within a single pass over the page
+        let values: Vec<&str> = prepated_parameters.iter().map(|(_, v)| v.as_str()).collect();
+        let counts = count_all(&self.text, &values);
+        let baseline_counts = count_all(&baseline.text, &values);
 
-            if self
-                .request
-                .as_ref()
-                .unwrap()
-                .defaults
-                .amount_of_reflections
-                != new_count
-            {
+        for (i, (k, _)) in prepated_parameters.iter().enumerate() {
+            let new_count = counts[i].saturating_sub(baseline_counts[i]);
+
+            if self.request.defaults.amount_of_reflections != new_count {
                 self.reflected_parameters.insert(k.to_string(), new_count);
             }
         }
@@ -179,7 +263,7 @@ impl<'a> Response<'a> {
         // because there can be a parameter that changes the page
         // in this case, the page may return the different amount of reflections to every parameter
         // and this another random parameter will look like a reflected one and may cause false positives
-        } else if self.reflected_parameters.len() == 1 && self.request.as_ref().unwrap().prepared_parameters.len() > 2 {
+        } else if self.reflected_parameters.len() == 1 && self.request.prepared_parameters.len() > 2 {
             return (
                 Some(self.reflected_parameters.keys().next().unwrap()),
                 false,
@@ -187,7 +271,7 @@ impl<'a> Response<'a> {
         };
 
         // only one reflected parameter besides additional one - return it
-        if self.request.as_ref().unwrap().prepared_parameters.len()
+        if self.request.prepared_parameters.len()
             == self.reflected_parameters.len()
             && self.reflected_parameters.len() == 1
         {
@@ -221,6 +305,46 @@ impl<'a> Response<'a> {
         (None, true)
     }
 
+    /// a copy without the request in order to store the response longer than the request lives
+    pub fn baseline(&self) -> Baseline {
+        Baseline {
+            time: self.time,
+            code: self.code,
+            headers: self.headers.clone(),
+            text: self.text.clone(),
+            http_version: self.http_version,
+            remote_addr: self.remote_addr,
+            hashed_body: Default::default(),
+        }
+    }
+
+    /// the stored response to the same request
+    pub fn from_baseline(baseline: Baseline, mut request: Request) -> Self {
+        request.prepare();
+
+        Response {
+            time: baseline.time,
+            code: baseline.code,
+            headers: baseline.headers,
+            text: baseline.text,
+            reflected_parameters: HashMap::new(),
+            request,
+            http_version: baseline.http_version,
+            remote_addr: baseline.remote_addr,
+            redirects: Vec::new(),
+        }
+    }
+
+    /// the text without headers (they're added by add_headers)
+    pub fn body(&self) -> &str {
+        body(&self.headers, &self.text)
+    }
+
+    /// a human readable description of how the page changed compared to the baseline
+    pub fn label(&self, baseline: &Baseline) -> Option<String> {
+        labels::label(baseline.body(), self.body())
+    }
+
     /// adds headers to response text
     pub fn add_headers(&mut self) {
         let mut text = String::new();
@@ -236,7 +360,7 @@ impl<'a> Response<'a> {
         &self,
         id: usize,
         config: &Config,
-        initial_response: &Response,
+        baseline: &Baseline,
         reason_kind: ReasonKind,
         parameter: &str,
         diff: Option<&str>,
@@ -249,27 +373,45 @@ impl<'a> Response<'a> {
             String::new()
         };
 
+        // only page changes have labels
+        let label = if reason_kind == ReasonKind::Text {
+            self.label(baseline)
+        } else {
+            None
+        };
+
+        let category = categorize(
+            &config.categories,
+            &reason_kind,
+            parameter.split('=').next().unwrap_or(parameter),
+            self.code,
+            label.as_deref(),
+        );
+
         let mut message = match reason_kind {
             ReasonKind::Code => format!(
                 "{}{}: code {} -> {}",
                 &id_if_important,
                 &parameter,
-                initial_response.code(),
+                baseline.code(),
                 self.code(),
             ),
             ReasonKind::Text => format!(
-                "{}{}: page {} -> {} ({})",
+                "{}{}: page {} -> {} ({}){}",
                 &id_if_important,
                 &parameter,
-                initial_response.text.len(),
+                baseline.text.len(),
                 self.text.len().to_string().bright_yellow(),
-                diff.unwrap()
+                diff.unwrap(),
+                label.as_ref().map(|x| format!(" {}", x.dimmed())).unwrap_or_default()
             ),
             ReasonKind::Reflected => format!(
-                "{}{}: {}",
+                "{}{}: {}{}",
                 &id_if_important,
                 "reflects".bright_blue(),
-                parameter
+                parameter,
+                // where the parameter was reflected in case it's known
+                diff.map(|x| format!(" ({})", x)).unwrap_or_default()
             ),
             ReasonKind::NotReflected => format!(
                 "{}{}: {}",
@@ -277,50 +419,65 @@ impl<'a> Response<'a> {
                 "changes reflections".bright_cyan(),
                 parameter
             ),
+            ReasonKind::Cors => format!(
+                "{}{}: {} ({})",
+                &id_if_important,
+                "changes cors".bright_magenta(),
+                parameter,
+                diff.unwrap()
+            ),
+            ReasonKind::Security => format!(
+                "{}{}: {} ({})",
+                &id_if_important,
+                "changes security headers".bright_red(),
+                parameter,
+                diff.unwrap()
+            ),
         };
 
+        if let Some(category) = category {
+            message += &category.tag();
+        }
+
+        if config.verbose > 1 {
+            message += &format!(
+                " [{} ms, {:+}]",
+                self.time,
+                self.time as i128 - baseline.time as i128
+            );
+        }
+
         if config.verbose > 0 {
             if !config.save_responses.is_empty() {
                 message += &format!(" [saved to {}]", save_request(config, self, parameter)?);
             }
 
+            message += &format!("\n    {} {}", "reproduce:".dimmed(), self.request.reproduction(parameter));
+
             if config.disable_progress_bar {
                 writeln!(io::stdout(), "{}", message).ok();
             } else {
                 progress_bar.println(message);
             }
-        } else if !config.save_responses.is_empty() {
-            save_request(config, self, parameter)?;
+        } else {
+            self.save(config, parameter)?;
         }
 
         Ok(())
     }
 
-    fn kind(&self) -> Status {
-        if self.code <= 199 {
-            Status::Other
-        } else if self.code <= 299 {
-            Status::Ok
-        } else if self.code <= 399 {
-            Status::Redirect
-        } else if self.code <= 499 {
-            Status::UserFault
-        } else if self.code <= 599 {
-            Status::ServerFault
-        } else {
-            Status::Other
+    /// save the response with found parameter without writing about it
+    pub fn save(&self, config: &Config, parameter: &str) -> Result<(), Box<dyn Error>> {
+        if !config.save_responses.is_empty() {
+            save_request(config, self, parameter)?;
         }
+
+        Ok(())
     }
 
     /// returns self.code but with colors
     pub fn code(&self) -> String {
-        match self.kind() {
-            Status::Ok => self.code.to_string().bright_green().to_string(),
-            Status::Redirect => self.code.to_string().bright_blue().to_string(),
-            Status::UserFault => self.code.to_string().bright_yellow().to_string(),
-            Status::ServerFault => self.code.to_string().bright_red().to_string(),
-            Status::Other => self.code.to_string().magenta().to_string(),
-        }
+        colored_code(self.code)
     }
 
     /// get possible parameters from the page itself
@@ -359,23 +516,304 @@ impl<'a> Response<'a> {
 
     /// print the whole response
     pub fn print(&self) -> String {
-        let http_version = match self.http_version {
-            Some(val) => match val {
-                http::Version::HTTP_09 => "HTTP/0.9",
-                http::Version::HTTP_10 => "HTTP/1.0",
-                http::Version::HTTP_11 => "HTTP/1.1",
-                http::Version::HTTP_2 => "HTTP/2",
-                http::Version::HTTP_3 => "HTTP/3",
-                _ => "HTTP/x",
+        print(self.http_version, self.code, &self.text)
+    }
+
+    /// print the request and response
+    pub fn print_all(&self) -> String {
+        self.request.print_sent() + "\n\n" + &self.print()
+    }
+
+    /// the prepared request and the parsed response as a json document (--test -O json)
+    pub fn exchange(&self) -> Exchange<'_> {
+        let body = self.body();
+
+        let mut limit = body.len().min(TEST_BODY_LIMIT);
+        while !body.is_char_boundary(limit) {
+            limit -= 1;
+        }
+
+        Exchange {
+            request: ExchangeRequest {
+                method: &self.request.method,
+                url: self.request.url(),
+                headers: &self.request.headers,
+                body: &self.request.body,
+            },
+            response: ExchangeResponse {
+                code: self.code,
+                headers: &self.headers,
+                body: &body[..limit],
+                truncated: limit < body.len(),
             },
-            None => "HTTP/x",
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Exchange<'a> {
+    pub request: ExchangeRequest<'a>,
+    pub response: ExchangeResponse<'a>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ExchangeRequest<'a> {
+    pub method: &'a str,
+    pub url: String,
+    pub headers: &'a [(String, String)],
+    pub body: &'a str,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ExchangeResponse<'a> {
+    pub code: u16,
+    pub headers: &'a [(String, String)],
+
+    /// without headers, truncated to TEST_BODY_LIMIT bytes
+    pub body: &'a str,
+    pub truncated: bool,
+}
+
+impl Baseline {
+    /// the hash of the body without the lines within the learned diffs. Cached until the diffs change
+    fn body_hash(&self, diffs: &[String], skipped: &HashSet<usize>) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        diffs.hash(&mut hasher);
+        let diffs_hash = hasher.finish();
+
+        let mut hashed_body = self.hashed_body.lock();
+
+        match *hashed_body {
+            Some((hash, body_hash)) if hash == diffs_hash => body_hash,
+            _ => {
+                let hash = body_hash(&self.headers, &self.text, skipped);
+                *hashed_body = Some((diffs_hash, hash));
+                hash
+            }
+        }
+    }
+
+    /// count how many times we can see the string in the response
+    pub fn count(&self, string: &str) -> usize {
+        count(&self.text, string)
+    }
+
+    pub fn filter_headers(&self, predicate: fn(&str) -> bool) -> Vec<(String, String)> {
+        filter_headers(&self.headers, predicate)
+    }
+
+    /// the text without headers
+    pub fn body(&self) -> &str {
+        body(&self.headers, &self.text)
+    }
+
+    /// returns self.code but with colors
+    pub fn code(&self) -> String {
+        colored_code(self.code)
+    }
+
+    pub fn print(&self) -> String {
+        print(self.http_version, self.code, &self.text)
+    }
+
+    /// the printed baseline with another body, so diffs of bodies are at the same positions as the learned ones
+    pub fn print_with_body(&self, body: &str) -> String {
+        let headers = &self.text[..self.text.len() - self.body().len()];
+
+        print(self.http_version, self.code, &format!("{}{}", headers, body))
+    }
+
+    /// appends the baseline of the method and url to the file as a json line
+    pub fn save(&self, filename: &str, method: &str, url: &str) -> Result<(), Box<dyn Error>> {
+        let line = serde_json::to_string(&SavedBaseline {
+            method: method.to_string(),
+            url: url.to_string(),
+            code: self.code,
+            headers: self.headers.clone(),
+            text: self.text.clone(),
+            http_version: self.http_version.map(|x| format!("{:?}", x)),
+        })?;
+
+        let mut file = std::fs::OpenOptions::new().create(true).append(true).open(filename)?;
+        writeln!(file, "{}", line)?;
+
+        Ok(())
+    }
+
+    /// loads the last saved baseline of the method and url
+    pub fn load(filename: &str, method: &str, url: &str) -> Result<Self, Box<dyn Error>> {
+        let mut baseline = None;
+
+        for line in std::fs::read_to_string(filename)?.lines().filter(|x| !x.trim().is_empty()) {
+            let saved: SavedBaseline = match serde_json::from_str(line) {
+                Ok(val) => val,
+                Err(err) => Err(format!("Unable to parse {} as a saved baseline: {}", filename, err))?,
+            };
+
+            if saved.method == method && saved.url == url {
+                baseline = Some(saved);
+            }
+        }
+
+        let saved = match baseline {
+            Some(val) => val,
+            None => Err(format!("{} doesn't contain a baseline for {} {}", filename, method, url))?,
+        };
+
+        let http_version = match saved.http_version.as_deref() {
+            Some("HTTP/0.9") => Some(http::Version::HTTP_09),
+            Some("HTTP/1.0") => Some(http::Version::HTTP_10),
+            Some("HTTP/1.1") => Some(http::Version::HTTP_11),
+            Some("HTTP/2.0") => Some(http::Version::HTTP_2),
+            Some("HTTP/3.0") => Some(http::Version::HTTP_3),
+            _ => None,
         };
 
-        format!("{} {} \n{}", http_version, self.code, self.text)
+        Ok(Self {
+            code: saved.code,
+            headers: saved.headers,
+            text: saved.text,
+            http_version,
+            ..Default::default()
+        })
     }
+}
 
-    /// print the request and response
-    pub fn print_all(&self) -> String {
-        self.request.as_ref().unwrap().print_sent() + "\n\n" + &self.print()
+/// the baseline within --save-baseline files
+#[derive(Serialize, Deserialize)]
+struct SavedBaseline {
+    method: String,
+    url: String,
+    code: u16,
+    headers: Vec<(String, String)>,
+    text: String,
+    http_version: Option<String>,
+}
+
+fn count(text: &str, string: &str) -> usize {
+    count_all(text, &[string])[0]
+}
+
+/// case insensitive amounts of occurrences of every pattern within the text
+/// the text is lowercased once and searched with a single automaton, so hundreds of values within a huge page are cheap to count
+fn count_all(text: &str, patterns: &[&str]) -> Vec<usize> {
+    let mut counts = vec![0; patterns.len()];
+
+    if patterns.is_empty() {
+        return counts;
+    }
+
+    let automaton = AhoCorasick::new(patterns.iter().map(|x| x.to_lowercase()));
+
+    for found in automaton.find_overlapping_iter(&text.to_lowercase()) {
+        counts[found.pattern()] += 1;
+    }
+
+    counts
+}
+
+fn filter_headers(headers: &[(String, String)], predicate: fn(&str) -> bool) -> Vec<(String, String)> {
+    let mut headers: Vec<(String, String)> = headers
+        .iter()
+        .filter(|(k, _)| predicate(k))
+        .map(|(k, v)| (k.to_lowercase(), v.to_owned()))
+        .collect();
+
+    headers.sort();
+    headers
+}
+
+fn body<'b>(headers: &[(String, String)], text: &'b str) -> &'b str {
+    let headers_len: usize = headers.iter().map(|(k, v)| k.len() + v.len() + 3).sum();
+
+    text.get(headers_len + 1..).unwrap_or("")
+}
+
+fn kind(code: u16) -> Status {
+    if code <= 199 {
+        Status::Other
+    } else if code <= 299 {
+        Status::Ok
+    } else if code <= 399 {
+        Status::Redirect
+    } else if code <= 499 {
+        Status::UserFault
+    } else if code <= 599 {
+        Status::ServerFault
+    } else {
+        Status::Other
+    }
+}
+
+fn colored_code(code: u16) -> String {
+    match kind(code) {
+        Status::Ok => code.to_string().bright_green().to_string(),
+        Status::Redirect => code.to_string().bright_blue().to_string(),
+        Status::UserFault => code.to_string().bright_yellow().to_string(),
+        Status::ServerFault => code.to_string().bright_red().to_string(),
+        Status::Other => code.to_string().magenta().to_string(),
+    }
+}
+
+fn print(http_version: Option<http::Version>, code: u16, text: &str) -> String {
+    format!("{}\n{}", status_line(http_version, code), text)
+}
+
+/// lines of the printed baseline within the learned diffs: "-a,b +c,d" -> c..c+d
+fn learned_lines(diffs: &[String]) -> HashSet<usize> {
+    diffs
+        .iter()
+        .filter_map(|x| x.split_whitespace().nth(1))
+        .filter_map(|x| x.trim_start_matches('+').split_once(','))
+        .filter_map(|(start, len)| Some(start.parse::<usize>().ok()?..start.parse::<usize>().ok()? + len.parse::<usize>().ok()?))
+        .flatten()
+        .collect()
+}
+
+/// the hash of the body without the skipped lines of the printed response
+fn body_hash(headers: &[(String, String)], text: &str, skipped: &HashSet<usize>) -> u64 {
+    // the status line, headers and the empty line go before the body
+    let offset = headers.len() + 2;
+
+    let mut hasher = DefaultHasher::new();
+    for (i, line) in body(headers, text).lines().enumerate() {
+        if !skipped.contains(&(offset + i)) {
+            line.hash(&mut hasher);
+        }
     }
+    hasher.finish()
+}
+
+/// whether the texts have the same amount of lines and differ only within the skipped ones
+fn equal_except<'a>(
+    mut lines1: impl Iterator<Item = &'a str>,
+    mut lines2: impl Iterator<Item = &'a str>,
+    skipped: &HashSet<usize>,
+) -> bool {
+    for i in 0.. {
+        match (lines1.next(), lines2.next()) {
+            (Some(line1), Some(line2)) if line1 == line2 || skipped.contains(&i) => continue,
+            (None, None) => return true,
+            _ => return false,
+        }
+    }
+
+    false
+}
+
+/// HTTP/1.1 200
+fn status_line(http_version: Option<http::Version>, code: u16) -> String {
+    let http_version = match http_version {
+        Some(val) => match val {
+            http::Version::HTTP_09 => "HTTP/0.9",
+            http::Version::HTTP_10 => "HTTP/1.0",
+            http::Version::HTTP_11 => "HTTP/1.1",
+            http::Version::HTTP_2 => "HTTP/2",
+            http::Version::HTTP_3 => "HTTP/3",
+            _ => "HTTP/x",
+        },
+        None => "HTTP/x",
+    };
+
+    format!("{} {} ", http_version, code)
 }
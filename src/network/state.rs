@@ -0,0 +1,161 @@
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    fs,
+    io::{self, BufWriter, Write},
+    net::SocketAddr,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+
+use colored::Colorize;
+use parking_lot::Mutex;
+
+use crate::{runner::memory::Learned, utils};
+
+use super::{
+    metrics::format_bytes,
+    utils::{AuditEntry, TokenBucket},
+};
+
+/// state shared by every request of the run regardless of the url: pacing, rate limits, learned settings and the traffic
+/// created along with the options and carried within request defaults, so separate library calls don't affect each other
+#[derive(Debug, Default)]
+pub struct RunState {
+    /// filename -> the buffered writer of --audit-log. Every entry is written whole under the lock, so parallel requests don't interleave
+    audit_logs: Mutex<HashMap<String, BufWriter<fs::File>>>,
+
+    /// host -> when the last request to the host was sent in microseconds since the unix epoch (--audit-log)
+    last_sent: Mutex<HashMap<String, u128>>,
+
+    /// host -> the earliest time the next request can be sent at (--rate)
+    next_slot: Mutex<HashMap<String, Instant>>,
+
+    /// shared between every request of the run regardless of the host (--rate-limit)
+    rate_limit: Mutex<Option<TokenBucket>>,
+
+    /// bytes sent and received by every request of the run. Limited with --max-bytes
+    traffic: AtomicUsize,
+
+    /// stops the run the same way as Ctrl+C does
+    limit_reached: AtomicBool,
+
+    /// host:port -> whether the host is checked over HTTP/1.1 instead of HTTP/2
+    pub http1_fallback: Mutex<HashMap<String, bool>>,
+
+    /// host:port -> the address the client is pinned to. None in case there's no need to pin
+    pub pinned: Mutex<HashMap<String, Option<SocketAddr>>>,
+
+    /// host:port and the injection place -> learned settings
+    pub learned: Mutex<HashMap<String, Learned>>,
+}
+
+impl RunState {
+    /// whether the run should stop sending new requests: Ctrl+C or the traffic limit
+    pub fn is_stopped(&self) -> bool {
+        utils::is_interrupted() || self.limit_reached.load(Ordering::Relaxed)
+    }
+
+    /// waits for a token of the run's bucket so that no more than `rate` requests per second are sent in total
+    pub async fn limit_rate(&self, rate: u32) {
+        loop {
+            let wait = {
+                let mut bucket = self.rate_limit.lock();
+                let now = Instant::now();
+
+                match bucket.get_or_insert_with(|| TokenBucket::new(rate, now)).take(now) {
+                    Some(wait) => wait,
+                    None => return,
+                }
+            };
+
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// adapts the run's rate to 429 and 503 responses (--adaptive-rate)
+    /// returns the new rate in case it was changed
+    pub fn adapt_rate(&self, code: u16) -> Option<f64> {
+        self.rate_limit.lock().as_mut()?.adapt(code, Instant::now())
+    }
+
+    /// waits for the next free slot of the host so that no more than `rate` requests per second are sent to it
+    /// slots are shared between every url of the host
+    pub async fn throttle(&self, host: &str, rate: u32) {
+        let interval = Duration::from_secs(1) / rate;
+
+        let slot = {
+            let mut next_slot = self.next_slot.lock();
+            let now = Instant::now();
+
+            let slot = match next_slot.get(host) {
+                Some(next) if *next > now => *next,
+                _ => now,
+            };
+
+            next_slot.insert(host.to_string(), slot + interval);
+            slot
+        };
+
+        tokio::time::sleep_until(slot.into()).await;
+    }
+
+    /// remembers when the request to the host was sent and returns the time passed since the previous one
+    pub fn record_sent(&self, host: &str, sent_at: u128) -> Option<u128> {
+        self.last_sent
+            .lock()
+            .insert(host.to_string(), sent_at)
+            .map(|previous| sent_at.saturating_sub(previous))
+    }
+
+    /// appends the entry to --audit-log. The file is opened once and entries are flushed with flush_audit()
+    pub fn write_audit(&self, filename: &str, entry: &AuditEntry) {
+        let line = serde_json::to_string(entry).unwrap_or_default();
+        let mut audit_logs = self.audit_logs.lock();
+
+        let writer = match audit_logs.entry(filename.to_string()) {
+            Entry::Occupied(writer) => writer.into_mut(),
+            Entry::Vacant(entry) => match fs::OpenOptions::new().create(true).append(true).open(filename) {
+                Ok(file) => entry.insert(BufWriter::new(file)),
+                Err(err) => {
+                    log::error!("Unable to open {}: {}", filename, err);
+                    return;
+                }
+            },
+        };
+
+        if let Err(err) = writeln!(writer, "{}", line) {
+            log::error!("Unable to write to {}: {}", filename, err);
+        }
+    }
+
+    /// writes buffered --audit-log entries to their files
+    pub fn flush_audit(&self) {
+        for (filename, writer) in self.audit_logs.lock().iter_mut() {
+            if let Err(err) = writer.flush() {
+                log::error!("Unable to write to {}: {}", filename, err);
+            }
+        }
+    }
+
+    /// adds bytes to the traffic of the run and stops it once the traffic limit is reached (--max-bytes)
+    pub fn record_traffic(&self, bytes: usize, max: usize) {
+        let total = self.traffic.fetch_add(bytes, Ordering::Relaxed) + bytes;
+
+        if max == 0 || total < max || self.limit_reached.swap(true, Ordering::Relaxed) {
+            return;
+        }
+
+        writeln!(
+            io::stderr(),
+            "\n{} {} were sent and received, waiting for sent requests to finish",
+            "Traffic limit:".yellow(),
+            format_bytes(total)
+        )
+        .ok();
+    }
+
+    /// bytes sent and received by the whole run
+    pub fn traffic(&self) -> usize {
+        self.traffic.load(Ordering::Relaxed)
+    }
+}
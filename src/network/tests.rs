@@ -1,10 +1,27 @@
 #[cfg(test)]
 mod tests {
+    use std::{fs, path::Path, time::Instant};
+
+    use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
     use tokio::time::Duration;
 
+    use crate::{diff, Error};
+    use crate::config::{args::get_config_from, utils::parse_size};
     use crate::network::{
+        cache::ResponseCache,
+        doh,
+        labels,
+        metrics::{format_bytes, PoolStats, Stage, TrafficMetrics, TrafficStats},
+        nested::{self, NestedEncoding, NestedValue},
         request::{Request, RequestDefaults},
-        utils::{Headers, InjectionPlace, is_binary_content},
+        response::{Baseline, Response},
+        state::RunState,
+        utils::{
+            AuditEntry, BodyPart, DataType, Headers, create_client, InjectionPlace, DelayLanes, RetryPolicy, TokenBucket, is_binary_content, is_cors_header, is_protocol_error, load_ca_certs, load_identity,
+            normalize_path, split_body_includes,
+        },
+        values::ValueGenerators,
+        websocket::{parse_frame, Frame},
     };
 
     #[test]
@@ -18,6 +35,92 @@ mod tests {
         assert!(!is_binary_content(Some("application/html".to_string())));
     }
 
+    #[test]
+    fn token_bucket() {
+        let now = Instant::now();
+        let mut bucket = TokenBucket::new(4, now);
+
+        // a single token, so requests are evenly spaced
+        assert_eq!(bucket.take(now), None);
+        assert_eq!(bucket.take(now), Some(Duration::from_millis(250)));
+        assert_eq!(bucket.take(now + Duration::from_millis(250)), None);
+
+        // the token doesn't accumulate while idle
+        let later = now + Duration::from_secs(10);
+        assert_eq!(bucket.take(later), None);
+        assert!(bucket.take(later).is_some());
+    }
+
+    #[test]
+    fn delay_lanes() {
+        let now = Instant::now();
+        let delay = Duration::from_millis(400);
+        let lanes = DelayLanes::new(4);
+
+        // parallel requests are spread over the delay
+        let slots: Vec<Duration> = (0..5).map(|_| lanes.take(delay, now) - now).collect();
+        assert_eq!(slots, [0, 100, 200, 300, 400].map(Duration::from_millis));
+
+        // fewer parallel requests keep the delay between requests of every one of them
+        lanes.resize(2);
+        let slots: Vec<Duration> = (0..2).map(|_| lanes.take(delay, now) - now).collect();
+        assert_eq!(slots, [Duration::from_millis(700), Duration::from_millis(800)]);
+    }
+
+    #[test]
+    fn adaptive_rate() {
+        let now = Instant::now();
+        let mut bucket = TokenBucket::new(8, now);
+
+        assert_eq!(bucket.adapt(200, now), None);
+        assert_eq!(bucket.adapt(429, now), Some(4.));
+
+        // responses to the requests sent before the decrease don't lower the rate again
+        assert_eq!(bucket.adapt(429, now + Duration::from_millis(100)), None);
+        assert_eq!(bucket.adapt(503, now + Duration::from_secs(1)), Some(2.));
+
+        // a quarter back after 20 other responses in a row, but not above the max rate
+        assert!((0..19).all(|_| bucket.adapt(200, now).is_none()));
+        assert_eq!(bucket.adapt(200, now), Some(2.5));
+
+        for i in 2..10 {
+            bucket.adapt(429, now + Duration::from_secs(i));
+        }
+        assert_eq!(bucket.adapt(429, now + Duration::from_secs(10)), None);
+
+        for _ in 0..20 * 20 {
+            bucket.adapt(200, now);
+        }
+        assert_eq!(bucket.adapt(200, now), None);
+        assert_eq!(bucket.take(now + Duration::from_secs(20)), None);
+        assert_eq!(bucket.take(now + Duration::from_secs(20)), Some(Duration::from_millis(125)));
+    }
+
+    #[test]
+    fn retry_policy() {
+        // any failed request is retried by default, responses only when asked to
+        let policy = RetryPolicy::default();
+        assert!(policy.on_error);
+        assert!(policy.codes.is_empty());
+
+        let policy = RetryPolicy::new(2, ["timeout", "429", "503"].iter().copied()).unwrap();
+        assert!(!policy.on_error && !policy.on_connect && policy.on_timeout);
+        assert_eq!(policy.codes, [429, 503]);
+        assert!(RetryPolicy::new(1, ["sometimes"].iter().copied()).is_err());
+
+        for attempt in 0..3 {
+            let delay = RetryPolicy::backoff(attempt).as_millis() as u64;
+            assert!(delay >= 500 << attempt && delay <= 1000 << attempt);
+        }
+        assert!(RetryPolicy::backoff(100) <= Duration::from_secs(30));
+
+        let headers = |value: &str| vec![("Retry-After".to_string(), value.to_string())];
+        assert_eq!(RetryPolicy::retry_after(&headers("5")), Some(Duration::from_secs(5)));
+        assert_eq!(RetryPolicy::retry_after(&headers("3600")), Some(Duration::from_secs(30)));
+        assert_eq!(RetryPolicy::retry_after(&headers("Wed, 21 Oct 2015 07:28:00 GMT")), None);
+        assert_eq!(RetryPolicy::retry_after(&[]), None);
+    }
+
     #[test]
     fn query_creation() {
         let mut l = RequestDefaults::default();
@@ -44,6 +147,7 @@ mod tests {
             None,
             false,
             false,
+            false,
             "",
             false,
             false,
@@ -77,6 +181,7 @@ mod tests {
             None,
             false,
             false,
+            false,
             "{\"something\":1}",
             false,
             false,
@@ -87,4 +192,984 @@ mod tests {
         assert_eq!(defaults.body, "{\"something\":1, %s}");
         assert_eq!(defaults.template, "\"%k\": %v");
     }
+
+    #[test]
+    fn path_segments_generation() {
+        let defaults = RequestDefaults::new::<String>(
+            "GET",
+            "https://example.com/api/users/123/?a=b",
+            Vec::new(),
+            Duration::from_millis(0),
+            Default::default(),
+            None,
+            None,
+            false,
+            None,
+            false,
+            false,
+            true,
+            "",
+            false,
+            false,
+        )
+        .unwrap();
+
+        assert_eq!(defaults.injection_place, InjectionPlace::PathSegment);
+        assert_eq!(defaults.path, "/api/users/123/%s?a=b");
+        assert_eq!(defaults.template, "%k");
+        assert!(defaults.disable_custom_parameters);
+        assert_eq!(defaults.path_id_candidates(), ["/api/users/122?a=b", "/api/users/124?a=b"]);
+    }
+
+    #[test]
+    fn named_body_markers() {
+        let defaults = RequestDefaults::new::<String>(
+            "POST",
+            "https://example.com/path",
+            Vec::new(),
+            Duration::from_millis(0),
+            Default::default(),
+            None,
+            None,
+            false,
+            None,
+            false,
+            false,
+            false,
+            "{\"a\":1, %s:json%, \"q\":\"b=2&%s:urlencoded%\"}",
+            false,
+            false,
+        )
+        .unwrap();
+
+        let mut request = Request::new(&defaults, vec!["x=1".to_string()]);
+        request.prepare();
+
+        assert_eq!(request.body, "{\"a\":1, \"x\":1, \"q\":\"b=2&x=1\"}");
+
+        // words with special chars stay valid json
+        let mut request = Request::new(&defaults, vec!["a\"b\\c=d\"e".to_string()]);
+        request.prepare();
+
+        assert!(request.body.starts_with("{\"a\":1, \"a\\\"b\\\\c\":\"d\\\"e\""));
+    }
+
+    #[test]
+    fn existing_query_is_kept() {
+        let url = "https://example.com/path?b=x y&a=1#top";
+
+        let mut defaults = RequestDefaults::new::<String>(
+            "GET",
+            url,
+            Vec::new(),
+            Duration::from_millis(0),
+            Default::default(),
+            None,
+            None,
+            false,
+            None,
+            false,
+            false,
+            false,
+            "",
+            false,
+            false,
+        )
+        .unwrap();
+
+        assert_eq!(defaults.path, "/path?b=x%20y&a=1&%s");
+
+        defaults.keep_raw_query(url).unwrap();
+
+        assert_eq!(defaults.path, "/path?b=x y&a=1&%s");
+        assert_eq!(defaults.query_names(), vec!["b", "a"]);
+
+        assert_eq!(normalize_path(&defaults.path), "/path?b=x%20y&a=1&%s");
+        assert_eq!(normalize_path("/ü/100%/%41?q={1}"), "/%C3%BC/100%25/%41?q=%7B1%7D");
+    }
+
+    #[test]
+    fn raw_path_is_kept() {
+        let url = "https://example.com/a/../ü b/%2e?q={1}#top";
+
+        let mut defaults = RequestDefaults {
+            path: format!("{}&%s", &url::Url::parse(url).unwrap()[url::Position::BeforePath..url::Position::AfterQuery]),
+            ..Default::default()
+        };
+
+        assert_eq!(defaults.path, "/%C3%BC%20b/?q={1}&%s");
+
+        defaults.keep_raw_path(url).unwrap();
+
+        assert_eq!(defaults.path, "/a/../ü b/%2e?q={1}&%s");
+    }
+
+    #[test]
+    fn client_certificates() {
+        let filename = std::env::temp_dir().join(format!("x8-cert-{}.p12", std::process::id()));
+        let filename = filename.to_str().unwrap();
+
+        std::fs::write(filename, [0x30, 0x82, 0x01, 0x00]).unwrap();
+
+        let identity = load_identity(filename, "", "");
+        let ca_certs = load_ca_certs(filename);
+        std::fs::remove_file(filename).ok();
+
+        assert!(identity.unwrap_err().to_string().contains("PKCS#12"));
+        assert!(ca_certs.is_err());
+        assert!(load_identity("/nonexistent/cert.pem", "", "").is_err());
+
+        // PKCS#12 certificates are loaded for the native-tls backend
+        let p12 = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/tls/client.p12");
+        let p12 = p12.to_str().unwrap();
+
+        assert!(load_identity(p12, "", "x8").unwrap().1);
+        assert!(load_identity(p12, "", "wrong").unwrap_err().to_string().contains("--cert-password"));
+        assert!(load_identity(p12, "key.pem", "x8").unwrap_err().to_string().contains("--key"));
+
+        let mut config = get_config_from(["x8", "-u", "https://localhost/", "--cert", p12, "--cert-password", "x8"]).unwrap();
+        assert!(create_client(&config, false, None).is_ok());
+
+        config.cert_password = "wrong".to_string();
+        assert!(create_client(&config, false, None).is_err());
+    }
+
+    #[test]
+    fn saved_baseline() {
+        let filename = std::env::temp_dir().join(format!("x8-baseline-{}.json", std::process::id()));
+        let filename = filename.to_str().unwrap();
+
+        let baseline = Baseline {
+            code: 200,
+            headers: vec![("content-type".to_string(), "text/html".to_string())],
+            text: "content-type: text/html\n\nclean".to_string(),
+            http_version: Some(http::Version::HTTP_2),
+            ..Default::default()
+        };
+
+        baseline.save(filename, "GET", "https://example.com/").unwrap();
+        Baseline { code: 404, ..Default::default() }.save(filename, "POST", "https://example.com/").unwrap();
+
+        let loaded = Baseline::load(filename, "GET", "https://example.com/").unwrap();
+        std::fs::remove_file(filename).ok();
+
+        assert_eq!(loaded.code, 200);
+        assert_eq!(loaded.body(), "clean");
+        assert_eq!(loaded.print(), baseline.print());
+    }
+
+    #[test]
+    fn cors_headers_delta() {
+        let baseline = Baseline {
+            headers: vec![
+                ("Access-Control-Allow-Origin".to_string(), "https://example.com".to_string()),
+                ("Access-Control-Allow-Methods".to_string(), "GET".to_string()),
+                ("Content-Type".to_string(), "text/html".to_string()),
+            ],
+            ..Default::default()
+        };
+
+        let defaults = RequestDefaults::default();
+        let mut response = Request::new(&defaults, Vec::new()).empty_response();
+        response.headers = vec![
+            ("access-control-allow-origin".to_string(), "*".to_string()),
+            ("access-control-allow-credentials".to_string(), "true".to_string()),
+            ("content-type".to_string(), "application/json".to_string()),
+        ];
+
+        assert_eq!(
+            response.headers_delta(&baseline, is_cors_header),
+            vec![
+                "+access-control-allow-credentials: true",
+                "access-control-allow-origin: https://example.com -> *",
+                "-access-control-allow-methods: GET",
+            ]
+        );
+        assert!(response.headers_delta(&response.baseline(), is_cors_header).is_empty());
+    }
+
+    #[test]
+    fn reproduction_snippets() {
+        let defaults = RequestDefaults::new::<String>(
+            "GET",
+            "https://example.com/path",
+            Vec::new(),
+            Duration::from_millis(0),
+            Default::default(),
+            None,
+            None,
+            false,
+            None,
+            false,
+            false,
+            false,
+            "",
+            false,
+            false,
+        )
+        .unwrap();
+
+        let mut request = Request::new(&defaults, vec!["admin".to_string(), "debug".to_string()]);
+        request.prepare();
+        let value = request.prepared_parameters[1].1.clone();
+
+        assert_eq!(
+            request.reproduction("debug"),
+            format!("https://example.com/path?debug={}", value)
+        );
+
+        let defaults = RequestDefaults::new::<String>(
+            "POST",
+            "https://example.com:8443/path",
+            Vec::new(),
+            Duration::from_millis(0),
+            Default::default(),
+            None,
+            None,
+            false,
+            None,
+            false,
+            false,
+            false,
+            "a='b'&%s",
+            false,
+            false,
+        )
+        .unwrap();
+
+        let request = Request::new(&defaults, Vec::new());
+
+        assert_eq!(
+            request.reproduction("debug=1"),
+            "curl -X POST 'https://example.com:8443/path' -H 'Content-Type: application/x-www-form-urlencoded' --data-raw 'a='\\''b'\\''&debug=1'"
+        );
+
+        // default headers are added to every request built from the command line
+        let config = get_config_from(["x8", "-u", "https://example.com/path?a=b"]).unwrap();
+        let defaults = RequestDefaults::from_config(&config, "GET", "https://example.com/path?a=b").unwrap();
+        assert!(defaults.custom_headers.iter().any(|(k, _)| k == "User-Agent"));
+
+        let request = Request::new(&defaults, Vec::new());
+        assert_eq!(request.reproduction("debug=1"), "https://example.com/path?a=b&debug=1");
+
+        let config = get_config_from(["x8", "-u", "https://example.com/path", "--headers"]).unwrap();
+        let defaults = RequestDefaults::from_config(&config, "GET", "https://example.com/path").unwrap();
+
+        let request = Request::new(&defaults, Vec::new());
+        assert!(request.reproduction("x-debug").starts_with("curl -X GET 'https://example.com/path' "));
+    }
+
+    #[test]
+    fn header_name_and_method_injection() {
+        let defaults = RequestDefaults::new::<String>(
+            "GET",
+            "https://example.com/path",
+            Vec::from([("X-%s-Debug".to_string(), "1".to_string())]),
+            Duration::from_millis(0),
+            Default::default(),
+            None,
+            None,
+            false,
+            None,
+            false,
+            false,
+            false,
+            "",
+            false,
+            false,
+        )
+        .unwrap();
+
+        assert_eq!(defaults.injection_place, InjectionPlace::HeaderName);
+        assert_eq!(defaults.path, "/path");
+
+        let mut request = Request::new(&defaults, vec!["one".to_string(), "two".to_string()]);
+        request.prepare();
+
+        assert_eq!(request.headers.get_value("X-one-Debug").unwrap(), "1");
+        assert_eq!(request.headers.get_value("X-two-Debug").unwrap(), "1");
+        assert!(!request.headers.contains_key("X-%s-Debug"));
+
+        let defaults = RequestDefaults::new::<String>(
+            "%s",
+            "https://example.com/path",
+            Vec::new(),
+            Duration::from_millis(0),
+            Default::default(),
+            None,
+            None,
+            false,
+            None,
+            false,
+            false,
+            false,
+            "",
+            false,
+            false,
+        )
+        .unwrap();
+
+        assert_eq!(defaults.injection_place, InjectionPlace::Method);
+
+        let mut request = Request::new(&defaults, vec!["PROPFIND".to_string()]);
+        request.prepare();
+
+        assert_eq!(request.method, "PROPFIND");
+        assert_eq!(request.path, "/path");
+
+        // the initial and probe requests don't have a method word
+        let mut request = Request::new(&defaults, Vec::new());
+        request.prepare();
+
+        assert_eq!(request.method, "GET");
+    }
+
+    #[test]
+    fn body_includes_splitting() {
+        assert_eq!(
+            split_body_includes("{\"a\":\"{{file:payload.bin}}\", %s}"),
+            vec![
+                BodyPart::Text("{\"a\":\"".to_string()),
+                BodyPart::File("payload.bin".to_string()),
+                BodyPart::Text("\", %s}".to_string()),
+            ]
+        );
+        assert_eq!(
+            split_body_includes("{{file:a}}{{file:b"),
+            vec![BodyPart::File("a".to_string()), BodyPart::Text("{{file:b".to_string())]
+        );
+    }
+
+    #[test]
+    fn control_requests_caching() {
+        let defaults = RequestDefaults::default();
+
+        assert_eq!(
+            Request::new_random(&defaults, 8).cache_key(),
+            Request::new_random(&defaults, 8).cache_key()
+        );
+        assert_ne!(
+            Request::new_random(&defaults, 8).cache_key(),
+            Request::new_random(&defaults, 16).cache_key()
+        );
+        assert_ne!(
+            Request::new(&defaults, vec!["admin=1".to_string()]).cache_key(),
+            Request::new(&defaults, vec!["admin=2".to_string()]).cache_key()
+        );
+
+        let mut response = Request::new(&defaults, Vec::new()).empty_response();
+        response.code = 200;
+
+        let cache = ResponseCache::new(Duration::from_secs(60));
+        cache.insert("key".to_string(), &response);
+        assert_eq!(cache.get("key").unwrap().code, 200);
+        assert!(cache.get("other").is_none());
+
+        let disabled_cache = ResponseCache::new(Duration::from_secs(0));
+        disabled_cache.insert("key".to_string(), &response);
+        assert!(disabled_cache.get("key").is_none());
+
+        // control requests of the runner go through the cache of the url and method
+        let config = get_config_from(["x8", "-u", "https://example.com/", "--cache-ttl", "60"]).unwrap();
+        let defaults = RequestDefaults::from_config(&config, "GET", "https://example.com/").unwrap();
+        let control_defaults = defaults.clone();
+
+        defaults.cache.insert(Request::new_random(&defaults, 0).cache_key(), &response);
+        assert!(control_defaults.cache.get(&Request::new_random(&control_defaults, 0).cache_key()).is_some());
+
+        let other = RequestDefaults::from_config(&config, "POST", "https://example.com/").unwrap();
+        assert!(other.cache.get(&Request::new_random(&defaults, 0).cache_key()).is_none());
+    }
+
+    #[test]
+    fn doh_answer_parsing() {
+        let text = r#"{"Status":0,"Answer":[
+            {"name":"www.example.com.","type":5,"TTL":300,"data":"example.com."},
+            {"name":"example.com.","type":1,"TTL":300,"data":"93.184.216.34"},
+            {"name":"example.com.","type":28,"TTL":300,"data":"2606:2800:220:1:248:1893:25c8:1946"}
+        ]}"#;
+
+        assert_eq!(
+            doh::parse_answer(text).unwrap(),
+            vec![
+                "93.184.216.34".parse::<std::net::IpAddr>().unwrap(),
+                "2606:2800:220:1:248:1893:25c8:1946".parse().unwrap(),
+            ]
+        );
+        assert!(doh::parse_answer(r#"{"Status":3}"#).unwrap().is_empty());
+        assert!(doh::parse_answer(r#"{"Status":2}"#).is_err());
+    }
+
+    #[test]
+    fn body_without_headers() {
+        let defaults = RequestDefaults::default();
+        let mut response = Request::new(&defaults, Vec::new()).empty_response();
+        response.headers = vec![("Date".to_string(), "Mon, 1 Jan 2024".to_string())];
+        response.text = "<html>variant a</html>".to_string();
+        response.add_headers();
+
+        assert_eq!(response.body(), "<html>variant a</html>");
+        assert_eq!(response.baseline().body(), "<html>variant a</html>");
+        assert_eq!(
+            response.baseline().print_with_body("<html>variant b</html>"),
+            response.baseline().print().replace("variant a", "variant b")
+        );
+        assert_eq!(Baseline::default().body(), "");
+    }
+
+    #[tokio::test]
+    async fn value_transform() {
+        let defaults = RequestDefaults {
+            path: "/?%s".to_string(),
+            template: "%k=%v".to_string(),
+            joiner: "&".to_string(),
+            value_transform: "tr a-z A-Z".to_string(),
+            ..Default::default()
+        };
+
+        let mut request = Request::new(&defaults, vec!["debug=on".to_string(), "admin=yes".to_string()]);
+        request.transform().await.unwrap();
+        request.prepare();
+
+        assert_eq!(request.path, "/?debug=ON&admin=YES");
+        // reflections are searched by the original values
+        assert_eq!(request.prepared_parameters[0].1, "on");
+
+        // the original values are never sent instead
+        let defaults = RequestDefaults { value_transform: "head -n 1".to_string(), ..defaults };
+
+        let mut request = Request::new(&defaults, vec!["debug=on".to_string(), "admin=yes".to_string()]);
+        assert!(request.transform().await.is_err());
+    }
+
+    #[test]
+    fn reflections_counting() {
+        let defaults = RequestDefaults {
+            path: "/?%s".to_string(),
+            template: "%k=%v".to_string(),
+            joiner: "&".to_string(),
+            ..Default::default()
+        };
+
+        let mut request = Request::new(&defaults, vec!["a".to_string(), "b".to_string(), "c".to_string()]);
+        request.prepare();
+
+        let value = |i: usize| request.prepared_parameters[i].1.clone();
+        let text = format!("<p>{}</p><a href='?a={}&b={}'>", value(0).to_uppercase(), value(0), value(1));
+
+        let baseline = {
+            let mut response = Request::new(&defaults, Vec::new()).empty_response();
+            response.text = format!("<a>{}</a>", value(1));
+            response.baseline()
+        };
+
+        let mut response = request.empty_response();
+        response.text = text;
+        response.fill_reflected_parameters(&baseline);
+
+        // b is reflected within the baseline as well, so only the new reflection is counted
+        assert_eq!(response.reflected_parameters.get("a"), Some(&2));
+        assert_eq!(response.reflected_parameters.get("b"), None);
+        assert_eq!(response.reflected_parameters.get("c"), None);
+    }
+
+    #[test]
+    fn test_exchange_json() {
+        let defaults = RequestDefaults::default();
+        let mut response = Request::new(&defaults, Vec::new()).empty_response();
+        response.code = 200;
+        response.text = "é".repeat(3000);
+        response.add_headers();
+
+        let exchange = response.exchange();
+
+        assert_eq!(exchange.response.code, 200);
+        assert!(exchange.response.truncated);
+        assert_eq!(exchange.response.body.len(), 4096);
+    }
+
+    #[test]
+    fn value_generators() {
+        let generators = ValueGenerators::new("numeric", &["(?i)^is_=bool", "date$=format:2024-01-31"]).unwrap();
+
+        assert!(generators.get("page").generate().chars().all(|c| c.is_ascii_digit()));
+        assert!(["true", "false"].contains(&generators.get("is_admin").generate().as_str()));
+        assert!(!generators.get("IS_ADMIN").is_unique());
+
+        let date = generators.get("from_date").generate();
+        assert_eq!(date.len(), 10);
+        assert_eq!((&date[4..5], &date[7..8]), ("-", "-"));
+
+        let generators = ValueGenerators::new("realistic", &["^page$=list:1"]).unwrap();
+
+        assert!(generators.get("user_email").generate().ends_with("@example.com"));
+        assert_eq!(generators.get("order_uuid").generate().len(), 36);
+        assert!(generators.get("userId").generate().chars().all(|c| c.is_ascii_digit()));
+        assert!(generators.get("user_ID").generate().chars().all(|c| c.is_ascii_digit()));
+        for name in ["paid", "valid", "android"] {
+            assert!((0..5).any(|_| !generators.get(name).generate().chars().all(|c| c.is_ascii_digit())));
+        }
+        assert!(!generators.get("created_at").is_unique());
+        assert_eq!(generators.get("page").generate(), "1");
+        assert!(generators.get("q").is_unique());
+
+        assert!(ValueGenerators::new("hex", &[]).is_err());
+        assert!(ValueGenerators::new("random", &["no_type"]).is_err());
+    }
+
+    #[test]
+    fn pool_stats() {
+        let defaults = RequestDefaults::default();
+        let clone = defaults.clone();
+
+        defaults.pool.start();
+        clone.pool.start();
+        defaults.pool.finish(Some(http::Version::HTTP_2));
+        clone.pool.finish(None);
+        clone.pool.start();
+        clone.pool.finish(Some(http::Version::HTTP_11));
+
+        let metrics = defaults.pool.metrics();
+        assert_eq!((metrics.requests, metrics.http2, metrics.peak_in_flight), (2, 1, 2));
+        assert!(metrics.is_multiplexed());
+        assert!(!PoolStats::default().metrics().is_multiplexed());
+    }
+
+    #[test]
+    fn requests_per_stage() {
+        let defaults = RequestDefaults::default();
+        let clone = defaults.clone();
+
+        defaults.stages.sent();
+        clone.stages.set_stage(Stage::Fuzz);
+        defaults.stages.sent();
+        clone.stages.sent();
+        clone.stages.retried();
+        defaults.stages.set_stage(Stage::Custom);
+        clone.stages.sent();
+
+        let metrics = defaults.stages.metrics();
+        assert_eq!((metrics.learn, metrics.fuzz, metrics.verify, metrics.custom), (1, 2, 0, 1));
+        assert_eq!(metrics.total(), 5);
+        assert_eq!(metrics.to_string(), "learn 1 | fuzz 2 | verify 0 | custom 1 | retries 1");
+    }
+
+    #[test]
+    fn nested_values() {
+        let params = [("debug".to_string(), "1".to_string())];
+
+        // {"a":1}
+        let json = NestedValue::new("data", "eyJhIjoxfQ%3D%3D").unwrap();
+        assert_eq!(json.encoding, NestedEncoding::Base64Json { url_safe: false, padded: true });
+        assert_eq!(json.decoded, r#"{"a":1}"#);
+        // {"a":1,"debug":"1"}
+        assert_eq!(json.encode(params.iter()), "eyJhIjoxLCJkZWJ1ZyI6IjEifQ%3D%3D");
+
+        let urlencoded = NestedValue::new("q", "a%3D1%26b%3D2").unwrap();
+        assert_eq!(urlencoded.encoding, NestedEncoding::Urlencoded);
+        assert_eq!(urlencoded.encode(params.iter()), "a%3D1%26b%3D2%26debug%3D1");
+
+        let php = NestedValue::new("s", "a:1:{s:1:\"a\";i:1;}").unwrap();
+        assert_eq!(php.encoding, NestedEncoding::PhpSerialized);
+        assert_eq!(
+            php.encode(params.iter()),
+            utf8_percent_encode("a:2:{s:1:\"a\";i:1;s:5:\"debug\";s:1:\"1\";}", NON_ALPHANUMERIC).to_string()
+        );
+
+        assert!(NestedValue::new("id", "1234").is_none());
+        assert!(NestedValue::new("name", "admin").is_none());
+
+        let (url, nested) = nested::inject("https://example.com/?id=1&data=eyJhIjoxfQ==#top", "data").unwrap();
+        assert_eq!(url, "https://example.com/?id=1&data=%s#top");
+        assert_eq!(nested.name, "data");
+        assert!(nested::inject("https://example.com/?id=1", "id").is_err());
+    }
+
+    #[test]
+    fn low_memory_diff() {
+        let mut defaults = RequestDefaults::default();
+        let mut baseline = Request::new(&defaults, Vec::new()).empty_response();
+        baseline.text = "<html>\n<p>a</p>\n<p>b</p>\n</html>".to_string();
+        let baseline = baseline.baseline();
+
+        let mut response = Request::new(&defaults, Vec::new()).empty_response();
+        response.text = "<html>\n<p>a</p>\n<p>debug</p>\n<p>b</p>\n</html>".to_string();
+        let (_, diffs) = response.compare(&baseline, &[]).unwrap();
+
+        defaults.low_memory = true;
+        let mut response = Request::new(&defaults, Vec::new()).empty_response();
+        response.text = "<html>\n<p>a</p>\n<p>debug</p>\n<p>b</p>\n</html>".to_string();
+
+        assert!(!diffs.is_empty());
+        assert_eq!(response.compare(&baseline, &[]).unwrap().1, diffs);
+
+        // hashes of huge pages are diffed via temporary files, reading them block by block
+        let page: Vec<String> = (0..3000).map(|x| format!("<p>{}</p>", x)).collect();
+        let mut changed = page.clone();
+        changed[1500] = "<p>debug</p>".to_string();
+        changed.insert(2100, "<p>new</p>".to_string());
+        changed.remove(10);
+
+        for (text1, text2) in [(&changed, &page), (&page, &changed), (&page, &page), (&page[..2000].to_vec(), &page)] {
+            assert_eq!(
+                diff::diff_external(text1.iter().map(|x| x.as_str()), text2.iter().map(|x| x.as_str())).unwrap(),
+                diff::diff(&text1.join("\n"), &text2.join("\n")).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn diff_labels() {
+        assert_eq!(
+            labels::label("{\"id\":1,\n\"role\":\"user\",\n\"name\":\"a\"}", "{\"id\":1,\n\"role\":\"admin\",\n\"name\":\"a\"}"),
+            Some("json key `role` value changed".to_string())
+        );
+        assert_eq!(
+            labels::label("<form>\n<input name=a>\n</form>", "<form>\n<input name=a>\n<input name=b>\n</form>"),
+            Some("new form field appeared".to_string())
+        );
+        assert_eq!(
+            labels::label("<p>Error: id is required</p>", "<p>Error: invalid id</p>"),
+            Some("error message changed".to_string())
+        );
+        assert_eq!(labels::label("<p>a</p>", "<p>b</p>"), None);
+    }
+
+    #[test]
+    fn hash_precheck() {
+        let defaults = RequestDefaults::default();
+        let mut baseline = Request::new(&defaults, Vec::new()).empty_response();
+        baseline.text = "<html>\n<p>a</p>\n</html>".to_string();
+        let baseline = baseline.baseline();
+
+        let mut response = Request::new(&defaults, Vec::new()).empty_response();
+        response.text = baseline.text.clone();
+        assert_eq!(response.compare(&baseline, &[]).unwrap(), (false, Vec::new()));
+
+        response.text = "<html>\n<p>b</p>\n</html>".to_string();
+        assert!(!response.compare(&baseline, &[]).unwrap().1.is_empty());
+
+        let hashing = defaults.hashing.metrics();
+        assert_eq!((hashing.hits, hashing.misses, hashing.collisions), (1, 1, 0));
+
+        // lines within the learned diffs don't prevent skipping the diff
+        let page = |date: &str, body: &str| {
+            let mut response = Request::new(&defaults, Vec::new()).empty_response();
+            response.headers = vec![("date".to_string(), date.to_string())];
+            response.text = format!("date: {}\n\n{}", date, body);
+            response
+        };
+
+        let baseline = page("1", "<html>\n<p>a</p>\n</html>").baseline();
+        let (_, diffs) = page("2", "<html>\n<p>a</p>\n</html>").compare(&baseline, &[]).unwrap();
+        assert_eq!(diffs, vec!["-1,1 +1,1"]);
+
+        assert_eq!(page("3", "<html>\n<p>a</p>\n</html>").compare(&baseline, &diffs).unwrap(), (false, Vec::new()));
+        assert!(!page("3", "<html>\n<p>b</p>\n</html>").compare(&baseline, &diffs).unwrap().1.is_empty());
+
+        let hashing = defaults.hashing.metrics();
+        assert_eq!((hashing.hits, hashing.misses, hashing.collisions), (2, 3, 1));
+    }
+
+    #[test]
+    fn cookie_injection() {
+        let defaults = RequestDefaults::new::<String>(
+            "GET",
+            "https://example.com/path",
+            Vec::from([("Cookie".to_string(), "session=abc; %s".to_string())]),
+            Duration::from_millis(0),
+            Default::default(),
+            None,
+            None,
+            true,
+            None,
+            false,
+            true,
+            false,
+            "",
+            true,
+            false,
+        )
+        .unwrap();
+
+        assert_eq!(defaults.injection_place, InjectionPlace::Cookie);
+
+        let mut request = Request::new(&defaults, vec!["debug=1".to_string(), "lang=en us".to_string()]);
+        request.prepare();
+
+        assert_eq!(request.headers.get_value("Cookie").unwrap(), "session=abc; debug=1; lang=en%20us");
+    }
+
+    #[test]
+    fn multipart_body() {
+        let defaults = RequestDefaults::new::<String>(
+            "POST",
+            "https://example.com/upload",
+            Vec::from([("Content-Type".to_string(), "multipart/form-data; boundary=abc".to_string())]),
+            Duration::from_millis(0),
+            Default::default(),
+            None,
+            None,
+            false,
+            Some(DataType::Multipart),
+            false,
+            false,
+            false,
+            "--abc\r\nContent-Disposition: form-data; name=\"file\"\r\n\r\nx\r\n--abc--\r\n",
+            true,
+            false,
+        )
+        .unwrap();
+
+        assert_eq!(defaults.boundary, "abc");
+
+        let mut request = Request::new(&defaults, vec!["debug=1".to_string()]);
+        request.prepare();
+
+        assert_eq!(
+            request.body,
+            "--abc\r\nContent-Disposition: form-data; name=\"file\"\r\n\r\nx\r\n\
+--abc\r\nContent-Disposition: form-data; name=\"debug\"\r\n\r\n1\r\n--abc--\r\n"
+        );
+    }
+
+    /// reads the raw response (the status line, headers and the body) the same way as the received one is processed
+    fn fixture_response(defaults: &RequestDefaults, path: &Path) -> Option<Response> {
+        let raw = fs::read_to_string(path).ok()?;
+        let (head, body) = raw.split_once("\n\n").unwrap_or((&raw, ""));
+        let mut lines = head.lines();
+
+        let mut response = Request::new(defaults, Vec::new()).empty_response();
+        response.code = lines.next()?.split(' ').nth(1)?.parse().ok()?;
+        response.http_version = Some(http::Version::HTTP_11);
+        response.headers = lines
+            .filter_map(|x| x.split_once(':'))
+            .map(|(k, v)| (k.trim().to_string(), v.trim().to_string()))
+            .collect();
+        response.text = body.to_string();
+
+        response.beautify_body();
+        response.add_headers();
+
+        Some(response)
+    }
+
+    /// every fixtures/diff/<case> directory contains baseline.http, response.http and expected.json
+    /// learn.http is optional and contains one more response without parameters to learn dynamic lines from
+    #[test]
+    fn diff_fixtures() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/diff");
+        let defaults = RequestDefaults::default();
+
+        let mut cases: Vec<_> = fs::read_dir(&dir).unwrap().map(|x| x.unwrap().path()).filter(|x| x.is_dir()).collect();
+        cases.sort();
+
+        assert!(!cases.is_empty());
+
+        let mut failures = Vec::new();
+
+        for case in cases.iter() {
+            let baseline = fixture_response(&defaults, &case.join("baseline.http")).unwrap().baseline();
+
+            let known_diffs = match fixture_response(&defaults, &case.join("learn.http")) {
+                Some(learn) => learn.compare(&baseline, &[]).unwrap().1,
+                None => Vec::new(),
+            };
+
+            let (code_diff, new_diffs) = fixture_response(&defaults, &case.join("response.http"))
+                .unwrap()
+                .compare(&baseline, &known_diffs)
+                .unwrap();
+
+            let expected: serde_json::Value = serde_json::from_str(&fs::read_to_string(case.join("expected.json")).unwrap()).unwrap();
+
+            if expected["code_diff"] != code_diff || expected["new_diffs"] != new_diffs.len() {
+                failures.push(format!(
+                    "{}: expected code_diff {} and {} new diffs, got {} and {:?}",
+                    case.file_name().unwrap().to_string_lossy(),
+                    expected["code_diff"],
+                    expected["new_diffs"],
+                    code_diff,
+                    new_diffs
+                ));
+            }
+        }
+
+        assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+    }
+
+    #[test]
+    fn error_kinds() {
+        let defaults = |url: &str| {
+            RequestDefaults::new::<String>(
+                "GET", url, Vec::new(), Duration::from_millis(0), Default::default(), None, None, false, None, false, false, false,
+                "", false, false,
+            )
+        };
+
+        assert!(matches!(defaults("example.com/path"), Err(Error::Url(_))));
+        assert!(matches!(defaults("data:text/plain,x"), Err(Error::Url(_))));
+
+        // the kind survives boxing within the runner
+        let boxed: Box<dyn std::error::Error> = Box::new(Error::UnstablePage("code".to_string()));
+        assert!(matches!(Error::from(boxed), Error::UnstablePage(x) if x == "code"));
+
+        let boxed: Box<dyn std::error::Error> = Box::new(std::io::Error::from(std::io::ErrorKind::NotFound));
+        assert!(matches!(Error::from(boxed), Error::Io(_)));
+
+        let boxed: Box<dyn std::error::Error> = Box::new(Error::Unreachable("timed out".to_string()));
+        assert_eq!(boxed.to_string(), "Unable to reach server (timed out)");
+        assert!(matches!(Error::from(boxed), Error::Unreachable(_)));
+
+        let boxed: Box<dyn std::error::Error> = "Unable to reach server".into();
+        assert!(matches!(Error::from(boxed), Error::Other(x) if x == "Unable to reach server"));
+    }
+
+    #[test]
+    fn traffic_sizes() {
+        assert_eq!(parse_size("1024").unwrap(), 1024);
+        assert_eq!(parse_size("64k").unwrap(), 64 * 1024);
+        assert_eq!(parse_size("500MB").unwrap(), 500 * 1024 * 1024);
+        assert!(parse_size("lots").is_err());
+
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(1536), "1.5 KB");
+        assert_eq!(format_bytes(3 * 1024 * 1024), "3.0 MB");
+
+        let stats = TrafficStats::default();
+        stats.record(100, 2000);
+        stats.record(50, 0);
+        assert_eq!(stats.metrics(), TrafficMetrics { sent: 150, received: 2000 });
+    }
+
+    #[tokio::test]
+    async fn http2_protocol_errors() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // answers the HTTP/2 preface over HTTP/1.1
+        let http1 = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let http1_addr = http1.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = http1.accept().await {
+                // PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n
+                let mut preface = [0; 24];
+                stream.read_exact(&mut preface).await.ok();
+                stream.write_all(b"HTTP/1.1 400 Bad Request\r\nContent-Length: 0\r\n\r\n").await.ok();
+
+                // unread frames would reset the connection instead of letting the client fail on the response
+                let mut buf = [0; 1024];
+                while matches!(stream.read(&mut buf).await, Ok(n) if n > 0) {}
+            }
+        });
+
+        // accepts connections and never responds
+        let silent = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let silent_addr = silent.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut streams = Vec::new();
+            while let Ok((stream, _)) = silent.accept().await {
+                streams.push(stream);
+            }
+        });
+
+        let client = reqwest::Client::builder()
+            .http2_prior_knowledge()
+            .timeout(Duration::from_millis(500))
+            .build()
+            .unwrap();
+
+        let err = Error::from(client.get(format!("http://{}/", http1_addr)).send().await.unwrap_err());
+        assert!(is_protocol_error(&err));
+
+        let err = Error::from(client.get(format!("http://{}/", silent_addr)).send().await.unwrap_err());
+        assert!(!is_protocol_error(&err));
+
+        assert!(!is_protocol_error(&Error::Other("Unable to reach server".to_string())));
+    }
+
+    #[test]
+    fn audit_log_entries() {
+        let filename = std::env::temp_dir().join("x8_audit_log_test.jsonl");
+        let filename = filename.to_str().unwrap();
+        fs::write(filename, "").unwrap();
+
+        let state = RunState::default();
+        let state = &state;
+
+        std::thread::scope(|scope| {
+            for thread in 0..4 {
+                scope.spawn(move || {
+                    for i in 0..100 {
+                        let url = format!("https://example.com/{}/{}", thread, "a".repeat(i * 10));
+                        let entry = AuditEntry { sent_at: i as u128, since_previous: None, method: "GET", url: &url, code: Some(200), time: 1 };
+
+                        state.write_audit(filename, &entry);
+                    }
+                });
+            }
+        });
+
+        state.flush_audit();
+
+        let lines: Vec<serde_json::Value> = fs::read_to_string(filename)
+            .unwrap()
+            .lines()
+            .map(|x| serde_json::from_str(x).unwrap())
+            .collect();
+
+        fs::remove_file(filename).ok();
+
+        assert_eq!(lines.len(), 400);
+        assert!(lines.iter().all(|x| x["code"] == 200));
+    }
+
+    #[test]
+    fn owned_responses() {
+        fn spawnable<T: Send + 'static>(_: &T) {}
+
+        // the defaults are dropped before the response
+        let response = {
+            let defaults = RequestDefaults::default();
+            Request::new(&defaults, vec!["debug".to_string()]).empty_response()
+        };
+
+        spawnable(&response);
+        assert_eq!(response.request.prepared_parameters[0].0, "debug");
+    }
+
+    #[test]
+    fn separate_run_states() {
+        let state = RunState::default();
+        let other = RunState::default();
+
+        assert_eq!(state.record_sent("example.com", 10), None);
+        assert_eq!(state.record_sent("example.com", 25), Some(15));
+        assert_eq!(other.record_sent("example.com", 30), None);
+
+        state.record_traffic(600, 1000);
+        assert!(!state.is_stopped());
+        state.record_traffic(600, 1000);
+        assert!(state.is_stopped());
+
+        // the limit of one run doesn't stop the other one
+        assert!(!other.is_stopped());
+        assert_eq!(other.traffic(), 0);
+    }
+
+    #[test]
+    fn websocket_frames() {
+        assert_eq!(parse_frame(b"\x81\x05hello"), Some(Frame::Message(b"hello".to_vec())));
+        // incomplete
+        assert_eq!(parse_frame(b"\x81\x05hel"), None);
+
+        // pings are skipped
+        let frame = parse_frame(b"\x89\x00\x88\x06\x03\xf0bye!").unwrap();
+        assert_eq!(frame, Frame::Close(Some(1008), "bye!".to_string()));
+        assert_eq!(frame.describe(), "websocket close: 1008 bye!");
+
+        // the extended length and the masked payload
+        let mut bytes = vec![0x82, 0xfe, 0x00, 0x7e, 1, 2, 3, 4];
+        bytes.extend((0..126u8).map(|x| b'a' ^ [1, 2, 3, 4][x as usize % 4]));
+        assert_eq!(parse_frame(&bytes), Some(Frame::Message(vec![b'a'; 126])));
+    }
 }
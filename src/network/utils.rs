@@ -1,12 +1,17 @@
-use std::{time::Duration, error::Error};
+use std::{cell::RefCell, error::Error, fs, io::Read, net::SocketAddr, process::Stdio, sync::atomic::{AtomicUsize, Ordering}, time::{Duration, Instant}};
 
 use lazy_static::lazy_static;
-use percent_encoding::{AsciiSet, CONTROLS};
+use parking_lot::Mutex;
+use rand::Rng;
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
 use regex::Regex;
 use reqwest::Client;
 use serde::Serialize;
+use tokio::{io::AsyncWriteExt, process::Command};
 
-use crate::{config::structs::Config, utils::random_line};
+use crate::{
+    config::structs::Config, error, utils::random_line, ADAPTIVE_RATE_INTERVAL, ADAPTIVE_RATE_RECOVERY, MAX_RETRY_DELAY, MIN_ADAPTIVE_RATE, RETRY_DELAY,
+};
 
 use super::response::Response;
 
@@ -24,6 +29,211 @@ lazy_static! {
         .add(b'/')
         .add(b'=')
         .add(b'%');
+
+    /// characters that can't be sent within the path or query as is
+    pub static ref PATH_UNSAFE: AsciiSet = CONTROLS
+        .add(b' ')
+        .add(b'"')
+        .add(b'<')
+        .add(b'>')
+        .add(b'`')
+        .add(b'{')
+        .add(b'}')
+        .add(b'|')
+        .add(b'\\')
+        .add(b'^');
+}
+
+/// the limit of requests per second of the whole run (--rate-limit)
+/// the bucket holds a single token, so requests are evenly spaced and the limit is never exceeded even for a moment
+#[derive(Debug)]
+pub struct TokenBucket {
+    /// the current rate. Lower than the max one while the server responds with 429 or 503 (--adaptive-rate)
+    rate: f64,
+    max_rate: f64,
+
+    tokens: f64,
+    updated: Instant,
+
+    /// responses without 429 or 503 in a row
+    successes: usize,
+
+    /// when the rate was halved the last time
+    decreased: Option<Instant>,
+}
+
+impl TokenBucket {
+    pub fn new(rate: u32, now: Instant) -> Self {
+        TokenBucket {
+            rate: rate as f64,
+            max_rate: rate as f64,
+            tokens: 1.,
+            updated: now,
+            successes: 0,
+            decreased: None,
+        }
+    }
+
+    /// takes the token in case it's available, otherwise returns the time to wait for it
+    pub fn take(&mut self, now: Instant) -> Option<Duration> {
+        self.tokens = (self.tokens + now.duration_since(self.updated).as_secs_f64() * self.rate).min(1.);
+        self.updated = now;
+
+        if self.tokens >= 1. {
+            self.tokens -= 1.;
+            return None;
+        }
+
+        Some(Duration::from_secs_f64((1. - self.tokens) / self.rate))
+    }
+
+    /// halves the rate on 429 and 503 responses and slowly restores it after a series of other responses
+    /// the rate is halved once per ADAPTIVE_RATE_INTERVAL, so responses to the requests that were already sent don't lower it further
+    /// returns the new rate in case it was changed
+    pub fn adapt(&mut self, code: u16, now: Instant) -> Option<f64> {
+        if code == 429 || code == 503 {
+            self.successes = 0;
+
+            if matches!(self.decreased, Some(decreased) if now.duration_since(decreased) < Duration::from_millis(ADAPTIVE_RATE_INTERVAL)) {
+                return None;
+            }
+
+            let rate = (self.rate / 2.).max(MIN_ADAPTIVE_RATE);
+            if rate < self.rate {
+                self.rate = rate;
+                self.decreased = Some(now);
+                return Some(rate);
+            }
+        } else if self.rate < self.max_rate {
+            self.successes += 1;
+
+            if self.successes >= ADAPTIVE_RATE_RECOVERY {
+                self.successes = 0;
+                self.rate = (self.rate * 1.25).min(self.max_rate);
+                return Some(self.rate);
+            }
+        }
+
+        None
+    }
+}
+
+/// spreads --delay between concurrent requests
+/// every one of the parallel requests (lanes) waits the delay since the previous request of its lane was sent,
+/// so -c 4 --delay 1000 sends 4 evenly spaced requests per second instead of a burst of 4 after every sleep
+#[derive(Debug, Default)]
+pub struct DelayLanes {
+    /// the earliest time every lane can send the next request at
+    /// filled on the first request, because the delay isn't known before
+    lanes: Mutex<Vec<Instant>>,
+
+    /// the amount of lanes. Follows the adaptive concurrency
+    count: AtomicUsize,
+}
+
+impl DelayLanes {
+    pub fn new(lanes: usize) -> Self {
+        Self {
+            lanes: Mutex::new(Vec::new()),
+            count: AtomicUsize::new(lanes.max(1)),
+        }
+    }
+
+    /// changes the amount of lanes once the amount of parallel requests changes
+    pub fn resize(&self, lanes: usize) {
+        self.count.store(lanes.max(1), Ordering::Relaxed);
+    }
+
+    /// takes the lane that is free first for the delay and returns the time the request can be sent at
+    pub fn take(&self, delay: Duration, now: Instant) -> Instant {
+        let mut lanes = self.lanes.lock();
+        let count = self.count.load(Ordering::Relaxed);
+
+        // lanes are staggered, so parallel requests are spread over the delay instead of being sent at once
+        let stagger = |i: usize| now + delay * i as u32 / count as u32;
+
+        if lanes.len() > count {
+            // the busiest lanes are kept to never send faster than before
+            lanes.sort();
+            let removed = lanes.len() - count;
+            lanes.drain(..removed);
+        }
+
+        while lanes.len() < count {
+            let i = lanes.len();
+            lanes.push(stagger(i));
+        }
+
+        // there's always at least one lane
+        let lane = lanes.iter_mut().min().unwrap();
+        let slot = std::cmp::max(*lane, now);
+        *lane = slot + delay;
+
+        slot
+    }
+
+    /// waits until one of the lanes is free and takes it for the delay
+    pub async fn wait(&self, delay: Duration) {
+        if delay.is_zero() {
+            return;
+        }
+
+        let slot = self.take(delay, Instant::now());
+
+        tokio::time::sleep_until(slot.into()).await;
+    }
+}
+
+/// a line of --audit-log
+#[derive(Debug, Serialize)]
+pub struct AuditEntry<'a> {
+    /// microseconds since the unix epoch
+    pub sent_at: u128,
+
+    /// microseconds since the previous request to the same host was sent
+    pub since_previous: Option<u128>,
+
+    pub method: &'a str,
+    pub url: &'a str,
+
+    /// None in case of errors
+    pub code: Option<u16>,
+
+    /// response time in milliseconds
+    pub time: u128,
+}
+
+/// escapes the string to be placed within json quotes: a"b\c -> a\"b\\c
+pub fn json_escape(value: &str) -> String {
+    // serializing a string can't fail
+    let escaped = serde_json::to_string(value).unwrap();
+
+    // remove the surrounding quotes
+    escaped[1..escaped.len() - 1].to_string()
+}
+
+/// percent encodes spaces, unicode and other unsafe chars within the path and query
+/// already encoded sequences and the %s injection point stay as is: /a b/%C3%BC%s -> /a%20b/%C3%BC%s
+pub fn normalize_path(path: &str) -> String {
+    let encoded = utf8_percent_encode(path, &PATH_UNSAFE).to_string();
+    let bytes = encoded.as_bytes();
+
+    let mut normalized = String::with_capacity(encoded.len());
+
+    for (i, c) in encoded.char_indices() {
+        let is_sequence = c == '%'
+            && (bytes.get(i + 1) == Some(&b's')
+                || (bytes.get(i + 1).is_some_and(u8::is_ascii_hexdigit)
+                    && bytes.get(i + 2).is_some_and(u8::is_ascii_hexdigit)));
+
+        if c == '%' && !is_sequence {
+            normalized.push_str("%25");
+        } else {
+            normalized.push(c);
+        }
+    }
+
+    normalized
 }
 
 /// enum mainly created for the correct json parsing
@@ -40,6 +250,10 @@ pub enum DataType {
     ProbablyJson,
 
     Urlencoded,
+
+    /// multipart/form-data with a part per parameter
+    Multipart,
+
     Headers
 }
 
@@ -47,15 +261,277 @@ pub enum DataType {
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Copy)]
 pub enum InjectionPlace {
     Path,
+    /// every word is sent as a separate path segment: /api/users/123/<word>
+    PathSegment,
     Body,
     Headers,
     HeaderValue,
+    /// parameters are sent as name=value pairs within the Cookie header
+    Cookie,
+    /// every word is placed within a header name: X-<word>-Debug
+    HeaderName,
+    /// every word is placed within the method. Only one word per request
+    Method,
 }
 
 impl Default for InjectionPlace {
     fn default() -> Self { InjectionPlace::Path }
 }
 
+/// how the request body is framed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BodyFraming {
+    /// whatever the client decides (usually Content-Length)
+    #[default]
+    Auto,
+    ContentLength,
+    /// Transfer-Encoding: chunked
+    Chunked,
+    /// Content-Length together with Transfer-Encoding: chunked
+    Both,
+}
+
+/// how redirects are followed (--follow-redirects)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RedirectPolicy {
+    /// 0 - redirects aren't followed and the first response is compared
+    pub max_hops: usize,
+
+    /// redirects to other hosts aren't followed so fuzz traffic doesn't leak to third parties
+    pub same_host: bool,
+}
+
+impl RedirectPolicy {
+    fn build(self) -> reqwest::redirect::Policy {
+        if self.max_hops == 0 {
+            return reqwest::redirect::Policy::none();
+        }
+
+        reqwest::redirect::Policy::custom(move |attempt| {
+            // previous() starts with the initial url
+            if attempt.previous().len() > self.max_hops
+                || (self.same_host && attempt.url().host_str() != attempt.previous()[0].host_str())
+            {
+                // the redirect response itself is returned
+                return attempt.stop();
+            }
+
+            // the policy runs within the task that sends the request
+            let _ = REDIRECT_CHAIN.try_with(|x| x.borrow_mut().push(attempt.url().to_string()));
+
+            attempt.follow()
+        })
+    }
+}
+
+tokio::task_local! {
+    /// urls the request currently being sent was redirected to
+    pub static REDIRECT_CHAIN: RefCell<Vec<String>>;
+}
+
+/// which failed requests are retried and how many times (--retries, --retry-on)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// 0 - failed requests aren't retried
+    pub retries: usize,
+
+    /// every failed request is retried regardless of the reason
+    pub on_error: bool,
+
+    pub on_connect: bool,
+    pub on_timeout: bool,
+
+    /// status codes of temporary failures
+    pub codes: Vec<u16>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            retries: 1,
+            on_error: true,
+            on_connect: false,
+            on_timeout: false,
+            // responses are retried only when asked to, since some targets answer with these codes to parameters
+            codes: Vec::new(),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// conditions are error, connect, timeout or status codes
+    pub fn new<'a>(retries: usize, conditions: impl Iterator<Item = &'a str>) -> Result<Self, Box<dyn Error>> {
+        let mut policy = Self {
+            retries,
+            on_error: false,
+            on_connect: false,
+            on_timeout: false,
+            codes: Vec::new(),
+        };
+
+        for condition in conditions {
+            match condition {
+                "error" => policy.on_error = true,
+                "connect" => policy.on_connect = true,
+                "timeout" => policy.on_timeout = true,
+                code => policy.codes.push(
+                    code.parse()
+                        .map_err(|_| format!("Incorrect --retry-on condition: {}. Available: error, connect, timeout, <status code>", code))?,
+                ),
+            }
+        }
+
+        Ok(policy)
+    }
+
+    pub fn is_retryable(&self, err: &reqwest::Error) -> bool {
+        self.on_error || (self.on_connect && err.is_connect()) || (self.on_timeout && err.is_timeout())
+    }
+
+    /// exponential backoff with jitter: a random time between the half and the full delay of the attempt
+    pub fn backoff(attempt: usize) -> Duration {
+        let delay = RETRY_DELAY.saturating_mul(1 << attempt.min(16)).min(MAX_RETRY_DELAY);
+
+        Duration::from_millis(rand::thread_rng().gen_range(delay / 2, delay + 1))
+    }
+
+    /// the delay from the Retry-After header in seconds (dates aren't supported)
+    pub fn retry_after(headers: &[(String, String)]) -> Option<Duration> {
+        headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case("retry-after"))?
+            .1
+            .trim()
+            .parse::<u64>()
+            .ok()
+            .map(|x| Duration::from_millis(x.saturating_mul(1000).min(MAX_RETRY_DELAY)))
+    }
+}
+
+/// pipes values through the external command, one value per line (--value-transform)
+/// the command needs to print transformed values one per line in the same order
+pub async fn transform_values(command: &str, values: &[&str]) -> error::Result<Vec<String>> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()?;
+
+    let mut stdin = child.stdin.take().ok_or("Unable to open stdin")?;
+    let input = format!("{}\n", values.join("\n"));
+
+    // the output is read while the values are written, otherwise both sides can wait on full pipes
+    // stdin is closed right after the values are written, so the command knows where they end
+    let write = async move {
+        let written = stdin.write_all(input.as_bytes()).await;
+        drop(stdin);
+        written
+    };
+    let (written, output) = tokio::join!(write, child.wait_with_output());
+    let output = output?;
+
+    // commands that don't read stdin till the end close the pipe earlier
+    if let Err(err) = written {
+        if err.kind() != std::io::ErrorKind::BrokenPipe {
+            Err(err)?;
+        }
+    }
+
+    if !output.status.success() {
+        Err(format!("--value-transform exited with {}", output.status))?;
+    }
+
+    let transformed: Vec<String> = String::from_utf8_lossy(&output.stdout).lines().map(|x| x.to_string()).collect();
+
+    if transformed.len() != values.len() {
+        Err(format!("--value-transform returned {} values instead of {}", transformed.len(), values.len()))?;
+    }
+
+    Ok(transformed)
+}
+
+/// a part of the body that is either sent as is or streamed from a file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BodyPart {
+    Text(String),
+    /// {{file:path}} include
+    File(String),
+}
+
+/// whether the body starts like a json object. The body streamed from a file is checked by the first byte of the file
+pub fn looks_like_json(body: &str) -> bool {
+    match split_body_includes(body).first() {
+        Some(BodyPart::File(path)) => {
+            let mut first = [0; 1];
+            fs::File::open(path).and_then(|mut file| file.read(&mut first)).map(|_| first[0] == b'{').unwrap_or(false)
+        }
+        _ => body.starts_with('{'),
+    }
+}
+
+/// splits the body by {{file:path}} includes
+pub fn split_body_includes(body: &str) -> Vec<BodyPart> {
+    let mut parts = Vec::new();
+    let mut rest = body;
+
+    while let Some(start) = rest.find("{{file:") {
+        let path_start = start + "{{file:".len();
+
+        let end = match rest[path_start..].find("}}") {
+            Some(end) => path_start + end,
+            None => break,
+        };
+
+        if start > 0 {
+            parts.push(BodyPart::Text(rest[..start].to_string()));
+        }
+
+        parts.push(BodyPart::File(rest[path_start..end].to_string()));
+        rest = &rest[end + 2..];
+    }
+
+    if !rest.is_empty() {
+        parts.push(BodyPart::Text(rest.to_string()));
+    }
+
+    parts
+}
+
+/// whether the request failed because the server didn't respond within --timeout
+pub fn is_timeout(err: &(dyn Error + 'static)) -> bool {
+    match err.downcast_ref::<crate::Error>() {
+        Some(crate::Error::Network(err)) => err.is_timeout(),
+        _ => matches!(err.downcast_ref::<reqwest::Error>(), Some(err) if err.is_timeout()),
+    }
+}
+
+/// whether the string can be used as a method or a header name
+pub fn is_http_token(s: &str) -> bool {
+    !s.is_empty() && s.chars().all(|c| c.is_ascii_alphanumeric() || "!#$%&'*+-.^_`|~".contains(c))
+}
+
+/// headers that describe the CORS policy
+pub fn is_cors_header(name: &str) -> bool {
+    name.to_lowercase().starts_with("access-control-allow-")
+}
+
+/// headers that describe security policies of the page
+pub fn is_security_header(name: &str) -> bool {
+    matches!(
+        name.to_lowercase().as_str(),
+        "content-security-policy"
+            | "content-security-policy-report-only"
+            | "x-frame-options"
+            | "strict-transport-security"
+            | "x-content-type-options"
+            | "x-xss-protection"
+            | "referrer-policy"
+            | "permissions-policy"
+    )
+}
+
 pub trait Headers {
     fn contains_key(&self, key: &str) -> bool;
     fn get_index_case_insensitive(&self, key: &str) -> Option<usize>;
@@ -104,19 +580,17 @@ pub(super) fn save_request(
     response: &Response,
     param_key: &str,
 ) -> Result<String, Box<dyn Error>> {
-    let output = response.print_all();
+    let mut output = response.print_all();
+
+    if !response.redirects.is_empty() {
+        output = format!("Redirects:\n{}\n\n{}", response.redirects.join("\n"), output);
+    }
 
     let filename = format!(
         "{}/{}-{}-{}-{}",
         &config.save_responses,
-        &response.request.as_ref().unwrap().defaults.host,
-        response
-            .request
-            .as_ref()
-            .unwrap()
-            .defaults
-            .method
-            .to_lowercase(),
+        &response.request.defaults.host,
+        response.request.defaults.method.to_lowercase(),
         param_key,
         random_line(3) //nonce to prevent overwrites
     );
@@ -126,10 +600,64 @@ pub(super) fn save_request(
     Ok(filename)
 }
 
-pub fn create_client(config: &Config, replay: bool) -> Result<Client, Box<dyn Error>> {
+/// loads the client certificate along with its private key (--cert, --key, --cert-password)
+/// returns whether the certificate is in PKCS#12, since only the native-tls backend supports such certificates
+pub fn load_identity(cert: &str, key: &str, password: &str) -> Result<(reqwest::Identity, bool), Box<dyn Error>> {
+    let mut pem = fs::read(cert).map_err(|err| format!("Unable to read {}: {}", cert, err))?;
+
+    if !pem.windows(10).any(|x| x == b"-----BEGIN") {
+        if !key.is_empty() {
+            Err(format!("{} is a PKCS#12 file, the private key is taken from it so --key isn't needed", cert))?;
+        }
+
+        let identity = reqwest::Identity::from_pkcs12_der(&pem, password)
+            .map_err(|err| format!("Unable to load the PKCS#12 client certificate (wrong --cert-password?): {}", err))?;
+
+        return Ok((identity, true));
+    }
+
+    if !key.is_empty() {
+        pem.push(b'\n');
+        pem.extend(fs::read(key).map_err(|err| format!("Unable to read {}: {}", key, err))?);
+    }
+
+    let identity = reqwest::Identity::from_pem(&pem).map_err(|err| format!("Unable to load the client certificate: {}", err))?;
+
+    Ok((identity, false))
+}
+
+/// loads every certificate from the PEM bundle or a single DER certificate (--ca-cert)
+pub fn load_ca_certs(filename: &str) -> Result<Vec<reqwest::Certificate>, Box<dyn Error>> {
+    let content = fs::read(filename).map_err(|err| format!("Unable to read {}: {}", filename, err))?;
+
+    let certs = if content.windows(27).any(|x| x == b"-----BEGIN CERTIFICATE-----") {
+        String::from_utf8_lossy(&content)
+            .split_inclusive("-----END CERTIFICATE-----")
+            .filter(|x| x.contains("-----BEGIN CERTIFICATE-----"))
+            .map(|x| reqwest::Certificate::from_pem(x.trim().as_bytes()))
+            .collect::<Result<Vec<reqwest::Certificate>, reqwest::Error>>()
+    } else {
+        reqwest::Certificate::from_der(&content).map(|x| vec![x])
+    };
+
+    Ok(certs.map_err(|err| format!("Unable to load {}: {}", filename, err))?)
+}
+
+/// targets are usually checked by ip, through intercepting proxies or have self-signed certificates
+/// so server certificates are verified only against the root certificates provided by the user (--ca-cert)
+/// the client certificate (--cert) doesn't change it
+pub fn verifies_certs(config: &Config) -> bool {
+    !config.ca_cert.is_empty() && !config.insecure
+}
+
+/// resolved -- overrides dns resolution for the host (--doh)
+pub fn create_client(config: &Config, replay: bool, resolved: Option<(&str, SocketAddr)>) -> Result<Client, Box<dyn Error>> {
     let mut client = Client::builder()
-        .danger_accept_invalid_certs(true)
+        .danger_accept_invalid_certs(!verifies_certs(config))
         .timeout(Duration::from_secs(config.timeout as u64))
+        // otherwise an unresponsive address stalls the request instead of falling back to the next resolved one
+        // the http connector splits the timeout evenly between the resolved addresses, while the whole connection is limited by the full one
+        .connect_timeout(Duration::from_secs(std::cmp::min(config.timeout, config.connect_timeout) as u64))
         .http1_title_case_headers()
         .cookie_store(true)
         .http09_responses()
@@ -139,6 +667,26 @@ pub fn create_client(config: &Config, replay: bool) -> Result<Client, Box<dyn Er
         client = client.no_trust_dns();
     }
 
+    if !config.cert.is_empty() {
+        let (identity, pkcs12) = load_identity(&config.cert, &config.key, &config.cert_password)?;
+
+        if pkcs12 {
+            client = client.use_native_tls();
+        }
+
+        client = client.identity(identity);
+    }
+
+    if !config.ca_cert.is_empty() {
+        for cert in load_ca_certs(&config.ca_cert)? {
+            client = client.add_root_certificate(cert);
+        }
+    }
+
+    if let Some((host, addr)) = resolved {
+        client = client.resolve(host, addr);
+    }
+
     if replay {
         client = client.proxy(match reqwest::Proxy::all(&config.replay_proxy) {
             Ok(val) => val,
@@ -152,9 +700,7 @@ pub fn create_client(config: &Config, replay: bool) -> Result<Client, Box<dyn Er
         }
     }
 
-    if !config.follow_redirects {
-        client = client.redirect(reqwest::redirect::Policy::none());
-    }
+    client = client.redirect(config.redirects.build());
 
     if config.http_version.is_some() {
         match config.http_version {
@@ -167,6 +713,38 @@ pub fn create_client(config: &Config, replay: bool) -> Result<Client, Box<dyn Er
     Ok(client.build()?)
 }
 
+/// whether the request failed because the server doesn't speak HTTP/2: invalid frames, protocol errors or the connection closed right after the preface
+/// timeouts and connection errors aren't related to the protocol
+pub fn is_protocol_error(err: &(dyn Error + 'static)) -> bool {
+    // parts of hyper and h2 error messages
+    const PROTOCOL_ERRORS: [&str; 4] = ["http2 error", "PROTOCOL_ERROR", "HTTP_1_1_REQUIRED", "connection closed before message completed"];
+
+    let mut source = Some(err);
+
+    while let Some(err) = source {
+        let network_err = match err.downcast_ref::<error::Error>() {
+            Some(error::Error::Network(err)) => Some(err),
+            _ => err.downcast_ref::<reqwest::Error>(),
+        };
+
+        if network_err.is_some_and(|x| x.is_timeout() || x.is_connect())
+            || err.downcast_ref::<std::io::Error>().is_some_and(|x| x.kind() == std::io::ErrorKind::TimedOut)
+        {
+            return false;
+        }
+
+        let message = err.to_string();
+
+        if PROTOCOL_ERRORS.iter().any(|x| message.contains(x)) {
+            return true;
+        }
+
+        source = err.source();
+    }
+
+    false
+}
+
 /// check whether the content is binary
 /// so we can ignore the body in comparing
 /// a few reasons for it:
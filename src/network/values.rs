@@ -0,0 +1,245 @@
+use std::{error::Error, fmt::Debug, sync::Arc};
+
+use lazy_static::lazy_static;
+use rand::Rng;
+use regex::Regex;
+
+use crate::{utils::random_line, VALUE_LENGTH};
+
+/// generates values for parameters without a user supplied value
+pub trait ValueGenerator: Debug + Send + Sync {
+    fn generate(&self) -> String;
+
+    /// whether generated values are unlikely to repeat
+    /// non unique values can't be used to search for reflections
+    fn is_unique(&self) -> bool {
+        true
+    }
+}
+
+/// random word of RANDOM_CHARSET chars
+#[derive(Debug)]
+pub struct RandomString(pub usize);
+
+impl ValueGenerator for RandomString {
+    fn generate(&self) -> String {
+        random_line(self.0)
+    }
+}
+
+/// random number of the given amount of digits without leading zeros
+#[derive(Debug)]
+pub struct Numeric(pub usize);
+
+impl ValueGenerator for Numeric {
+    fn generate(&self) -> String {
+        let mut rng = rand::thread_rng();
+
+        (0..self.0)
+            .map(|i| {
+                let digit = if i == 0 { rng.gen_range(1, 10) } else { rng.gen_range(0, 10) };
+                (b'0' + digit as u8) as char
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug)]
+pub struct Boolean;
+
+impl ValueGenerator for Boolean {
+    fn generate(&self) -> String {
+        if rand::thread_rng().gen() { "true" } else { "false" }.to_string()
+    }
+
+    fn is_unique(&self) -> bool {
+        false
+    }
+}
+
+/// random value from the list
+#[derive(Debug)]
+pub struct OneOf(pub Vec<String>);
+
+impl ValueGenerator for OneOf {
+    fn generate(&self) -> String {
+        self.0[rand::thread_rng().gen_range(0, self.0.len())].clone()
+    }
+
+    fn is_unique(&self) -> bool {
+        false
+    }
+}
+
+/// random number within the range (inclusive)
+#[derive(Debug)]
+pub struct Integer(pub u32, pub u32);
+
+impl ValueGenerator for Integer {
+    fn generate(&self) -> String {
+        rand::thread_rng().gen_range(self.0, self.1 + 1).to_string()
+    }
+
+    fn is_unique(&self) -> bool {
+        false
+    }
+}
+
+/// random email like x8kq2mzl@example.com
+#[derive(Debug)]
+pub struct Email;
+
+impl ValueGenerator for Email {
+    fn generate(&self) -> String {
+        format!("{}@example.com", random_line(8))
+    }
+}
+
+/// random url like https://example.com/x8kq2m
+#[derive(Debug)]
+pub struct Url;
+
+impl ValueGenerator for Url {
+    fn generate(&self) -> String {
+        format!("https://example.com/{}", random_line(VALUE_LENGTH))
+    }
+}
+
+/// random valid date between 1970 and 2030 like 1994-07-21
+#[derive(Debug)]
+pub struct Date;
+
+impl ValueGenerator for Date {
+    fn generate(&self) -> String {
+        let mut rng = rand::thread_rng();
+        format!("{}-{:02}-{:02}", rng.gen_range(1970, 2031), rng.gen_range(1, 13), rng.gen_range(1, 29))
+    }
+
+    // dates are often present within pages
+    fn is_unique(&self) -> bool {
+        false
+    }
+}
+
+lazy_static! {
+    /// generators for parameters whose names hint at the expected value (--value-type realistic)
+    /// the first matching hint is used
+    static ref HINTS: Vec<(Regex, Arc<dyn ValueGenerator>)> = {
+        let hints: Vec<(&str, Arc<dyn ValueGenerator>)> = vec![
+            (r"(?i)e-?mail", Arc::new(Email)),
+            (r"(?i)uuid|guid", Arc::new(FormatPreserving("123e4567-e89b-42d3-a456-426614174000".to_string()))),
+            (r"(?i)url|uri$|redirect|callback|return_?to|next$|href", Arc::new(Url)),
+            (r"(?i)phone|mobile|(^|_)tel$", Arc::new(FormatPreserving("+15550123456".to_string()))),
+            (r"(?i)date|birth|(^|_)(day|since|until|from|to)$|_at$", Arc::new(Date)),
+            // camelCase ids are matched case sensitively, otherwise paid or android look like ids
+            (r"(?i)(^|[_-])id$|(?-i:[a-z]I[dD])$|^id_|(^|_)(num|number|code)$", Arc::new(Numeric(VALUE_LENGTH))),
+            (
+                r"(?i)^(page|per_?page|limit|offset|count|size|page_?size|age|qty|quantity|amount|price|year|version|v)$",
+                Arc::new(Integer(1, 100)),
+            ),
+        ];
+
+        hints.into_iter().map(|(regex, generator)| (Regex::new(regex).unwrap(), generator)).collect()
+    };
+}
+
+/// random value in the format of the sample: 2024-01-31 -> 7310-58-04, 123e4567-e89b-... -> 9f0a1c2d-...
+/// digits are replaced with digits, hex letters with hex letters, other letters with letters of the same case
+#[derive(Debug)]
+pub struct FormatPreserving(pub String);
+
+impl ValueGenerator for FormatPreserving {
+    fn generate(&self) -> String {
+        let mut rng = rand::thread_rng();
+
+        self.0
+            .chars()
+            .map(|c| {
+                let (base, len) = match c {
+                    '0'..='9' => (b'0', 10),
+                    'a'..='f' => (b'a', 6),
+                    'A'..='F' => (b'A', 6),
+                    'g'..='z' => (b'a', 26),
+                    'G'..='Z' => (b'A', 26),
+                    _ => return c,
+                };
+
+                (base + rng.gen_range(0, len)) as char
+            })
+            .collect()
+    }
+}
+
+/// parses the generator description: random, numeric, bool, list:a,b,c, format:<sample>
+/// realistic is handled by ValueGenerators because it depends on the parameter's name
+pub fn parse_value_generator(value: &str) -> Result<Arc<dyn ValueGenerator>, Box<dyn Error>> {
+    let generator: Arc<dyn ValueGenerator> = if value == "random" {
+        Arc::new(RandomString(VALUE_LENGTH))
+    } else if value == "numeric" {
+        Arc::new(Numeric(VALUE_LENGTH))
+    } else if value == "bool" {
+        Arc::new(Boolean)
+    } else if let Some(list) = value.strip_prefix("list:").filter(|x| !x.is_empty()) {
+        Arc::new(OneOf(list.split(',').map(|x| x.to_string()).collect()))
+    } else if let Some(sample) = value.strip_prefix("format:").filter(|x| !x.is_empty()) {
+        Arc::new(FormatPreserving(sample.to_string()))
+    } else {
+        Err(format!("Incorrect value type specified: {}", value))?
+    };
+
+    Ok(generator)
+}
+
+/// the default generator and generators for parameters that match the patterns
+#[derive(Debug, Clone)]
+pub struct ValueGenerators {
+    pub default: Arc<dyn ValueGenerator>,
+    pub patterns: Vec<(Regex, Arc<dyn ValueGenerator>)>,
+
+    /// pick values by hints within names (emails for *email*, dates for *_at, ..) before falling back to the default
+    pub realistic: bool,
+}
+
+impl Default for ValueGenerators {
+    fn default() -> Self {
+        Self {
+            default: Arc::new(RandomString(VALUE_LENGTH)),
+            patterns: Vec::new(),
+            realistic: false,
+        }
+    }
+}
+
+impl ValueGenerators {
+    /// parses the default generator and <regex>=<generator> pairs
+    pub fn new(default: &str, patterns: &[&str]) -> Result<Self, Box<dyn Error>> {
+        let realistic = default == "realistic";
+
+        let mut generators = Self {
+            default: if realistic { Arc::new(RandomString(VALUE_LENGTH)) } else { parse_value_generator(default)? },
+            patterns: Vec::new(),
+            realistic,
+        };
+
+        for pattern in patterns {
+            let (regex, generator) = match pattern.split_once('=') {
+                Some(val) => val,
+                None => Err(format!("Incorrect --param-value-type specified: {}. Use <regex>=<type>", pattern))?,
+            };
+
+            generators.patterns.push((Regex::new(regex)?, parse_value_generator(generator)?));
+        }
+
+        Ok(generators)
+    }
+
+    /// returns the generator for the parameter
+    pub fn get(&self, parameter: &str) -> &dyn ValueGenerator {
+        let hints = if self.realistic { HINTS.as_slice() } else { &[] };
+
+        match self.patterns.iter().chain(hints.iter()).find(|(regex, _)| regex.is_match(parameter)) {
+            Some((_, generator)) => generator.as_ref(),
+            None => self.default.as_ref(),
+        }
+    }
+}
@@ -0,0 +1,102 @@
+use std::{convert::TryInto, time::Duration};
+
+use tokio::io::AsyncReadExt;
+
+use crate::WEBSOCKET_READ_TIMEOUT;
+
+/// the first data or close frame sent by the server after the handshake (--websocket)
+#[derive(Debug, PartialEq, Eq)]
+pub enum Frame {
+    Message(Vec<u8>),
+
+    /// the close code and the reason. The code is optional within close frames
+    Close(Option<u16>, String),
+}
+
+impl Frame {
+    /// the frame as a part of the body, so it's compared like the rest of the page
+    pub fn describe(&self) -> String {
+        match self {
+            Frame::Message(payload) => format!("websocket message: {}", String::from_utf8_lossy(payload)),
+            Frame::Close(Some(code), reason) => format!("websocket close: {} {}", code, reason),
+            Frame::Close(None, _) => "websocket close".to_string(),
+        }
+    }
+}
+
+/// skips control frames like pings and returns the first message or the close frame
+/// None in case the bytes don't contain the whole frame yet
+pub fn parse_frame(mut bytes: &[u8]) -> Option<Frame> {
+    loop {
+        let (opcode, payload, rest) = split_frame(bytes)?;
+
+        match opcode {
+            // continuation frames of fragmented messages go after the first one
+            0x0..=0x2 => return Some(Frame::Message(payload)),
+            0x8 => {
+                let code = payload.get(..2).map(|x| u16::from_be_bytes([x[0], x[1]]));
+                let reason = payload.get(2..).map(String::from_utf8_lossy).unwrap_or_default().to_string();
+
+                return Some(Frame::Close(code, reason));
+            }
+            _ => bytes = rest,
+        }
+    }
+}
+
+/// (opcode, unmasked payload, the rest of the bytes)
+fn split_frame(bytes: &[u8]) -> Option<(u8, Vec<u8>, &[u8])> {
+    let opcode = bytes.first()? & 0x0f;
+    let masked = bytes.get(1)? & 0x80 != 0;
+
+    let (length, mut offset) = match bytes[1] & 0x7f {
+        126 => (u16::from_be_bytes(bytes.get(2..4)?.try_into().ok()?) as usize, 4),
+        127 => (u64::from_be_bytes(bytes.get(2..10)?.try_into().ok()?) as usize, 10),
+        length => (length as usize, 2),
+    };
+
+    let mask = if masked {
+        offset += 4;
+        Some(bytes.get(offset - 4..offset)?)
+    } else {
+        None
+    };
+
+    let payload = bytes.get(offset..offset.checked_add(length)?)?;
+    let payload = match mask {
+        Some(mask) => payload.iter().enumerate().map(|(i, x)| x ^ mask[i % 4]).collect(),
+        None => payload.to_vec(),
+    };
+
+    Some((opcode, payload, &bytes[offset + length..]))
+}
+
+/// reads the first frame after the handshake, so parameters that change close codes or the first message are found as well
+/// the description is empty in case the server doesn't send anything within WEBSOCKET_READ_TIMEOUT or the upgrade fails
+pub async fn first_frame(response: reqwest::Response) -> Vec<u8> {
+    let mut connection = match response.upgrade().await {
+        Ok(val) => val,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut bytes = Vec::new();
+    let mut buf = [0; 4096];
+
+    let read = async {
+        loop {
+            if let Some(frame) = parse_frame(&bytes) {
+                return frame.describe().into_bytes();
+            }
+
+            match connection.read(&mut buf).await {
+                Ok(0) | Err(_) => return Vec::new(),
+                Ok(n) => bytes.extend_from_slice(&buf[..n]),
+            }
+        }
+    };
+
+    // the connection is closed once it's dropped
+    tokio::time::timeout(Duration::from_millis(WEBSOCKET_READ_TIMEOUT), read)
+        .await
+        .unwrap_or_default()
+}
@@ -0,0 +1,87 @@
+use std::error::Error;
+
+use crate::{
+    diff::diff,
+    network::{
+        request::{Request, RequestDefaults},
+        response::Baseline,
+    },
+    utils::random_line,
+    VALUE_LENGTH,
+};
+
+use super::utils::FoundParameter;
+
+/// sends every found parameter with the same value and collapses parameters with identical responses:
+/// [id, user_id, userId] -> [id (aliases: user_id, userId)]
+/// parameters with non random values and parameters that don't change the body with the shared value are left as is
+/// responses are compared by the body without the learned dynamic parts, so headers like Date don't matter
+pub(super) async fn collapse_aliases(
+    baseline: &Baseline,
+    request_defaults: &RequestDefaults,
+    found_params: Vec<FoundParameter>,
+    diffs: &[String],
+) -> Result<Vec<FoundParameter>, Box<dyn Error>> {
+    let value = random_line(VALUE_LENGTH);
+
+    // (index of the parameter within found_params, its response)
+    let mut responses: Vec<(usize, Baseline)> = Vec::new();
+
+    for (i, param) in found_params.iter().enumerate() {
+        if param.value.is_some() {
+            continue;
+        }
+
+        let response = Request::new(request_defaults, vec![format!("{}={}", param.name, value)])
+            .send()
+            .await?;
+
+        // a changed code or headers alone (e.g. the same error page for any unexpected parameter) don't tell that parameters are related
+        let body_changed = diff(&baseline.print_with_body(response.body()), &baseline.print())?
+            .iter()
+            .any(|x| !diffs.contains(x));
+
+        if body_changed {
+            responses.push((i, response.baseline()));
+        }
+    }
+
+    // index of the parameter -> index of the parameter it's an alias of
+    let mut alias_of: Vec<Option<usize>> = vec![None; found_params.len()];
+
+    // headers (e.g. Date) are left out, and the parts that change by themselves are skipped
+    for (pos, (i, response)) in responses.iter().enumerate() {
+        if alias_of[*i].is_some() {
+            continue;
+        }
+
+        for (j, other) in responses.iter().skip(pos + 1) {
+            if alias_of[*j].is_none()
+                && response.code == other.code
+                && diff(&baseline.print_with_body(response.body()), &baseline.print_with_body(other.body()))?
+                    .iter()
+                    .all(|x| diffs.contains(x))
+            {
+                alias_of[*j] = Some(*i);
+            }
+        }
+    }
+
+    let mut collapsed: Vec<FoundParameter> = Vec::with_capacity(found_params.len());
+    let mut original_indexes: Vec<usize> = Vec::with_capacity(found_params.len());
+
+    for (i, param) in found_params.into_iter().enumerate() {
+        match alias_of[i] {
+            Some(original) => {
+                let pos = original_indexes.iter().position(|x| *x == original).unwrap();
+                collapsed[pos].aliases.push(param.name);
+            }
+            None => {
+                original_indexes.push(i);
+                collapsed.push(param);
+            }
+        }
+    }
+
+    Ok(collapsed)
+}
@@ -0,0 +1,130 @@
+use std::{collections::HashMap, error::Error, sync::Arc};
+
+use parking_lot::Mutex;
+use reqwest::Client;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use url::Url;
+
+use crate::{
+    config::structs::{Config, HostOverrides},
+    network::utils::create_client,
+};
+
+use super::output::RunnerOutput;
+
+/// the outcome of checking a single url with every method
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UrlSummary {
+    pub url: String,
+    pub found: usize,
+    pub errors: usize,
+
+    /// whether any method was stopped early (--stop-after)
+    pub stopped: bool,
+
+    /// frameworks and languages detected by any method
+    pub backend: Vec<String>,
+}
+
+/// schedules checks of multiple urls: shares one client between them and limits concurrent checks per host
+pub struct Scheduler {
+    /// None in case every target needs its own client (--as, --doh)
+    client: Option<Client>,
+
+    /// the max amount of concurrent url checks per host. 0 - no limit
+    host_workers: usize,
+
+    /// per host settings from the targets file
+    overrides: HashMap<String, HostOverrides>,
+
+    hosts: Mutex<HashMap<String, Arc<Semaphore>>>,
+
+    /// results per url in the order urls were finished
+    summaries: Mutex<Vec<UrlSummary>>,
+}
+
+impl Scheduler {
+    pub fn new(config: &Config) -> Result<Self, Box<dyn Error>> {
+        // identities shouldn't share cookies and pinned addresses need a client per host
+        let client = if config.urls.len() > 1 && config.identities.is_empty() && config.doh.is_empty() {
+            Some(create_client(config, false, None)?)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            client,
+            host_workers: config.host_workers,
+            overrides: config.host_overrides.clone(),
+            hosts: Mutex::new(HashMap::new()),
+            summaries: Mutex::new(Vec::new()),
+        })
+    }
+
+    /// the client to send requests to every target with
+    pub fn client(&self) -> Option<&Client> {
+        self.client.as_ref()
+    }
+
+    /// waits until the url's host has a free worker
+    /// the permit needs to be held until the url is checked
+    pub async fn acquire(&self, url: &str) -> Option<OwnedSemaphorePermit> {
+        // the targets file may limit the host on its own
+        let workers = self.overrides.get(&host(url)).and_then(|x| x.workers).unwrap_or(self.host_workers);
+
+        if workers == 0 {
+            return None;
+        }
+
+        let semaphore = Arc::clone(
+            self.hosts
+                .lock()
+                .entry(host(url))
+                .or_insert_with(|| Arc::new(Semaphore::new(workers))),
+        );
+
+        semaphore.acquire_owned().await.ok()
+    }
+
+    /// stores the outcome of checking the url with a single method
+    pub fn record(&self, url: &str, output: Option<&RunnerOutput>) {
+        let mut summaries = self.summaries.lock();
+
+        let index = match summaries.iter().position(|x| x.url == url) {
+            Some(index) => index,
+            None => {
+                summaries.push(UrlSummary {
+                    url: url.to_string(),
+                    ..Default::default()
+                });
+                summaries.len() - 1
+            }
+        };
+
+        match output {
+            Some(output) => {
+                summaries[index].found += output.found_params.len();
+                summaries[index].stopped |= output.stopped_after.is_some();
+
+                for name in output.backend.iter() {
+                    if !summaries[index].backend.contains(name) {
+                        summaries[index].backend.push(name.clone());
+                    }
+                }
+            }
+            None => summaries[index].errors += 1,
+        }
+    }
+
+    pub fn summaries(&self) -> Vec<UrlSummary> {
+        self.summaries.lock().clone()
+    }
+}
+
+/// urls without a host are limited together with the same urls
+fn host(url: &str) -> String {
+    Url::parse(url)
+        .ok()
+        .and_then(|x| x.host_str().map(|x| x.to_string()))
+        .unwrap_or_else(|| url.to_string())
+}
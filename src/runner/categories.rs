@@ -0,0 +1,127 @@
+use std::error::Error;
+
+use colored::{Color, Colorize};
+use regex::Regex;
+use serde::Deserialize;
+
+use super::utils::ReasonKind;
+
+/// a named category of findings from the --categories file
+/// e.g. {"name": "privilege", "color": "bright red", "kinds": ["Code", "Text"], "param": "^(admin|role|is_?staff)$"}
+#[derive(Debug, Clone)]
+pub struct Category {
+    pub name: String,
+
+    /// the reason kind color is used in case it's not specified
+    pub color: Option<Color>,
+
+    /// the conditions. Every specified one has to match
+    pub kinds: Vec<ReasonKind>,
+    pub status: Vec<u16>,
+    pub param: Option<Regex>,
+
+    /// matches the label of the page change, e.g. "error message appeared"
+    pub label: Option<Regex>,
+}
+
+#[derive(Deserialize)]
+struct CategoryRule {
+    name: String,
+    #[serde(default)]
+    color: String,
+    #[serde(default)]
+    kinds: Vec<ReasonKind>,
+    #[serde(default)]
+    status: Vec<u16>,
+    #[serde(default)]
+    param: String,
+    #[serde(default)]
+    label: String,
+}
+
+/// reads categories from the json array of rules. The first matching rule wins
+pub fn read_categories(filename: &str) -> Result<Vec<Category>, Box<dyn Error>> {
+    let text = match std::fs::read_to_string(filename) {
+        Ok(val) => val,
+        Err(err) => Err(format!("Unable to read categories from {}: {}", filename, err))?,
+    };
+
+    parse_categories(&text)
+}
+
+pub fn parse_categories(text: &str) -> Result<Vec<Category>, Box<dyn Error>> {
+    let rules: Vec<CategoryRule> = match serde_json::from_str(text) {
+        Ok(val) => val,
+        Err(err) => Err(format!("Unable to parse categories: {}", err))?,
+    };
+
+    let regex = |pattern: &str| -> Result<Option<Regex>, Box<dyn Error>> {
+        if pattern.is_empty() {
+            return Ok(None);
+        }
+
+        match Regex::new(pattern) {
+            Ok(val) => Ok(Some(val)),
+            Err(err) => Err(format!("Incorrect category pattern {}: {}", pattern, err))?,
+        }
+    };
+
+    let mut categories = Vec::with_capacity(rules.len());
+
+    for rule in rules {
+        if rule.name.is_empty() {
+            Err("Every category needs a name")?;
+        }
+
+        let color = if rule.color.is_empty() {
+            None
+        } else {
+            // "bright_red" is accepted as well as "bright red"
+            match rule.color.replace('_', " ").parse::<Color>() {
+                Ok(val) => Some(val),
+                Err(_) => Err(format!("Unknown color {} of the {} category", rule.color, rule.name))?,
+            }
+        };
+
+        categories.push(Category {
+            color,
+            kinds: rule.kinds,
+            status: rule.status,
+            param: regex(&rule.param)?,
+            label: regex(&rule.label)?,
+            name: rule.name,
+        });
+    }
+
+    Ok(categories)
+}
+
+impl Category {
+    pub fn matches(&self, kind: &ReasonKind, param: &str, status: u16, label: Option<&str>) -> bool {
+        (self.kinds.is_empty() || self.kinds.contains(kind))
+            && (self.status.is_empty() || self.status.contains(&status))
+            && self.param.as_ref().is_none_or(|x| x.is_match(param))
+            && self.label.as_ref().is_none_or(|x| label.is_some_and(|label| x.is_match(label)))
+    }
+
+    /// the category name within brackets in its color: " [privilege]"
+    pub fn tag(&self) -> String {
+        let tag = format!("[{}]", self.name);
+
+        match self.color {
+            Some(color) => format!(" {}", tag.color(color)),
+            None => format!(" {}", tag),
+        }
+    }
+}
+
+/// the first category the finding matches
+pub fn categorize<'a>(
+    categories: &'a [Category],
+    kind: &ReasonKind,
+    param: &str,
+    status: u16,
+    label: Option<&str>,
+) -> Option<&'a Category> {
+    categories.iter().find(|x| x.matches(kind, param, status, label))
+}
@@ -0,0 +1,115 @@
+use std::{cmp::Reverse, collections::HashSet, error::Error};
+
+use serde::Serialize;
+
+use crate::network::{
+    request::{Request, RequestDefaults},
+    response::{Baseline, Response},
+};
+
+use super::utils::FoundParameter;
+
+/// a pair of found parameters that changes the page in a way neither of them does alone (--combinations)
+/// e.g. `debug` shows the stack trace only together with `env=dev`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Combination {
+    pub params: [String; 2],
+    pub status: u16,
+    pub size: usize,
+
+    /// lines that appear only when both parameters are sent
+    pub new_lines: usize,
+}
+
+/// sends every pair of the top found parameters (by impact) within one request
+/// the pair is reported in case the response has a code or lines that neither the baseline nor the parameters alone have
+/// the new lines are confirmed with the second request, so dynamic lines like tokens and timestamps are ignored
+pub(super) async fn check_combinations(
+    baseline: &Baseline,
+    request_defaults: &RequestDefaults,
+    found_params: &[FoundParameter],
+    diffs: &[String],
+    top: usize,
+) -> Result<Vec<Combination>, Box<dyn Error>> {
+    let mut found_params: Vec<&FoundParameter> = found_params.iter().collect();
+    found_params.sort_by_key(|x| Reverse(x.calculate_impact(baseline.code, baseline.text.len())));
+    found_params.truncate(top);
+
+    // the same values are sent alone and within pairs, so reflections don't differ
+    let params: Vec<String> = found_params
+        .iter()
+        .map(|x| {
+            let (name, value) = x.get();
+            format!("{}={}", name, value)
+        })
+        .collect();
+
+    let mut alone = Vec::with_capacity(params.len());
+
+    for param in params.iter() {
+        alone.push(Request::new(request_defaults, vec![param.clone()]).send().await?.baseline());
+    }
+
+    let mut combinations = Vec::new();
+
+    for i in 0..params.len() {
+        for j in i + 1..params.len() {
+            let send = || Request::new(request_defaults, vec![params[i].clone(), params[j].clone()]).send();
+
+            let response = send().await?;
+
+            // additive changes are the same as within one of the responses with a single parameter
+            if !differs(&response, &alone[i], diffs)? || !differs(&response, &alone[j], diffs)? {
+                continue;
+            }
+
+            let code_changed = response.code != baseline.code && response.code != alone[i].code && response.code != alone[j].code;
+            let mut new_lines = unseen_lines(&response, [baseline, &alone[i], &alone[j]]);
+
+            if !code_changed && new_lines.is_empty() {
+                continue;
+            }
+
+            let confirmation = send().await?;
+
+            if code_changed && confirmation.code != response.code {
+                continue;
+            }
+
+            let confirmed_lines = unseen_lines(&confirmation, [baseline, &alone[i], &alone[j]]);
+            new_lines.retain(|x| confirmed_lines.contains(x));
+
+            if !code_changed && new_lines.is_empty() {
+                continue;
+            }
+
+            combinations.push(Combination {
+                params: [found_params[i].name.clone(), found_params[j].name.clone()],
+                status: response.code,
+                size: response.text.len(),
+                new_lines: new_lines.len(),
+            });
+        }
+    }
+
+    Ok(combinations)
+}
+
+/// whether the response differs from the one with a single parameter, ignoring known unstable lines
+fn differs(response: &Response, alone: &Baseline, diffs: &[String]) -> Result<bool, Box<dyn Error>> {
+    let (is_code_diff, new_diffs) = response.compare(alone, diffs)?;
+    Ok(is_code_diff || !new_diffs.is_empty())
+}
+
+/// lines of the response that aren't present within any of the other pages
+fn unseen_lines(response: &Response, others: [&Baseline; 3]) -> HashSet<String> {
+    let known: HashSet<&str> = others.iter().flat_map(|x| x.text.lines()).map(|x| x.trim()).collect();
+
+    response
+        .text
+        .lines()
+        .map(|x| x.trim())
+        .filter(|x| !x.is_empty() && !known.contains(x))
+        .map(|x| x.to_string())
+        .collect()
+}
@@ -0,0 +1,132 @@
+use std::{error::Error, net::SocketAddr};
+
+use indicatif::ProgressBar;
+
+use crate::{
+    config::structs::Config,
+    network::{
+        request::{Request, RequestDefaults},
+        utils::create_client,
+    },
+    utils,
+};
+
+/// compares the page served over ipv4 and ipv6 in case the host resolves to both of them
+/// the client may alternate between the families, so different backends behind them would look like an unstable page
+/// pins the client to the ipv4 address in case the pages differ, or to the only family that responds
+/// the choice is made once per host, and failures of the probes never stop the scan
+pub async fn pin_address_family(
+    config: &Config,
+    request_defaults: &mut RequestDefaults,
+    progress_bar: &ProgressBar,
+    id: usize,
+) -> Result<(), Box<dyn Error>> {
+    let key = format!("{}:{}", request_defaults.host, request_defaults.port);
+
+    let cached = request_defaults.state.pinned.lock().get(&key).copied();
+    let addr = match cached {
+        Some(addr) => addr,
+        None => {
+            let addr = choose_address(config, request_defaults, progress_bar, id).await;
+            request_defaults.state.pinned.lock().insert(key, addr);
+            addr
+        }
+    };
+
+    if let Some(addr) = addr {
+        request_defaults.client = pinned(config, request_defaults, addr)?.client;
+    }
+
+    Ok(())
+}
+
+async fn choose_address(
+    config: &Config,
+    request_defaults: &RequestDefaults,
+    progress_bar: &ProgressBar,
+    id: usize,
+) -> Option<SocketAddr> {
+    // unresolvable hosts are reported by the preflight check
+    let addrs: Vec<SocketAddr> = tokio::net::lookup_host(format!("{}:{}", request_defaults.host, request_defaults.port))
+        .await
+        .ok()?
+        .collect();
+
+    let (ipv4, ipv6) = match (addrs.iter().find(|x| x.is_ipv4()), addrs.iter().find(|x| x.is_ipv6())) {
+        (Some(ipv4), Some(ipv6)) => (*ipv4, *ipv6),
+        _ => return None,
+    };
+
+    let ipv4_defaults = pinned(config, request_defaults, ipv4).ok()?;
+    let ipv6_defaults = pinned(config, request_defaults, ipv6).ok()?;
+
+    // the host may be unreachable over one of the families from here
+    let ipv4_response = Request::new(&ipv4_defaults, Vec::new()).send().await;
+    let ipv6_response = Request::new(&ipv6_defaults, Vec::new()).send().await;
+
+    let (ipv4_response, ipv6_response) = match (ipv4_response, ipv6_response) {
+        (Ok(ipv4_response), Ok(ipv6_response)) => (ipv4_response, ipv6_response),
+        // both failing is reported by the preflight check
+        (Err(_), Err(_)) => return None,
+        (ipv4_response, _) => {
+            let (working, failing) = if ipv4_response.is_ok() { (ipv4, ipv6) } else { (ipv6, ipv4) };
+
+            utils::info(
+                config,
+                id,
+                progress_bar,
+                "~",
+                format!(
+                    "{} doesn't respond over {}. Pinned to {} for the rest of the run",
+                    request_defaults.host,
+                    failing.ip(),
+                    working.ip()
+                ),
+            );
+
+            return Some(working);
+        }
+    };
+
+    let baseline = ipv4_response.baseline();
+
+    // dynamic lines differ between any two responses, so they are excluded from the comparison
+    let (_, diffs) = Request::new(&ipv4_defaults, Vec::new()).send().await.ok()?.compare(&baseline, &[]).ok()?;
+
+    let mut differs = true;
+
+    for response in [Ok(ipv6_response), Request::new(&ipv6_defaults, Vec::new()).send().await] {
+        let (is_code_diff, new_diffs) = response.ok()?.compare(&baseline, &diffs).ok()?;
+
+        // both responses need to differ, otherwise it's just a random change
+        differs &= is_code_diff || !new_diffs.is_empty();
+    }
+
+    if !differs {
+        return None;
+    }
+
+    utils::info(
+        config,
+        id,
+        progress_bar,
+        "~",
+        format!(
+            "{} serves different pages over IPv4 ({}) and IPv6 ({}). Pinned to {} for the rest of the run",
+            request_defaults.host,
+            ipv4.ip(),
+            ipv6.ip(),
+            ipv4.ip()
+        ),
+    );
+
+    Some(ipv4)
+}
+
+/// request defaults with the client that connects to the address only
+fn pinned(config: &Config, request_defaults: &RequestDefaults, addr: SocketAddr) -> Result<RequestDefaults, Box<dyn Error>> {
+    let mut request_defaults = request_defaults.clone();
+    request_defaults.client = create_client(config, false, Some((&request_defaults.host, addr)))?;
+
+    Ok(request_defaults)
+}
@@ -0,0 +1,78 @@
+use std::error::Error;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    network::{
+        request::{Request, RequestDefaults},
+        response::Baseline,
+    },
+    utils::random_line,
+    VALUE_LENGTH,
+};
+
+use super::utils::FoundParameter;
+
+/// which value the target uses when the same key is sent twice (--duplicate-keys)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicateHandling {
+    /// k=used&k=ignored
+    FirstWins,
+
+    /// k=ignored&k=used
+    LastWins,
+
+    /// both values are used (joined, turned into an array, ..)
+    Both,
+}
+
+/// sends every found parameter twice with distinct values within the same request
+/// the used value is detected by reflections, otherwise by whether the page changes only with the found value at a certain position
+pub(super) async fn check_duplicate_keys(
+    baseline: &Baseline,
+    request_defaults: &RequestDefaults,
+    found_params: &mut [FoundParameter],
+    diffs: &[String],
+) -> Result<(), Box<dyn Error>> {
+    for param in found_params.iter_mut() {
+        let (name, value) = param.get();
+        let other = random_line(VALUE_LENGTH);
+
+        let send = |first: &str, second: &str| {
+            Request::new(
+                request_defaults,
+                vec![format!("{}={}", name, first), format!("{}={}", name, second)],
+            )
+            .send()
+        };
+
+        let response = send(&value, &other).await?;
+
+        // values that are reflected by default (like true or 1) can't tell anything
+        let reflected = |x: &str| response.count(x) > baseline.count(x);
+
+        param.duplicates = match (reflected(&value), reflected(&other)) {
+            (true, true) => Some(DuplicateHandling::Both),
+            (true, false) => Some(DuplicateHandling::FirstWins),
+            (false, true) => Some(DuplicateHandling::LastWins),
+            // any value changes the page, so the order can't be told by the page
+            (false, false) if param.value.is_none() => None,
+            (false, false) => {
+                let changed = |(is_code_diff, new_diffs): (bool, Vec<String>)| is_code_diff || !new_diffs.is_empty();
+
+                let first = changed(response.compare(baseline, diffs)?);
+                let last = changed(send(&other, &value).await?.compare(baseline, diffs)?);
+
+                match (first, last) {
+                    (true, true) => Some(DuplicateHandling::Both),
+                    (true, false) => Some(DuplicateHandling::FirstWins),
+                    (false, true) => Some(DuplicateHandling::LastWins),
+                    (false, false) => None,
+                }
+            }
+        };
+    }
+
+    Ok(())
+}
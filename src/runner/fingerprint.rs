@@ -0,0 +1,134 @@
+use crate::network::response::Response;
+
+/// a backend framework or language detected by headers, cookie names and error page signatures
+#[derive(Debug, PartialEq, Eq)]
+pub struct Technology {
+    pub name: &'static str,
+
+    /// (header name, lowercased part of the value). An empty part matches any value
+    headers: &'static [(&'static str, &'static str)],
+
+    /// names of cookies set by the framework
+    cookies: &'static [&'static str],
+
+    /// parts of default error pages and forms
+    body: &'static [&'static str],
+
+    /// parameters handled by the framework itself. Checked before the wordlist with --framework-words
+    pub words: &'static [&'static str],
+}
+
+const TECHNOLOGIES: [Technology; 7] = [
+    Technology {
+        name: "Rails",
+        headers: &[("x-runtime", ""), ("x-powered-by", "phusion passenger")],
+        // session cookies are named after the app
+        cookies: &[],
+        body: &[
+            "We're sorry, but something went wrong",
+            "ActionController::",
+            "name=\"authenticity_token\"",
+        ],
+        words: &["authenticity_token", "utf8", "_method", "commit", "format", "locale", "page", "per_page", "include", "fields"],
+    },
+    Technology {
+        name: "Django",
+        headers: &[],
+        cookies: &["csrftoken", "sessionid", "django_language"],
+        body: &[
+            "csrfmiddlewaretoken",
+            "You're seeing this error because you have <code>DEBUG = True</code>",
+        ],
+        words: &["csrfmiddlewaretoken", "next", "format", "page", "page_size", "ordering", "search", "fields", "expand", "language"],
+    },
+    Technology {
+        name: "Spring",
+        headers: &[("x-application-context", "")],
+        cookies: &[],
+        body: &["Whitelabel Error Page", "org.springframework."],
+        words: &["_csrf", "lang", "locale", "page", "size", "sort", "projection", "format", "trace", "debug"],
+    },
+    Technology {
+        name: "Laravel",
+        headers: &[],
+        cookies: &["laravel_session"],
+        body: &["Illuminate\\", "Whoops! There was an error."],
+        words: &["_token", "_method", "page", "per_page", "sort", "include", "filter", "with", "XDEBUG_SESSION_START", "debug"],
+    },
+    Technology {
+        name: "PHP",
+        headers: &[("x-powered-by", "php")],
+        cookies: &["PHPSESSID"],
+        body: &["<b>Fatal error</b>:", "<b>Warning</b>:", "<b>Notice</b>:"],
+        words: &["XDEBUG_SESSION_START", "XDEBUG_PROFILE", "XDEBUG_TRACE", "debug", "action", "page", "file", "include", "lang", "PHPSESSID"],
+    },
+    Technology {
+        name: "Express",
+        headers: &[("x-powered-by", "express")],
+        cookies: &["connect.sid"],
+        body: &["<pre>Cannot GET ", "<pre>Cannot POST "],
+        words: &["callback", "_method", "debug", "format", "populate", "select", "sort", "limit", "skip", "fields"],
+    },
+    Technology {
+        name: "ASP.NET",
+        headers: &[("x-powered-by", "asp.net"), ("x-aspnet-version", ""), ("x-aspnetmvc-version", "")],
+        cookies: &["ASP.NET_SessionId", ".AspNetCore.Session", ".AspNetCore.Antiforgery"],
+        body: &["__VIEWSTATE", "Server Error in '/' Application"],
+        words: &["__VIEWSTATE", "__EVENTTARGET", "__EVENTARGUMENT", "__EVENTVALIDATION", "ReturnUrl", "handler", "culture", "ui-culture"],
+    },
+];
+
+impl Technology {
+    fn matches(&self, headers: &[(String, String)], cookies: &[&str], body: &str) -> bool {
+        self.headers.iter().any(|(name, part)| {
+            headers
+                .iter()
+                .any(|(k, v)| k.eq_ignore_ascii_case(name) && v.to_lowercase().contains(part))
+        }) || self
+            .cookies
+            .iter()
+            .any(|name| cookies.iter().any(|x| x.eq_ignore_ascii_case(name)))
+            || self.body.iter().any(|x| body.contains(x))
+    }
+}
+
+/// technologies detected by the response. Frameworks go before the languages they're written in
+pub fn detect(headers: &[(String, String)], body: &str) -> Vec<&'static Technology> {
+    let cookies: Vec<&str> = headers
+        .iter()
+        .filter(|(k, _)| k.eq_ignore_ascii_case("set-cookie"))
+        .filter_map(|(_, v)| v.split('=').next())
+        .map(|x| x.trim())
+        .collect();
+
+    TECHNOLOGIES.iter().filter(|x| x.matches(headers, &cookies, body)).collect()
+}
+
+pub fn fingerprint(response: &Response) -> Vec<&'static Technology> {
+    // html pages are split into lines after every tag for diffing
+    detect(&response.headers, &response.body().replace(">\n", ">"))
+}
+
+/// adds newly detected technologies, so frameworks still go before the languages they're written in
+pub fn merge(known: &mut Vec<&'static Technology>, detected: Vec<&'static Technology>) {
+    for technology in detected {
+        if !known.contains(&technology) {
+            known.push(technology);
+        }
+    }
+
+    known.sort_by_key(|x| TECHNOLOGIES.iter().position(|t| t.name == x.name));
+}
+
+/// words of every technology without duplicates
+pub fn framework_words(technologies: &[&Technology]) -> Vec<String> {
+    let mut words: Vec<String> = Vec::new();
+
+    for word in technologies.iter().flat_map(|x| x.words.iter()) {
+        if !words.iter().any(|x| x == word) {
+            words.push(word.to_string());
+        }
+    }
+
+    words
+}
@@ -0,0 +1,102 @@
+use std::error::Error;
+
+use serde::Serialize;
+
+use crate::network::{
+    request::{Request, RequestDefaults},
+    response::Baseline,
+};
+
+use super::utils::FoundParameter;
+
+/// malformed ways to send the same header
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum FoldingVariant {
+    /// `Header : value`
+    SpaceBeforeColon,
+
+    /// `Header:\r\n\tvalue` (obsolete line folding)
+    TabContinuation,
+}
+
+/// how the target handled the malformed header
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum FoldingOutcome {
+    /// the header worked the same way as the well-formed one
+    Accepted,
+
+    /// the response is the same as without the header, so it was dropped or not recognized
+    Ignored,
+
+    /// the request was refused with 400
+    Rejected,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HeaderFolding {
+    pub name: String,
+    pub variant: FoldingVariant,
+    pub outcome: FoldingOutcome,
+    pub status: u16,
+}
+
+impl FoldingVariant {
+    pub fn all() -> [FoldingVariant; 2] {
+        [FoldingVariant::SpaceBeforeColon, FoldingVariant::TabContinuation]
+    }
+
+    /// returns the malformed (name, value) pair
+    pub fn apply(&self, name: &str, value: &str) -> (String, String) {
+        match self {
+            FoldingVariant::SpaceBeforeColon => (format!("{} ", name), value.to_string()),
+            FoldingVariant::TabContinuation => (name.to_string(), format!("\r\n\t{}", value)),
+        }
+    }
+}
+
+/// resends found headers with whitespace variants to detect differential handling
+/// the raw transport sends header names and values as is
+pub(super) async fn check_header_folding(
+    baseline: &Baseline,
+    request_defaults: &RequestDefaults,
+    found_params: &[FoundParameter],
+    diffs: &[String],
+) -> Result<Vec<HeaderFolding>, Box<dyn Error>> {
+    let mut results = Vec::new();
+
+    for param in found_params {
+        let (name, value) = param.get();
+
+        for variant in FoldingVariant::all().iter() {
+            let (folded_name, folded_value) = variant.apply(&name, &value);
+
+            let mut request = Request::new(request_defaults, Vec::new());
+            request.prepare();
+            request.set_header(folded_name, folded_value);
+
+            let response = request.send().await?;
+
+            let outcome = if response.code == 400 && baseline.code != 400 {
+                FoldingOutcome::Rejected
+            } else {
+                let (is_code_diff, new_diffs) = response.compare(baseline, diffs)?;
+
+                // the random value of a reflected header is reflected only in case the header was recognized
+                if is_code_diff || !new_diffs.is_empty() || response.text.contains(&value) {
+                    FoldingOutcome::Accepted
+                } else {
+                    FoldingOutcome::Ignored
+                }
+            };
+
+            results.push(HeaderFolding {
+                name: name.clone(),
+                variant: *variant,
+                outcome,
+                status: response.code,
+            });
+        }
+    }
+
+    Ok(results)
+}
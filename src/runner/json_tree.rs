@@ -0,0 +1,172 @@
+use std::error::Error;
+
+use indicatif::ProgressBar;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{
+    config::structs::Config,
+    network::{
+        request::{Request, RequestDefaults},
+        utils::{json_escape, InjectionPlace},
+    },
+    utils,
+};
+
+use super::{runner::Runner, utils::FoundParameter};
+
+/// a key that accepts an object within the json body along with the keys found inside of it (--json-depth)
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct JsonKey {
+    pub name: String,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub keys: Vec<JsonKey>,
+}
+
+impl JsonKey {
+    /// adds the path like [user, profile, bio] to the tree
+    pub fn insert(tree: &mut Vec<JsonKey>, path: &[String]) {
+        let (name, rest) = match path.split_first() {
+            Some(val) => val,
+            None => return,
+        };
+
+        let index = match tree.iter().position(|x| &x.name == name) {
+            Some(index) => index,
+            None => {
+                tree.push(JsonKey {
+                    name: name.to_owned(),
+                    keys: Vec::new(),
+                });
+                tree.len() - 1
+            }
+        };
+
+        JsonKey::insert(&mut tree[index].keys, rest);
+    }
+
+    /// user{role, profile{bio}}
+    pub fn print(tree: &[JsonKey]) -> String {
+        tree.iter()
+            .map(|x| {
+                if x.keys.is_empty() {
+                    x.name.clone()
+                } else {
+                    format!("{}{{{}}}", x.name, JsonKey::print(&x.keys))
+                }
+            })
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+}
+
+/// request defaults with the injection point moved into the object under the key: {%s} -> {"user":{%s}}
+/// parameters added to every request stay at the previous level
+/// returns None in case parameters aren't sent within a plain json body
+pub fn nest_body(request_defaults: &RequestDefaults, key: &str) -> Option<RequestDefaults> {
+    if !request_defaults.is_json
+        || request_defaults.injection_place != InjectionPlace::Body
+        || !request_defaults.body.contains("%s")
+        // hybrid bodies with named markers
+        || request_defaults.body.contains("%s:")
+    {
+        return None;
+    }
+
+    let object = request_defaults
+        .template
+        .replace("%k", &json_escape(key))
+        .replace("%v", "{%s}");
+
+    let fixed = Request::new(request_defaults, Vec::new()).make_query();
+
+    let query = if fixed.is_empty() {
+        object
+    } else {
+        format!("{}{}{}", fixed, request_defaults.joiner, object)
+    };
+
+    let mut nested = request_defaults.clone();
+    nested.body = request_defaults.body.replace("%s", &query);
+    nested.parameters = Vec::new();
+
+    Some(nested)
+}
+
+/// whether the json response contains the key with an object value at any level
+pub fn has_object(text: &str, key: &str) -> bool {
+    fn find(value: &Value, key: &str) -> bool {
+        match value {
+            Value::Object(map) => map.iter().any(|(k, v)| (k == key && v.is_object()) || find(v, key)),
+            Value::Array(values) => values.iter().any(|x| find(x, key)),
+            _ => false,
+        }
+    }
+
+    // json bodies are split into lines for diffing, sometimes within keys: {"\nok": true}
+    match serde_json::from_str::<Value>(&text.replace('\n', "")) {
+        Ok(value) => find(&value, key),
+        Err(_) => false,
+    }
+}
+
+/// checks keys within objects of found parameters up to --json-depth levels deep
+/// the key is checked only in case the response to {"key":{}} contains the key with an object value
+pub(super) async fn discover_json_tree(
+    config: &Config,
+    request_defaults: &RequestDefaults,
+    params: &[String],
+    found_params: &[FoundParameter],
+    progress_bar: &ProgressBar,
+    id: usize,
+) -> Result<Vec<JsonKey>, Box<dyn Error>> {
+    let mut tree = Vec::new();
+
+    // (path to the key, request defaults with parameters sent next to the key)
+    let mut queue: Vec<(Vec<String>, RequestDefaults)> = found_params
+        .iter()
+        .map(|x| (vec![x.name.clone()], request_defaults.clone()))
+        .collect();
+
+    while !queue.is_empty() && !config.state.is_stopped() {
+        let (path, parent_defaults) = queue.remove(0);
+
+        let mut nested_defaults = match nest_body(&parent_defaults, path.last().unwrap()) {
+            Some(val) => val,
+            None => {
+                utils::info(config, id, progress_bar, "~", "--json-depth works only with plain json bodies");
+                break;
+            }
+        };
+
+        let response = Request::new(&nested_defaults, Vec::new()).send().await?;
+
+        if !has_object(response.body(), path.last().unwrap()) {
+            continue;
+        }
+
+        utils::info(config, id, progress_bar, "json", format!("checking keys within {}", path.join(".")));
+
+        JsonKey::insert(&mut tree, &path);
+
+        let mut params = params.to_vec();
+        let output = Runner::new(config, &mut nested_defaults, progress_bar, id)
+            .await?
+            .run(&mut params)
+            .await?;
+
+        for param in output.found_params {
+            let mut key_path = path.clone();
+            key_path.push(param.name);
+
+            JsonKey::insert(&mut tree, &key_path);
+
+            if key_path.len() <= config.json_depth {
+                queue.push((key_path, nested_defaults.clone()));
+            }
+        }
+    }
+
+    Ok(tree)
+}
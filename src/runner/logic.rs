@@ -1,18 +1,79 @@
-use std::{cmp, collections::HashMap, error::Error, sync::Arc};
+use std::{
+    cmp,
+    collections::{hash_map::DefaultHasher, HashMap, HashSet, VecDeque},
+    error::Error,
+    hash::{Hash, Hasher},
+    sync::Arc,
+    time::Instant,
+};
 
 use async_recursion::async_recursion;
 use futures::stream::StreamExt;
 use parking_lot::Mutex;
+use percent_encoding::utf8_percent_encode;
 
 use crate::{
-    network::request::Request,
-    runner::utils::{FoundParameter, ReasonKind}, utils::progress_style_check_requests,
+    error,
+    network::{request::Request, utils::{is_timeout, Headers, InjectionPlace, FRAGMENT}},
+    runner::utils::{
+        format_duration, split_by_length, verify, AdaptiveConcurrency, ChunkStrategy, EtaModel, FoundParameter, ReasonKind,
+    },
+    utils::{self, progress_style_check_requests, WordlistReader},
+    CHUNK_RETRIES, STREAM_CHUNKS, VALUE_LENGTH,
 };
 
-use super::runner::Runner;
+/// parts of 400 responses that mean the request body (or query) couldn't be parsed
+const MALFORMED_MARKERS: [&str; 8] = [
+    "invalid json",
+    "malformed",
+    "parse error",
+    "unable to parse",
+    "could not parse",
+    "syntax error",
+    "unexpected token",
+    "unexpected end of",
+];
+
+use super::{
+    resume::{self, PendingChunks, TargetState},
+    runner::Runner,
+    skipped::{SkipReason, SkippedWord},
+    watch::WordlistWatcher,
+};
 
 /// impl logic for checking parameters
 impl<'a> Runner<'a> {
+    /// whether enough parameters were found to stop the check (--stop-after)
+    /// with --verify new candidates are verified first, so false positives don't stop the check
+    async fn stop_after_reached(
+        &self,
+        shared_found_params: &Mutex<&'a mut Vec<FoundParameter>>,
+        shared_diffs: &Mutex<&'a mut Vec<String>>,
+        stop_after: &Mutex<(usize, HashSet<String>)>,
+    ) -> bool {
+        // found parameters are only appended, so every candidate is checked once
+        let candidates = {
+            let found_params = shared_found_params.lock();
+            let mut stop_after = stop_after.lock();
+            let candidates = found_params[stop_after.0..].to_vec();
+            stop_after.0 = found_params.len();
+            candidates
+        };
+
+        let verified = if self.config.verify && !candidates.is_empty() {
+            let diffs = shared_diffs.lock().to_vec();
+            verify(&self.baseline(), &self.request_defaults, &candidates, &diffs, &self.stable)
+                .await
+                .unwrap_or_default()
+        } else {
+            candidates
+        };
+
+        let mut stop_after = stop_after.lock();
+        stop_after.1.extend(verified.into_iter().map(|x| x.name));
+        stop_after.1.len() >= self.config.stop_after
+    }
+
     /// just splits params into two parts and runs check_parameters_recursion for every part
     async fn repeat(
         &self,
@@ -48,22 +109,67 @@ impl<'a> Runner<'a> {
         mut params: Vec<String>,
     ) -> Result<(), Box<dyn Error>> {
         let request = Request::new(&self.request_defaults, params.clone());
-        let mut response = match request.clone().wrapped_send().await {
+        let mut timed_out = false;
+        let mut response = match request.clone().send().await {
             Ok(val) => val,
-            Err(_) => match Request::new_random(&self.request_defaults, params.len())
-                .send()
-                .await
-            {
-                //we don't return the actual response because it was a random request without original parameters
-                //instead we return an empty response from the original request
-                Ok(_) => request.empty_response(),
-                //looks like either server or network is down
-                Err(err) => Err(format!("Unable to reach server ({})", err))?,
-            },
+            Err(err) => {
+                timed_out = is_timeout(&err);
+
+                // the timeout was already retried, so a hanging endpoint gets a single control request
+                // instead of stalling the chunk for a few more timeouts
+                let mut control_defaults = self.request_defaults.clone();
+                if timed_out {
+                    control_defaults.retry.retries = 0;
+                }
+
+                // chunks failing at once share the control request within --cache-ttl
+                match control_defaults.cache.send(Request::new_random(&control_defaults, params.len())).await {
+                    //we don't return the actual response because it was a random request without original parameters
+                    //instead we return an empty response from the original request
+                    Ok(_) => request.empty_response(),
+                    //looks like either server or network is down
+                    Err(err) => Err(error::Error::Unreachable(err.to_string()))?,
+                }
+            }
         };
 
+        // empty responses don't have a meaningful time
+        if response.code != 0 {
+            self.chunk_times.lock().push(response.time);
+        }
+
+        self.track_remote_addr(&response);
+
+        // the chunk has too many headers. It's not a difference in behavior, so just split it
+        if response.code == 431 && !self.is_page_code(431) && params.len() > 1 {
+            return self
+                .repeat(
+                    shared_diffs,
+                    shared_green_lines,
+                    shared_found_params,
+                    params,
+                )
+                .await;
+        }
+
+        // the chunk broke the body format, so the response says nothing about other parameters of the chunk
+        // split it until the word that causes the error is found. A single word is checked as usual,
+        // because parameters like `date` may fail to parse random values
+        if response.code == 400 && !self.is_page_code(400) && params.len() > 1 && is_malformed(response.body()) {
+            self.log_diffs(&params, &response, &[], "split (malformed)");
+
+            return self
+                .repeat(
+                    shared_diffs,
+                    shared_green_lines,
+                    shared_found_params,
+                    params,
+                )
+                .await;
+        }
+
         if self.stable.reflections {
-            response.fill_reflected_parameters(&self.initial_response);
+            response.fill_reflected_parameters(&self.baseline());
 
             let (reflected_parameter, repeat) = response.proceed_reflected_parameters();
 
@@ -83,10 +189,24 @@ impl<'a> Runner<'a> {
                         &vec![],
                         response.code,
                         response.text.len(),
+                        response.time,
                         kind.clone(),
                     ));
                     drop(found_params);
 
+                    // reflections within headers (redirects, cache keys, CORS echoes) are counted along with the body
+                    // but it's worth mentioning where exactly the value was reflected
+                    let reflected_headers = match response.request.prepared_parameters.get_value(reflected_parameter) {
+                        Some(value) if kind == ReasonKind::Reflected => response.reflected_headers(&value),
+                        _ => Vec::new(),
+                    };
+
+                    let reflected_headers = if reflected_headers.is_empty() {
+                        None
+                    } else {
+                        Some(format!("headers: {}", reflected_headers.join(", ")))
+                    };
+
                     // remove found parameter from the list
                     params.remove(
                         params
@@ -95,15 +215,7 @@ impl<'a> Runner<'a> {
                             .unwrap(),
                     );
 
-                    response.write_and_save(
-                        self.id,
-                        self.config,
-                        &self.initial_response,
-                        kind,
-                        reflected_parameter,
-                        None,
-                        self.progress_bar,
-                    )?;
+                    self.write_and_save(&response, kind, reflected_parameter, reflected_headers.as_deref())?;
                 }
             }
 
@@ -123,7 +235,53 @@ impl<'a> Runner<'a> {
             }
         }
 
-        if self.initial_response.code != response.code {
+        // compare specific headers like Access-Control-Allow-* or security ones
+        // the code should be the same because error pages usually have different headers
+        if self.baseline().code == response.code {
+            for (predicate, kind) in self.header_channels() {
+                if self.stable.unstable_headers.contains(&kind) {
+                    continue;
+                }
+
+                let headers_delta = response.headers_delta(&self.baseline(), predicate);
+
+                if headers_delta.is_empty() {
+                    continue;
+                }
+
+                // there's only 1 parameter left that's changing the headers
+                if params.len() == 1 {
+                    let mut found_params = shared_found_params.lock();
+
+                    if !found_params.iter().any(|x| x.name == params[0]) {
+                        self.write_and_save(&response, kind.clone(), &params[0], Some(&headers_delta.join(", ")))?;
+
+                        found_params.push(FoundParameter::new(
+                            &params[0],
+                            &headers_delta,
+                            response.code,
+                            response.text.len(),
+                            response.time,
+                            kind,
+                        ));
+                    }
+
+                    return Ok(());
+                } else {
+                    return self
+                        .repeat(
+                            shared_diffs,
+                            shared_green_lines,
+                            shared_found_params,
+                            params.clone(),
+                        )
+                        .await;
+                }
+            }
+        }
+
+        // codes of other variants of the page are compared within the body check
+        if !self.is_page_code(response.code) {
             // increases the specific response code counter
             // helps to notice whether the page's completely changed
             // like, for example, when the IP got banned by the server
@@ -136,17 +294,15 @@ impl<'a> Runner<'a> {
                         if n_val > 50 {
                             drop(green_lines);
 
-                            let check_response =
-                                Request::new_random(&self.request_defaults, params.len())
-                                    .wrapped_send()
-                                    .await
-                                    .unwrap_or_default();
-
-                            if check_response.code != self.initial_response.code {
-                                return Err(format!(
-                                    "{} The page became unstable (code)",
-                                    self.request_defaults.url()
-                                ))?;
+                            // a fresh request with retries, so a single failure doesn't stop the check
+                            let check_code = Request::new_random(&self.request_defaults, params.len())
+                                .send()
+                                .await
+                                .map(|x| x.code)
+                                .unwrap_or_default();
+
+                            if !self.is_page_code(check_code) {
+                                return Err(error::Error::UnstablePage(format!("code, {}", self.request_defaults.url())))?;
                             } else {
                                 let mut green_lines = shared_green_lines.lock();
                                 green_lines.insert(response.code.to_string(), 0);
@@ -159,31 +315,29 @@ impl<'a> Runner<'a> {
                 }
             }
 
+            // only the chunk timed out, so one of the parameters makes the server hang
+            let code = if timed_out { "timeout".to_string() } else { response.code.to_string() };
+            let code_diff = vec![format!("{} -> {}", &self.baseline().code, code)];
+
             // there's only 1 parameter left that's changing the page's code
             if params.len() == 1 {
-                response.write_and_save(
-                    self.id,
-                    self.config,
-                    &self.initial_response,
-                    ReasonKind::Code,
-                    &params[0],
-                    None,
-                    self.progress_bar,
-                )?;
+                self.log_diffs(&params, &response, &code_diff, "found (code)");
+
+                self.write_and_save(&response, ReasonKind::Code, &params[0], None)?;
 
                 let mut found_params = shared_found_params.lock();
                 found_params.push(FoundParameter::new(
                     &params[0],
-                    &vec![format!(
-                        "{} -> {}",
-                        &self.initial_response.code, response.code
-                    )],
+                    &code_diff,
                     response.code,
                     response.text.len(),
+                    response.time,
                     ReasonKind::Code,
                 ));
             // there's more than 1 parameter left - split the list and repeat
             } else {
+                self.log_diffs(&params, &response, &code_diff, "split (code)");
+
                 return self
                     .repeat(
                         shared_diffs,
@@ -197,7 +351,7 @@ impl<'a> Runner<'a> {
             // check whether the new_diff has at least 1 unique diff compared to stored diffs
             let (_, new_diffs) = {
                 let diffs = shared_diffs.lock();
-                response.compare(&self.initial_response, &diffs)?
+                self.compare_with_closest(&response, &diffs)?
             };
 
             // and then make a new request to check whether it's a permament diff or not
@@ -205,6 +359,7 @@ impl<'a> Runner<'a> {
                 if self.config.strict {
                     let found_params = shared_found_params.lock();
                     if found_params.iter().any(|x| x.diffs == new_diffs.join("|")) {
+                        self.log_diffs(&params, &response, &new_diffs, "skipped (strict)");
                         return Ok(());
                     }
                 }
@@ -217,7 +372,7 @@ impl<'a> Runner<'a> {
 
                 let (_, tmp_diffs) = {
                     let diffs = shared_diffs.lock();
-                    tmp_resp.compare(&self.initial_response, &diffs)?
+                    self.compare_with_closest(&tmp_resp, &diffs)?
                 };
 
                 let mut diffs = shared_diffs.lock();
@@ -228,6 +383,10 @@ impl<'a> Runner<'a> {
 
             let diffs = shared_diffs.lock();
 
+            if !new_diffs.is_empty() && new_diffs.iter().all(|x| diffs.contains(x)) {
+                self.log_diffs(&params, &response, &new_diffs, "ignored (unstable)");
+            }
+
             // check whether the page still(after making a random request and storing it's diffs) has an unique diffs
             for diff in new_diffs.iter() {
                 if !diffs.contains(diff) {
@@ -238,32 +397,34 @@ impl<'a> Runner<'a> {
                         // repeating --strict checks. We need to do it twice because we're usually running in parallel
                         // and some parameters may be found after the first check
                         if self.config.strict && found_params.iter().any(|x| x.diffs == new_diffs.join("|")) {
+                            self.log_diffs(&params, &response, &new_diffs, "skipped (strict)");
                             return Ok(());
                         }
 
-                        response.write_and_save(
-                            self.id,
-                            self.config,
-                            &self.initial_response,
-                            ReasonKind::Text,
-                            &params[0],
-                            Some(diff),
-                            self.progress_bar,
-                        )?;
+                        self.log_diffs(&params, &response, &new_diffs, "found (text)");
 
-                        found_params.push(FoundParameter::new(
+                        self.write_and_save(&response, ReasonKind::Text, &params[0], Some(diff))?;
+
+                        let mut found_param = FoundParameter::new(
                             &params[0],
                             &new_diffs,
                             response.code,
                             response.text.len(),
+                            response.time,
                             ReasonKind::Text,
-                        ));
+                        );
+                        found_param.label = response.label(&self.baseline());
+
+                        found_params.push(found_param);
                         break;
                     // we don't know what parameter caused the difference in response yet
                     // so we are repeating
                     } else {
                         drop(diffs);
                         drop(found_params);
+
+                        self.log_diffs(&params, &response, &new_diffs, "split (text)");
+
                         return self
                             .repeat(
                                 shared_diffs,
@@ -280,49 +441,451 @@ impl<'a> Runner<'a> {
         Ok(())
     }
 
+    /// splits parameters into chunks according to the chunk strategy
+    fn make_chunks(&self, params: &[String]) -> Vec<Vec<String>> {
+        // the streamed wordlist may be the only source of words
+        if params.is_empty() {
+            return Vec::new();
+        }
+
+        let max = cmp::min(self.max, params.len());
+
+        // the limit is either specified by the user or detected while learning
+        let limit = match (&self.config.chunk_strategy, self.url_length_limit) {
+            (ChunkStrategy::UrlLength(limit), Some(detected)) => Some(cmp::min(*limit, detected)),
+            (ChunkStrategy::UrlLength(limit), None) => Some(*limit),
+            (_, detected) => detected,
+        };
+
+        match limit {
+            Some(limit) if self.request_defaults.injection_place == InjectionPlace::Path => {
+                let template = &self.request_defaults.template;
+                let joiner = &self.request_defaults.joiner;
+
+                // the length of parameter within the url with its joiner
+                let param_len = |param: &str| {
+                    let (key, value) = match param.split_once('=') {
+                        Some((key, value)) => (key, value.to_string()),
+                        None => (param, "x".repeat(VALUE_LENGTH)),
+                    };
+
+                    let param = template.replace("%k", key).replace("%v", &value);
+
+                    if self.request_defaults.encode {
+                        utf8_percent_encode(&param, &FRAGMENT).to_string().len() + joiner.len()
+                    } else {
+                        param.len() + joiner.len()
+                    }
+                };
+
+                // the url without injection point but with parameters that are added to every request
+                let base_len = self.request_defaults.url().len() - "%s".len()
+                    + self
+                        .request_defaults
+                        .parameters
+                        .iter()
+                        .map(|(k, v)| param_len(&format!("{}={}", k, v)))
+                        .sum::<usize>();
+
+                split_by_length(params, max, limit, base_len, param_len)
+            }
+            _ if matches!(self.request_defaults.injection_place, InjectionPlace::Headers | InjectionPlace::HeaderName)
+                && self.config.max_header_bytes != 0 =>
+            {
+                let custom_headers = &self.request_defaults.custom_headers;
+
+                // "name: value\r\n" for every sent header
+                let param_len = |param: &str| match self.request_defaults.injection_place {
+                    InjectionPlace::HeaderName => custom_headers
+                        .iter()
+                        .filter(|(k, _)| k.contains("%s"))
+                        .map(|(k, v)| k.len() - "%s".len() + param.len() + v.len() + 4)
+                        .sum(),
+                    _ => match param.split_once('=') {
+                        Some((key, value)) => key.len() + value.len() + 4,
+                        None => param.len() + VALUE_LENGTH + 4,
+                    },
+                };
+
+                let base_len = custom_headers
+                    .iter()
+                    .filter(|(k, _)| !k.contains("%s"))
+                    .map(|(k, v)| k.len() + v.len() + 4)
+                    .sum::<usize>();
+
+                split_by_length(params, max, self.config.max_header_bytes, base_len, param_len)
+            }
+            _ => params.chunks(max).map(|x| x.to_vec()).collect(),
+        }
+    }
+
     /// check parameters in a loop chunk by chunk
     pub async fn check_parameters(
         &self,
         params: &Vec<String>,
     ) -> Result<(Vec<String>, Vec<FoundParameter>), Box<dyn Error>> {
-        let max = cmp::min(self.max, params.len());
+        // the progress of the interrupted scan
+        let state_key = self.state_key();
+        let restored = if self.config.resume.is_empty() {
+            None
+        } else {
+            resume::restore(&self.config.resume, &state_key)
+        };
 
-        // the amount of requests needed for process all the parameters
-        let all = params.len() / max;
+        let chunks = match &restored {
+            Some(state) => {
+                utils::info(
+                    self.config,
+                    self.id,
+                    self.progress_bar,
+                    "resume",
+                    format!(
+                        "{} parameters left to check, {} were found before",
+                        state.remaining.len(),
+                        state.found_params.len()
+                    ),
+                );
+
+                self.make_chunks(&state.remaining)
+            }
+            None => self.make_chunks(params),
+        };
 
         // change and reset the progress bar
-        self.prepare_progress_bar(progress_style_check_requests(self.config), all + 1);
+        self.prepare_progress_bar(progress_style_check_requests(self.config), chunks.len());
 
         // wrap the variables to share them between futures
         let mut diffs = self.diffs.clone();
         let mut green_lines = HashMap::new();
         let mut found_params = Vec::new();
 
+        if let Some(state) = restored {
+            for diff in state.diffs {
+                if !diffs.contains(&diff) {
+                    diffs.push(diff);
+                }
+            }
+
+            found_params = state.found_params;
+        }
+
+        // chunks that weren't checked yet in the original order
+        let mut pending = PendingChunks::default();
+        let mut chunks = pending.add(chunks);
+        let pending = Mutex::new(pending);
+
+        // a checkpoint is skipped while the previous one is still being written
+        let writing = tokio::sync::Mutex::new(());
+
         let shared_diffs = Arc::new(Mutex::new(&mut diffs));
         let shared_green_lines = Arc::new(Mutex::new(&mut green_lines));
         let shared_found_params = Arc::new(Mutex::new(&mut found_params));
 
-        let _futures_data = futures::stream::iter(params.chunks(max).map(|chunk| {
-            let shared_diffs = Arc::clone(&shared_diffs);
-            let shared_green_lines = Arc::clone(&shared_green_lines);
-            let shared_found_params = Arc::clone(&shared_found_params);
+        // flaky connections decrease the amount of parallel requests
+        let concurrency = AdaptiveConcurrency::new(self.config.concurrency);
+        let failed_chunks = Mutex::new(Vec::new());
+        let interrupted_chunks = Mutex::new(Vec::new());
 
-            async move {
-                self.progress_bar.inc(1);
+        let eta = EtaModel::default();
 
-                self.check_parameters_recursion(
-                    shared_diffs,
-                    shared_green_lines,
-                    shared_found_params,
-                    chunk.to_vec(),
-                )
-                .await
+        // (found parameters checked for --stop-after, names of the verified ones)
+        let stop_after = Mutex::new((0, HashSet::new()));
+        self.progress_bar.set_message("");
+
+        // words appended to the file while the scan is running (--watch-wordlist)
+        let watcher = if self.config.watch_wordlist.is_empty() {
+            None
+        } else {
+            Some(Mutex::new(WordlistWatcher::new(&self.config.watch_wordlist, params)))
+        };
+
+        // huge wordlists are read by parts once the previous part is sent
+        let reader = if self.config.stream_wordlist {
+            Some(Mutex::new(WordlistReader::new(
+                &self.config.wordlist,
+                self.config.shard,
+                self.config.headers_discovery,
+            )?))
+        } else {
+            None
+        };
+
+        // hashes of streamed words and words from the list, so every word is sent once without keeping the whole wordlist
+        let streamed = Mutex::new(params.iter().map(|x| word_hash(x)).collect::<HashSet<u64>>());
+
+        for attempt in 0..=CHUNK_RETRIES {
+            if attempt > 0 {
+                if chunks.is_empty() || self.request_defaults.state.is_stopped() || *self.stopped_early.lock() {
+                    break;
+                }
+
+                utils::info(
+                    self.config,
+                    self.id,
+                    self.progress_bar,
+                    "~",
+                    format!(
+                        "Retrying {} chunks after connection errors (concurrency - {})",
+                        chunks.len(),
+                        concurrency.limit()
+                    ),
+                );
+
+                self.progress_bar.inc_length(chunks.len() as u64);
             }
-        }))
-        .buffer_unordered(self.config.concurrency)
-        .collect::<Vec<Result<(), Box<dyn Error>>>>()
-        .await;
+
+            let watcher = watcher.as_ref().filter(|_| attempt == 0);
+            let reader = reader.as_ref().filter(|_| attempt == 0);
+            let pending_ref = &pending;
+            let streamed = &streamed;
+
+            // new words from the watched file are picked up at the next chunk boundary
+            futures::stream::unfold(VecDeque::from(chunks), move |mut queue| async move {
+                if let Some(watcher) = watcher {
+                    // manual ideas go before the rest of the list
+                    for chunk in self.watched_chunks(&mut watcher.lock(), pending_ref).into_iter().rev() {
+                        queue.push_front(chunk);
+                    }
+                }
+
+                if let Some(reader) = reader {
+                    // enough words to keep every parallel request busy for a while
+                    while queue.is_empty() {
+                        let words = reader.lock().next_words(self.max * self.config.concurrency * STREAM_CHUNKS);
+
+                        if words.is_empty() {
+                            break;
+                        }
+
+                        // the same filters as the ones applied to the list within run()
+                        let mut words = self.filter_words(words);
+                        words.retain(|x| streamed.lock().insert(word_hash(x)));
+
+                        queue.extend(self.add_chunks(words, pending_ref));
+                    }
+                }
+
+                queue.pop_front().map(|chunk| (chunk, queue))
+            })
+            .map(|chunk| {
+                let shared_diffs = Arc::clone(&shared_diffs);
+                let shared_green_lines = Arc::clone(&shared_green_lines);
+                let shared_found_params = Arc::clone(&shared_found_params);
+                let concurrency = &concurrency;
+                let failed_chunks = &failed_chunks;
+                let interrupted_chunks = &interrupted_chunks;
+                let pending = &pending;
+                let state_key = &state_key;
+                let eta = &eta;
+                let writing = &writing;
+                let stop_after = &stop_after;
+
+                async move {
+                    let (index, chunk) = chunk;
+
+                    // chunks that weren't sent before Ctrl+C remain pending
+                    if self.request_defaults.state.is_stopped() {
+                        interrupted_chunks.lock().push((index, chunk));
+                        return;
+                    }
+
+                    // the target has enough parameters already (--stop-after)
+                    if *self.stopped_early.lock() {
+                        return;
+                    }
+
+                    let permit = concurrency.acquire().await;
+
+                    self.progress_bar.inc(1);
+
+                    let start = Instant::now();
+
+                    let result = self.check_parameters_recursion(
+                        Arc::clone(&shared_diffs),
+                        shared_green_lines,
+                        Arc::clone(&shared_found_params),
+                        chunk.clone(),
+                    )
+                    .await;
+
+                    let unreachable = matches!(
+                        &result,
+                        Err(err) if matches!(err.downcast_ref::<error::Error>(), Some(error::Error::Unreachable(_)))
+                    );
+
+                    // the rest of the chunks aren't sent once this one brings enough parameters
+                    if self.config.stop_after > 0 && self.stop_after_reached(&shared_found_params, &shared_diffs, stop_after).await {
+                        *self.stopped_early.lock() = true;
+                    }
+
+                    concurrency.release(permit, !unreachable);
+
+                    // --delay is kept between requests of every parallel request that is left
+                    self.request_defaults.delay_lanes.resize(concurrency.limit());
+
+                    if unreachable {
+                        failed_chunks.lock().push((index, chunk));
+                    } else if !self.config.resume.is_empty() && pending.lock().remove(index) {
+                        if let Ok(_writing) = writing.try_lock() {
+                            let state = TargetState {
+                                remaining: pending.lock().remaining(),
+                                found_params: shared_found_params.lock().to_vec(),
+                                diffs: shared_diffs.lock().to_vec(),
+                                stable: Some(self.stable.clone()),
+                            };
+
+                            let (filename, state_key) = (self.config.resume.clone(), state_key.clone());
+
+                            // the state is serialized and written without blocking other requests
+                            match tokio::task::spawn_blocking(move || {
+                                resume::checkpoint(&filename, &state_key, state).map_err(|err| err.to_string())
+                            })
+                            .await
+                            {
+                                Ok(Err(err)) => log::debug!("Unable to save the scan state: {}", err),
+                                Err(err) => log::debug!("Unable to save the scan state: {}", err),
+                                Ok(Ok(())) => (),
+                            }
+                        }
+                    }
+
+                    eta.record_chunk(start.elapsed().as_millis(), unreachable);
+                    self.update_eta(eta, concurrency.limit(), shared_found_params.lock().len());
+                }
+            })
+            .buffer_unordered(self.config.concurrency)
+            .collect::<Vec<()>>()
+            .await;
+
+            chunks = std::mem::take(&mut *failed_chunks.lock());
+        }
+
+        if !chunks.is_empty() {
+            utils::info(
+                self.config,
+                self.id,
+                self.progress_bar,
+                "~",
+                format!("{} chunks were skipped because of connection errors", chunks.len()),
+            );
+        }
+
+        {
+            let mut skipped = self.skipped.lock();
+            let words = |chunks: &[(usize, Vec<String>)]| chunks.iter().flat_map(|(_, x)| x.iter().cloned()).collect::<Vec<String>>();
+
+            skipped.extend(SkippedWord::many(&words(&chunks), SkipReason::ConnectionError));
+            skipped.extend(SkippedWord::many(&words(&interrupted_chunks.lock()), SkipReason::Interrupted));
+        }
+
+        // skipped and interrupted chunks are checked once the scan is resumed
+        if !self.config.resume.is_empty() {
+            let state = TargetState {
+                remaining: pending.into_inner().remaining(),
+                found_params: found_params.clone(),
+                diffs: diffs.clone(),
+                stable: Some(self.stable.clone()),
+            };
+
+            resume::checkpoint(&self.config.resume, &state_key, state)?;
+        }
 
         Ok((diffs, found_params))
     }
+
+    /// makes chunks of the words that were appended to the watched file
+    fn watched_chunks(&self, watcher: &mut WordlistWatcher, pending: &Mutex<PendingChunks>) -> Vec<(usize, Vec<String>)> {
+        let words = self.filter_words(watcher.new_words());
+
+        if words.is_empty() {
+            return Vec::new();
+        }
+
+        utils::info(
+            self.config,
+            self.id,
+            self.progress_bar,
+            "info",
+            format!("{} parameters were added from {}", words.len(), self.config.watch_wordlist),
+        );
+
+        self.add_chunks(words, pending)
+    }
+
+    /// makes chunks of words that weren't known when the check was started and counts them within the progress
+    fn add_chunks(&self, words: Vec<String>, pending: &Mutex<PendingChunks>) -> Vec<(usize, Vec<String>)> {
+        if words.is_empty() {
+            return Vec::new();
+        }
+
+        let chunks = self.make_chunks(&words);
+
+        self.progress_bar.inc_length(chunks.len() as u64);
+
+        pending.lock().add(chunks)
+    }
+
+    /// identifies the target within the state file (--resume)
+    /// parameters added on recursion are a part of the key, so every depth has its own progress
+    pub(super) fn state_key(&self) -> String {
+        format!(
+            "{} {} {}",
+            self.request_defaults.method,
+            self.request_defaults.url_without_default_port(),
+            self.request_defaults
+                .parameters
+                .iter()
+                .map(|(k, _)| k.as_str())
+                .collect::<Vec<&str>>()
+                .join(",")
+        )
+    }
+
+    /// shows the remaining time and requests per stage within the progress bar
+    fn update_eta(&self, eta: &EtaModel, concurrency: usize, found: usize) {
+        let remaining = self.progress_bar.length().unwrap_or_default().saturating_sub(self.progress_bar.position());
+
+        // found parameters are checked once more one by one
+        let mut followup_requests = 0;
+
+        if self.config.verify {
+            followup_requests += found;
+        }
+        if !self.config.keep_aliases && found > 1 {
+            followup_requests += found;
+        }
+        if self.config.header_folding {
+            followup_requests += found * 2;
+        }
+        if self.config.duplicate_keys {
+            followup_requests += found;
+        }
+        if self.config.combinations > 1 {
+            // every parameter alone and every pair
+            let top = cmp::min(found, self.config.combinations);
+            followup_requests += top + top * top.saturating_sub(1) / 2;
+        }
+
+        let request_time = eta.request_time(&self.chunk_times) + self.config.delay.as_millis();
+
+        // shows why the amount of requests exceeds the size of the wordlist
+        let stages = self.request_defaults.stages.metrics();
+
+        match eta.estimate(remaining, concurrency, followup_requests, request_time) {
+            Some(eta) => self.progress_bar.set_message(format!("eta {} | {}", format_duration(eta), stages)),
+            None => self.progress_bar.set_message(stages.to_string()),
+        }
+    }
+}
+
+fn word_hash(word: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    word.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// whether the response body looks like a parse failure
+fn is_malformed(text: &str) -> bool {
+    let text = text.to_lowercase();
+    MALFORMED_MARKERS.iter().any(|x| text.contains(x))
 }
@@ -0,0 +1,42 @@
+use std::time::Duration;
+
+use crate::network::request::RequestDefaults;
+
+/// settings learned while checking a url
+/// they're shared between urls of the same host within the run, so a batch scan doesn't re-learn them for every url
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Learned {
+    /// the amount of parameters per request
+    pub max: usize,
+
+    pub url_length_limit: Option<usize>,
+
+    /// whether the amount of reflections of random values was stable
+    pub reflections: bool,
+
+    /// the longest wait the server asked for via Retry-After. Later urls of the host keep at least this delay between requests
+    pub delay: Duration,
+}
+
+/// settings learned on a previous url of the same host with the same injection place
+pub fn recall(request_defaults: &RequestDefaults) -> Option<Learned> {
+    request_defaults.state.learned.lock().get(&key(request_defaults)).copied()
+}
+
+pub fn remember(request_defaults: &RequestDefaults, learned: Learned) {
+    request_defaults.state.learned.lock().insert(key(request_defaults), learned);
+}
+
+/// raises the learned delay in case the server asked to slow down while the url was checked
+pub fn remember_delay(request_defaults: &RequestDefaults, delay: Duration) {
+    if let Some(learned) = request_defaults.state.learned.lock().get_mut(&key(request_defaults)) {
+        learned.delay = learned.delay.max(delay);
+    }
+}
+
+fn key(request_defaults: &RequestDefaults) -> String {
+    format!(
+        "{}:{}:{:?}",
+        request_defaults.host, request_defaults.port, request_defaults.injection_place
+    )
+}
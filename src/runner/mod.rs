@@ -1,4 +1,25 @@
+pub mod aliases;
+pub mod batch;
+pub mod categories;
+pub mod combinations;
+pub mod dualstack;
+pub mod duplicates;
+pub mod fingerprint;
+pub mod folding;
+pub mod json_tree;
 pub mod logic;
+pub mod memory;
 pub mod output;
+pub mod pair;
+pub mod preflight;
+pub mod resume;
+pub mod roles;
 pub mod runner;
+pub mod scan;
+pub mod skipped;
+pub mod sourcemaps;
 pub mod utils;
+pub mod warmup;
+pub mod watch;
+
+mod tests;
\ No newline at end of file
@@ -1,16 +1,43 @@
-use serde::Serialize;
+use std::{cmp::Reverse, collections::HashMap, error::Error, fs};
+
+use serde::{Deserialize, Serialize};
 use colored::Colorize;
 
 use crate::{
     config::structs::Config,
+    TEMPLATE_PLACEHOLDER,
     network::{
+        metrics::{HashMetrics, PoolMetrics, TrafficMetrics},
         request::{Request, RequestDefaults},
-        response::Response,
+        response::Baseline,
         utils::InjectionPlace,
     },
 };
 
-use super::utils::FoundParameter;
+use super::{
+    combinations::Combination,
+    folding::HeaderFolding,
+    json_tree::JsonKey,
+    pair::PairComparison,
+    roles::RoleMatrix,
+    skipped::{summarize, SkippedWord},
+    utils::{FoundParameter, Parameters, Timings},
+};
+
+/// a single found parameter along with its url (-O jsonl)
+#[derive(Debug, Serialize)]
+pub struct FoundParameterLine<'a> {
+    pub url: &'a str,
+    pub method: &'a str,
+    pub injection_place: InjectionPlace,
+
+    /// initial response code and size
+    pub baseline_status: u16,
+    pub baseline_size: usize,
+
+    #[serde(flatten)]
+    pub parameter: &'a FoundParameter,
+}
 
 #[derive(Debug, Serialize)]
 pub struct RunnerOutput {
@@ -30,6 +57,58 @@ pub struct RunnerOutput {
 
     pub injection_place: InjectionPlace,
 
+    /// response times of learning requests compared to requests with parameters
+    pub timings: Timings,
+
+    /// usage of the connection pool shared by every stage
+    pub pool: PoolMetrics,
+
+    /// how many responses were identical to the baseline by hash
+    pub hashing: HashMetrics,
+
+    /// bytes sent and received within every stage
+    pub traffic: TrafficMetrics,
+
+    /// found parameters that weren't found within the previous run (--compare-with). None without the option
+    /// found_params stays complete, so the output can be compared with the next run as well
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new_params: Option<Vec<String>>,
+
+    /// parameters that were found within the previous run (--compare-with) but not within this one
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub removed_params: Vec<String>,
+
+    /// how found headers with malformed whitespace were handled (--header-folding)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub header_folding: Vec<HeaderFolding>,
+
+    /// pairs of parameters that change the page only together (--combinations)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub combinations: Vec<Combination>,
+
+    /// words that weren't checked and why
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub skipped: Vec<SkippedWord>,
+
+    /// parameters that behave differently within the --compare-url one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pair: Option<PairComparison>,
+
+    /// which parameters exist and how they behave for every identity (--as)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub roles: Option<RoleMatrix>,
+
+    /// the amount of parameters after which the check was stopped (--stop-after)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stopped_after: Option<usize>,
+
+    /// keys found within objects of json parameters (--json-depth)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub json_tree: Vec<JsonKey>,
+
+    /// frameworks and languages detected by the initial response
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub backend: Vec<String>,
 
     /// prepared query with found parameters
     #[serde(skip_serializing)]
@@ -40,6 +119,41 @@ pub struct RunnerOutput {
     pub request: String,
 }
 
+/// names of found parameters from a previous run keyed by (method, url)
+pub type PreviousFindings = HashMap<(String, String), Vec<String>>;
+
+/// the part of RunnerOutput that is needed to compare runs
+#[derive(Deserialize)]
+struct PreviousOutput {
+    method: String,
+    url: String,
+    found_params: Vec<PreviousParameter>,
+}
+
+#[derive(Deserialize)]
+struct PreviousParameter {
+    name: String,
+}
+
+/// loads found parameters from the json output of a previous run
+pub fn load_previous_findings(filename: &str) -> Result<PreviousFindings, Box<dyn Error>> {
+    let outputs: Vec<PreviousOutput> = match serde_json::from_str(&fs::read_to_string(filename)?) {
+        Ok(val) => val,
+        Err(err) => Err(format!("Unable to parse {} as the json output: {}", filename, err))?,
+    };
+
+    let mut previous_findings = PreviousFindings::new();
+
+    for output in outputs {
+        previous_findings
+            .entry((output.method, output.url))
+            .or_default()
+            .extend(output.found_params.into_iter().map(|x| x.name));
+    }
+
+    Ok(previous_findings)
+}
+
 pub trait ParseOutputs {
     fn parse_output(&self, config: &Config) -> String;
 }
@@ -47,29 +161,92 @@ pub trait ParseOutputs {
 impl RunnerOutput {
     pub fn new(
         request_defaults: &RequestDefaults,
-        initial_response: &Response,
-        found_params: Vec<FoundParameter>,
+        baseline: &Baseline,
+        mut found_params: Vec<FoundParameter>,
+        timings: Timings,
     ) -> Self {
+        // the most promising parameters go first
+        for param in found_params.iter_mut() {
+            param.impact = param.calculate_impact(baseline.code, baseline.text.len());
+        }
+        found_params.sort_by_key(|x| Reverse(x.impact));
+
         Self {
             method: request_defaults.method.clone(),
             //remove injection point in case the injection point within url
             url: if request_defaults.injection_place == InjectionPlace::Path {
                 request_defaults.url_without_default_port().replace("?%s", "").replace("&%s", "")
+            } else if request_defaults.injection_place == InjectionPlace::PathSegment {
+                request_defaults.url_without_default_port().replace("/%s", "")
             } else {
                 request_defaults.url_without_default_port()
             },
-            status: initial_response.code,
-            size: initial_response.text.len(),
+            status: baseline.code,
+            size: baseline.text.len(),
             found_params,
             injection_place: request_defaults.injection_place,
+            timings,
+            pool: request_defaults.pool.metrics(),
+            hashing: request_defaults.hashing.metrics(),
+            traffic: request_defaults.traffic.metrics(),
+            new_params: None,
+            removed_params: Vec::new(),
+            header_folding: Vec::new(),
+            combinations: Vec::new(),
+            skipped: Vec::new(),
+            pair: None,
+            roles: None,
+            stopped_after: None,
+            json_tree: Vec::new(),
+            backend: Vec::new(),
             query: String::new(),
             request: String::new(),
         }
     }
 
+    /// whether there's nothing to report
+    pub fn is_empty(&self) -> bool {
+        self.found_params.is_empty()
+            && self.removed_params.is_empty()
+            && self.skipped.is_empty()
+            && self.pair.as_ref().is_none_or(|x| x.is_empty())
+    }
+
+    /// saves parameters that weren't found within the previous run and parameters that disappeared since then
+    pub fn compare_with(&mut self, previous_findings: &PreviousFindings) {
+        // the url wasn't checked before so every parameter is new
+        let previous_params = previous_findings
+            .get(&(self.method.clone(), self.url.clone()))
+            .cloned()
+            .unwrap_or_default();
+
+        self.new_params = Some(
+            self.found_params
+                .iter()
+                .filter(|x| !previous_params.contains(&x.name))
+                .map(|x| x.name.clone())
+                .collect(),
+        );
+
+        self.removed_params = previous_params
+            .into_iter()
+            .filter(|x| !self.found_params.contains_name(x))
+            .collect();
+    }
+
+    /// whether there's something to report: new parameters with --compare-with, any found parameter otherwise
+    pub fn has_new_params(&self) -> bool {
+        match &self.new_params {
+            Some(new_params) => !new_params.is_empty(),
+            None => !self.found_params.is_empty(),
+        }
+    }
+
     /// fills self.request and self.query if they're needed for output
     pub fn prepare(&mut self, config: &Config, request_defaults: &RequestDefaults) {
-        if config.output_format == "url" || config.output_format == "request" {
+        let is_template = config.output_format == "template";
+
+        if config.output_format == "url" || config.output_format == "request" || is_template {
             let mut request = Request::new(
                 request_defaults,
                 self.found_params
@@ -79,7 +256,9 @@ impl RunnerOutput {
                         //it should be treated differently (=true) should be added
                         //otherwise that parameter will have random value
                         |x| {
-                            if x.value.is_none() {
+                            if is_template {
+                                format!("{}={}", x.name, TEMPLATE_PLACEHOLDER)
+                            } else if x.value.is_none() {
                                 x.name.to_owned()
                             } else {
                                 format!("{}={}", x.name, x.value.as_ref().unwrap())
@@ -91,7 +270,8 @@ impl RunnerOutput {
 
             request.prepare();
 
-            if config.output_format == "url" {
+            // templates of parameters within the body or headers are whole requests
+            if config.output_format == "url" || (is_template && self.injection_place == InjectionPlace::Path) {
                 self.query = request.make_query();
             } else {
                 self.request = request.print();
@@ -102,7 +282,37 @@ impl RunnerOutput {
     /// parses the runner output struct to one specified in config format
     pub fn parse(&self, config: &Config) -> String {
         match config.output_format.as_str() {
-            "url" => {
+            "template" if !matches!(self.injection_place, InjectionPlace::Path | InjectionPlace::PathSegment) => {
+                self.request.clone()
+            }
+
+            // every found segment is a separate endpoint, so one url per segment
+            "url" | "template" if self.injection_place == InjectionPlace::PathSegment && !self.found_params.is_empty() => {
+                let (path, query) = match self.url.split_once('?') {
+                    Some((path, query)) => (path, format!("?{}", query)),
+                    None => (self.url.as_str(), String::new()),
+                };
+
+                // swapped ids are reported with the whole path
+                let origin = match path.find("://").and_then(|start| path[start + 3..].find('/').map(|end| start + 3 + end)) {
+                    Some(end) => &path[..end],
+                    None => path,
+                };
+
+                self.found_params
+                    .iter()
+                    .map(|x| {
+                        if x.name.starts_with('/') {
+                            format!("{}{}{}", origin, x.name, query)
+                        } else {
+                            format!("{}/{}{}", path.trim_end_matches('/'), x.name, query)
+                        }
+                    })
+                    .collect::<Vec<String>>()
+                    .join("\n")
+            }
+
+            "url" | "template" => {
                 //make line an url with injection point
                 let line = if !self.found_params.is_empty()
                     && self.injection_place == InjectionPlace::Path
@@ -121,16 +331,136 @@ impl RunnerOutput {
 
             "request" => self.request.clone(),
 
+            // one json object per parameter, so the output can be streamed into jq
+            "jsonl" => self
+                .found_params
+                .iter()
+                .map(|x| {
+                    serde_json::to_string(&FoundParameterLine {
+                        url: &self.url,
+                        method: &self.method,
+                        injection_place: self.injection_place,
+                        baseline_status: self.status,
+                        baseline_size: self.size,
+                        parameter: x,
+                    })
+                    .unwrap()
+                })
+                .collect::<Vec<String>>()
+                .join("\n"),
+
             _ => {
+                let new_params = match self.new_params.as_ref().filter(|x| !x.is_empty()) {
+                    Some(new_params) => format!(" {} {}", "new:".green(), new_params.join(", ")),
+                    None => String::new(),
+                };
+
+                let removed_params = if self.removed_params.is_empty() {
+                    String::new()
+                } else {
+                    format!(" {} {}", "removed:".red(), self.removed_params.join(", "))
+                };
+
+                let pair = match self.pair.as_ref().filter(|x| !x.is_empty()) {
+                    Some(pair) => {
+                        let mut parts = Vec::new();
+
+                        if !pair.only_first.is_empty() {
+                            parts.push(format!("only here: {}", pair.only_first.join(", ")));
+                        }
+                        if !pair.only_second.is_empty() {
+                            parts.push(format!("only there: {}", pair.only_second.join(", ")));
+                        }
+                        if !pair.different.is_empty() {
+                            parts.push(format!(
+                                "different: {}",
+                                pair.different
+                                    .iter()
+                                    .map(|x| format!("{} ({}/{})", x.name, x.first_status, x.second_status))
+                                    .collect::<Vec<String>>()
+                                    .join(", ")
+                            ));
+                        }
+
+                        format!(" {} {}: {}", "vs".magenta(), pair.url, parts.join("; "))
+                    }
+                    None => String::new(),
+                };
+
+                let combinations = if self.combinations.is_empty() {
+                    String::new()
+                } else {
+                    format!(
+                        " {} {}",
+                        "combined:".magenta(),
+                        self.combinations
+                            .iter()
+                            .map(|x| format!("{}+{} ({})", x.params[0], x.params[1], x.status))
+                            .collect::<Vec<String>>()
+                            .join(", ")
+                    )
+                };
+
+                let skipped = if self.skipped.is_empty() {
+                    String::new()
+                } else {
+                    format!(" {} {} ({})", "untested:".red(), self.skipped.len(), summarize(&self.skipped))
+                };
+
+                let roles = match &self.roles {
+                    Some(roles) if roles.different().next().is_some() => format!(
+                        " {} {}",
+                        "roles:".magenta(),
+                        roles
+                            .different()
+                            .map(|row| format!(
+                                "{} ({})",
+                                row.name,
+                                roles
+                                    .roles
+                                    .iter()
+                                    .zip(row.results.iter())
+                                    .map(|(role, result)| match result {
+                                        Some(result) => format!("{}: {}", role, result.status),
+                                        None => format!("{}: -", role),
+                                    })
+                                    .collect::<Vec<String>>()
+                                    .join(", ")
+                            ))
+                            .collect::<Vec<String>>()
+                            .join(", ")
+                    ),
+                    _ => String::new(),
+                };
+
+                let json_tree = if self.json_tree.is_empty() {
+                    String::new()
+                } else {
+                    format!(" {} {}", "json:".magenta(), JsonKey::print(&self.json_tree))
+                };
+
+                let stopped = match self.stopped_after {
+                    Some(amount) => format!(" {}", format!("(stopped after {})", amount).yellow()),
+                    None => String::new(),
+                };
+
                 format!(
-                    "{} {} % {}",
+                    "{} {} % {}{}{}{}{}{}{}{}{}",
                     &self.method.blue(),
                     &self.url,
                     self.found_params
                         .iter()
-                        .map(|x| x.get_colored())
+                        .map(|x| x.get_colored(&config.categories))
                         .collect::<Vec<String>>()
-                        .join(", ")
+                        .join(", "),
+                    new_params,
+                    removed_params,
+                    skipped,
+                    combinations,
+                    pair,
+                    roles,
+                    json_tree,
+                    stopped
                 )
             }
         }
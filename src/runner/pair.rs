@@ -0,0 +1,82 @@
+use serde::Serialize;
+
+use super::{
+    output::RunnerOutput,
+    utils::{FoundParameter, ReasonKind},
+};
+
+/// a parameter that was found within both urls but changes them differently
+#[derive(Debug, Clone, Serialize)]
+pub struct PairDifference {
+    pub name: String,
+    pub first_status: u16,
+    pub second_status: u16,
+    pub first_reason: ReasonKind,
+    pub second_reason: ReasonKind,
+}
+
+/// parameters that behave differently within the scanned url and the --compare-url one
+#[derive(Debug, Clone, Serialize)]
+pub struct PairComparison {
+    /// the second url
+    pub url: String,
+
+    /// initial response code of the second url
+    pub status: u16,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub only_first: Vec<String>,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub only_second: Vec<String>,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub different: Vec<PairDifference>,
+}
+
+impl PairComparison {
+    /// every url was checked against its own baseline, so only the results are compared
+    pub fn new(first: &RunnerOutput, second: &RunnerOutput) -> Self {
+        let find = |params: &[FoundParameter], name: &str| params.iter().find(|x| x.name == name).cloned();
+
+        let mut only_first = Vec::new();
+        let mut different = Vec::new();
+
+        for param in first.found_params.iter() {
+            match find(&second.found_params, &param.name) {
+                Some(other) => {
+                    if param.status != other.status || param.reason_kind != other.reason_kind {
+                        different.push(PairDifference {
+                            name: param.name.clone(),
+                            first_status: param.status,
+                            second_status: other.status,
+                            first_reason: param.reason_kind.clone(),
+                            second_reason: other.reason_kind.clone(),
+                        });
+                    }
+                }
+                None => only_first.push(param.name.clone()),
+            }
+        }
+
+        let only_second = second
+            .found_params
+            .iter()
+            .filter(|x| find(&first.found_params, &x.name).is_none())
+            .map(|x| x.name.clone())
+            .collect();
+
+        Self {
+            url: second.url.clone(),
+            status: second.status,
+            only_first,
+            only_second,
+            different,
+        }
+    }
+
+    /// whether both urls behave the same way
+    pub fn is_empty(&self) -> bool {
+        self.only_first.is_empty() && self.only_second.is_empty() && self.different.is_empty()
+    }
+}
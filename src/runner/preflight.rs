@@ -0,0 +1,132 @@
+use std::{error::Error, net::SocketAddr, time::Duration};
+
+use indicatif::ProgressBar;
+
+use crate::{
+    config::structs::Config,
+    network::{
+        doh,
+        request::{Request, RequestDefaults},
+    },
+    utils, MAX_PAGE_SIZE,
+};
+
+/// words that usually appear on maintenance pages
+const MAINTENANCE_MARKERS: [&str; 3] = ["under maintenance", "maintenance mode", "temporarily unavailable"];
+
+/// checks that the target is reachable and worth checking before the learn phase
+/// prints a report and returns an error with a readable explanation of the failed check
+pub async fn preflight(
+    config: &Config,
+    request_defaults: &RequestDefaults,
+    progress_bar: &ProgressBar,
+    id: usize,
+) -> Result<(), Box<dyn Error>> {
+    let target = format!("{}:{}", request_defaults.host, request_defaults.port);
+
+    // the target is resolved by the proxy in this case
+    if config.proxy.is_empty() {
+        let addrs: Result<Vec<SocketAddr>, String> = if config.doh.is_empty() {
+            tokio::net::lookup_host(&target)
+                .await
+                .map(|addrs| addrs.collect())
+                .map_err(|err| err.to_string())
+        } else {
+            doh::resolve(&config.doh, &request_defaults.host, config.timeout)
+                .await
+                .map(|ips| ips.into_iter().map(|ip| SocketAddr::new(ip, request_defaults.port)).collect())
+                .map_err(|err| err.to_string())
+        };
+
+        let addrs = match addrs {
+            Ok(addrs) if !addrs.is_empty() => addrs,
+            Ok(_) => Err(format!("Pre-flight: {} doesn't have any addresses", request_defaults.host))?,
+            Err(err) => Err(format!(
+                "Pre-flight: unable to resolve {} ({}). Check the hostname or use --proxy",
+                request_defaults.host, err
+            ))?,
+        };
+
+        report(config, progress_bar, id, format!(
+            "dns: {} -> {}",
+            request_defaults.host,
+            addrs.iter().map(|x| x.ip().to_string()).collect::<Vec<String>>().join(", ")
+        ));
+
+        match tokio::time::timeout(
+            Duration::from_secs(config.timeout as u64),
+            tokio::net::TcpStream::connect(addrs[0]),
+        )
+        .await
+        {
+            Ok(Ok(_)) => report(config, progress_bar, id, format!("connection: {} is open", target)),
+            Ok(Err(err)) => Err(format!("Pre-flight: unable to connect to {} ({})", target, err))?,
+            Err(_) => Err(format!(
+                "Pre-flight: connection to {} timed out. Increase --timeout in case the target is slow",
+                target
+            ))?,
+        };
+    }
+
+    let response = match Request::new(request_defaults, Vec::new()).send().await {
+        Ok(val) => val,
+        Err(err) if request_defaults.scheme == "https" && is_tls_error(&err) => Err(format!(
+            "Pre-flight: tls handshake with {} failed ({}). Check whether the target supports https",
+            target, err
+        ))?,
+        Err(err) => Err(format!("Pre-flight: the baseline request failed ({})", err))?,
+    };
+
+    if request_defaults.scheme == "https" {
+        report(config, progress_bar, id, "tls: handshake succeeded");
+    }
+
+    if !config.force {
+        if response.code == 503 {
+            Err("Pre-flight: the baseline response is 503. The target is probably down or under maintenance. Use --force to check it anyway")?;
+        }
+
+        let text = response.text.to_lowercase();
+
+        if let Some(marker) = MAINTENANCE_MARKERS.iter().find(|x| text.contains(*x)) {
+            Err(format!(
+                "Pre-flight: the baseline response looks like a maintenance page (contains '{}'). Use --force to check it anyway",
+                marker
+            ))?;
+        }
+
+        if response.text.len() > MAX_PAGE_SIZE {
+            Err("Pre-flight: the baseline response is bigger than 25MB. Use --force to check it anyway")?;
+        }
+    }
+
+    report(config, progress_bar, id, format!(
+        "baseline: {} ({} bytes, {} ms)",
+        response.code,
+        response.text.len(),
+        response.time
+    ));
+
+    Ok(())
+}
+
+fn report<T: std::fmt::Display>(config: &Config, progress_bar: &ProgressBar, id: usize, msg: T) {
+    utils::info(config, id, progress_bar, "preflight", msg);
+}
+
+/// reqwest hides tls errors within the error chain
+fn is_tls_error(err: &dyn Error) -> bool {
+    let mut source = Some(err);
+
+    while let Some(err) = source {
+        let msg = err.to_string().to_lowercase();
+
+        if ["tls", "ssl", "certificate", "handshake"].iter().any(|x| msg.contains(x)) {
+            return true;
+        }
+
+        source = err.source();
+    }
+
+    false
+}
@@ -0,0 +1,106 @@
+use std::{collections::{BTreeMap, HashMap}, error::Error, fs};
+
+use lazy_static::lazy_static;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+use crate::CHECKPOINT_CHUNKS;
+
+use super::utils::{FoundParameter, Stable};
+
+/// the progress of checking a single url with a single method
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TargetState {
+    /// parameters that weren't checked yet
+    pub remaining: Vec<String>,
+
+    pub found_params: Vec<FoundParameter>,
+
+    /// persistent diffs learned during the check
+    pub diffs: Vec<String>,
+
+    /// the page stability learned before the interruption. None within older state files
+    #[serde(default)]
+    pub stable: Option<Stable>,
+}
+
+/// chunks that weren't checked yet by their index, so the original order is kept
+#[derive(Debug, Default)]
+pub struct PendingChunks {
+    chunks: BTreeMap<usize, Vec<String>>,
+    next: usize,
+
+    /// chunks checked since the last checkpoint
+    checked: usize,
+}
+
+impl PendingChunks {
+    /// adds the chunks and returns them along with their indices
+    pub fn add(&mut self, chunks: Vec<Vec<String>>) -> Vec<(usize, Vec<String>)> {
+        chunks
+            .into_iter()
+            .map(|chunk| {
+                self.chunks.insert(self.next, chunk.clone());
+                self.next += 1;
+                (self.next - 1, chunk)
+            })
+            .collect()
+    }
+
+    /// marks the chunk as checked. Returns true once it's time to save the state
+    pub fn remove(&mut self, index: usize) -> bool {
+        if self.chunks.remove(&index).is_some() {
+            self.checked += 1;
+        }
+
+        if self.checked >= CHECKPOINT_CHUNKS {
+            self.checked = 0;
+            return true;
+        }
+
+        false
+    }
+
+    /// words of the chunks that weren't checked yet in the original order
+    pub fn remaining(&self) -> Vec<String> {
+        self.chunks.values().flatten().cloned().collect()
+    }
+}
+
+lazy_static! {
+    /// state files are read once and updated on every checkpoint
+    /// filename -> target -> state
+    static ref STATE: Mutex<HashMap<String, HashMap<String, TargetState>>> = Mutex::new(HashMap::new());
+}
+
+fn load(filename: &str) -> HashMap<String, TargetState> {
+    fs::read_to_string(filename)
+        .ok()
+        .and_then(|x| serde_json::from_str(&x).ok())
+        .unwrap_or_default()
+}
+
+/// returns the saved progress of the target in case the scan was interrupted (--resume)
+pub fn restore(filename: &str, key: &str) -> Option<TargetState> {
+    STATE
+        .lock()
+        .entry(filename.to_string())
+        .or_insert_with(|| load(filename))
+        .get(key)
+        .cloned()
+}
+
+/// saves the progress of the target
+/// the file is replaced atomically, so an interruption during the write doesn't corrupt it
+pub fn checkpoint(filename: &str, key: &str, state: TargetState) -> Result<(), Box<dyn Error>> {
+    let mut state_files = STATE.lock();
+    let saved = state_files.entry(filename.to_string()).or_insert_with(|| load(filename));
+
+    saved.insert(key.to_string(), state);
+
+    let temp = format!("{}.tmp", filename);
+    fs::write(&temp, serde_json::to_string(saved)?)?;
+    fs::rename(&temp, filename)?;
+
+    Ok(())
+}
@@ -0,0 +1,91 @@
+use serde::Serialize;
+
+use super::{
+    output::RunnerOutput,
+    utils::{Parameters, ReasonKind},
+};
+
+/// how the parameter behaves for a single identity
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RoleResult {
+    pub status: u16,
+    pub reason_kind: ReasonKind,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RoleRow {
+    pub name: String,
+
+    /// one per role in the same order, None in case the parameter wasn't found for the role
+    pub results: Vec<Option<RoleResult>>,
+}
+
+/// which parameters exist and how they behave for every identity (--as)
+#[derive(Debug, Clone, Serialize)]
+pub struct RoleMatrix {
+    pub roles: Vec<String>,
+    pub parameters: Vec<RoleRow>,
+}
+
+impl RoleRow {
+    /// whether the parameter behaves differently for at least one role
+    pub fn is_different(&self) -> bool {
+        self.results.iter().any(|x| *x != self.results[0])
+    }
+}
+
+impl RoleMatrix {
+    pub fn new(outputs: &[(String, RunnerOutput)]) -> Self {
+        let mut parameters: Vec<RoleRow> = Vec::new();
+
+        for (_, output) in outputs {
+            for param in output.found_params.iter() {
+                if parameters.iter().any(|x| x.name == param.name) {
+                    continue;
+                }
+
+                parameters.push(RoleRow {
+                    name: param.name.clone(),
+                    results: outputs
+                        .iter()
+                        .map(|(_, output)| {
+                            output.found_params.iter().find(|x| x.name == param.name).map(|x| RoleResult {
+                                status: x.status,
+                                reason_kind: x.reason_kind.clone(),
+                            })
+                        })
+                        .collect(),
+                });
+            }
+        }
+
+        Self {
+            roles: outputs.iter().map(|(name, _)| name.clone()).collect(),
+            parameters,
+        }
+    }
+
+    /// uses the output of the first role and adds parameters that were found for other roles only
+    pub fn merge(outputs: Vec<(String, RunnerOutput)>) -> RunnerOutput {
+        let matrix = Self::new(&outputs);
+
+        let mut outputs = outputs.into_iter().map(|(_, output)| output);
+        let mut merged = outputs.next().expect("at least one identity");
+
+        for output in outputs {
+            for param in output.found_params {
+                if !merged.found_params.contains_name(&param.name) {
+                    merged.found_params.push(param);
+                }
+            }
+        }
+
+        merged.roles = Some(matrix);
+        merged
+    }
+
+    /// rows with parameters that behave differently per role
+    pub fn different(&self) -> impl Iterator<Item = &RoleRow> {
+        self.parameters.iter().filter(|x| x.is_different())
+    }
+}
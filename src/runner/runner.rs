@@ -1,22 +1,38 @@
-use std::{error::Error, io::{self, Write}};
+use std::{collections::HashSet, error::Error, fs, io::{self, Write}, net::SocketAddr, sync::Arc};
 
 use colored::Colorize;
 use indicatif::{ProgressBar, ProgressStyle};
+use parking_lot::Mutex;
 
 use crate::{
     config::structs::Config,
+    error,
     network::{
+        metrics::{self, NetworkEstimates, Stage},
         request::{Request, RequestDefaults},
-        response::Response,
-        utils::{create_client, InjectionPlace},
+        response::{Baseline, Response},
+        utils::{create_client, is_cors_header, is_http_token, is_security_header, is_timeout, Headers, InjectionPlace},
     },
     utils::{self, color_id, random_line, progress_style_learn_requests, is_id_important},
-    DEFAULT_PROGRESS_URL_MAX_LEN, MAX_PAGE_SIZE,
+    BODY_MAX, DEFAULT_PROGRESS_URL_MAX_LEN, MAX_PAGE_SIZE, NETWORK_PROBES, MAX_PAGE_VARIANTS, MAX_URL_LENGTH, RANDOM_LENGTH, SIZE_REJECTION_CODES, UNSAFE_METHODS, URL_LENGTH_PRECISION, VARIANT_PROBES,
 };
 
 use super::{
+    aliases::collapse_aliases,
+    categories::categorize,
+    duplicates::check_duplicate_keys,
+    combinations::{check_combinations, Combination},
+    folding::{check_header_folding, HeaderFolding},
+    memory::{self, Learned},
+    resume,
     output::RunnerOutput,
-    utils::{fold_url, replay, verify, FoundParameter, Parameters, Stable},
+    skipped::{SkipReason, SkippedWord},
+    fingerprint::{self, fingerprint, framework_words, Technology},
+    sourcemaps::source_map_params,
+    utils::{
+        fold_url, is_page_noise, replay, verify, ChunkStrategy, FoundParameter, HeaderChannel, NamingStyle, ParamSource, Parameters,
+        ReasonKind, ReflectionParam, Stable, Timings,
+    },
 };
 
 pub struct Runner<'a> {
@@ -31,20 +47,56 @@ pub struct Runner<'a> {
     /// parameters found by scraping words from the page
     pub possible_params: Vec<String>,
 
+    /// identifiers from the original sources of the page's scripts (--source-maps)
+    pub source_map_params: Vec<String>,
+
+    /// frameworks and languages detected by the initial response, learning requests and error pages
+    pub backend: Mutex<Vec<&'static Technology>>,
+
     /// the max amount of parameters to send per request
     pub max: usize,
 
     /// whether body or/and reflections are stable
     pub stable: Stable,
 
-    /// initial response to compare with
-    pub initial_response: Response<'a>,
+    /// the response to compare with
+    baseline: Arc<Baseline>,
 
     /// page's diffs for the current url|method pair
     pub diffs: Vec<String>,
 
     /// progress bar object to print progress bar & found parameters
     pub progress_bar: &'a ProgressBar,
+
+    /// response times of learning requests
+    pub baseline_times: Vec<u128>,
+
+    /// response times of requests with parameters from the list
+    pub chunk_times: Mutex<Vec<u128>>,
+
+    /// the maximum url length accepted by the target. None in case it wasn't detected
+    pub url_length_limit: Option<usize>,
+
+    /// the address that served the last response
+    pub remote_addr: Mutex<Option<SocketAddr>>,
+
+    /// other stable variants of the page in case it alternates between a few backends
+    pub variants: Vec<Baseline>,
+
+    /// names of found parameters written to stdout so far (--max-printed)
+    printed: Mutex<Vec<String>>,
+
+    /// words from the list that weren't checked
+    pub skipped: Mutex<Vec<SkippedWord>>,
+
+    /// whether the check was stopped because enough parameters were found (--stop-after)
+    pub stopped_early: Mutex<bool>,
+
+    /// words that shouldn't be checked: documented (--openapi) and already found ones (--recursion-depth)
+    pub excluded: HashSet<String>,
+
+    /// the naming style of the target in case the wordlist is transformed to it (--match-casing)
+    pub naming_style: Option<NamingStyle>,
 }
 
 impl<'a> Runner<'a> {
@@ -55,60 +107,129 @@ impl<'a> Runner<'a> {
         progress_bar: &'a ProgressBar,
         id: usize,
     ) -> Result<Runner<'a>, Box<dyn Error>> {
+        request_defaults.stages.set_stage(Stage::Learn);
+
         // make first request and collect some information like code, reflections, possible parameters
         // we are making another request defaults because the original one will be changed right after
         let mut temp_request_defaults = request_defaults.clone();
 
         // we need a random_parameter with a long value in order to increase accuracy while determining the default amount of reflections
-        let mut random_parameter = vec![(random_line(10), random_line(10))];
+        // strict apis may reject unknown parameters, so it can be sent separately or not sent at all
+        let (reflection_param, inline) = match config.reflection_param {
+            ReflectionParam::Inline(len) => (Some((random_line(len), random_line(len))), true),
+            ReflectionParam::Separate(len) => (Some((random_line(len), random_line(len))), false),
+            ReflectionParam::Off => (None, false),
+        };
+
+        let url = request_defaults.url_without_default_port();
 
-        temp_request_defaults
-            .parameters
-            .append(&mut random_parameter);
+        // the clean state may be reachable only at certain times, so it can be loaded from the previous run
+        // the loaded response replaces the live initial one, so reflections are counted with a separate request
+        let initial_response = if !config.baseline.is_empty() {
+            let baseline = Baseline::load(&config.baseline, &request_defaults.method, &url)?;
+            Response::from_baseline(baseline, Request::new(&temp_request_defaults, vec![]))
+        } else {
+            if inline {
+                temp_request_defaults.parameters.push(reflection_param.clone().unwrap());
+            }
+
+            // the response to the request for cookies is reused within --cache-ttl
+            temp_request_defaults.cache.send(Request::new(&temp_request_defaults, vec![])).await?
+        };
+        let inline = inline && config.baseline.is_empty();
 
-        let initial_response = Request::new(&temp_request_defaults, vec![]).send().await?;
+        // words scraped from the page without noise like hashes and tokens
+        let mut page_params: Vec<String> = initial_response
+            .get_possible_parameters()
+            .into_iter()
+            .filter(|x| !is_page_noise(x))
+            .collect();
+
+        if config.max_page_params != 0 {
+            page_params.truncate(config.max_page_params);
+        }
 
         // add possible parameters to the list of parameters in case the injection place is not headers
-        let possible_params = if request_defaults.injection_place != InjectionPlace::Headers {
-            initial_response.get_possible_parameters()
+        let possible_params = if !matches!(
+            request_defaults.injection_place,
+            InjectionPlace::Headers | InjectionPlace::HeaderName | InjectionPlace::Method
+        ) {
+            page_params
+        } else {
+            Vec::new()
+        };
+
+        let source_map_params = if config.source_maps
+            && !matches!(
+                request_defaults.injection_place,
+                InjectionPlace::Headers | InjectionPlace::HeaderName | InjectionPlace::Method
+            )
+        {
+            source_map_params(request_defaults, &initial_response).await
         } else {
             Vec::new()
         };
 
+        let backend = fingerprint(&initial_response);
+
         // find how many times was the random parameter reflected
-        request_defaults.amount_of_reflections =
-            initial_response.count(&temp_request_defaults.parameters.first().unwrap().1);
-
-        // some "magic" to be able to return initial_response
-        // otherwise throws lifetime errors
-        // turns out you can't simple do 'initial_response.request = None'.
-        let initial_response = Response {
-            time: initial_response.time,
-            code: initial_response.code,
-            headers: initial_response.headers,
-            text: initial_response.text,
-            reflected_parameters: initial_response.reflected_parameters,
-            request: None,
-            http_version: initial_response.http_version,
+        request_defaults.amount_of_reflections = match reflection_param {
+            Some((_, value)) if inline => initial_response.count(&value),
+            Some((key, value)) => {
+                let mut temp_request_defaults = request_defaults.clone();
+                temp_request_defaults.parameters.push((key, value.clone()));
+
+                Request::new(&temp_request_defaults, vec![]).send().await?.count(&value)
+            }
+            None => 0,
         };
 
+        if !config.save_baseline.is_empty() {
+            initial_response.baseline().save(&config.save_baseline, &request_defaults.method, &url)?;
+        }
+
+        let baseline = initial_response.baseline();
+        let remote_addr = baseline.remote_addr;
+
         Ok(Runner {
             config,
             request_defaults: request_defaults.clone(),
             possible_params,
+            source_map_params,
+            backend: Mutex::new(backend),
             max: 0, //to be filled later, in stability-checker()
             stable: Default::default(),
-            initial_response,
+            baseline: Arc::new(baseline),
             diffs: Vec::new(),
             progress_bar,
             id,
+            baseline_times: Vec::new(),
+            chunk_times: Mutex::new(Vec::new()),
+            url_length_limit: None,
+            remote_addr: Mutex::new(remote_addr),
+            variants: Vec::new(),
+            printed: Mutex::new(Vec::new()),
+            skipped: Mutex::new(Vec::new()),
+            stopped_early: Mutex::new(false),
+            excluded: HashSet::new(),
+            naming_style: None,
         })
     }
 
+    /// words that shouldn't be checked in addition to the ones filtered by default
+    pub fn excluding(mut self, words: &[String]) -> Self {
+        self.excluded.extend(words.iter().cloned());
+        self
+    }
+
     /// actually runs the runner
     pub async fn run(mut self, params: &mut Vec<String>) -> Result<RunnerOutput, Box<dyn Error>> {
         self.write_banner_url();
 
+        if let Some(addr) = self.baseline().remote_addr {
+            utils::info(self.config, self.id, self.progress_bar, "info", format!("The baseline was served by {}", addr));
+        }
+
         // makes a few request to check page's behavior
         self.stability_checker().await?;
 
@@ -122,17 +243,107 @@ impl<'a> Runner<'a> {
             );
         }
 
-        // add only unique possible params to the vec of all params (the tool works properly only with unique parameters)
-        // less efficient than making it within the sorted vec but I want to preserve the order
-        for param in self.possible_params.iter() {
-            if !params.contains(param) {
-                params.push(param.to_owned());
+        // names already used by the target (scraped from the page and the query) tell how parameters are usually named there
+        if matches!(
+            self.request_defaults.injection_place,
+            InjectionPlace::Path | InjectionPlace::Body | InjectionPlace::HeaderValue | InjectionPlace::Cookie
+        ) {
+            let query_names = self.request_defaults.query_names();
+
+            if let Some(style) = NamingStyle::detect(self.possible_params.iter().chain(query_names.iter())) {
+                if self.config.match_casing {
+                    self.naming_style = Some(style);
+                }
+
+                utils::info(
+                    self.config,
+                    self.id,
+                    self.progress_bar,
+                    "info",
+                    format!(
+                        "The target uses {:?} case names{}",
+                        style,
+                        if self.config.match_casing { ". The wordlist was transformed to match" } else { ". Use --match-casing to transform the wordlist" }
+                    ),
+                );
             }
         }
 
+        *params = self.filter_words(std::mem::take(params));
+
+        let backend = self.backend.lock().clone();
+
+        if !backend.is_empty() {
+            utils::info(
+                self.config,
+                self.id,
+                self.progress_bar,
+                "backend",
+                backend.iter().map(|x| x.name).collect::<Vec<&str>>().join(", "),
+            );
+        }
+
+        // parameters handled by the framework itself go before the wordlist
+        if self.config.framework_words
+            && !backend.is_empty()
+            && !matches!(
+                self.request_defaults.injection_place,
+                InjectionPlace::Headers | InjectionPlace::HeaderName | InjectionPlace::Method | InjectionPlace::PathSegment
+            )
+        {
+            // --required parameters and --keep-query names can't be checked either
+            let words = self.filter_words(framework_words(&backend));
+
+            params.retain(|x| !words.contains(x));
+            params.splice(0..0, words);
+        }
+
+        // identifiers from the original sources are the most likely ones to exist, so they go first
+        if !self.source_map_params.is_empty() {
+            utils::info(
+                self.config,
+                self.id,
+                self.progress_bar,
+                "info",
+                format!("{} parameters were taken from source maps", self.source_map_params.len()),
+            );
+
+            params.retain(|x| !self.source_map_params.contains(x));
+            params.splice(0..0, self.source_map_params.iter().cloned());
+        }
+
+        // words from the page are checked as a separate segment after the wordlist
+        // only unique ones are added (the tool works properly only with unique parameters)
+        // less efficient than making it within the sorted vec but I want to preserve the order
+        let page_params: Vec<String> = self
+            .possible_params
+            .iter()
+            .filter(|x| !params.contains(x))
+            .cloned()
+            .collect();
+
+        if !page_params.is_empty() {
+            utils::info(
+                self.config,
+                self.id,
+                self.progress_bar,
+                "info",
+                format!("{} parameters were taken from the page", page_params.len()),
+            );
+        }
+
+        params.extend(page_params.iter().cloned());
+
         // try to find existing parameters from the list
-        let (diffs, mut found_params) = if !params.is_empty() {
-            self.check_parameters(params).await?
+        self.request_defaults.stages.set_stage(Stage::Fuzz);
+
+        let (diffs, mut found_params) = if !params.is_empty() || self.config.stream_wordlist {
+            let checked = self.check_parameters(params).await?;
+
+            // the server may ask to slow down only under the load of the check
+            memory::remember_delay(&self.request_defaults, self.request_defaults.stages.longest_wait());
+
+            checked
         } else {
             utils::info(
                 self.config,
@@ -144,22 +355,57 @@ impl<'a> Runner<'a> {
             (Vec::new(), Vec::new())
         };
 
+        // the flag is reset, so custom parameters are still checked
+        let stopped_early = std::mem::take(&mut *self.stopped_early.lock());
+
+        if stopped_early {
+            utils::info(
+                self.config,
+                self.id,
+                self.progress_bar,
+                "info",
+                format!("Stopped after {} parameters were found", found_params.len()),
+            );
+        }
+
         self.check_non_random_parameters(&mut found_params).await?;
 
         // remove duplicates
         let mut found_params = found_params.process(self.request_defaults.injection_place);
 
+        for param in found_params.iter_mut() {
+            if self.source_map_params.contains(&param.name) {
+                param.source = ParamSource::SourceMap;
+            } else if page_params.contains(&param.name) {
+                param.source = ParamSource::Page;
+            }
+
+            param.category = categorize(&self.config.categories, &param.reason_kind, &param.name, param.status, param.label.as_deref())
+                .map(|x| x.name.clone());
+        }
+
+        // no more requests are sent after Ctrl+C, so parameters are returned unverified
+        if self.request_defaults.state.is_stopped() {
+            let timings = Timings::new(&self.baseline_times, &self.chunk_times.lock());
+            let mut runner_output = RunnerOutput::new(&self.request_defaults, &self.baseline(), found_params, timings);
+            runner_output.skipped = std::mem::take(&mut *self.skipped.lock());
+            runner_output.stopped_after = stopped_early.then_some(self.config.stop_after);
+            runner_output.backend = self.backend_names();
+
+            return Ok(runner_output);
+        }
+
+        self.request_defaults.stages.set_stage(Stage::Verify);
+
         // verify found parameters
         if self.config.verify {
-            found_params = if let Ok(filtered_params) = verify(
-                &self.initial_response,
-                &self.request_defaults,
-                &found_params,
-                &diffs,
-                &self.stable,
-            )
-            .await
-            {
+            let verified = if self.config.verify_proxy.is_empty() {
+                verify(&self.baseline(), &self.request_defaults, &found_params, &diffs, &self.stable).await
+            } else {
+                self.verify_through_proxy(&found_params, &diffs).await
+            };
+
+            found_params = if let Ok(filtered_params) = verified {
                 filtered_params
             } else {
                 utils::info(
@@ -173,10 +419,21 @@ impl<'a> Runner<'a> {
             };
         }
 
+        // collapse parameters that are accepted under different names
+        if !self.config.keep_aliases
+            && found_params.len() > 1
+            && !matches!(
+                self.request_defaults.injection_place,
+                InjectionPlace::PathSegment | InjectionPlace::HeaderName | InjectionPlace::Method
+            )
+        {
+            found_params = self.collapse_aliases(found_params, &diffs).await;
+        }
+
         // replay request with found parameters via another proxy
         if !self.config.replay_proxy.is_empty() {
 
-            let client = match create_client(self.config, true) {
+            let client = match create_client(self.config, true, None) {
                 Ok(val) => Some(val),
                 Err(err) => {
                     utils::info(
@@ -210,161 +467,670 @@ impl<'a> Runner<'a> {
             }
         }
 
-        Ok(RunnerOutput::new(
-            &self.request_defaults,
-            &self.initial_response,
-            found_params,
-        ))
-    }
-
-    /// check parameters with non random values
-    async fn check_non_random_parameters(
-        &self,
-        found_params: &mut Vec<FoundParameter>,
-    ) -> Result<(), Box<dyn Error>> {
-        if !self.request_defaults.disable_custom_parameters {
-            let mut custom_parameters = self.config.custom_parameters.clone();
-            let mut params = Vec::new();
-
-            // in a loop check common parameters like debug, admin, .. with common values true, 1, false..
-            // until there's no values left
-            loop {
-                for (k, v) in custom_parameters.iter_mut() {
-                    //do not request parameters that already have been found
-                    if found_params
-                        .iter()
-                        .map(|x| x.name.split('=').next().unwrap())
-                        .any(|x| x == k)
-                    {
-                        continue;
-                    }
-
-                    if !v.is_empty() {
-                        params.push([k.as_str(), "=", v.pop().unwrap().as_str()].concat());
-                    }
-                }
-
-                if params.is_empty() {
-                    break;
-                }
+        // ids next to the ones from the path are separate endpoints rather than segments
+        if self.request_defaults.injection_place == InjectionPlace::PathSegment {
+            found_params.append(&mut self.check_path_ids(&diffs).await);
+        }
 
-                found_params.append(&mut self.check_parameters(&params).await?.1);
-                params.clear();
+        // reveals first-wins vs last-wins parsing of repeated keys
+        if self.config.duplicate_keys
+            && matches!(
+                self.request_defaults.injection_place,
+                InjectionPlace::Path | InjectionPlace::Body | InjectionPlace::Cookie
+            )
+        {
+            if let Err(err) = check_duplicate_keys(&self.baseline(), &self.request_defaults, &mut found_params, &diffs).await {
+                utils::info(
+                    self.config,
+                    self.id,
+                    self.progress_bar,
+                    "~",
+                    format!("was unable to check duplicate keys: {}", err),
+                );
             }
         }
 
-        Ok(())
-    }
-
-    /// makes several requests in order to learn how the page behaves
-    /// tries to increase the max amount of parameters per request in case the default value not changed
-    async fn stability_checker(&mut self) -> Result<(), Box<dyn Error>> {
-        // guess or get from the user the amount of parameters to send per request
-        let default_max = match self.config.max {
-            Some(var) => var as isize,
-            None => match self.request_defaults.injection_place {
-                InjectionPlace::Body => -512,
-                InjectionPlace::Path => self.try_to_guess_the_right_max_for_query().await?,
-                InjectionPlace::Headers => -64,
-                InjectionPlace::HeaderValue => -64,
-            },
+        let combinations = if self.config.combinations > 1
+            && found_params.len() > 1
+            && !matches!(
+                self.request_defaults.injection_place,
+                InjectionPlace::PathSegment | InjectionPlace::HeaderName | InjectionPlace::Method
+            )
+        {
+            self.combinations(&found_params, &diffs).await
+        } else {
+            Vec::new()
         };
 
-        self.max = default_max.unsigned_abs();
+        let header_folding = if self.config.header_folding
+            && self.request_defaults.injection_place == InjectionPlace::Headers
+            && !found_params.is_empty()
+        {
+            self.header_folding(&found_params, &diffs).await
+        } else {
+            Vec::new()
+        };
 
-        // make a few requests and collect all persistent diffs, check for stability
-        self.empty_reqs().await?;
+        let mut timings = Timings::new(&self.baseline_times, &self.chunk_times.lock());
 
-        if self.config.reflected_only && !self.stable.reflections {
-            Err("Reflections are not stable")?;
+        if self.config.network_estimates {
+            timings.network_estimates = self.network_estimates().await;
         }
 
-        // check whether it is possible to use 192 or 256 params in a single request instead of 128 default
-        if default_max == -128 {
-            self.try_to_increase_max().await?;
+        if self.config.verbose > 1 {
+            utils::info(
+                self.config,
+                self.id,
+                self.progress_bar,
+                "timings",
+                format!(
+                    "p50 {} ms ({:+}), p95 {} ms ({:+})",
+                    timings.chunks_p50, timings.p50_delta, timings.chunks_p95, timings.p95_delta
+                ),
+            );
         }
 
-        Ok(())
-    }
-
-    /// makes first requests and checks page behavior
-    /// fills self.diffs and self.stable
-    pub async fn empty_reqs(&mut self) -> Result<(), Box<dyn Error>> {
-        let mut stable = Stable {
-            body: true,
-            reflections: true,
-        };
-        let mut diffs: Vec<String> = Vec::new();
-
-        // set up progress bar
-        self.prepare_progress_bar(progress_style_learn_requests(self.config), self.config.learn_requests_count);
-
-        for _ in 0..self.config.learn_requests_count {
-            // to increase stability
-            tokio::time::sleep(tokio::time::Duration::from_millis(150)).await;
+        if self.config.verbose > 1 {
+            let pool = self.request_defaults.pool.metrics();
 
-            let response = Request::new_random(&self.request_defaults, self.max)
-                .send()
-                .await?;
+            utils::info(
+                self.config,
+                self.id,
+                self.progress_bar,
+                "pool",
+                format!(
+                    "{} requests over a shared client (HTTP/2: {}), up to {} in flight{}",
+                    pool.requests,
+                    pool.http2,
+                    pool.peak_in_flight,
+                    if pool.is_multiplexed() { ", multiplexed" } else { "" }
+                ),
+            );
 
-            self.progress_bar.inc(1);
+            let stages = self.request_defaults.stages.metrics();
 
-            // do not check pages >25MB because usually its just a binary file or sth
-            if response.text.len() > MAX_PAGE_SIZE && !self.config.force {
-                Err("The page's size > 25MB. Use --force flag to disable this error")?;
-            }
+            utils::info(
+                self.config,
+                self.id,
+                self.progress_bar,
+                "requests",
+                format!("{} in total: {}", stages.total(), stages),
+            );
 
-            if !response.reflected_parameters.is_empty() {
-                stable.reflections = false;
-            }
+            let hashing = self.request_defaults.hashing.metrics();
 
-            let (is_code_diff, mut new_diffs) = response.compare(&self.initial_response, &diffs)?;
+            utils::info(
+                self.config,
+                self.id,
+                self.progress_bar,
+                "hashing",
+                format!(
+                    "{} responses were identical to the baseline, {} were diffed ({} hash collisions)",
+                    hashing.hits, hashing.misses, hashing.collisions
+                ),
+            );
 
-            if is_code_diff {
-                Err("The page is not stable (code)")?
-            }
+            let traffic = self.request_defaults.traffic.metrics();
 
-            diffs.append(&mut new_diffs);
+            utils::info(
+                self.config,
+                self.id,
+                self.progress_bar,
+                "traffic",
+                format!(
+                    "{} sent, {} received ({} within the whole run)",
+                    metrics::format_bytes(traffic.sent),
+                    metrics::format_bytes(traffic.received),
+                    metrics::format_bytes(self.request_defaults.state.traffic())
+                ),
+            );
         }
 
-        // check the last time
-        let response = Request::new_random(&self.request_defaults, self.max)
-            .send()
-            .await?;
-
-        // in case the page is still different from other random ones - the body isn't stable
-        if !response
-            .compare(&self.initial_response, &diffs)?
-            .1
-            .is_empty()
-        {
+        if let Some(network) = timings.network_estimates {
             utils::info(
                 self.config,
                 self.id,
                 self.progress_bar,
-                "~",
-                "The page is not stable (body)",
+                "network",
+                format!(
+                    "probe estimates: dns {} ms, connect {} ms, tls {} ms, ttfb {} ms",
+                    network.dns, network.connect, network.tls, network.ttfb
+                ),
             );
-            stable.body = false;
         }
 
-        (self.diffs, self.stable) = (diffs, stable);
+        let mut runner_output = RunnerOutput::new(
+            &self.request_defaults,
+            &self.baseline(),
+            found_params,
+            timings,
+        );
+        runner_output.header_folding = header_folding;
+        runner_output.skipped = std::mem::take(&mut *self.skipped.lock());
+        runner_output.combinations = combinations;
+        runner_output.stopped_after = stopped_early.then_some(self.config.stop_after);
+        runner_output.backend = self.backend_names();
 
-        Ok(())
+        Ok(runner_output)
     }
 
-    /// checks whether the increasing of the amount of parameters changes the page
-    /// changes self.max in case the page is stable with more parameters per request
-    pub async fn try_to_increase_max(&mut self) -> Result<(), Box<dyn Error>> {
-
-        let delta = self.max / 2;
+    /// estimates request phases with a few separate probes. The checked requests aren't measured
+    async fn network_estimates(&self) -> Option<NetworkEstimates> {
+        let mut samples = Vec::with_capacity(NETWORK_PROBES);
 
-        let response = Request::new_random(&self.request_defaults, self.max + delta)
-            .send()
+        for _ in 0..NETWORK_PROBES {
+            match metrics::probe(self.config, &self.request_defaults).await {
+                Ok(val) => samples.push(val),
+                Err(err) => {
+                    utils::info(
+                        self.config,
+                        self.id,
+                        self.progress_bar,
+                        "~",
+                        format!("was unable to probe the network: {}", err),
+                    );
+                    return None;
+                }
+            }
+        }
+
+        Some(metrics::median(&samples))
+    }
+
+    /// requests the path with numeric ids swapped for their neighbours: /api/users/123 -> /api/users/122
+    /// ids that don't respond with 404 and change the page are reported with the path as the name
+    async fn check_path_ids(&self, diffs: &[String]) -> Vec<FoundParameter> {
+        let mut found_params = Vec::new();
+
+        for path in self.request_defaults.path_id_candidates() {
+            let mut request_defaults = self.request_defaults.clone();
+            request_defaults.path = path.clone();
+
+            let response = match Request::new(&request_defaults, Vec::new()).send().await {
+                Ok(val) if val.code != 404 => val,
+                _ => continue,
+            };
+
+            let (is_code_diff, new_diffs) = match self.compare_with_closest(&response, diffs) {
+                Ok(val) => val,
+                Err(_) => continue,
+            };
+
+            if !is_code_diff && new_diffs.is_empty() {
+                continue;
+            }
+
+            let name = path.split('?').next().unwrap_or_default();
+            let kind = if is_code_diff { ReasonKind::Code } else { ReasonKind::Text };
+
+            if let Err(err) = self.write_and_save(&response, kind.clone(), name, None) {
+                log::debug!("Unable to save the response: {}", err);
+            }
+
+            found_params.push(FoundParameter::new(name, &new_diffs, response.code, response.text.len(), response.time, kind));
+        }
+
+        found_params
+    }
+
+    /// collapses aliases and reports them. Returns found parameters as is in case of errors
+    async fn collapse_aliases(&self, found_params: Vec<FoundParameter>, diffs: &[String]) -> Vec<FoundParameter> {
+        let collapsed = match collapse_aliases(&self.baseline(), &self.request_defaults, found_params.clone(), diffs).await {
+            Ok(val) => val,
+            Err(err) => {
+                utils::info(
+                    self.config,
+                    self.id,
+                    self.progress_bar,
+                    "~",
+                    format!("was unable to check parameters for aliases: {}", err),
+                );
+                return found_params;
+            }
+        };
+
+        for param in collapsed.iter().filter(|x| !x.aliases.is_empty()) {
+            utils::info(
+                self.config,
+                self.id,
+                self.progress_bar,
+                "aliases",
+                format!("{} = {}", param.name, param.aliases.join(", ")),
+            );
+        }
+
+        collapsed
+    }
+
+    /// checks how found headers with malformed whitespace are handled and reports the results
+    async fn header_folding(&self, found_params: &[FoundParameter], diffs: &[String]) -> Vec<HeaderFolding> {
+        let results = match check_header_folding(&self.baseline(), &self.request_defaults, found_params, diffs).await {
+            Ok(val) => val,
+            Err(err) => {
+                utils::info(
+                    self.config,
+                    self.id,
+                    self.progress_bar,
+                    "~",
+                    format!("was unable to check header folding: {}", err),
+                );
+                return Vec::new();
+            }
+        };
+
+        for result in results.iter() {
+            utils::info(
+                self.config,
+                self.id,
+                self.progress_bar,
+                "folding",
+                format!("{} ({:?}): {:?} with {}", result.name, result.variant, result.outcome, result.status),
+            );
+        }
+
+        results
+    }
+
+    /// checks pairs of found parameters and reports the ones that change the page only together
+    async fn combinations(&self, found_params: &[FoundParameter], diffs: &[String]) -> Vec<Combination> {
+        let combinations =
+            match check_combinations(&self.baseline(), &self.request_defaults, found_params, diffs, self.config.combinations).await {
+                Ok(val) => val,
+                Err(err) => {
+                    utils::info(
+                        self.config,
+                        self.id,
+                        self.progress_bar,
+                        "~",
+                        format!("was unable to check combinations: {}", err),
+                    );
+                    return Vec::new();
+                }
+            };
+
+        for combination in combinations.iter() {
+            utils::info(
+                self.config,
+                self.id,
+                self.progress_bar,
+                "combined",
+                format!(
+                    "{} + {}: {} ({} new lines)",
+                    combination.params[0], combination.params[1], combination.status, combination.new_lines
+                ),
+            );
+        }
+
+        combinations
+    }
+
+    /// check parameters with non random values
+    async fn check_non_random_parameters(
+        &self,
+        found_params: &mut Vec<FoundParameter>,
+    ) -> Result<(), Box<dyn Error>> {
+        if !self.request_defaults.disable_custom_parameters {
+            self.request_defaults.stages.set_stage(Stage::Custom);
+
+            let mut custom_parameters = self.config.custom_parameters.clone();
+            let mut params = Vec::new();
+
+            // in a loop check common parameters like debug, admin, .. with common values true, 1, false..
+            // until there's no values left
+            loop {
+                for (k, v) in custom_parameters.iter_mut() {
+                    //do not request parameters that already have been found or are sent with every request
+                    if found_params
+                        .iter()
+                        .map(|x| x.name.split('=').next().unwrap())
+                        .any(|x| x == k)
+                        || self.request_defaults.parameters.contains_key(k)
+                    {
+                        continue;
+                    }
+
+                    if !v.is_empty() {
+                        params.push([k.as_str(), "=", v.pop().unwrap().as_str()].concat());
+                    }
+                }
+
+                if params.is_empty() {
+                    break;
+                }
+
+                found_params.append(&mut self.check_parameters(&params).await?.1);
+                params.clear();
+            }
+        }
+
+        Ok(())
+    }
+
+    /// makes several requests in order to learn how the page behaves
+    /// tries to increase the max amount of parameters per request in case the default value not changed
+    async fn stability_checker(&mut self) -> Result<(), Box<dyn Error>> {
+        let learning = self.config.max.is_none() && self.config.chunk_strategy != ChunkStrategy::Single;
+
+        // previous urls of the host already went through the learning
+        let learned = if learning { memory::recall(&self.request_defaults) } else { None };
+
+        if let Some(learned) = learned {
+            utils::info(
+                self.config,
+                self.id,
+                self.progress_bar,
+                "info",
+                "Using the amount of parameters per request learned on a previous url of the host",
+            );
+
+            if learned.delay > self.request_defaults.delay {
+                utils::info(
+                    self.config,
+                    self.id,
+                    self.progress_bar,
+                    "info",
+                    format!("Using the delay of {}ms the server asked for on a previous url of the host", learned.delay.as_millis()),
+                );
+
+                self.request_defaults.delay = learned.delay;
+            }
+        }
+
+        // some servers and gateways reject or silently truncate long urls
+        if self.request_defaults.injection_place == InjectionPlace::Path && self.config.chunk_strategy != ChunkStrategy::Single {
+            self.url_length_limit = match learned {
+                Some(learned) => learned.url_length_limit,
+                None => self.detect_url_length_limit().await,
+            };
+
+            if let Some(limit) = self.url_length_limit {
+                utils::info(
+                    self.config,
+                    self.id,
+                    self.progress_bar,
+                    "info",
+                    format!("Url length limit - {}", limit),
+                );
+            }
+        }
+
+        // guess or get from the user the amount of parameters to send per request
+        let default_max = if let Some(learned) = learned {
+            learned.max as isize
+        } else if self.config.chunk_strategy == ChunkStrategy::Single {
+            1
+        } else {
+            match self.config.max {
+                Some(var) => var as isize,
+                None => match self.request_defaults.injection_place {
+                    InjectionPlace::Body => self.try_to_guess_the_right_max_for_body().await?,
+                    InjectionPlace::Path => self.try_to_guess_the_right_max_for_query().await?,
+                    // only one segment can be checked per request
+                    InjectionPlace::PathSegment => -1,
+                    InjectionPlace::Headers => -64,
+                    InjectionPlace::HeaderValue => -64,
+                    InjectionPlace::Cookie => -64,
+                    InjectionPlace::HeaderName => -64,
+                    // only one method can be sent per request
+                    InjectionPlace::Method => -1,
+                },
+            }
+        };
+
+        self.max = default_max.unsigned_abs();
+
+        // too many headers are rejected with 431 instead of being checked
+        if learned.is_none() && self.config.max.is_none() && matches!(self.request_defaults.injection_place, InjectionPlace::Headers | InjectionPlace::HeaderName) {
+            self.fit_headers_max().await;
+        }
+
+        if learned.is_none() && self.config.max.is_some() && self.max > 1 {
+            self.check_user_max().await;
+        }
+
+        // make a few requests and collect all persistent diffs, check for stability
+        self.empty_reqs().await?;
+
+        // the page may look more stable this time, so the stability learned before the interruption is kept (--resume)
+        if !self.config.resume.is_empty() {
+            if let Some(stable) = resume::restore(&self.config.resume, &self.state_key()).and_then(|x| x.stable) {
+                self.stable.merge(stable);
+            }
+        }
+
+        // reflections may change only once in a while, so instability seen on a previous url of the host is trusted
+        if learned.is_some_and(|x| !x.reflections) && self.stable.reflections {
+            utils::info(
+                self.config,
+                self.id,
+                self.progress_bar,
+                "info",
+                "Reflections were unstable on a previous url of the host",
+            );
+
+            self.stable.reflections = false;
+        }
+
+        if self.config.reflected_only && !self.stable.reflections {
+            Err("Reflections are not stable")?;
+        }
+
+        // check whether it is possible to use 192 or 256 params in a single request instead of 128 default
+        if default_max == -128 {
+            self.try_to_increase_max().await?;
+        }
+
+        if learning && learned.is_none() {
+            memory::remember(
+                &self.request_defaults,
+                Learned {
+                    max: self.max,
+                    url_length_limit: self.url_length_limit,
+                    reflections: self.stable.reflections,
+                    delay: self.request_defaults.stages.longest_wait(),
+                },
+            );
+        }
+
+        Ok(())
+    }
+
+    /// makes first requests and checks page behavior
+    /// fills self.diffs and self.stable
+    pub async fn empty_reqs(&mut self) -> Result<(), Box<dyn Error>> {
+        let mut stable = Stable {
+            body: true,
+            reflections: self.config.reflection_param != ReflectionParam::Off,
+            unstable_headers: Vec::new(),
+        };
+        let mut diffs: Vec<String> = Vec::new();
+        let mut baseline_times = Vec::with_capacity(self.config.learn_requests_count);
+
+        // a loaded baseline (--baseline) may differ from the live page, so the dynamic parts are learned between live responses
+        let mut live_baseline: Option<Arc<Baseline>> = None;
+
+        // set up progress bar
+        self.prepare_progress_bar(progress_style_learn_requests(self.config), self.config.learn_requests_count);
+
+        for _ in 0..self.config.learn_requests_count {
+            // to increase stability
+            tokio::time::sleep(tokio::time::Duration::from_millis(150)).await;
+
+            let response = match Request::new_random(&self.request_defaults, self.max).send().await {
+                Ok(val) => val,
+                Err(err) if is_timeout(&err) => Err(format!(
+                    "The page didn't respond within {} seconds while learning. Increase --timeout in case the target is slow",
+                    self.config.timeout
+                ))?,
+                Err(err) => Err(err)?,
+            };
+
+            self.progress_bar.inc(1);
+
+            self.detect_backend(&response);
+
+            baseline_times.push(response.time);
+
+            // do not check pages >25MB because usually its just a binary file or sth
+            if response.text.len() > MAX_PAGE_SIZE && !self.config.force {
+                Err("The page's size > 25MB. Use --force flag to disable this error")?;
+            }
+
+            if !response.reflected_parameters.is_empty() {
+                stable.reflections = false;
+            }
+
+            let baseline = if self.config.baseline.is_empty() {
+                self.baseline()
+            } else {
+                Arc::clone(live_baseline.get_or_insert_with(|| Arc::new(response.baseline())))
+            };
+
+            for (predicate, kind) in self.header_channels() {
+                if !stable.unstable_headers.contains(&kind) && !response.headers_delta(&baseline, predicate).is_empty() {
+                    utils::info(
+                        self.config,
+                        self.id,
+                        self.progress_bar,
+                        "~",
+                        format!("The page is not stable ({:?} headers)", kind),
+                    );
+                    stable.unstable_headers.push(kind);
+                }
+            }
+
+            let (is_code_diff, mut new_diffs) = response.compare(&baseline, &diffs)?;
+
+            if is_code_diff {
+                Err("The page is not stable (code)")?
+            }
+
+            diffs.append(&mut new_diffs);
+        }
+
+        // the page may alternate between a few stable variants (A/B, blue/green)
+        // random values can't be reflected, otherwise every response is different
+        if !diffs.is_empty() && self.request_defaults.amount_of_reflections == 0 && live_baseline.is_none() {
+            if let Some((variants, variant_diffs)) = self.detect_variants().await? {
+                utils::info(
+                    self.config,
+                    self.id,
+                    self.progress_bar,
+                    "~",
+                    format!(
+                        "The page alternates between {} variants. Responses are compared with the closest one",
+                        variants.len() + 1
+                    ),
+                );
+
+                self.variants = variants;
+                diffs = variant_diffs;
+            }
+        }
+
+        // check the last time
+        let response = Request::new_random(&self.request_defaults, self.max)
+            .send()
+            .await?;
+
+        let last_diffs = match &live_baseline {
+            Some(baseline) => response.compare(baseline, &diffs)?.1,
+            None => self.compare_with_closest(&response, &diffs)?.1,
+        };
+
+        // in case the page is still different from other random ones - the body isn't stable
+        if !last_diffs.is_empty() {
+            utils::info(
+                self.config,
+                self.id,
+                self.progress_bar,
+                "~",
+                "The page is not stable (body)",
+            );
+            stable.body = false;
+        }
+
+        (self.diffs, self.stable, self.baseline_times) = (diffs, stable, baseline_times);
+
+        Ok(())
+    }
+
+    /// sends a few more random requests and groups them by body
+    /// returns other variants and diffs within variants in case every variant appeared at least twice
+    async fn detect_variants(&self) -> Result<Option<(Vec<Baseline>, Vec<String>)>, Box<dyn Error>> {
+        let mut variants: Vec<(Baseline, usize)> = vec![(self.baseline().as_ref().clone(), 1)];
+        let mut diffs: Vec<String> = Vec::new();
+
+        for _ in 0..VARIANT_PROBES {
+            tokio::time::sleep(tokio::time::Duration::from_millis(150)).await;
+
+            let response = Request::new_random(&self.request_defaults, self.max)
+                .send()
+                .await?;
+
+            let variants_len = variants.len();
+
+            match variants.iter_mut().find(|(x, _)| x.code == response.code && x.body() == response.body()) {
+                Some((variant, hits)) => {
+                    *hits += 1;
+
+                    // headers like Date still differ within the same variant
+                    let (_, mut new_diffs) = response.compare(variant, &diffs)?;
+                    diffs.append(&mut new_diffs);
+                }
+                // too many distinct responses -- the page is just unstable
+                None if variants_len == MAX_PAGE_VARIANTS => return Ok(None),
+                None => variants.push((response.baseline(), 1)),
+            }
+        }
+
+        if variants.len() > 1 && variants.iter().all(|(_, hits)| *hits >= 2) {
+            Ok(Some((variants.into_iter().skip(1).map(|(x, _)| x).collect(), diffs)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// whether the baseline or one of the variants of the page is returned with the code
+    pub fn is_page_code(&self, code: u16) -> bool {
+        self.baseline().code == code || self.variants.iter().any(|x| x.code == code)
+    }
+
+    /// compares the response with the baseline or the closest variant of the page
+    pub fn compare_with_closest(
+        &self,
+        response: &Response,
+        diffs: &[String],
+    ) -> Result<(bool, Vec<String>), Box<dyn Error>> {
+        let mut closest = response.compare(&self.baseline(), diffs)?;
+
+        for variant in self.variants.iter() {
+            if !closest.0 && closest.1.is_empty() {
+                break;
+            }
+
+            let result = response.compare(variant, diffs)?;
+
+            if (result.0, result.1.len()) < (closest.0, closest.1.len()) {
+                closest = result;
+            }
+        }
+
+        Ok(closest)
+    }
+
+    /// checks whether the increasing of the amount of parameters changes the page
+    /// changes self.max in case the page is stable with more parameters per request
+    pub async fn try_to_increase_max(&mut self) -> Result<(), Box<dyn Error>> {
+
+        let delta = self.max / 2;
+
+        let response = Request::new_random(&self.request_defaults, self.max + delta)
+            .send()
             .await?;
 
         let (is_code_different, new_diffs) =
-            response.compare(&self.initial_response, &self.diffs)?;
+            response.compare(&self.baseline(), &self.diffs)?;
         let mut is_the_body_the_same = true;
 
         if !new_diffs.is_empty() {
@@ -378,7 +1144,7 @@ impl<'a> Runner<'a> {
                 .await?;
 
             let (is_code_different, new_diffs) =
-                response.compare(&self.initial_response, &self.diffs)?;
+                response.compare(&self.baseline(), &self.diffs)?;
 
             if !new_diffs.is_empty() {
                 is_the_body_the_same = false;
@@ -395,49 +1161,400 @@ impl<'a> Runner<'a> {
     }
 
     /// tries to detect the right amount of parameters that can be send per request in query
+    /// bisects between the accepted and the rejected amount in case 128 parameters are rejected (414, 400, dropped connections)
     /// TODO maybe detect based on reflection as well
     pub async fn try_to_guess_the_right_max_for_query(&mut self) -> Result<isize, Box<dyn Error>> {
+        let code = match self.rejection(128).await {
+            Some(code) => code,
+            // the choosen max is okay
+            None => return Ok(-128),
+        };
 
-        let mut max = 128;
+        if self.control_response().await?.code != self.baseline().code {
+            Err(error::Error::UnstablePage("code".to_string()))?
+        };
 
-        let mut response = match Request::new_random(&self.request_defaults, max)
-            .send()
-            .await {
-                Ok(val) => val,
-                // some servers may cut connection in case url is too long
-                // that's why we assume that this request returned response with status code = 0.
-                Err(_) => {
-                    Request::empty_response(Request::new_random(&self.request_defaults, 0))
-                }
+        let max = self.bisect_max(128).await;
+
+        if max == 0 {
+            Err("Unable to guess the max amount of parameters per request. Try to use --max command line argument.")?
+        }
+
+        self.info_rejected(128, code, max);
+
+        Ok(max as isize *-1)
+    }
+
+    /// detects the max amount of keys within the body, e.g. apis that reject json objects with more than N unknown keys
+    pub async fn try_to_guess_the_right_max_for_body(&self) -> Result<isize, Box<dyn Error>> {
+        let code = match self.rejection(BODY_MAX).await {
+            Some(code) => code,
+            None => return Ok(-(BODY_MAX as isize)),
         };
 
-        loop {
-            // the choosen max is okay
-            if self.initial_response.code == response.code {
-                break
+        if self.control_response().await?.code != self.baseline().code {
+            Err(error::Error::UnstablePage("code".to_string()))?
+        }
+
+        let accepted = self.bisect_max(BODY_MAX).await;
+
+        if accepted == 0 {
+            Err("The body doesn't accept unknown keys. Try to use --max command line argument.")?
+        }
+
+        self.info_rejected(BODY_MAX, code, accepted);
+
+        Ok(-(accepted as isize))
+    }
+
+    /// the page without parameters. Reused within --cache-ttl, so repeated checks of the baseline code don't refetch it
+    async fn control_response(&self) -> Result<Response, Box<dyn Error>> {
+        self.request_defaults.cache.send(Request::new_random(&self.request_defaults, 0)).await
+    }
+
+    /// bisects between 0 and the rejected amount of parameters like detect_url_length_limit()
+    /// returns the largest accepted amount, 0 in case even a single parameter is rejected
+    async fn bisect_max(&self, rejected: usize) -> usize {
+        let mut accepted = 0;
+        let mut rejected = rejected;
+
+        while rejected > accepted + 1 {
+            let middle = (accepted + rejected) / 2;
+
+            if self.rejection(middle).await.is_none() {
+                accepted = middle;
+            } else {
+                rejected = middle;
+            }
+        }
+
+        accepted
+    }
+
+    /// makes a request with the specified amount of random parameters
+    /// returns the code in case it differs from the baseline one, 0 in case the connection was dropped
+    async fn rejection(&self, amount: usize) -> Option<u16> {
+        match Request::new_random(&self.request_defaults, amount).send().await {
+            Ok(response) if response.code == self.baseline().code => None,
+            Ok(response) => {
+                // error pages usually reveal the framework
+                self.detect_backend(&response);
+                Some(response.code)
             }
+            // some servers may cut connection in case the url or the body is too large
+            Err(_) => Some(0),
+        }
+    }
+
+    fn info_rejected(&self, amount: usize, code: u16, max: usize) {
+        let code = if code == 0 { "dropped connection".to_string() } else { code.to_string() };
+
+        utils::info(
+            self.config,
+            self.id,
+            self.progress_bar,
+            "~",
+            format!("{} parameters per request are rejected ({}). Amount of parameters per request - {}", amount, code, max),
+        );
+    }
+
+    /// a too big --max makes the server reject every request, so no parameter can be found
+    /// lowers the max in case requests are rejected because of their size (400, 413, 414, 431)
+    async fn check_user_max(&mut self) {
+        let code = match self.rejection(self.max).await {
+            Some(code) if SIZE_REJECTION_CODES.contains(&code) => code,
+            _ => return,
+        };
+
+        let max = self.bisect_max(self.max).await.max(1);
+
+        utils::info(
+            self.config,
+            self.id,
+            self.progress_bar,
+            "~",
+            format!("--max {} is rejected ({}). Amount of parameters per request - {}", self.max, code, max),
+        );
 
-            if Request::new_random(&self.request_defaults, 0).send().await?.code != self.initial_response.code {
-                Err("The page became unstable (code)")?
+        self.max = max;
+    }
+
+    /// removes words that can't or shouldn't be checked and rewrites the rest in the target's naming style
+    /// applied to the whole list as well as to every part of the streamed wordlist
+    pub fn filter_words(&self, words: Vec<String>) -> Vec<String> {
+        // existing values shouldn't be overwritten or duplicated
+        let query_names = if self.config.keep_query && self.request_defaults.injection_place == InjectionPlace::Path {
+            self.request_defaults.query_names()
+        } else {
+            Vec::new()
+        };
+
+        // words with spaces or other special chars can't be placed within methods or header names
+        let http_tokens_only = matches!(self.request_defaults.injection_place, InjectionPlace::HeaderName | InjectionPlace::Method);
+        let methods_only = self.request_defaults.injection_place == InjectionPlace::Method;
+
+        let mut unique = HashSet::with_capacity(words.len());
+        let mut filtered = Vec::with_capacity(words.len());
+        let mut invalid = Vec::new();
+        let mut unsafe_methods = Vec::new();
+
+        for word in words {
+            let word = match self.naming_style {
+                Some(style) => style.apply(&word),
+                None => word,
             };
 
-            max /= 2;
+            // parameters sent with every request (--required) can't be checked
+            if query_names.contains(&word)
+                || self.request_defaults.parameters.contains_key(&word)
+                || self.excluded.contains(&word)
+                || !unique.insert(word.clone())
+            {
+                continue;
+            }
 
-            if max < 4 {
-                Err("Unable to guess the max amount of parameters per request. Try to use --max command line argument.")?
+            if http_tokens_only && !is_http_token(&word) {
+                invalid.push(word);
+            } else if methods_only
+                && self.config.safe
+                && UNSAFE_METHODS.contains(&self.request_defaults.method.replace("%s", &word).to_uppercase().as_str())
+            {
+                unsafe_methods.push(word);
+            } else {
+                filtered.push(word);
             }
+        }
 
-            response = match Request::new_random(&self.request_defaults, max)
-                .send()
-                .await {
-                    Ok(val) => val,
-                    Err(_) => {
-                        Request::empty_response(Request::new_random(&self.request_defaults, 0))
-                    }
-            };
+        let mut skipped = self.skipped.lock();
+        skipped.extend(SkippedWord::many(&invalid, SkipReason::InvalidCharacters));
+        skipped.extend(SkippedWord::many(&unsafe_methods, SkipReason::UnsafeMethod));
+
+        filtered
+    }
+
+    fn backend_names(&self) -> Vec<String> {
+        self.backend.lock().iter().map(|x| x.name.to_string()).collect()
+    }
+
+    /// the initial response may not reveal the backend, unlike cookies set later or error pages
+    fn detect_backend(&self, response: &Response) {
+        fingerprint::merge(&mut self.backend.lock(), fingerprint(response));
+    }
+
+    /// the response every other response is compared with
+    pub fn baseline(&self) -> Arc<Baseline> {
+        Arc::clone(&self.baseline)
+    }
+
+    /// reports when another address started serving responses (fail over between resolved addresses)
+    pub fn track_remote_addr(&self, response: &Response) {
+        let addr = match response.remote_addr {
+            Some(addr) => addr,
+            None => return,
+        };
+
+        let mut remote_addr = self.remote_addr.lock();
+
+        if *remote_addr != Some(addr) {
+            if let Some(old_addr) = *remote_addr {
+                utils::info(
+                    self.config,
+                    self.id,
+                    self.progress_bar,
+                    "~",
+                    format!("Switched from {} to {}", old_addr, addr),
+                );
+            }
+
+            *remote_addr = Some(addr);
         }
+    }
 
-        Ok(max as isize *-1)
+    /// verifies found parameters from the second vantage point (--verify-proxy)
+    /// the page may look differently from there, so its own initial response is used
+    async fn verify_through_proxy(
+        &self,
+        found_params: &Vec<FoundParameter>,
+        diffs: &Vec<String>,
+    ) -> Result<Vec<FoundParameter>, Box<dyn Error>> {
+        let config = Config {
+            proxy: self.config.verify_proxy.clone(),
+            ..self.config.clone()
+        };
+
+        let mut request_defaults = self.request_defaults.clone();
+        request_defaults.client = create_client(&config, false, None)?;
+
+        // responses through the verify proxy can't be mixed with the direct ones
+        request_defaults.cache = Default::default();
+
+        let baseline = Request::new(&request_defaults, Vec::new()).send().await?.baseline();
+
+        if baseline.code != self.baseline().code {
+            utils::info(
+                self.config,
+                self.id,
+                self.progress_bar,
+                "~",
+                format!(
+                    "The initial response code differs through the verify proxy: {} -> {}",
+                    self.baseline().code(),
+                    baseline.code()
+                ),
+            );
+        }
+
+        verify(&baseline, &request_defaults, found_params, diffs, &self.stable).await
+    }
+
+    /// appends the evaluated diff and the decision made about it to --diffs-file
+    pub fn log_diffs(&self, params: &[String], response: &Response, diffs: &[String], decision: &str) {
+        if self.config.diffs_file.is_empty() {
+            return;
+        }
+
+        let line = serde_json::json!({
+            "method": self.request_defaults.method,
+            "url": self.request_defaults.url_without_default_port(),
+            "parameters": params,
+            "code": response.code,
+            "initial_code": self.baseline().code,
+            "diffs": diffs,
+            "decision": decision,
+        });
+
+        let result = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.config.diffs_file)
+            .and_then(|mut file| writeln!(file, "{}", line));
+
+        if let Err(err) = result {
+            utils::info(
+                self.config,
+                self.id,
+                self.progress_bar,
+                "~",
+                format!("Unable to write to {}: {}", self.config.diffs_file, err),
+            );
+        }
+    }
+
+    /// writes about the found parameter unless too many parameters were written already
+    /// a huge amount of findings usually means a broken detection, so a single warning with a sample is printed instead
+    pub fn write_and_save(
+        &self,
+        response: &Response,
+        reason_kind: ReasonKind,
+        parameter: &str,
+        diff: Option<&str>,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut printed = self.printed.lock();
+
+        if self.config.max_printed == 0 || printed.len() < self.config.max_printed {
+            printed.push(parameter.to_string());
+            drop(printed);
+
+            return response.write_and_save(
+                self.id,
+                self.config,
+                &self.baseline(),
+                reason_kind,
+                parameter,
+                diff,
+                self.progress_bar,
+            );
+        }
+
+        if printed.len() == self.config.max_printed {
+            printed.push(parameter.to_string());
+
+            utils::info(
+                self.config,
+                self.id,
+                self.progress_bar,
+                "~",
+                format!(
+                    "More than {} parameters were found (e.g. {}). Probably the page echoes everything or the detection is broken. Further parameters aren't printed but are still saved to the output",
+                    self.config.max_printed,
+                    printed.iter().take(5).cloned().collect::<Vec<String>>().join(", ")
+                ),
+            );
+        }
+
+        response.save(self.config, parameter)
+    }
+
+    /// returns header channels enabled by the user
+    pub fn header_channels(&self) -> Vec<HeaderChannel> {
+        let mut channels: Vec<HeaderChannel> = Vec::new();
+
+        if self.config.cors {
+            channels.push((is_cors_header, ReasonKind::Cors));
+        }
+
+        if self.config.security_headers {
+            channels.push((is_security_header, ReasonKind::Security));
+        }
+
+        channels
+    }
+
+    /// bisects the amount of headers per request in case the target responds with 431 (Request Header Fields Too Large)
+    async fn fit_headers_max(&mut self) {
+        if self.baseline().code == 431 || self.rejection(self.max).await != Some(431) {
+            return;
+        }
+
+        self.max = self.bisect_max(self.max).await.max(1);
+
+        utils::info(
+            self.config,
+            self.id,
+            self.progress_bar,
+            "~",
+            format!("Too many headers (431). Amount of headers per request - {}", self.max),
+        );
+    }
+
+    /// binary searches the maximum url length that doesn't change the page's code (414, 400, waf pages)
+    /// returns None in case urls up to MAX_URL_LENGTH are accepted
+    pub async fn detect_url_length_limit(&self) -> Option<usize> {
+        if self.is_url_length_accepted(MAX_URL_LENGTH).await {
+            return None;
+        }
+
+        // the url without parameters was accepted within the initial request
+        let mut accepted = self.request_defaults.url().len() - "%s".len();
+        let mut rejected = MAX_URL_LENGTH;
+
+        while rejected > accepted + URL_LENGTH_PRECISION {
+            let middle = (accepted + rejected) / 2;
+
+            if self.is_url_length_accepted(middle).await {
+                accepted = middle;
+            } else {
+                rejected = middle;
+            }
+        }
+
+        Some(accepted)
+    }
+
+    /// makes a request with a single long parameter to make the url of the specified length
+    async fn is_url_length_accepted(&self, length: usize) -> bool {
+        let key = random_line(RANDOM_LENGTH);
+        let base_len = self.request_defaults.url().len() - "%s".len();
+        let value_len = length.saturating_sub(base_len + key.len() + 1);
+
+        match Request::new(&self.request_defaults, vec![format!("{}={}", key, random_line(value_len))])
+            .send()
+            .await
+        {
+            Ok(response) => response.code == self.baseline().code,
+            // some servers may cut connection in case url is too long
+            Err(_) => false,
+        }
     }
 
     pub fn prepare_progress_bar(&self, sty: ProgressStyle, length: usize) {
@@ -474,6 +1591,9 @@ impl<'a> Runner<'a> {
     }
 
     pub fn write_banner_url(&self) {
+        if self.config.json_only {
+            return;
+        }
 
         let id = if is_id_important(self.config) {
             format!("[{}] ", color_id(self.id))
@@ -486,8 +1606,8 @@ impl<'a> Runner<'a> {
             id,
             self.request_defaults.method.blue(),
             self.request_defaults.url_without_default_port().green(),
-            self.initial_response.code(),
-            self.initial_response.text.len().to_string().green(),
+            self.baseline().code(),
+            self.baseline().text.len().to_string().green(),
             self.request_defaults
                 .amount_of_reflections
                 .to_string()
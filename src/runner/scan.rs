@@ -0,0 +1,534 @@
+use std::{cmp, error::Error, iter::FromIterator, net::SocketAddr, time::Duration};
+
+use indicatif::ProgressBar;
+use reqwest::Client;
+
+use crate::{
+    config::structs::Config,
+    network::{
+        doh,
+        nested::find_nested,
+        request::{Request, RequestDefaults},
+        utils::{create_client, is_protocol_error, Headers},
+    },
+    utils, UNSAFE_METHODS,
+};
+
+use super::{
+    dualstack::pin_address_family,
+    json_tree::discover_json_tree,
+    preflight::preflight,
+    output::{PreviousFindings, RunnerOutput},
+    pair::PairComparison,
+    roles::RoleMatrix,
+    runner::Runner,
+    skipped::save_skipped,
+    utils::{Parameters, ReasonKind},
+    warmup::warmup,
+};
+
+/// checks a single url with a single method
+/// in case --compare-url is specified, the second url is checked with the same parameters as well
+/// the client is shared between urls of a batch scan, otherwise every url gets its own one
+#[allow(clippy::too_many_arguments)]
+pub async fn scan_url(
+    config: &Config,
+    url: &str,
+    method: &str,
+    params: &mut Vec<String>,
+    progress_bar: &ProgressBar,
+    id: usize,
+    previous_findings: &PreviousFindings,
+    client: Option<&Client>,
+) -> Result<RunnerOutput, Box<dyn Error>> {
+    // settings of the host from the targets file
+    let host_config = config.for_url(url);
+    let config = host_config.as_ref().unwrap_or(config);
+
+    // the body format and required parameters of the operation from the spec
+    let operation_config = config.for_operation(method, url);
+    let config = operation_config.as_ref().unwrap_or(config);
+
+    if !config.identities.is_empty() {
+        let mut outputs = Vec::with_capacity(config.identities.len());
+
+        for (name, headers) in config.identities.iter() {
+            utils::info(config, id, progress_bar, "role", format!("checking as {}", name));
+
+            let mut params = params.clone();
+            let output = scan_target(config, url, method, &mut params, headers, progress_bar, id, previous_findings, client).await?;
+
+            outputs.push((name.clone(), output));
+        }
+
+        return Ok(RoleMatrix::merge(outputs));
+    }
+
+    if config.compare_url.is_empty() {
+        return scan_target(config, url, method, params, &[], progress_bar, id, previous_findings, client).await;
+    }
+
+    // the list is changed through the run
+    let mut pair_params = params.clone();
+
+    let mut runner_output = scan_target(config, url, method, params, &[], progress_bar, id, previous_findings, client).await?;
+
+    let pair_output = scan_target(
+        config,
+        &config.compare_url,
+        method,
+        &mut pair_params,
+        &[],
+        progress_bar,
+        id,
+        previous_findings,
+        client,
+    )
+    .await?;
+
+    runner_output.pair = Some(PairComparison::new(&runner_output, &pair_output));
+
+    Ok(runner_output)
+}
+
+/// creates request defaults, gets cookies and runs the runner
+/// identity headers overwrite the headers with the same names
+#[allow(clippy::too_many_arguments)]
+async fn scan_target(
+    config: &Config,
+    url: &str,
+    method: &str,
+    params: &mut Vec<String>,
+    identity_headers: &[(String, String)],
+    progress_bar: &ProgressBar,
+    id: usize,
+    previous_findings: &PreviousFindings,
+    client: Option<&Client>,
+) -> Result<RunnerOutput, Box<dyn Error>> {
+    let mut request_defaults = RequestDefaults::from_config(config, method, url)?;
+
+    if let Some(client) = client {
+        request_defaults.client = client.clone();
+    }
+
+    if config.nested.is_empty() {
+        for nested in find_nested(url) {
+            utils::info(
+                config,
+                id,
+                progress_bar,
+                "~",
+                format!(
+                    "The value of {} looks like {}. Use --nested {} to check parameters within it",
+                    nested.name, nested.encoding, nested.name
+                ),
+            );
+        }
+    }
+
+    for (k, v) in identity_headers {
+        match request_defaults.custom_headers.get_index_case_insensitive(k) {
+            Some(index) => request_defaults.custom_headers[index].1 = v.to_owned(),
+            None => request_defaults.custom_headers.push((k.to_owned(), v.to_owned())),
+        }
+    }
+
+    // the client needs to connect to the address resolved via DNS-over-HTTPS
+    // only one address can be pinned, so the first responsive one is chosen
+    let mut pinned = None;
+
+    if !config.doh.is_empty() {
+        let addrs: Vec<SocketAddr> = doh::resolve(&config.doh, &request_defaults.host, config.timeout)
+            .await?
+            .into_iter()
+            .map(|ip| SocketAddr::new(ip, request_defaults.port))
+            .collect();
+
+        let mut pinned_addr = addrs[0];
+
+        for addr in addrs.iter() {
+            let connection = tokio::time::timeout(
+                Duration::from_secs(cmp::min(config.timeout, config.connect_timeout) as u64),
+                tokio::net::TcpStream::connect(addr),
+            )
+            .await;
+
+            if let Ok(Ok(_)) = connection {
+                pinned_addr = *addr;
+                break;
+            }
+        }
+
+        request_defaults.client = create_client(config, false, Some((&request_defaults.host, pinned_addr)))?;
+        pinned = Some(pinned_addr);
+    }
+
+    // some servers reject or mishandle HTTP/2 prior knowledge, so the whole scan is made over HTTP/1.1 instead
+    let http1_config;
+    let config = if config.http_version == Some(http::Version::HTTP_2)
+        && needs_http1(config, &request_defaults, progress_bar, id).await
+    {
+        http1_config = Config {
+            http_version: Some(http::Version::HTTP_11),
+            ..config.clone()
+        };
+
+        request_defaults.client = create_client(
+            &http1_config,
+            false,
+            pinned.map(|addr| (request_defaults.host.as_str(), addr)),
+        )?;
+
+        &http1_config
+    } else {
+        config
+    };
+
+    // the proxy resolves the host on its own and --doh pins the address already
+    if config.proxy.is_empty() && pinned.is_none() {
+        pin_address_family(config, &mut request_defaults, progress_bar, id).await?;
+    }
+
+    if !config.disable_preflight {
+        preflight(config, &request_defaults, progress_bar, id).await?;
+    }
+
+    // get cookies
+    let response = request_defaults.cache.send(Request::new(&request_defaults, Vec::new())).await?;
+
+    let sticky_cookies = sticky_cookies(config, &response.headers);
+    let sticky_headers = sticky_headers(config, &response.headers);
+    drop(response);
+
+    pin_backend(config, &mut request_defaults, sticky_cookies, sticky_headers, progress_bar, id);
+
+    if config.warmup > 0 {
+        if let Err(err) = warmup(config, &request_defaults, progress_bar, id).await {
+            utils::info(config, id, progress_bar, "~", format!("The warmup failed: {}", err));
+        }
+    }
+
+    // documented parameters are known already, so only undocumented ones are searched for
+    let documented = config.operation(method, url).map(|x| x.documented.clone()).unwrap_or_default();
+
+    run(
+        config,
+        &mut request_defaults,
+        params,
+        &documented,
+        progress_bar,
+        id,
+        previous_findings,
+    )
+    .await
+}
+
+/// whether the host should be checked over HTTP/1.1 because it doesn't speak HTTP/2 prior knowledge
+/// the host is probed once per run. Timeouts and connection errors don't cause the fallback and are reported by the preflight check
+async fn needs_http1(
+    config: &Config,
+    request_defaults: &RequestDefaults,
+    progress_bar: &ProgressBar,
+    id: usize,
+) -> bool {
+    let key = format!("{}:{}", request_defaults.host, request_defaults.port);
+
+    if let Some(fallback) = request_defaults.state.http1_fallback.lock().get(&key).copied() {
+        return fallback;
+    }
+
+    let fallback = match request_defaults.cache.send(Request::new(request_defaults, Vec::new())).await {
+        Ok(_) => false,
+        Err(err) if is_protocol_error(err.as_ref()) => {
+            utils::info(
+                config,
+                id,
+                progress_bar,
+                "~",
+                format!("The request over HTTP/2 failed ({}). Falling back to HTTP/1.1", err),
+            );
+
+            true
+        }
+        // the host may respond over HTTP/2 once it's reachable
+        Err(_) => return false,
+    };
+
+    request_defaults.state.http1_fallback.lock().insert(key, fallback);
+
+    fallback
+}
+
+/// returns methods to check the url with
+/// in case --discover-methods is specified, methods from Allow and Access-Control-Allow-Methods of the OPTIONS response are added
+pub async fn url_methods(config: &Config, url: &str, progress_bar: &ProgressBar, id: usize) -> Vec<String> {
+    // documented methods of the url from the spec
+    let mut methods = if config.operations.is_empty() {
+        config.methods.clone()
+    } else {
+        config.operations.iter().filter(|x| x.url == url).map(|x| x.method.clone()).collect()
+    };
+
+    if !config.discover_methods {
+        return methods;
+    }
+
+    let response = match RequestDefaults::from_config(config, "OPTIONS", url) {
+        Ok(request_defaults) => Request::new(&request_defaults, Vec::new()).send().await.map(|x| x.headers),
+        Err(err) => Err(err),
+    };
+
+    let headers = match response {
+        Ok(val) => val,
+        Err(err) => {
+            utils::info(config, id, progress_bar, "~", format!("Unable to discover methods: {}", err));
+            return methods;
+        }
+    };
+
+    let discovered = parse_allowed_methods(&headers);
+
+    utils::info(
+        config,
+        id,
+        progress_bar,
+        "methods",
+        if discovered.is_empty() {
+            "OPTIONS didn't return allowed methods".to_string()
+        } else {
+            format!("allowed: {}", discovered.join(", "))
+        },
+    );
+
+    for method in discovered {
+        if config.safe && UNSAFE_METHODS.contains(&method.as_str()) {
+            utils::info(config, id, progress_bar, "~", format!("{} is skipped because of --safe", method));
+            continue;
+        }
+
+        if !methods.contains(&method) {
+            methods.push(method);
+        }
+    }
+
+    methods
+}
+
+/// parses Allow and Access-Control-Allow-Methods headers
+/// methods without parameters (OPTIONS, HEAD, TRACE, CONNECT) are skipped
+pub fn parse_allowed_methods(headers: &[(String, String)]) -> Vec<String> {
+    let mut methods: Vec<String> = Vec::new();
+
+    for (_, v) in headers
+        .iter()
+        .filter(|(k, _)| k.eq_ignore_ascii_case("allow") || k.eq_ignore_ascii_case("access-control-allow-methods"))
+    {
+        for method in v.split(',').map(|x| x.trim().to_uppercase()) {
+            if method.is_empty()
+                || !method.chars().all(|c| c.is_ascii_alphabetic())
+                || ["OPTIONS", "HEAD", "TRACE", "CONNECT"].contains(&method.as_str())
+                || methods.contains(&method)
+            {
+                continue;
+            }
+
+            methods.push(method);
+        }
+    }
+
+    methods
+}
+
+/// returns load balancer affinity cookies (--sticky-cookie) set by the response
+fn sticky_cookies(config: &Config, headers: &[(String, String)]) -> Vec<(String, String)> {
+    let mut cookies: Vec<(String, String)> = Vec::new();
+
+    for (_, v) in headers.iter().filter(|(k, _)| k.eq_ignore_ascii_case("set-cookie")) {
+        if let Some((name, value)) = v.split(';').next().unwrap_or("").split_once('=') {
+            let name = name.trim();
+
+            if config.sticky_cookies.iter().any(|x| x == name) && !cookies.iter().any(|(x, _)| x == name) {
+                cookies.push((name.to_string(), value.trim().to_string()));
+            }
+        }
+    }
+
+    cookies
+}
+
+/// returns headers (--sticky-header) from the response
+fn sticky_headers(config: &Config, headers: &[(String, String)]) -> Vec<(String, String)> {
+    let mut sticky_headers: Vec<(String, String)> = Vec::new();
+
+    for (k, v) in headers.iter() {
+        if let Some(name) = config.sticky_headers.iter().find(|x| x.eq_ignore_ascii_case(k)) {
+            if !sticky_headers.iter().any(|(x, _)| x == name) {
+                sticky_headers.push((name.to_string(), v.to_string()));
+            }
+        }
+    }
+
+    sticky_headers
+}
+
+/// adds captured affinity values to every request so all the responses come from the same backend
+fn pin_backend(
+    config: &Config,
+    request_defaults: &mut RequestDefaults,
+    cookies: Vec<(String, String)>,
+    headers: Vec<(String, String)>,
+    progress_bar: &ProgressBar,
+    id: usize,
+) {
+    for name in config
+        .sticky_cookies
+        .iter()
+        .filter(|x| !cookies.iter().any(|(k, _)| k == *x))
+        .chain(config.sticky_headers.iter().filter(|x| !headers.iter().any(|(k, _)| k == *x)))
+    {
+        utils::info(config, id, progress_bar, "~", format!("{} wasn't found within the first response", name));
+    }
+
+    if !cookies.is_empty() {
+        let cookies = cookies
+            .iter()
+            .map(|(k, v)| format!("{}={}", k, v))
+            .collect::<Vec<String>>()
+            .join("; ");
+
+        utils::info(config, id, progress_bar, "info", format!("Pinned to the backend with {}", cookies));
+
+        match request_defaults.custom_headers.get_index_case_insensitive("cookie") {
+            Some(index) => {
+                let value = format!("{}; {}", cookies, request_defaults.custom_headers[index].1);
+                request_defaults.custom_headers[index].1 = value;
+            }
+            None => request_defaults.custom_headers.push(("Cookie".to_string(), cookies)),
+        }
+    }
+
+    for (k, v) in headers {
+        utils::info(config, id, progress_bar, "info", format!("Pinned to the backend with {}: {}", k, v));
+
+        match request_defaults.custom_headers.get_index_case_insensitive(&k) {
+            Some(index) => request_defaults.custom_headers[index].1 = v,
+            None => request_defaults.custom_headers.push((k, v)),
+        }
+    }
+}
+
+/// runs the runner and repeats the run with found parameters in case --recursion-depth is specified
+pub async fn run(
+    config: &Config,
+    request_defaults: &mut RequestDefaults,
+    params: &mut Vec<String>,
+    excluded: &[String],
+    progress_bar: &ProgressBar,
+    id: usize,
+    previous_findings: &PreviousFindings,
+) -> Result<RunnerOutput, Box<dyn Error>> {
+    let mut runner_output = Runner::new(config, request_defaults, progress_bar, id)
+        .await?
+        .excluding(excluded)
+        .run(params)
+        .await?;
+
+    // streamed wordlists are read from the file once again on every depth
+    let mut excluded = excluded.to_vec();
+
+    // the whole block related to the recursive searching
+    if !runner_output.found_params.is_empty() && !config.state.is_stopped() && runner_output.stopped_after.is_none() {
+        for depth in 1..config.recursion_depth + 1 {
+            // remove already found parameters from the list to prevent duplicates
+            params.retain(|x| !runner_output.found_params.contains_name(x));
+            excluded.extend(runner_output.found_params.iter().map(|x| x.name.clone()));
+
+            // custom parameters work badly with recursion enabled
+            request_defaults.disable_custom_parameters = true;
+
+            // so we are keeping parameters that don't change pages' code
+            // or change it to 200
+            // we cant simply overwrite request_defaults.parameters because there's user-supplied parameters as well.
+            request_defaults.parameters.append(&mut Vec::from_iter(
+                runner_output
+                    .found_params
+                    .iter()
+                    .filter(|x| {
+                        !request_defaults.parameters.contains_key(&x.name)
+                            && (x.reason_kind != ReasonKind::Code || x.status == 200)
+                    })
+                    .map(|x| x.get()),
+            ));
+
+            utils::info(
+                config,
+                id,
+                progress_bar,
+                "recursion",
+                format!(
+                    "({}) repeating with {}",
+                    depth,
+                    request_defaults
+                        .parameters
+                        .iter()
+                        .map(|x| x.0.as_str())
+                        .collect::<Vec<&str>>()
+                        .join(", ")
+                ),
+            );
+
+            let mut new_output = Runner::new(config, request_defaults, progress_bar, id)
+                .await?
+                .excluding(&excluded)
+                .run(params)
+                .await?;
+
+            runner_output.skipped.append(&mut new_output.skipped);
+            let mut new_found_params = new_output.found_params;
+
+            // no new params where found - just quit the loop
+            if !new_found_params
+                .iter()
+                .any(|x| !runner_output.found_params.contains_name(&x.name))
+            {
+                break;
+            }
+
+            runner_output.found_params.append(&mut new_found_params);
+        }
+    }
+
+    // we probably changed request_defaults.parameters within the loop above
+    // so we are removing all of the added parameters in there
+    // leaving only user-supplied ones
+    // (to not cause double parameters in some output types)
+    request_defaults.parameters = request_defaults
+        .parameters
+        .iter()
+        .filter(|x| !runner_output.found_params.contains_name(&x.0))
+        .map(|x| x.to_owned())
+        .collect();
+
+    if config.json_depth > 0
+        && !runner_output.found_params.is_empty()
+        && !config.state.is_stopped()
+        && runner_output.stopped_after.is_none()
+    {
+        runner_output.json_tree =
+            discover_json_tree(config, request_defaults, params, &runner_output.found_params, progress_bar, id).await?;
+    }
+
+    if !config.compare_with.is_empty() {
+        runner_output.compare_with(previous_findings);
+    }
+
+    if !config.save_skipped.is_empty() {
+        if let Err(err) = save_skipped(&config.save_skipped, &runner_output.skipped) {
+            utils::info(config, id, progress_bar, "~", format!("was unable to save skipped words: {}", err));
+        }
+    }
+
+    runner_output.prepare(config, request_defaults);
+
+    Ok(runner_output)
+}
@@ -0,0 +1,88 @@
+use std::{
+    error::Error,
+    fmt,
+    fs::OpenOptions,
+    io::Write,
+};
+
+use serde::Serialize;
+
+/// why a word from the list wasn't checked
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SkipReason {
+    /// the chunk failed with connection errors after every retry
+    ConnectionError,
+
+    /// the word can't be a part of a header name or a method
+    InvalidCharacters,
+
+    /// the scan was stopped with Ctrl+C before the word was sent
+    Interrupted,
+
+    /// the word would be sent as a method refused by --safe
+    UnsafeMethod,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SkippedWord {
+    pub word: String,
+    pub reason: SkipReason,
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SkipReason::ConnectionError => write!(f, "connection errors"),
+            SkipReason::InvalidCharacters => write!(f, "invalid characters"),
+            SkipReason::Interrupted => write!(f, "interrupted"),
+            SkipReason::UnsafeMethod => write!(f, "unsafe method"),
+        }
+    }
+}
+
+impl SkippedWord {
+    pub fn new(word: &str, reason: SkipReason) -> Self {
+        Self {
+            word: word.to_string(),
+            reason,
+        }
+    }
+
+    /// the words with the same reason
+    pub fn many<'a>(words: impl IntoIterator<Item = &'a String>, reason: SkipReason) -> Vec<Self> {
+        words.into_iter().map(|x| Self::new(x, reason)).collect()
+    }
+}
+
+/// the amount of words per reason in the order of appearance: "connection errors: 128, interrupted: 64"
+pub fn summarize(skipped: &[SkippedWord]) -> String {
+    let mut reasons: Vec<(SkipReason, usize)> = Vec::new();
+
+    for word in skipped {
+        match reasons.iter_mut().find(|(reason, _)| *reason == word.reason) {
+            Some((_, amount)) => *amount += 1,
+            None => reasons.push((word.reason, 1)),
+        }
+    }
+
+    reasons
+        .iter()
+        .map(|(reason, amount)| format!("{}: {}", reason, amount))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+/// appends skipped words to the file one per line, so they can be checked once more with -w (--save-skipped)
+pub fn save_skipped(filename: &str, skipped: &[SkippedWord]) -> Result<(), Box<dyn Error>> {
+    let words: String = skipped
+        .iter()
+        .map(|x| x.word.clone() + "\n")
+        .collect();
+
+    if !words.is_empty() {
+        OpenOptions::new().create(true).append(true).open(filename)?.write_all(words.as_bytes())?;
+    }
+
+    Ok(())
+}
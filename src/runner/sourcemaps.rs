@@ -0,0 +1,125 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+use url::Url;
+
+use crate::{
+    network::{
+        request::{Request, RequestDefaults},
+        response::Response,
+        utils::InjectionPlace,
+    },
+    MAX_SOURCE_MAPS,
+};
+
+lazy_static! {
+    static ref RE_SOURCE_MAPPING_URL: Regex = Regex::new(r#"[#@]\s*sourceMappingURL=(\S+)"#).unwrap();
+    static ref RE_SCRIPT_SRC: Regex = Regex::new(r#"(?i)<script[^>]+src\s*=\s*["']?([^"'\s>]+\.js[^"'\s>]*)"#).unwrap();
+    static ref RE_IDENTIFIER: Regex = Regex::new(r#"^[A-Za-z_$][\w$]{2,30}$"#).unwrap();
+    static ref RE_OBJECT_KEYS: Regex = Regex::new(r#"[\{,]\s*["']?([A-Za-z_][\w-]{2,30})["']?\s*:"#).unwrap();
+    static ref RE_QUERY_KEYS: Regex = Regex::new(r#"[?&]([A-Za-z_][\w-]{1,30})="#).unwrap();
+}
+
+/// fetches source maps referenced by the page and its scripts (--source-maps)
+/// returns identifiers from the original sources: names of variables and arguments, object keys and query keys
+pub(super) async fn source_map_params(request_defaults: &RequestDefaults, initial_response: &Response) -> Vec<String> {
+    let base = match Url::parse(&request_defaults.url_without_default_port()) {
+        Ok(val) => val,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut maps: Vec<Url> = source_map_urls(&base, &initial_response.headers, initial_response.body());
+
+    // the page usually references scripts while scripts reference maps
+    let scripts: Vec<Url> = RE_SCRIPT_SRC
+        .captures_iter(initial_response.body())
+        .filter_map(|x| base.join(&x[1]).ok())
+        .take(MAX_SOURCE_MAPS)
+        .collect();
+
+    for script in scripts {
+        if let Some((headers, body)) = fetch(request_defaults, &script).await {
+            for map in source_map_urls(&script, &headers, &body) {
+                if !maps.contains(&map) {
+                    maps.push(map);
+                }
+            }
+        }
+    }
+
+    let mut params: Vec<String> = Vec::new();
+
+    for map in maps.iter().take(MAX_SOURCE_MAPS) {
+        let body = match fetch(request_defaults, map).await {
+            Some((_, body)) => body,
+            None => continue,
+        };
+
+        let map: serde_json::Value = match serde_json::from_str(&body) {
+            Ok(val) => val,
+            Err(_) => continue,
+        };
+
+        let names = map["names"].as_array().into_iter().flatten().filter_map(|x| x.as_str());
+
+        let sources: Vec<&str> = map["sourcesContent"].as_array().into_iter().flatten().filter_map(|x| x.as_str()).collect();
+
+        let keys = sources.iter().flat_map(|source| {
+            RE_OBJECT_KEYS
+                .captures_iter(source)
+                .chain(RE_QUERY_KEYS.captures_iter(source))
+                .map(|x| x[1].to_string())
+                .collect::<Vec<String>>()
+        });
+
+        for name in names.map(|x| x.to_string()).chain(keys) {
+            if RE_IDENTIFIER.is_match(&name.replace('-', "_")) && !params.contains(&name) {
+                params.push(name);
+            }
+        }
+    }
+
+    params
+}
+
+/// sourceMappingURL comments and SourceMap headers
+fn source_map_urls(base: &Url, headers: &[(String, String)], body: &str) -> Vec<Url> {
+    let header = headers
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case("sourcemap") || k.eq_ignore_ascii_case("x-sourcemap"))
+        .map(|(_, v)| v.to_owned());
+
+    RE_SOURCE_MAPPING_URL
+        .captures_iter(body)
+        .map(|x| x[1].to_string())
+        .chain(header)
+        // inline maps are base64 encoded data urls
+        .filter(|x| !x.starts_with("data:"))
+        .filter_map(|x| base.join(&x).ok())
+        .collect()
+}
+
+/// sends a GET request to the url with the same headers and client and returns headers and body of the response
+/// only files from the same host are fetched
+async fn fetch(request_defaults: &RequestDefaults, url: &Url) -> Option<(Vec<(String, String)>, String)> {
+    if url.host_str() != Some(request_defaults.host.as_str()) {
+        return None;
+    }
+
+    let mut defaults = request_defaults.clone();
+    defaults.method = "GET".to_string();
+    defaults.body = String::new();
+    defaults.injection_place = InjectionPlace::Path;
+    defaults.scheme = url.scheme().to_string();
+    defaults.port = url.port_or_known_default()?;
+    defaults.path = url[url::Position::BeforePath..].to_string();
+    defaults.parameters = Vec::new();
+    defaults.nested = None;
+
+    let response = Request::new(&defaults, Vec::new()).send().await.ok()?;
+
+    if response.code == 200 {
+        Some((response.headers.clone(), response.body().to_string()))
+    } else {
+        None
+    }
+}
@@ -0,0 +1,560 @@
+#[cfg(test)]
+mod tests {
+    use std::{fs, time::Duration};
+
+    use crate::network::{request::RequestDefaults, response::Baseline, utils::InjectionPlace};
+    use crate::utils::{shard, WordlistReader};
+    use crate::CHECKPOINT_CHUNKS;
+    use crate::serve;
+    use crate::runner::{
+        categories::{categorize, parse_categories},
+        fingerprint::{detect, framework_words, merge, Technology},
+        folding::FoldingVariant,
+        json_tree::{has_object, JsonKey},
+        memory::{self, Learned},
+        resume::{self, PendingChunks, TargetState},
+        output::{load_previous_findings, PreviousFindings, RunnerOutput},
+        pair::PairComparison,
+        roles::RoleMatrix,
+        scan::parse_allowed_methods,
+        skipped::{save_skipped, summarize, SkipReason, SkippedWord},
+        utils::{
+            format_duration, is_page_noise, percentile, split_by_length, AdaptiveConcurrency, EtaModel, FoundParameter, NamingStyle, ReasonKind,
+            Stable, Timings,
+        },
+        watch::WordlistWatcher,
+    };
+
+    #[test]
+    fn percentile_calculation() {
+        let times = vec![50, 10, 40, 20, 30, 100, 60, 70, 90, 80];
+
+        assert_eq!(percentile(&times, 50), 50);
+        assert_eq!(percentile(&times, 95), 100);
+        assert_eq!(percentile(&times, 0), 10);
+        assert_eq!(percentile(&[], 50), 0);
+    }
+
+    #[test]
+    fn timings_deltas() {
+        let timings = Timings::new(&[100, 100, 120], &[300, 80, 310, 90]);
+
+        assert_eq!(timings.baseline_p50, 100);
+        assert_eq!(timings.chunks_p50, 90);
+        assert_eq!(timings.p50_delta, -10);
+        assert_eq!(timings.p95_delta, 310 - 120);
+    }
+
+    #[test]
+    fn impact_score() {
+        let text = FoundParameter::new("debug", &["a".to_string(), "b".to_string()], 200, 150, 0, ReasonKind::Text);
+        let code = FoundParameter::new("admin", &["200 -> 500".to_string()], 500, 100, 0, ReasonKind::Code);
+        let reflected = FoundParameter::new("q", &[], 200, 100, 0, ReasonKind::Reflected);
+
+        // 2 diff lines and +50% of size
+        assert_eq!(text.calculate_impact(200, 100), 4 + 25);
+        assert_eq!(code.calculate_impact(200, 100), 2 + 30);
+        assert_eq!(reflected.calculate_impact(200, 100), 0);
+    }
+
+    #[test]
+    fn wordlist_shards() {
+        let params: Vec<String> = (0..200).map(|x| x.to_string()).collect();
+
+        let shards: Vec<Vec<String>> = (1..=3).map(|i| shard(params.clone(), i, 3)).collect();
+
+        assert_eq!(shards[0][0], "0");
+        assert_eq!(shards[1][0], "64");
+        assert_eq!(shards[0].len() + shards[1].len() + shards[2].len(), params.len());
+        assert!(shards[0].iter().all(|x| !shards[1].contains(x) && !shards[2].contains(x)));
+
+        // the streamed wordlist is split the same way
+        let filename = std::env::temp_dir().join(format!("x8-stream-{}.txt", std::process::id()));
+        std::fs::write(&filename, params.join("\n")).unwrap();
+
+        let mut reader = WordlistReader::new(filename.to_str().unwrap(), Some((2, 3)), false).unwrap();
+        let mut streamed = reader.next_words(50);
+        streamed.extend(reader.next_words(50));
+
+        std::fs::remove_file(&filename).unwrap();
+
+        assert_eq!(streamed, shards[1]);
+        assert!(reader.next_words(50).is_empty());
+    }
+
+    #[test]
+    fn naming_style() {
+        let names: Vec<String> = ["userId", "pageSize", "sortOrder", "q", "csrf_token"].iter().map(|x| x.to_string()).collect();
+
+        assert_eq!(NamingStyle::detect(names.iter()), Some(NamingStyle::Camel));
+        assert_eq!(NamingStyle::detect(names[3..].iter()), None);
+
+        assert_eq!(NamingStyle::Camel.apply("user_id"), "userId");
+        assert_eq!(NamingStyle::Snake.apply("userID"), "user_id");
+        assert_eq!(NamingStyle::Kebab.apply("page_size"), "page-size");
+        assert_eq!(NamingStyle::Snake.apply("debug"), "debug");
+        assert_eq!(NamingStyle::Snake.apply("user[id]"), "user[id]");
+    }
+
+    #[test]
+    fn page_noise() {
+        assert!(!is_page_noise("id"));
+        assert!(!is_page_noise("redirect_url"));
+        assert!(!is_page_noise("utm_source2024"));
+        assert!(!is_page_noise("oauth2RedirectUri"));
+
+        assert!(is_page_noise("a"));
+        assert!(is_page_noise("1234"));
+        assert!(is_page_noise("d41d8cd98f00b204e9800998ecf8427e"));
+    }
+
+    #[test]
+    fn chunks_by_length() {
+        let params: Vec<String> = ["a", "bb", "ccc", "dddd", "e"].iter().map(|x| x.to_string()).collect();
+
+        // base length 10, every parameter takes its length + 1 for the joiner
+        let chunks = split_by_length(&params, 3, 16, 10, |x| x.len() + 1);
+
+        assert_eq!(chunks, vec![vec!["a", "bb"], vec!["ccc"], vec!["dddd"], vec!["e"]]);
+
+        // max is respected as well
+        let chunks = split_by_length(&params, 2, 1024, 10, |x| x.len() + 1);
+
+        assert_eq!(chunks, vec![vec!["a", "bb"], vec!["ccc", "dddd"], vec!["e"]]);
+    }
+
+    #[test]
+    fn concurrency_adapts_to_errors() {
+        futures::executor::block_on(async {
+            let concurrency = AdaptiveConcurrency::new(8);
+
+            for _ in 0..3 {
+                let permit = concurrency.acquire().await;
+                concurrency.release(permit, false);
+            }
+
+            assert_eq!(concurrency.limit(), 4);
+
+            for _ in 0..10 {
+                let permit = concurrency.acquire().await;
+                concurrency.release(permit, true);
+            }
+
+            assert_eq!(concurrency.limit(), 5);
+        });
+    }
+
+    #[test]
+    fn eta_estimation() {
+        let eta = EtaModel::default();
+
+        assert_eq!(eta.estimate(10, 2, 0, 100), None);
+
+        eta.record_chunk(1000, false);
+        eta.record_chunk(3000, false);
+
+        // 10 chunks * 2000ms / 2 parallel requests + 5 followup requests * 100ms
+        assert_eq!(eta.estimate(10, 2, 5, 100), Some(10500));
+
+        // a quarter of chunks fails and is retried
+        eta.record_chunk(2000, true);
+        eta.record_chunk(2000, false);
+
+        assert_eq!(eta.estimate(10, 2, 0, 100), Some(12500));
+
+        // the median is kept until enough chunks are checked
+        let times = parking_lot::Mutex::new(vec![100, 300, 200]);
+        assert_eq!(eta.request_time(&times), 200);
+
+        times.lock().extend([500, 500]);
+        assert_eq!(eta.request_time(&times), 200);
+
+        for _ in 0..crate::ETA_MEDIAN_CHUNKS {
+            eta.record_chunk(2000, false);
+        }
+        assert_eq!(eta.request_time(&times), 300);
+
+        assert_eq!(format_duration(9000), "9s");
+        assert_eq!(format_duration(65000), "1m 05s");
+        assert_eq!(format_duration(3723000), "1h 02m");
+    }
+
+    #[test]
+    fn header_folding_variants() {
+        assert_eq!(
+            FoldingVariant::SpaceBeforeColon.apply("X-Debug", "1"),
+            ("X-Debug ".to_string(), "1".to_string())
+        );
+        assert_eq!(
+            FoldingVariant::TabContinuation.apply("X-Debug", "1"),
+            ("X-Debug".to_string(), "\r\n\t1".to_string())
+        );
+    }
+
+    #[test]
+    fn pair_comparison() {
+        let defaults = RequestDefaults::default();
+        let baseline = Baseline::default();
+
+        let first = RunnerOutput::new(
+            &defaults,
+            &baseline,
+            vec![
+                FoundParameter::new("admin", &[], 200, 0, 0, ReasonKind::Text),
+                FoundParameter::new("id", &[], 200, 0, 0, ReasonKind::Text),
+                FoundParameter::new("debug", &[], 200, 0, 0, ReasonKind::Text),
+            ],
+            Timings::default(),
+        );
+        let second = RunnerOutput::new(
+            &defaults,
+            &baseline,
+            vec![
+                FoundParameter::new("id", &[], 403, 0, 0, ReasonKind::Code),
+                FoundParameter::new("debug", &[], 200, 0, 0, ReasonKind::Text),
+                FoundParameter::new("v", &[], 200, 0, 0, ReasonKind::Text),
+            ],
+            Timings::default(),
+        );
+
+        let pair = PairComparison::new(&first, &second);
+
+        assert_eq!(pair.only_first, vec!["admin"]);
+        assert_eq!(pair.only_second, vec!["v"]);
+        assert_eq!(pair.different.len(), 1);
+        assert_eq!(pair.different[0].name, "id");
+        assert_eq!(pair.different[0].second_status, 403);
+    }
+
+    #[test]
+    fn path_segment_urls() {
+        let config = crate::config::args::get_config_from(["x8", "-u", "https://example.com/", "-O", "url"]).unwrap();
+
+        let defaults = RequestDefaults {
+            scheme: "https".to_string(),
+            host: "example.com".to_string(),
+            port: 443,
+            path: "/api/users/123/%s?a=b".to_string(),
+            injection_place: InjectionPlace::PathSegment,
+            ..Default::default()
+        };
+
+        let output = RunnerOutput::new(
+            &defaults,
+            &Baseline::default(),
+            vec![
+                FoundParameter::new("edit", &[], 200, 0, 0, ReasonKind::Code),
+                FoundParameter::new("/api/users/122", &[], 200, 0, 0, ReasonKind::Code),
+            ],
+            Timings::default(),
+        );
+
+        let urls = output.parse(&config);
+        assert!(urls.contains("https://example.com/api/users/123/edit?a=b"));
+        assert!(urls.contains("https://example.com/api/users/122?a=b"));
+    }
+
+    #[test]
+    fn compare_with_previous_run() {
+        let defaults = RequestDefaults::default();
+        let output = |names: &[&str]| {
+            let params = names.iter().map(|x| FoundParameter::new(*x, &[], 200, 0, 0, ReasonKind::Code)).collect();
+            RunnerOutput::new(&defaults, &Baseline::default(), params, Timings::default())
+        };
+
+        let mut first = output(&["debug", "admin"]);
+        first.compare_with(&PreviousFindings::new());
+        assert_eq!(first.new_params, Some(vec!["debug".to_string(), "admin".to_string()]));
+
+        // the output of a run is the input of the next one
+        let filename = std::env::temp_dir().join("x8_compare_with_test.json");
+        fs::write(&filename, serde_json::to_string(&[&first]).unwrap()).unwrap();
+        let previous_findings = load_previous_findings(filename.to_str().unwrap()).unwrap();
+        fs::remove_file(&filename).ok();
+
+        let mut second = output(&["debug", "page"]);
+        second.compare_with(&previous_findings);
+
+        assert_eq!(second.found_params.len(), 2);
+        assert_eq!(second.new_params, Some(vec!["page".to_string()]));
+        assert_eq!(second.removed_params, ["admin"]);
+        assert!(second.has_new_params());
+
+        let mut third = output(&["debug", "admin"]);
+        third.compare_with(&previous_findings);
+        assert!(!third.has_new_params());
+
+        // without --compare-with every found parameter counts
+        assert!(output(&["debug"]).has_new_params());
+    }
+
+    #[test]
+    fn role_matrix() {
+        let defaults = RequestDefaults::default();
+        let baseline = Baseline::default();
+
+        let output = |params: Vec<FoundParameter>| RunnerOutput::new(&defaults, &baseline, params, Timings::default());
+
+        let merged = RoleMatrix::merge(vec![
+            ("admin".to_string(), output(vec![
+                FoundParameter::new("debug", &[], 200, 0, 0, ReasonKind::Text),
+                FoundParameter::new("id", &[], 200, 0, 0, ReasonKind::Text),
+            ])),
+            ("guest".to_string(), output(vec![
+                FoundParameter::new("id", &[], 200, 0, 0, ReasonKind::Text),
+                FoundParameter::new("lang", &[], 200, 0, 0, ReasonKind::Text),
+            ])),
+        ]);
+
+        assert_eq!(merged.found_params.len(), 3);
+
+        let matrix = merged.roles.unwrap();
+        assert_eq!(matrix.roles, vec!["admin", "guest"]);
+        assert_eq!(matrix.different().map(|x| x.name.as_str()).collect::<Vec<&str>>(), vec!["debug", "lang"]);
+        assert!(matrix.parameters[0].results[1].is_none());
+    }
+
+    #[test]
+    fn allowed_methods() {
+        let headers = vec![
+            ("Allow".to_string(), "GET, HEAD, OPTIONS, put".to_string()),
+            ("access-control-allow-methods".to_string(), "PUT,PATCH, *".to_string()),
+        ];
+
+        assert_eq!(parse_allowed_methods(&headers), vec!["GET", "PUT", "PATCH"]);
+    }
+
+    #[test]
+    fn learned_settings_per_host() {
+        let defaults = RequestDefaults {
+            host: "memory.example.com".to_string(),
+            port: 443,
+            ..Default::default()
+        };
+
+        let learned = Learned {
+            max: 256,
+            url_length_limit: Some(4096),
+            reflections: false,
+            delay: Duration::ZERO,
+        };
+
+        assert_eq!(memory::recall(&defaults), None);
+
+        memory::remember(&defaults, learned);
+        assert_eq!(memory::recall(&defaults), Some(learned));
+
+        // the server asked to slow down while the url was checked
+        defaults.stages.waited(Duration::from_secs(2));
+        memory::remember_delay(&defaults, defaults.stages.longest_wait());
+        defaults.stages.waited(Duration::from_secs(1));
+        memory::remember_delay(&defaults, defaults.stages.longest_wait());
+        assert_eq!(memory::recall(&defaults).unwrap().delay, Duration::from_secs(2));
+
+        let other_port = RequestDefaults {
+            port: 8443,
+            ..defaults.clone()
+        };
+        assert_eq!(memory::recall(&other_port), None);
+    }
+
+    #[test]
+    fn resume_state() {
+        let filename = std::env::temp_dir().join(format!("x8-resume-{}.json", std::process::id()));
+        let filename = filename.to_str().unwrap();
+
+        assert!(resume::restore(filename, "GET https://example.com/?%s ").is_none());
+
+        let state = TargetState {
+            remaining: vec!["debug".to_string(), "admin".to_string()],
+            found_params: vec![FoundParameter::new("test", &[], 200, 10, 5, ReasonKind::Code)],
+            diffs: vec!["line".to_string()],
+            stable: Some(Stable { body: false, reflections: true, unstable_headers: vec![ReasonKind::Cors] }),
+        };
+        resume::checkpoint(filename, "GET https://example.com/?%s ", state).unwrap();
+
+        let restored = resume::restore(filename, "GET https://example.com/?%s ").unwrap();
+        assert_eq!(restored.remaining, ["debug", "admin"]);
+        assert_eq!(restored.found_params[0].name, "test");
+        assert!(std::fs::read_to_string(filename).unwrap().contains("\"remaining\":[\"debug\",\"admin\"]"));
+
+        // the instability learned before the interruption is kept
+        let mut stable = Stable { body: true, reflections: true, unstable_headers: Vec::new() };
+        stable.merge(restored.stable.unwrap());
+        assert!(!stable.body && stable.reflections);
+        assert_eq!(stable.unstable_headers, [ReasonKind::Cors]);
+
+        std::fs::remove_file(filename).unwrap();
+    }
+
+    #[test]
+    fn pending_chunks() {
+        let mut pending = PendingChunks::default();
+
+        let chunks = pending.add((0..CHECKPOINT_CHUNKS + 1).map(|x| vec![x.to_string()]).collect());
+        assert_eq!(chunks[1], (1, vec!["1".to_string()]));
+
+        // the state is saved once per CHECKPOINT_CHUNKS checked chunks
+        assert!((1..CHECKPOINT_CHUNKS).all(|x| !pending.remove(x)));
+        assert!(pending.remove(CHECKPOINT_CHUNKS));
+        assert!(!pending.remove(CHECKPOINT_CHUNKS));
+
+        assert_eq!(pending.remaining(), ["0"]);
+    }
+
+    #[test]
+    fn skipped_words() {
+        let words = vec!["debug".to_string(), "admin".to_string()];
+
+        let mut skipped = SkippedWord::many(&words, SkipReason::ConnectionError);
+        skipped.push(SkippedWord::new("test", SkipReason::Interrupted));
+
+        assert_eq!(summarize(&skipped), "connection errors: 2, interrupted: 1");
+
+        let filename = std::env::temp_dir().join(format!("x8-skipped-{}.txt", std::process::id()));
+        let filename = filename.to_str().unwrap();
+
+        save_skipped(filename, &skipped).unwrap();
+        let saved = std::fs::read_to_string(filename).unwrap();
+        std::fs::remove_file(filename).unwrap();
+
+        assert_eq!(saved, "debug\nadmin\ntest\n");
+    }
+
+    #[test]
+    fn custom_categories() {
+        let categories = parse_categories(r#"[
+            {"name": "privilege", "color": "bright_red", "param": "^(admin|role)$"},
+            {"name": "errors", "kinds": ["Text"], "label": "error"},
+            {"name": "redirects", "status": [301, 302]}
+        ]"#).unwrap();
+
+        let find = |kind, param, status, label| categorize(&categories, &kind, param, status, label).map(|x| x.name.as_str());
+
+        assert_eq!(find(ReasonKind::Code, "admin", 500, None), Some("privilege"));
+        assert_eq!(find(ReasonKind::Text, "debug", 200, Some("error message appeared")), Some("errors"));
+        assert_eq!(find(ReasonKind::Code, "debug", 200, Some("error message appeared")), None);
+        assert_eq!(find(ReasonKind::Code, "next", 302, None), Some("redirects"));
+        assert_eq!(find(ReasonKind::Reflected, "debug", 200, None), None);
+
+        let mut param = FoundParameter::new("admin", &[], 500, 0, 0, ReasonKind::Code);
+        param.category = Some("privilege".to_string());
+        assert!(param.get_colored(&categories).contains("[privilege]"));
+
+        assert!(parse_categories(r#"[{"name": "x", "color": "pink"}]"#).is_err());
+        assert!(parse_categories(r#"[{"name": "x", "param": "("}]"#).is_err());
+    }
+
+    #[test]
+    fn watched_wordlist() {
+        use std::io::Write;
+
+        let filename = std::env::temp_dir().join(format!("x8-watch-{}.txt", std::process::id()));
+        let mut watcher = WordlistWatcher::new(filename.to_str().unwrap(), &["debug".to_string()]);
+
+        // the file doesn't exist yet
+        assert!(watcher.new_words().is_empty());
+
+        let mut file = std::fs::File::create(&filename).unwrap();
+        file.write_all(b"debug\nadmin\nro").unwrap();
+        assert_eq!(watcher.new_words(), ["admin"]);
+
+        file.write_all(b"le\nadmin\n").unwrap();
+        assert_eq!(watcher.new_words(), ["role"]);
+        assert!(watcher.new_words().is_empty());
+
+        std::fs::remove_file(&filename).unwrap();
+    }
+
+    #[test]
+    fn json_tree() {
+        let path = |x: &str| x.split('.').map(|x| x.to_string()).collect::<Vec<String>>();
+
+        let mut tree = Vec::new();
+        JsonKey::insert(&mut tree, &path("user"));
+        JsonKey::insert(&mut tree, &path("user.role"));
+        JsonKey::insert(&mut tree, &path("user.profile.bio"));
+        JsonKey::insert(&mut tree, &path("settings"));
+
+        assert_eq!(JsonKey::print(&tree), "user{role, profile{bio}}, settings");
+
+        // responses are split into lines before they're parsed
+        assert!(has_object("{\"\nok\": true, \"data\": [{\"user\": {}}]}", "user"));
+        assert!(!has_object("{\"user\": \"invalid\"}", "user"));
+        assert!(!has_object("<html>user</html>", "user"));
+    }
+
+    #[test]
+    fn backend_fingerprint() {
+        let headers = |x: &[(&str, &str)]| x.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect::<Vec<(String, String)>>();
+        let names = |x: Vec<&Technology>| x.iter().map(|x| x.name).collect::<Vec<&str>>();
+
+        assert_eq!(
+            names(detect(&headers(&[("X-Powered-By", "PHP/8.1"), ("Set-Cookie", "laravel_session=abc; path=/")]), "")),
+            ["Laravel", "PHP"]
+        );
+        assert_eq!(names(detect(&headers(&[("Set-Cookie", "csrftoken=abc")]), "")), ["Django"]);
+        assert_eq!(names(detect(&[], "<h1>Whitelabel Error Page</h1>")), ["Spring"]);
+        assert!(detect(&headers(&[("Server", "nginx")]), "<p>hello</p>").is_empty());
+
+        let words = framework_words(&detect(&headers(&[("X-Powered-By", "PHP/8.1"), ("Set-Cookie", "laravel_session=abc")]), ""));
+        assert_eq!(words[0], "_token");
+        assert_eq!(words.iter().filter(|x| *x == "debug").count(), 1);
+
+        // the framework revealed by an error page goes before the language detected earlier
+        let mut backend = detect(&headers(&[("X-Powered-By", "PHP/8.1")]), "");
+        merge(&mut backend, detect(&[], "<h1>Whoops! There was an error.</h1>"));
+        merge(&mut backend, detect(&headers(&[("Set-Cookie", "PHPSESSID=abc")]), ""));
+        assert_eq!(names(backend), ["Laravel", "PHP"]);
+    }
+
+    #[tokio::test]
+    async fn serve_connections() {
+        use std::sync::Arc;
+
+        use parking_lot::Mutex;
+        use tokio::{io::{AsyncReadExt, AsyncWriteExt}, net::{TcpListener, TcpStream}};
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let config = crate::config::args::get_config_from(["x8", "-u", "https://example.com/", "--safe"]).unwrap();
+        let jobs = Arc::new(Mutex::new(Vec::new()));
+
+        tokio::spawn(serve::accept_connections(listener, Arc::new(config), Arc::clone(&jobs), Arc::new(vec!["debug".to_string()])));
+
+        let request = |raw: String| async move {
+            let mut stream = TcpStream::connect(addr).await.unwrap();
+            stream.write_all(raw.as_bytes()).await.unwrap();
+
+            let mut response = String::new();
+            stream.read_to_string(&mut response).await.unwrap();
+            response
+        };
+
+        // the client that doesn't send anything doesn't block others
+        let _slow = TcpStream::connect(addr).await.unwrap();
+
+        let body = r#"{"urls": ["https://example.com/a"]}"#;
+        let response = tokio::time::timeout(
+            Duration::from_secs(2),
+            request(format!("POST /scans HTTP/1.1\r\nContent-Length: {}\r\n\r\n{}", body.len(), body)),
+        )
+        .await
+        .unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 201 Created"));
+        assert!(response.ends_with(r#"{"id":1}"#));
+        assert_eq!(jobs.lock()[0].urls, ["https://example.com/a"]);
+        assert_eq!(jobs.lock()[0].methods, ["GET"]);
+
+        assert!(request("GET /scans/2 HTTP/1.1\r\n\r\n".to_string()).await.starts_with("HTTP/1.1 404 Not Found"));
+
+        // --safe applies to methods sent through the api as well
+        let body = r#"{"urls": ["https://example.com/a"], "methods": ["GET", "patch"]}"#;
+        let response = request(format!("POST /scans HTTP/1.1\r\nContent-Length: {}\r\n\r\n{}", body.len(), body)).await;
+        assert!(response.starts_with("HTTP/1.1 400 Bad Request"));
+        assert!(response.contains("--safe refuses the patch method"));
+        assert_eq!(jobs.lock().len(), 1);
+    }
+}
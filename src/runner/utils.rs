@@ -1,38 +1,435 @@
 use std::{
+    collections::HashMap,
     error::Error,
 };
 
 use lazy_static::lazy_static;
+use parking_lot::Mutex;
+use tokio::sync::{Semaphore, SemaphorePermit};
 use regex::Regex;
 use reqwest::Client;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use colored::Colorize;
 
 use crate::{
     config::structs::Config,
     network::{
+        metrics::NetworkEstimates,
         request::{Request, RequestDefaults},
-        response::Response,
+        response::Baseline,
         utils::InjectionPlace,
     },
-    utils::random_line, VALUE_LENGTH,
+    utils::random_line, VALUE_LENGTH, CONCURRENCY_DECREASE_AFTER, ETA_MEDIAN_CHUNKS, CONCURRENCY_INCREASE_AFTER, MIN_PAGE_PARAM_LENGTH,
 };
 
-#[derive(Debug, Default)]
+use super::{categories::Category, duplicates::DuplicateHandling};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Stable {
     pub body: bool,
     pub reflections: bool,
+
+    /// header channels (Cors, Security) which headers differ between random requests
+    pub unstable_headers: Vec<ReasonKind>,
+}
+
+impl Stable {
+    /// keeps the instability found by either of the checks
+    pub fn merge(&mut self, other: Stable) {
+        self.body &= other.body;
+        self.reflections &= other.reflections;
+
+        for kind in other.unstable_headers {
+            if !self.unstable_headers.contains(&kind) {
+                self.unstable_headers.push(kind);
+            }
+        }
+    }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ReasonKind {
     Code,
     Text,
     Reflected,
     NotReflected,
+
+    /// the parameter changes Access-Control-Allow-* headers
+    Cors,
+
+    /// the parameter changes security headers like Content-Security-Policy or X-Frame-Options
+    Security,
+}
+
+/// limits the amount of parallel requests
+/// halves the limit after a few connection errors in a row and restores it gradually after successful requests
+pub struct AdaptiveConcurrency {
+    semaphore: Semaphore,
+
+    /// the limit provided by the user
+    max: usize,
+
+    state: Mutex<ConcurrencyState>,
+}
+
+#[derive(Debug, Default)]
+struct ConcurrencyState {
+    /// the amount of permits within the semaphore
+    capacity: usize,
+
+    /// the desired amount of permits
+    limit: usize,
+
+    errors_in_row: usize,
+    successes_in_row: usize,
+}
+
+impl AdaptiveConcurrency {
+    pub fn new(max: usize) -> Self {
+        Self {
+            semaphore: Semaphore::new(max),
+            max,
+            state: Mutex::new(ConcurrencyState {
+                capacity: max,
+                limit: max,
+                ..Default::default()
+            }),
+        }
+    }
+
+    pub async fn acquire(&self) -> SemaphorePermit<'_> {
+        // the semaphore is never closed
+        self.semaphore.acquire().await.unwrap()
+    }
+
+    /// the current limit of parallel requests
+    pub fn limit(&self) -> usize {
+        self.state.lock().limit
+    }
+
+    /// adjusts the limit depending on whether the request was successful
+    pub fn release(&self, permit: SemaphorePermit<'_>, success: bool) {
+        let mut state = self.state.lock();
+
+        if success {
+            state.errors_in_row = 0;
+            state.successes_in_row += 1;
+
+            if state.successes_in_row >= CONCURRENCY_INCREASE_AFTER && state.limit < self.max {
+                state.limit += 1;
+                state.successes_in_row = 0;
+            }
+        } else {
+            state.successes_in_row = 0;
+            state.errors_in_row += 1;
+
+            if state.errors_in_row >= CONCURRENCY_DECREASE_AFTER && state.limit > 1 {
+                state.limit /= 2;
+                state.errors_in_row = 0;
+            }
+        }
+
+        // the capacity decreases one by one as requests finish
+        if state.capacity > state.limit {
+            permit.forget();
+            state.capacity -= 1;
+        } else if state.capacity < state.limit {
+            self.semaphore.add_permits(state.limit - state.capacity);
+            state.capacity = state.limit;
+        }
+    }
 }
 
-#[derive(Debug, Clone, Serialize)]
+/// estimates the remaining time of the parameter check and the phases after it
+/// chunks are weighted by their measured duration, so splits, delays and throttling are taken into account
+#[derive(Debug, Default)]
+pub struct EtaModel {
+    state: Mutex<EtaState>,
+}
+
+#[derive(Debug, Default)]
+struct EtaState {
+    /// the amount of checked chunks and their total duration in milliseconds
+    chunks: u64,
+    chunks_time: u128,
+
+    /// chunks that failed and are going to be retried
+    failed: u64,
+
+    /// the median request time and the amount of chunks it was computed after
+    median: Option<(u128, u64)>,
+}
+
+impl EtaModel {
+    pub fn record_chunk(&self, time: u128, failed: bool) {
+        let mut state = self.state.lock();
+
+        state.chunks += 1;
+        state.chunks_time += time;
+
+        if failed {
+            state.failed += 1;
+        }
+    }
+
+    /// the median of request times. Sorting all of them after every chunk would make long scans quadratic,
+    /// so the median is recomputed once in ETA_MEDIAN_CHUNKS chunks
+    pub fn request_time(&self, times: &Mutex<Vec<u128>>) -> u128 {
+        let mut state = self.state.lock();
+
+        match state.median {
+            Some((median, chunks)) if state.chunks < chunks + ETA_MEDIAN_CHUNKS => median,
+            _ => {
+                let median = percentile(&times.lock(), 50);
+                state.median = Some((median, state.chunks));
+                median
+            }
+        }
+    }
+
+    /// returns the remaining time in milliseconds. None until at least one chunk is checked
+    /// followup_requests are sent one by one after the check (verification, aliases, ..)
+    pub fn estimate(&self, remaining_chunks: u64, concurrency: usize, followup_requests: usize, request_time: u128) -> Option<u128> {
+        let state = self.state.lock();
+
+        if state.chunks == 0 {
+            return None;
+        }
+
+        let chunk_time = state.chunks_time as f64 / state.chunks as f64;
+
+        // the same share of the remaining chunks is likely to be retried
+        let retry_rate = state.failed as f64 / state.chunks as f64;
+
+        let checking = remaining_chunks as f64 * (1. + retry_rate) * chunk_time / concurrency.max(1) as f64;
+
+        Some(checking as u128 + followup_requests as u128 * request_time)
+    }
+}
+
+/// 3723000 -> 1h 02m, 65000 -> 1m 05s, 9000 -> 9s
+pub fn format_duration(ms: u128) -> String {
+    let secs = ms / 1000;
+
+    if secs >= 3600 {
+        format!("{}h {:02}m", secs / 3600, secs % 3600 / 60)
+    } else if secs >= 60 {
+        format!("{}m {:02}s", secs / 60, secs % 60)
+    } else {
+        format!("{}s", secs)
+    }
+}
+
+/// how to group parameters into requests
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChunkStrategy {
+    /// up to max parameters per request
+    Fixed,
+
+    /// up to max parameters per request while the url stays under the limit in bytes
+    /// applies only to parameters within the query
+    UrlLength(usize),
+
+    /// one parameter per request
+    Single,
+}
+
+/// how the random parameter that counts the default amount of reflections is sent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReflectionParam {
+    /// along with the initial request. The value has the given length
+    Inline(usize),
+
+    /// within a separate request, so the initial response stays without unknown parameters
+    Separate(usize),
+
+    /// not sent at all. Reflections aren't checked
+    Off,
+}
+
+/// how names of multiword parameters are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NamingStyle {
+    /// userId
+    Camel,
+
+    /// user_id
+    Snake,
+
+    /// user-id
+    Kebab,
+}
+
+impl NamingStyle {
+    /// returns the style of the most of multiword names
+    /// None in case there are too few of them or no style dominates
+    pub fn detect<'a>(names: impl Iterator<Item = &'a String>) -> Option<Self> {
+        let (mut camel, mut snake, mut kebab) = (0, 0, 0);
+
+        for name in names {
+            match Self::of(name) {
+                Some(NamingStyle::Camel) => camel += 1,
+                Some(NamingStyle::Snake) => snake += 1,
+                Some(NamingStyle::Kebab) => kebab += 1,
+                None => (),
+            }
+        }
+
+        let total = camel + snake + kebab;
+
+        [(camel, NamingStyle::Camel), (snake, NamingStyle::Snake), (kebab, NamingStyle::Kebab)]
+            .iter()
+            .find(|(amount, _)| total >= 3 && *amount * 10 >= total * 7)
+            .map(|(_, style)| *style)
+    }
+
+    /// None for single word names and names with mixed styles
+    fn of(name: &str) -> Option<Self> {
+        let is_camel = name.chars().next().is_some_and(|c| c.is_ascii_lowercase())
+            && name.chars().any(|c| c.is_ascii_uppercase())
+            && name.chars().all(|c| c.is_ascii_alphanumeric());
+
+        if is_camel {
+            Some(NamingStyle::Camel)
+        } else if name.contains('_') && !name.contains('-') && name.chars().all(|c| !c.is_ascii_uppercase()) {
+            Some(NamingStyle::Snake)
+        } else if name.contains('-') && !name.contains('_') && name.chars().all(|c| !c.is_ascii_uppercase()) {
+            Some(NamingStyle::Kebab)
+        } else {
+            None
+        }
+    }
+
+    /// user_id -> userId. Single word names and names with other chars stay as is
+    pub fn apply(&self, name: &str) -> String {
+        if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+            return name.to_string();
+        }
+
+        let mut words: Vec<String> = Vec::new();
+
+        for part in name.split(['_', '-']).filter(|x| !x.is_empty()) {
+            let mut word = String::new();
+            let mut prev_lowercase = false;
+
+            // userID -> [user, id]
+            for c in part.chars() {
+                if c.is_ascii_uppercase() && prev_lowercase {
+                    words.push(word);
+                    word = String::new();
+                }
+                prev_lowercase = c.is_ascii_lowercase() || c.is_ascii_digit();
+                word.push(c.to_ascii_lowercase());
+            }
+
+            words.push(word);
+        }
+
+        if words.len() < 2 {
+            return name.to_string();
+        }
+
+        match self {
+            NamingStyle::Camel => words
+                .iter()
+                .enumerate()
+                .map(|(i, word)| if i == 0 { word.to_owned() } else { word[..1].to_uppercase() + &word[1..] })
+                .collect(),
+            NamingStyle::Snake => words.join("_"),
+            NamingStyle::Kebab => words.join("-"),
+        }
+    }
+}
+
+/// splits parameters into chunks of up to max parameters
+/// a new chunk starts once the chunk's length exceeds the limit. Every chunk contains at least one parameter
+pub fn split_by_length<F: Fn(&str) -> usize>(
+    params: &[String],
+    max: usize,
+    limit: usize,
+    base_len: usize,
+    param_len: F,
+) -> Vec<Vec<String>> {
+    let mut chunks = Vec::new();
+    let mut chunk: Vec<String> = Vec::new();
+    let mut len = base_len;
+
+    for param in params {
+        let current_len = param_len(param);
+
+        if !chunk.is_empty() && (chunk.len() >= max || len + current_len > limit) {
+            chunks.push(std::mem::take(&mut chunk));
+            len = base_len;
+        }
+
+        len += current_len;
+        chunk.push(param.to_owned());
+    }
+
+    if !chunk.is_empty() {
+        chunks.push(chunk);
+    }
+
+    chunks
+}
+
+/// response times in milliseconds
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Timings {
+    /// learning requests with random parameters
+    pub baseline_p50: u128,
+    pub baseline_p95: u128,
+
+    /// requests with parameters from the wordlist
+    pub chunks_p50: u128,
+    pub chunks_p95: u128,
+
+    /// chunks - baseline
+    pub p50_delta: i128,
+    pub p95_delta: i128,
+
+    /// medians of request phases estimated with separate probes after the check (--network-estimates)
+    /// not the timings of the checked requests
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub network_estimates: Option<NetworkEstimates>,
+}
+
+impl Timings {
+    pub fn new(baseline: &[u128], chunks: &[u128]) -> Self {
+        let (baseline_p50, baseline_p95) = (percentile(baseline, 50), percentile(baseline, 95));
+        let (chunks_p50, chunks_p95) = (percentile(chunks, 50), percentile(chunks, 95));
+
+        Self {
+            baseline_p50,
+            baseline_p95,
+            chunks_p50,
+            chunks_p95,
+            p50_delta: chunks_p50 as i128 - baseline_p50 as i128,
+            p95_delta: chunks_p95 as i128 - baseline_p95 as i128,
+            network_estimates: None,
+        }
+    }
+}
+
+/// returns the p-th percentile (0-100) using the nearest-rank method
+/// 0 in case there are no times
+pub fn percentile(times: &[u128], p: usize) -> u128 {
+    if times.is_empty() {
+        return 0;
+    }
+
+    let mut times = times.to_vec();
+    times.sort_unstable();
+
+    let rank = (p * times.len()).div_ceil(100);
+
+    times[rank.clamp(1, times.len()) - 1]
+}
+
+/// a function to select headers to compare and a reason kind to report parameters with
+pub type HeaderChannel = (fn(&str) -> bool, ReasonKind);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FoundParameter {
     pub name: String,
 
@@ -42,7 +439,83 @@ pub struct FoundParameter {
     pub diffs: String,
     pub status: u16,
     pub size: usize,
+
+    /// response time in milliseconds
+    pub time: u128,
+
     pub reason_kind: ReasonKind,
+
+    /// parameters that produce the same response with the same value
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
+
+    /// how much the page changed, used to sort the output. Higher is more promising
+    pub impact: u32,
+
+    /// where the parameter was taken from
+    #[serde(default, skip_serializing_if = "ParamSource::is_wordlist")]
+    pub source: ParamSource,
+
+    /// how the page changed in human words, e.g. "error message appeared"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+
+    /// which value is used when the parameter is sent twice (--duplicate-keys)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duplicates: Option<DuplicateHandling>,
+
+    /// the name of the matched category from the --categories file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+}
+
+/// where the parameter was taken from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ParamSource {
+    #[default]
+    Wordlist,
+
+    /// scraped from the initial response
+    Page,
+
+    /// taken from source maps (--source-maps)
+    SourceMap,
+}
+
+impl ParamSource {
+    pub fn is_wordlist(&self) -> bool {
+        *self == ParamSource::Wordlist
+    }
+}
+
+/// whether the word scraped from the page is unlikely to be a parameter:
+/// too short, a number or a random looking token like a hash
+pub fn is_page_noise(word: &str) -> bool {
+    if word.len() < MIN_PAGE_PARAM_LENGTH || word.chars().all(|c| c.is_ascii_digit()) {
+        return true;
+    }
+
+    // names with digits like oauth2RedirectUri are fine
+    let digits = word.chars().filter(|c| c.is_ascii_digit()).count();
+    if word.len() < 16 || digits * 5 < word.len() || word.contains('_') || word.contains('-') {
+        return false;
+    }
+
+    // shannon entropy in bits per char
+    let mut counts: HashMap<char, usize> = HashMap::new();
+    for c in word.chars() {
+        *counts.entry(c).or_default() += 1;
+    }
+
+    let len = word.chars().count() as f64;
+    let entropy: f64 = counts
+        .values()
+        .map(|x| *x as f64 / len)
+        .map(|p| -p * p.log2())
+        .sum();
+
+    entropy > 3.0
 }
 
 impl FoundParameter {
@@ -51,6 +524,7 @@ impl FoundParameter {
         diffs: &[String],
         status: u16,
         size: usize,
+        time: u128,
         reason_kind: ReasonKind,
     ) -> Self {
         let name = name.into();
@@ -71,7 +545,14 @@ impl FoundParameter {
             diffs: diffs.join("|"),
             status,
             size,
+            time,
             reason_kind,
+            aliases: Vec::new(),
+            impact: 0,
+            source: ParamSource::Wordlist,
+            label: None,
+            duplicates: None,
+            category: None,
         }
     }
 
@@ -83,19 +564,80 @@ impl FoundParameter {
         )
     }
 
+    /// scores the change of the page compared to the initial response:
+    /// up to 40 for diff lines, up to 50 for the size delta and up to 30 for a different status code
+    pub fn calculate_impact(&self, initial_code: u16, initial_size: usize) -> u32 {
+        let diff_lines = self.diffs.split('|').filter(|x| !x.is_empty()).count().min(20) as u32;
+
+        let size_delta = (self.size as i64 - initial_size as i64).unsigned_abs() * 100 / initial_size.max(1) as u64;
+
+        // errors are the most interesting, then access granted, redirects and other client errors
+        let code = if self.status == initial_code {
+            0
+        } else {
+            match self.status / 100 {
+                5 => 30,
+                2 => 20,
+                3 => 15,
+                _ => 10,
+            }
+        };
+
+        diff_lines * 2 + size_delta.min(100) as u32 / 2 + code
+    }
+
     /// returns colored param name and param=value in case a non random value is used
-    pub fn get_colored(&self) -> String {
+    /// the name is colored with the category color in case the category has one
+    pub fn get_colored(&self, categories: &[Category]) -> String {
+        let category = self.category.as_ref().and_then(|name| categories.iter().find(|x| &x.name == name));
+
         let param = match self.reason_kind {
             ReasonKind::Code => self.name.yellow(),
             ReasonKind::Text => self.name.bright_yellow(),
             ReasonKind::Reflected => self.name.bright_blue(),
             ReasonKind::NotReflected => self.name.bright_cyan(),
+            ReasonKind::Cors => self.name.bright_magenta(),
+            ReasonKind::Security => self.name.bright_red(),
+        };
+
+        let param = match category.and_then(|x| x.color) {
+            Some(color) => param.color(color),
+            None => param,
         };
 
-        if self.value.is_some() {
+        let param = if self.value.is_some() {
             format!("{}={}", param, self.value.as_ref().unwrap())
         } else {
             param.to_string()
+        };
+
+        let param = match self.source {
+            ParamSource::Wordlist => param,
+            ParamSource::Page => format!("{} (from page)", param),
+            ParamSource::SourceMap => format!("{} (from source map)", param),
+        };
+
+        let param = match &self.label {
+            Some(label) => format!("{} [{}]", param, label),
+            None => param,
+        };
+
+        let param = match category {
+            Some(category) => param + &category.tag(),
+            None => param,
+        };
+
+        let param = match self.duplicates {
+            Some(DuplicateHandling::FirstWins) => format!("{} (first wins)", param),
+            Some(DuplicateHandling::LastWins) => format!("{} (last wins)", param),
+            Some(DuplicateHandling::Both) => format!("{} (both values used)", param),
+            None => param,
+        };
+
+        if self.aliases.is_empty() {
+            param
+        } else {
+            format!("{} (aliases: {})", param, self.aliases.join(", "))
         }
     }
 }
@@ -223,9 +765,9 @@ pub(super) async fn replay<'a>(
 }
 
 /// verifies found parameters by requesting the page with found parameters yet one time
-pub(super) async fn verify<'a>(
-    initial_response: &'a Response<'a>,
-    request_defaults: &'a RequestDefaults,
+pub(super) async fn verify(
+    baseline: &Baseline,
+    request_defaults: &RequestDefaults,
     found_params: &Vec<FoundParameter>,
     diffs: &Vec<String>,
     stable: &Stable,
@@ -234,18 +776,19 @@ pub(super) async fn verify<'a>(
 
     for param in found_params {
         let param_value = param.get();
-        let mut response = Request::new(request_defaults, vec![format!("{}={}", param_value.0, param_value.1)])
-            .send()
+        let mut response = request_defaults
+            .cache
+            .send(Request::new(request_defaults, vec![format!("{}={}", param_value.0, param_value.1)]))
             .await?;
 
-        let (is_code_diff, new_diffs) = response.compare(initial_response, diffs)?;
+        let (is_code_diff, new_diffs) = response.compare(baseline, diffs)?;
         let mut is_the_body_the_same = true;
 
         if !new_diffs.is_empty() {
             is_the_body_the_same = false;
         }
 
-        response.fill_reflected_parameters(initial_response);
+        response.fill_reflected_parameters(baseline);
 
         if is_code_diff || !response.reflected_parameters.is_empty() || stable.body && !is_the_body_the_same {
             filtered_params.push(param.clone());
@@ -356,7 +899,7 @@ impl ParamPatterns {
 
 /// under development
 pub(super) async fn _smart_verify(
-    initial_response: &Response<'_>,
+    baseline: &Baseline,
     request_defaults: &RequestDefaults,
     found_params: &Vec<FoundParameter>,
     diffs: &Vec<String>,
@@ -371,14 +914,14 @@ pub(super) async fn _smart_verify(
             .send()
             .await?;
 
-        let (is_code_the_same, new_diffs) = response.compare(initial_response, &diffs)?;
+        let (is_code_the_same, new_diffs) = response.compare(baseline, &diffs)?;
         let mut is_the_body_the_same = true;
 
         if !new_diffs.is_empty() {
             is_the_body_the_same = false;
         }
 
-        response.fill_reflected_parameters(initial_response);
+        response.fill_reflected_parameters(baseline);
 
         if !is_code_the_same || !response.reflected_parameters.is_empty() || stable.body && !is_the_body_the_same {
             filtered_params.push(param.clone());
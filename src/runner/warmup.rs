@@ -0,0 +1,92 @@
+use std::{error::Error, time::{Duration, Instant}};
+
+use futures::future::join_all;
+use indicatif::ProgressBar;
+
+use crate::{
+    config::structs::Config,
+    network::{request::{Request, RequestDefaults}, utils::Headers},
+    utils,
+};
+
+use super::utils::percentile;
+
+/// opens connections to the target before learning so cold handshakes don't skew the first responses
+/// then repeats the same amount of requests and reports how many of them were served over reused connections
+pub async fn warmup(
+    config: &Config,
+    request_defaults: &RequestDefaults,
+    progress_bar: &ProgressBar,
+    id: usize,
+) -> Result<(), Box<dyn Error>> {
+    let connections = config.warmup;
+
+    // the time to open a connection
+    // a request over a reused connection should be at least that much faster than the cold one
+    let connect = if config.proxy.is_empty() {
+        let start = Instant::now();
+
+        tokio::time::timeout(
+            Duration::from_secs(config.timeout as u64),
+            tokio::net::TcpStream::connect(format!("{}:{}", request_defaults.host, request_defaults.port)),
+        )
+        .await??;
+
+        start.elapsed().as_millis()
+    } else {
+        0
+    };
+
+    let cold = send_concurrently(request_defaults, connections).await?;
+    let warm = send_concurrently(request_defaults, connections).await?;
+
+    let cold_p50 = percentile(&cold.iter().map(|(time, _)| *time).collect::<Vec<u128>>(), 50);
+    let warm_p50 = percentile(&warm.iter().map(|(time, _)| *time).collect::<Vec<u128>>(), 50);
+
+    let reused = warm
+        .iter()
+        .filter(|(time, close)| !close && time + connect <= cold_p50)
+        .count();
+
+    utils::info(
+        config,
+        id,
+        progress_bar,
+        "warmup",
+        format!(
+            "{}/{} connections reused (cold p50 {} ms, warm p50 {} ms)",
+            reused, connections, cold_p50, warm_p50
+        ),
+    );
+
+    if cold.iter().chain(warm.iter()).any(|(_, close)| *close) {
+        utils::info(
+            config,
+            id,
+            progress_bar,
+            "~",
+            "The target closes connections after responses (Connection: close). Every request needs a new connection",
+        );
+    }
+
+    Ok(())
+}
+
+/// returns response times and whether the server asked to close the connection
+async fn send_concurrently(request_defaults: &RequestDefaults, amount: usize) -> Result<Vec<(u128, bool)>, Box<dyn Error>> {
+    let mut results = Vec::with_capacity(amount);
+
+    for response in join_all((0..amount).map(|_| Request::new(request_defaults, Vec::new()).send())).await {
+        let response = response?;
+
+        let close = response
+            .headers
+            .get_value_case_insensitive("connection")
+            .map(|x| x.eq_ignore_ascii_case("close"))
+            .unwrap_or(false);
+
+        results.push((response.time, close));
+    }
+
+    Ok(results)
+}
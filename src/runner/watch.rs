@@ -0,0 +1,67 @@
+use std::{
+    collections::HashSet,
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+};
+
+/// follows the file with extra words to check while the scan is running (--watch-wordlist)
+/// words that are already within the list are ignored, so the file can be appended to without care
+pub struct WordlistWatcher {
+    filename: String,
+
+    /// the amount of bytes that were read already
+    offset: u64,
+
+    known: HashSet<String>,
+}
+
+impl WordlistWatcher {
+    pub fn new(filename: &str, known: &[String]) -> Self {
+        Self {
+            filename: filename.to_string(),
+            offset: 0,
+            known: known.iter().cloned().collect(),
+        }
+    }
+
+    /// unknown words from complete lines that were appended since the previous call
+    /// a missing file is treated as an empty one, so it can be created after the scan is started
+    pub fn new_words(&mut self) -> Vec<String> {
+        let mut file = match File::open(&self.filename) {
+            Ok(val) => val,
+            Err(_) => return Vec::new(),
+        };
+
+        let len = file.metadata().map(|x| x.len()).unwrap_or_default();
+
+        // the file was truncated or replaced
+        if len < self.offset {
+            self.offset = 0;
+        }
+
+        if len == self.offset || file.seek(SeekFrom::Start(self.offset)).is_err() {
+            return Vec::new();
+        }
+
+        let mut appended = Vec::new();
+
+        if file.take(len - self.offset).read_to_end(&mut appended).is_err() {
+            return Vec::new();
+        }
+
+        // the last line may be still being written
+        let complete = match appended.iter().rposition(|x| *x == b'\n') {
+            Some(pos) => pos + 1,
+            None => return Vec::new(),
+        };
+
+        self.offset += complete as u64;
+
+        String::from_utf8_lossy(&appended[..complete])
+            .lines()
+            .map(|x| x.trim().to_string())
+            .filter(|x| !x.is_empty())
+            .filter(|x| self.known.insert(x.clone()))
+            .collect()
+    }
+}
@@ -0,0 +1,379 @@
+//! A small REST api to submit targets, query progress and fetch results.
+//! `x8 serve --listen 127.0.0.1:8089`
+//!
+//! POST /scans       {"urls": [..], "methods": [..], "params": [..]} -> {"id": <id>}
+//!                   methods and params are optional, the command line ones are used by default
+//! GET  /scans       list of jobs with their progress
+//! GET  /scans/<id>  the job with results and errors
+
+use std::{
+    error::Error,
+    io::{self, Write},
+    sync::Arc,
+    time::Duration,
+};
+
+use futures::StreamExt;
+use indicatif::ProgressBar;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+};
+use url::Url;
+
+use crate::{
+    config::structs::Config,
+    runner::{
+        output::{PreviousFindings, RunnerOutput},
+        scan::scan_url,
+    },
+    utils, UNSAFE_METHODS,
+};
+
+/// requests with larger bodies are rejected
+const MAX_BODY_SIZE: usize = 10 * 1024 * 1024;
+
+/// slow clients shouldn't block the api
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Done,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Job {
+    pub id: usize,
+
+    pub status: JobStatus,
+
+    pub urls: Vec<String>,
+
+    pub methods: Vec<String>,
+
+    /// the amount of checked url:method pairs
+    pub progress: usize,
+
+    /// the amount of url:method pairs to check
+    pub total: usize,
+
+    pub results: Vec<RunnerOutput>,
+
+    pub errors: Vec<String>,
+
+    #[serde(skip_serializing)]
+    pub params: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct ScanRequest {
+    urls: Vec<String>,
+
+    #[serde(default)]
+    methods: Vec<String>,
+
+    /// overwrites the wordlist
+    #[serde(default)]
+    params: Vec<String>,
+}
+
+/// accepts jobs via the api and runs them one after another
+pub async fn serve(config: &Config, params: Vec<String>) -> Result<(), Box<dyn Error>> {
+    let listener = TcpListener::bind(&config.listen).await?;
+
+    // the output goes to the api only
+    let mut config = config.clone();
+    config.verbose = 0;
+    config.disable_progress_bar = true;
+    config.json_only = true;
+
+    writeln!(io::stdout(), "Listening on {}", config.listen).ok();
+
+    let config = Arc::new(config);
+    let jobs: Arc<Mutex<Vec<Job>>> = Arc::new(Mutex::new(Vec::new()));
+
+    let api = accept_connections(listener, Arc::clone(&config), Arc::clone(&jobs), Arc::new(params));
+
+    let worker = async {
+        loop {
+            let next_job = jobs.lock().iter().position(|x| x.status == JobStatus::Queued);
+
+            match next_job {
+                Some(index) => run_job(&config, &jobs, index).await,
+                None => tokio::time::sleep(Duration::from_millis(500)).await,
+            }
+        }
+    };
+
+    tokio::join!(api, worker);
+
+    Ok(())
+}
+
+/// handles every connection within its own task, so slow clients don't block the rest of them
+pub async fn accept_connections(listener: TcpListener, config: Arc<Config>, jobs: Arc<Mutex<Vec<Job>>>, params: Arc<Vec<String>>) {
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(err) => {
+                utils::error(err, None, None, None);
+                continue;
+            }
+        };
+
+        let (config, jobs, params) = (Arc::clone(&config), Arc::clone(&jobs), Arc::clone(&params));
+
+        tokio::spawn(async move {
+            match tokio::time::timeout(CONNECTION_TIMEOUT, handle_connection(stream, &config, &jobs, &params)).await {
+                Ok(Err(err)) => log::debug!("Unable to handle the connection: {}", err),
+                Err(_) => log::debug!("The connection timed out"),
+                _ => (),
+            }
+        });
+    }
+}
+
+/// checks every url:method pair of the job
+async fn run_job(config: &Config, jobs: &Mutex<Vec<Job>>, index: usize) {
+    let (urls, methods, params) = {
+        let mut jobs = jobs.lock();
+        jobs[index].status = JobStatus::Running;
+        (jobs[index].urls.clone(), jobs[index].methods.clone(), jobs[index].params.clone())
+    };
+
+    // every job needs its own config because urls and methods are used for output alignment
+    let mut config = config.clone();
+    config.urls = urls.clone();
+    config.methods = methods.clone();
+
+    let workers = if config.workers == 0 {
+        urls.len() * methods.len()
+    } else {
+        config.workers
+    };
+
+    let previous_findings = PreviousFindings::new();
+
+    futures::stream::iter(
+        urls.iter()
+            .flat_map(|url| methods.iter().map(move |method| (url, method)))
+            .enumerate()
+            .map(|(id, (url, method))| {
+                let config = &config;
+                let params = &params;
+                let previous_findings = &previous_findings;
+
+                async move {
+                    let progress_bar = ProgressBar::hidden();
+                    let mut params = params.clone();
+
+                    let result = scan_url(
+                        config,
+                        url,
+                        method,
+                        &mut params,
+                        &progress_bar,
+                        id + 1,
+                        previous_findings,
+                        None,
+                    )
+                    .await;
+
+                    let mut jobs = jobs.lock();
+                    jobs[index].progress += 1;
+
+                    match result {
+                        Ok(output) => jobs[index].results.push(output),
+                        Err(err) => jobs[index].errors.push(format!("{} {}: {}", method, url, err)),
+                    }
+                }
+            }),
+    )
+    .buffer_unordered(workers)
+    .collect::<Vec<()>>()
+    .await;
+
+    jobs.lock()[index].status = JobStatus::Done;
+
+    config.state.flush_audit();
+}
+
+/// reads a single http request and writes the response
+async fn handle_connection(
+    mut stream: TcpStream,
+    config: &Config,
+    jobs: &Mutex<Vec<Job>>,
+    params: &[String],
+) -> Result<(), Box<dyn Error>> {
+    let (reader, mut writer) = stream.split();
+    let mut reader = BufReader::new(reader);
+
+    let mut firstline = String::new();
+    reader.read_line(&mut firstline).await?;
+
+    let mut firstline = firstline.split(' ');
+    let method = firstline.next().unwrap_or("").to_string();
+    let path = firstline.next().unwrap_or("").to_string();
+
+    let mut content_length = 0;
+
+    loop {
+        let mut line = String::new();
+
+        if reader.read_line(&mut line).await? == 0 || line.trim().is_empty() {
+            break;
+        }
+
+        if let Some((k, v)) = line.split_once(':') {
+            if k.trim().eq_ignore_ascii_case("content-length") {
+                content_length = v.trim().parse()?;
+            }
+        }
+    }
+
+    let (status, response) = if content_length > MAX_BODY_SIZE {
+        ("413 Payload Too Large", json!({"error": "The body is too large"}))
+    } else {
+        let mut body = vec![0; content_length];
+        reader.read_exact(&mut body).await?;
+
+        route(&method, &path, &body, config, jobs, params)
+    };
+
+    let response = response.to_string();
+
+    writer
+        .write_all(
+            format!(
+                "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                response.len(),
+                response
+            )
+            .as_bytes(),
+        )
+        .await?;
+
+    writer.flush().await?;
+
+    Ok(())
+}
+
+/// returns the status line and the json response
+fn route(
+    method: &str,
+    path: &str,
+    body: &[u8],
+    config: &Config,
+    jobs: &Mutex<Vec<Job>>,
+    params: &[String],
+) -> (&'static str, serde_json::Value) {
+    let path = path.trim_end_matches('/');
+
+    match (method, path) {
+        ("POST", "/scans") => submit_job(body, config, jobs, params),
+
+        ("GET", "/scans") => {
+            let jobs = jobs.lock();
+
+            (
+                "200 OK",
+                json!(jobs
+                    .iter()
+                    .map(|x| json!({
+                        "id": x.id,
+                        "status": x.status,
+                        "urls": x.urls,
+                        "progress": x.progress,
+                        "total": x.total,
+                    }))
+                    .collect::<Vec<serde_json::Value>>()),
+            )
+        }
+
+        ("GET", _) if path.starts_with("/scans/") => {
+            let id: usize = match path["/scans/".len()..].parse() {
+                Ok(val) => val,
+                Err(_) => return ("400 Bad Request", json!({"error": "Incorrect job id"})),
+            };
+
+            let jobs = jobs.lock();
+
+            match jobs.iter().find(|x| x.id == id) {
+                Some(job) => match serde_json::to_value(job) {
+                    Ok(val) => ("200 OK", val),
+                    Err(err) => ("500 Internal Server Error", json!({"error": err.to_string()})),
+                },
+                None => ("404 Not Found", json!({"error": "The job wasn't found"})),
+            }
+        }
+
+        _ => ("404 Not Found", json!({"error": "Unknown endpoint"})),
+    }
+}
+
+/// adds a new job to the queue
+fn submit_job(
+    body: &[u8],
+    config: &Config,
+    jobs: &Mutex<Vec<Job>>,
+    params: &[String],
+) -> (&'static str, serde_json::Value) {
+    let request: ScanRequest = match serde_json::from_slice(body) {
+        Ok(val) => val,
+        Err(err) => return ("400 Bad Request", json!({"error": err.to_string()})),
+    };
+
+    if request.urls.is_empty() {
+        return ("400 Bad Request", json!({"error": "No urls were provided"}));
+    }
+
+    let mut urls = Vec::with_capacity(request.urls.len());
+
+    for url in request.urls.iter() {
+        match Url::parse(url) {
+            Ok(val) => urls.push(val.to_string()),
+            Err(err) => return ("400 Bad Request", json!({"error": format!("{}: {}", url, err)})),
+        }
+    }
+
+    let methods = if request.methods.is_empty() {
+        config.methods.clone()
+    } else {
+        request.methods
+    };
+
+    if config.safe {
+        if let Some(method) = methods.iter().find(|x| UNSAFE_METHODS.contains(&x.to_uppercase().as_str())) {
+            return ("400 Bad Request", json!({"error": format!("--safe refuses the {} method", method)}));
+        }
+    }
+
+    let params = if request.params.is_empty() {
+        params.to_vec()
+    } else {
+        request.params
+    };
+
+    let mut jobs = jobs.lock();
+    let id = jobs.len() + 1;
+
+    jobs.push(Job {
+        id,
+        status: JobStatus::Queued,
+        total: urls.len() * methods.len(),
+        urls,
+        methods,
+        progress: 0,
+        results: Vec::new(),
+        errors: Vec::new(),
+        params,
+    });
+
+    ("201 Created", json!({ "id": id }))
+}
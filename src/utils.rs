@@ -2,6 +2,7 @@ use std::{
     fs::File,
     io::{self, BufRead, Write},
     path::Path,
+    sync::atomic::{AtomicBool, Ordering},
 };
 
 use colored::*;
@@ -10,7 +11,19 @@ use linked_hash_map::LinkedHashMap;
 use rand::Rng;
 use url::Url;
 
-use crate::{config::structs::Config, RANDOM_CHARSET};
+use crate::{config::structs::Config, RANDOM_CHARSET, SHARD_CHUNK_SIZE};
+
+/// set once Ctrl+C is pressed
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// stops sending new requests. Requests that are already sent are awaited and found parameters are printed
+pub fn interrupt() {
+    INTERRUPTED.store(true, Ordering::Relaxed);
+}
+
+pub fn is_interrupted() -> bool {
+    INTERRUPTED.load(Ordering::Relaxed)
+}
 
 pub fn progress_style_learn_requests(config: &Config) -> ProgressStyle {
     if config.disable_colors {
@@ -26,11 +39,11 @@ pub fn progress_style_learn_requests(config: &Config) -> ProgressStyle {
 
 pub fn progress_style_check_requests(config: &Config) -> ProgressStyle {
     if config.disable_colors {
-        ProgressStyle::with_template(&format!("{{prefix}} {{bar:{}}} {{pos:>7}}/{{len:7}}", config.progress_bar_len))
+        ProgressStyle::with_template(&format!("{{prefix}} {{bar:{}}} {{pos:>7}}/{{len:7}} {{msg}}", config.progress_bar_len))
             .unwrap()
             .progress_chars("##-")
     } else {
-        ProgressStyle::with_template(&format!("{{prefix}} {{bar:{}.cyan/blue}} {{pos:>7}}/{{len:7}}", config.progress_bar_len))
+        ProgressStyle::with_template(&format!("{{prefix}} {{bar:{}.cyan/blue}} {{pos:>7}}/{{len:7}} {{msg}}", config.progress_bar_len))
             .unwrap()
             .progress_chars("##-")
     }
@@ -132,6 +145,68 @@ where
     Ok(io::BufReader::new(file).lines())
 }
 
+/// selects every total-th chunk of parameters starting with the index-th one (--shard index/total)
+/// the same wordlist is always split the same way, so a few machines can check different parts of it
+pub fn shard(params: Vec<String>, index: usize, total: usize) -> Vec<String> {
+    params
+        .chunks(SHARD_CHUNK_SIZE)
+        .enumerate()
+        .filter(|(i, _)| i % total == index - 1)
+        .flat_map(|(_, chunk)| chunk.to_vec())
+        .collect()
+}
+
+/// reads the wordlist by parts, so huge wordlists aren't loaded into memory
+/// words are filtered and sharded the same way as the loaded ones
+pub struct WordlistReader {
+    lines: io::Lines<io::BufReader<File>>,
+
+    /// the amount of words that passed the filter, to select the shard
+    read: usize,
+
+    shard: Option<(usize, usize)>,
+    headers_discovery: bool,
+}
+
+impl WordlistReader {
+    pub fn new(filename: &str, shard: Option<(usize, usize)>, headers_discovery: bool) -> io::Result<Self> {
+        Ok(Self {
+            lines: read_lines(filename)?,
+            read: 0,
+            shard,
+            headers_discovery,
+        })
+    }
+
+    /// up to the amount of words. Empty once the wordlist is over
+    pub fn next_words(&mut self, amount: usize) -> Vec<String> {
+        let mut words = Vec::with_capacity(amount);
+
+        while words.len() < amount {
+            let word = match self.lines.next() {
+                Some(Ok(val)) => val,
+                // lines with invalid utf8 are skipped like within the loaded wordlist
+                Some(Err(_)) => continue,
+                None => break,
+            };
+
+            if self.headers_discovery && (word.eq_ignore_ascii_case("content-length") || word.eq_ignore_ascii_case("host")) {
+                continue;
+            }
+
+            let chunk = self.read / SHARD_CHUNK_SIZE;
+            self.read += 1;
+
+            match self.shard {
+                Some((index, total)) if chunk % total != index - 1 => continue,
+                _ => words.push(word),
+            }
+        }
+
+        words
+    }
+}
+
 /// read parameters from stdin
 pub fn read_stdin_lines() -> Vec<String> {
     let stdin = io::stdin();